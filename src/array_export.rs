@@ -0,0 +1,137 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fs;
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        source_array::{self, Language},
+    },
+    locale::tr,
+    sprite_editor::parse_address,
+};
+
+const LANGUAGES: [Language; 2] = [Language::C, Language::Rust];
+
+pub struct ArrayExportState {
+    from_memory: bool, // Otherwise the screen
+    address_input: String,
+    length: i32,
+    language: Language,
+    name: String,
+    status: Option<String>,
+}
+
+impl Default for ArrayExportState {
+    fn default() -> ArrayExportState {
+        ArrayExportState {
+            from_memory: false,
+            address_input: String::new(),
+            length: 16,
+            language: Language::C,
+            name: "screen".to_string(),
+            status: None,
+        }
+    }
+}
+
+// The bytes to export and a comment describing them, or None while the
+// memory region is invalid.
+fn contents(cpu: &Cpu, state: &ArrayExportState) -> Option<(Vec<u8>, String)> {
+    if !state.from_memory {
+        let screen = &cpu.screen;
+        let comment = format!("{}x{} screen", screen.width(), screen.height());
+        return Some((screen.to_bytes(), comment));
+    }
+
+    let addr = parse_address(&state.address_input)?;
+    let len = state.length.max(0) as usize;
+    if len == 0 || addr as usize + len > cpu.memory.size() {
+        return None;
+    }
+    let bytes = (0..len as u16)
+        .filter_map(|offset| cpu.memory.peek(addr + offset))
+        .collect();
+    let comment = format!("{} bytes at {:#05X}", len, addr);
+    Some((bytes, comment))
+}
+
+// Screen contents or a memory region as a source code array, to use
+// captured title screens or fonts in other programs.
+pub fn draw(ui: &Ui, cpu: &Cpu, state: &mut ArrayExportState, opened: &mut bool) {
+    ui.window(format!("{}###array_export", tr("Export as array")))
+        .opened(opened)
+        .size([460.0, 360.0], Condition::FirstUseEver)
+        .build(|| {
+            if ui.radio_button_bool(tr("Screen"), !state.from_memory) {
+                state.from_memory = false;
+            }
+            ui.same_line();
+            if ui.radio_button_bool(tr("Memory"), state.from_memory) {
+                state.from_memory = true;
+            }
+            if state.from_memory {
+                ui.same_line();
+                ui.set_next_item_width(ui.current_font_size() * 6.0);
+                ui.input_text("##array_address", &mut state.address_input)
+                    .hint(tr("Address"))
+                    .build();
+                ui.same_line();
+                ui.set_next_item_width(ui.current_font_size() * 6.0);
+                ui.input_int(tr("Bytes"), &mut state.length).build();
+            }
+
+            for language in LANGUAGES {
+                if ui.radio_button_bool(language.name(), state.language == language) {
+                    state.language = language;
+                }
+                ui.same_line();
+            }
+            ui.set_next_item_width(ui.current_font_size() * 10.0);
+            ui.input_text(tr("Name"), &mut state.name).build();
+
+            let Some((bytes, comment)) = contents(cpu, state) else {
+                ui.text_colored([1.0, 0.4, 0.4, 1.0], tr("Invalid memory region."));
+                return;
+            };
+            let source = source_array::format(&state.name, &comment, &bytes, state.language);
+            if ui.button(tr("Copy")) {
+                ui.set_clipboard_text(&source);
+                state.status = Some(tr("Copied to the clipboard.").to_string());
+            }
+            ui.same_line();
+            if ui.button(tr("Save as...")) {
+                state.status = save(&source, &state.name, state.language);
+            }
+            if let Some(status) = &state.status {
+                ui.same_line();
+                ui.text_disabled(status);
+            }
+
+            ui.child_window("##array_source")
+                .border(true)
+                .horizontal_scrollbar(true)
+                .build(|| ui.text(&source));
+        });
+}
+
+fn save(source: &str, name: &str, language: Language) -> Option<String> {
+    let extension = language.extension();
+    let path = rfd::FileDialog::new()
+        .add_filter(language.name(), &[extension])
+        .set_file_name(format!("{}.{}", name, extension))
+        .save_file()?;
+    Some(match fs::write(&path, source) {
+        Ok(()) => format!("{} {}", tr("Saved"), path.display()),
+        Err(err) => format!("{}: {}", path.display(), err),
+    })
+}
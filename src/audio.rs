@@ -0,0 +1,165 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    mem::size_of,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    AudioSubsystem,
+};
+
+use crate::core::beep::AudioSink;
+
+pub const SAMPLE_RATES: [i32; 3] = [22_050, 44_100, 48_000];
+pub const DEFAULT_SAMPLE_RATE: i32 = 22_050;
+
+// Samples per buffer. Smaller buffers lower the latency, but may
+// crackle on slow systems. 0 lets SDL pick.
+pub const BUFFER_SIZES: [u16; 6] = [0, 256, 512, 1024, 2048, 4096];
+pub const DEFAULT_BUFFER_SIZE: u16 = 0;
+
+// While audio is queued further ahead than this (e.g. while
+// fast-forwarding) new frames are dropped, so the sound doesn't lag
+// behind the game.
+const MAX_QUEUED: Duration = Duration::from_millis(150);
+
+// Latencies in microseconds, 0 until known. Shared between the sink
+// (owned by the cpu) and the UI.
+#[derive(Default)]
+pub struct AudioStats {
+    buffer_latency: AtomicU32,
+    measured_latency: AtomicU32,
+}
+
+impl AudioStats {
+    // Time a full buffer takes to play, from the device's actual spec.
+    pub fn buffer_latency(&self) -> Option<Duration> {
+        load_latency(&self.buffer_latency)
+    }
+
+    // Time until the latest frame's audio plays: the queued audio,
+    // plus the device's buffer.
+    pub fn measured_latency(&self) -> Option<Duration> {
+        load_latency(&self.measured_latency)
+    }
+}
+
+fn load_latency(latency: &AtomicU32) -> Option<Duration> {
+    match latency.load(Ordering::Relaxed) {
+        0 => None,
+        micros => Some(Duration::from_micros(micros as u64)),
+    }
+}
+
+fn store_latency(latency: &AtomicU32, duration: Duration) {
+    latency.store(duration.as_micros().max(1) as u32, Ordering::Relaxed);
+}
+
+fn buffer_duration(samples: u32, freq: i32) -> Duration {
+    Duration::from_secs_f64(samples as f64 / freq.max(1) as f64)
+}
+
+// Plays the frames rendered by the cpu through an SDL audio queue.
+pub struct QueueSink {
+    queue: Option<AudioQueue<f32>>,
+    sample_rate: i32,
+    stats: Arc<AudioStats>,
+}
+
+impl QueueSink {
+    pub fn new(
+        audio_subsystem: &AudioSubsystem,
+        sample_rate: i32,
+        buffer_size: u16,
+        stats: Arc<AudioStats>,
+    ) -> QueueSink {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(sample_rate),
+            channels: Some(1), // mono
+            samples: (buffer_size > 0).then_some(buffer_size),
+        };
+
+        let queue = match audio_subsystem.open_queue::<f32, _>(None, &desired_spec) {
+            Ok(queue) => queue,
+            Err(err) => {
+                log::error!("Could not open the audio device: {}", err);
+                return QueueSink {
+                    queue: None,
+                    sample_rate,
+                    stats,
+                };
+            }
+        };
+
+        let spec = queue.spec();
+        store_latency(
+            &stats.buffer_latency,
+            buffer_duration(spec.samples as u32, spec.freq),
+        );
+        let sample_rate = spec.freq;
+        queue.resume();
+
+        QueueSink {
+            queue: Some(queue),
+            sample_rate,
+            stats,
+        }
+    }
+}
+
+impl AudioSink for QueueSink {
+    fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        let Some(queue) = self.queue.as_ref() else {
+            return;
+        };
+
+        let queued_samples = |queue: &AudioQueue<f32>| queue.size() / size_of::<f32>() as u32;
+        // Too far behind, this frame is dropped whole so the queue drains
+        // without cutting a waveform short
+        let behind = buffer_duration(queued_samples(queue), self.sample_rate) > MAX_QUEUED;
+        if !behind && !queue.queue(samples) {
+            log::warn!("Could not queue audio: {}", sdl2::get_error());
+        }
+
+        let latency = buffer_duration(queued_samples(queue), self.sample_rate)
+            + self.stats.buffer_latency().unwrap_or_default();
+        store_latency(&self.stats.measured_latency, latency);
+    }
+}
+
+#[cfg(test)]
+mod audio_tests {
+    use std::time::Duration;
+
+    use super::{buffer_duration, AudioStats};
+
+    #[test]
+    fn test_buffer_duration() {
+        assert_eq!(buffer_duration(441, 44_100), Duration::from_millis(10));
+        assert_eq!(buffer_duration(512, 0), Duration::from_secs(512));
+    }
+
+    #[test]
+    fn test_unknown_latency() {
+        let stats = AudioStats::default();
+        assert_eq!(stats.buffer_latency(), None);
+        assert_eq!(stats.measured_latency(), None);
+    }
+}
@@ -0,0 +1,172 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        memory::Memory,
+        screen::{Palette, Screen},
+    },
+    machine::Machine,
+    PROGRAM_BEGIN,
+};
+
+// Micro benchmarks of the interpreter loop, to compare refactors of the
+// decode and execute path. Run with:
+//   cargo run --release --features bench -- bench [filter]
+const WARM_UP: Duration = Duration::from_millis(100);
+const SAMPLES: usize = 10;
+const SAMPLE_TIME: Duration = Duration::from_millis(50);
+
+// Programs looping over one opcode family, ending in JP 0x200.
+const FAMILIES: [(&str, &[u8]); 12] = [
+    ("00E0 cls", &[0x00, 0xE0, 0x00, 0xE0, 0x12, 0x00]),
+    ("1nnn jump", &[0x12, 0x02, 0x12, 0x04, 0x12, 0x00]),
+    ("2nnn/00EE call", &[0x22, 0x04, 0x12, 0x00, 0x00, 0xEE]),
+    (
+        "3xkk skip",
+        &[0x30, 0x00, 0x60, 0x00, 0x31, 0x01, 0x12, 0x00],
+    ),
+    (
+        "6xkk/7xkk load",
+        &[0x60, 0x12, 0x71, 0x01, 0x72, 0xFF, 0x12, 0x00],
+    ),
+    (
+        "8xyN alu",
+        &[0x80, 0x14, 0x81, 0x25, 0x82, 0x36, 0x83, 0x4E, 0x12, 0x00],
+    ),
+    ("Annn/Fx1E index", &[0xA3, 0x00, 0xF0, 0x1E, 0x12, 0x00]),
+    ("Cxkk random", &[0xC0, 0xFF, 0xC1, 0x0F, 0x12, 0x00]),
+    (
+        "Dxyn draw",
+        &[0xA0, 0x00, 0xD0, 0x15, 0x70, 0x03, 0x12, 0x00],
+    ),
+    (
+        "Fx33 bcd",
+        &[0xA3, 0x00, 0xF0, 0x33, 0x70, 0x07, 0x12, 0x00],
+    ),
+    (
+        "Fx55/Fx65 memory",
+        &[0xA3, 0x00, 0xFF, 0x55, 0xA3, 0x00, 0xFF, 0x65, 0x12, 0x00],
+    ),
+    ("Fx07/Fx15 timers", &[0xF0, 0x15, 0xF1, 0x07, 0x12, 0x00]),
+];
+
+pub fn run(filter: Option<&str>) {
+    let selected = |name: &str| filter.is_none_or(|filter| name.contains(filter));
+
+    for (name, program) in FAMILIES {
+        if !selected(name) {
+            continue;
+        }
+        let mut cpu = Cpu::new();
+        cpu.disable_beep();
+        cpu.load_rom(program, PROGRAM_BEGIN).unwrap();
+        report(&format!("step {}", name), || {
+            let _ = black_box(cpu.step());
+        });
+    }
+
+    // Whole frames of a program drawing every other instruction, with
+    // what they ran
+    if selected("machine frame") {
+        let mut machine = Machine::new();
+        machine.cpu.disable_beep();
+        machine.cpu.load_rom(FAMILIES[8].1, PROGRAM_BEGIN).unwrap();
+        let start = Instant::now();
+        report("machine frame", || machine.run_frame());
+        let stats = machine.stats();
+        println!(
+            "{:<36} {} cycles, {} frames, {} draws, {:.0} cycles/s",
+            "",
+            stats.cycles,
+            stats.frames,
+            stats.draws,
+            stats.cycles as f64 / start.elapsed().as_secs_f64()
+        );
+    }
+
+    if selected("decode") {
+        let mut memory = Memory::new();
+        let program: Vec<u8> = (0..=255).flat_map(|byte| [byte, byte ^ 0x5A]).collect();
+        memory.load_rom(&program, PROGRAM_BEGIN).unwrap();
+        let mut addr = PROGRAM_BEGIN;
+        report("decode", || {
+            let instruction = memory.read_instruction(black_box(addr));
+            black_box(instruction.opcode());
+            addr = if addr >= 0x3FE {
+                PROGRAM_BEGIN
+            } else {
+                addr + 2
+            };
+        });
+    }
+
+    for (width, height) in [(64, 32), (128, 64)] {
+        let name = format!("screen draw_sprite_row {}x{}", width, height);
+        if selected(&name) {
+            let mut screen = Screen::with_resolution(width, height);
+            let mut x = 0;
+            report(&name, || {
+                black_box(screen.draw_sprite_row(x, x % height, 0b1011_0110, true));
+                x = (x + 3) % width;
+            });
+        }
+
+        let name = format!("screen render_rgba {}x{}", width, height);
+        if selected(&name) {
+            let mut screen = Screen::with_resolution(width, height);
+            for x in (0..width).step_by(3) {
+                screen.set(x, x % height, true);
+            }
+            let palette = Palette {
+                enabled_px: [0xFF; 3],
+                disabled_px: [0; 3],
+            };
+            report(&name, || {
+                black_box(screen.render_rgba(&palette));
+            });
+        }
+    }
+}
+
+// Prints the best and median time per call, over several samples.
+fn report(name: &str, mut routine: impl FnMut()) {
+    let warm_up = Instant::now();
+    let mut calls_per_sample = 0_u64;
+    while warm_up.elapsed() < WARM_UP {
+        routine();
+        calls_per_sample += 1;
+    }
+    calls_per_sample =
+        (calls_per_sample * SAMPLE_TIME.as_nanos() as u64 / WARM_UP.as_nanos() as u64).max(1);
+
+    let mut samples: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..calls_per_sample {
+                routine();
+            }
+            start.elapsed().as_nanos() as f64 / calls_per_sample as f64
+        })
+        .collect();
+    samples.sort_by(f64::total_cmp);
+
+    println!(
+        "{:<36} {:>10.1} ns/iter (best {:.1})",
+        name,
+        samples[SAMPLES / 2],
+        samples[0]
+    );
+}
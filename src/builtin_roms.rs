@@ -0,0 +1,100 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::path::{Path, PathBuf};
+
+// Built-in ROMs are remembered as "builtin:<name>" paths, so restarting,
+// recording and playlists work the same as with ROMs on disk.
+const PATH_PREFIX: &str = "builtin:";
+
+pub struct BuiltinRom {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub data: &'static [u8],
+}
+
+// Written for this project and released into the public domain.
+// Sources and controls are in roms/README.md.
+pub const BUILTIN_ROMS: [BuiltinRom; 3] = [
+    BuiltinRom {
+        name: "logo",
+        description: "Draws the CHIP-8 logo",
+        data: include_bytes!("../roms/logo.ch8"),
+    },
+    BuiltinRom {
+        name: "selftest",
+        description: "Checks a few instructions, E marks a failure",
+        data: include_bytes!("../roms/selftest.ch8"),
+    },
+    BuiltinRom {
+        name: "catch",
+        description: "Catch the ball with Q and E",
+        data: include_bytes!("../roms/catch.ch8"),
+    },
+];
+
+impl BuiltinRom {
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(format!("{}{}.ch8", PATH_PREFIX, self.name))
+    }
+}
+
+// Finds the built-in ROM a path returned by BuiltinRom::path refers to.
+pub fn from_path(path: &Path) -> Option<&'static BuiltinRom> {
+    let name = path
+        .to_str()?
+        .strip_prefix(PATH_PREFIX)?
+        .strip_suffix(".ch8")?;
+    BUILTIN_ROMS.iter().find(|rom| rom.name == name)
+}
+
+#[cfg(test)]
+mod builtin_roms_tests {
+    use std::path::Path;
+
+    use super::{from_path, BUILTIN_ROMS};
+    use crate::core::cpu::Cpu;
+
+    fn run(data: &[u8], frames: usize) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(data, 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        for _ in 0..frames {
+            cpu.tick();
+        }
+        cpu
+    }
+
+    #[test]
+    fn test_paths() {
+        for rom in &BUILTIN_ROMS {
+            assert_eq!(from_path(&rom.path()).unwrap().name, rom.name);
+        }
+        assert!(from_path(Path::new("builtin:missing.ch8")).is_none());
+        assert!(from_path(Path::new("roms/logo.ch8")).is_none());
+    }
+
+    #[test]
+    fn test_logo_draws() {
+        let cpu = run(BUILTIN_ROMS[0].data, 200);
+        assert!(cpu.screen.iter().any(|(_, _, on)| on));
+    }
+
+    #[test]
+    fn test_selftest_passes() {
+        let cpu = run(BUILTIN_ROMS[1].data, 500);
+        assert_eq!(cpu.registers.v[6], 5);
+    }
+
+    #[test]
+    fn test_catch_runs() {
+        let cpu = run(BUILTIN_ROMS[2].data, 200);
+        assert!(cpu.screen.iter().any(|(_, _, on)| on));
+    }
+}
@@ -0,0 +1,779 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{fmt, path::PathBuf, time::Duration};
+
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::core::watchdog::Watchdog;
+
+pub const USAGE: &str = "Usage: chip-8-emu [options] [rom...]
+       chip-8-emu trace-gen <rom> [options]
+       chip-8-emu test <rom> [options]
+       chip-8-emu batch <rom> [options]
+
+Passing several ROMs plays them in rotation.
+
+Options:
+  --fullscreen            Start in borderless fullscreen
+  --kiosk                 Hide the menu bar and only quit with the quit combo
+  --quit-combo <combo>    Kiosk quit combo (default: Ctrl+Shift+Q)
+  --idle-reset <seconds>  Kiosk restart after this long without input,
+                          0 disables it (default: 120)
+  --advance <seconds>     Switch to the next ROM after this long, 0 only
+                          switches with F2 (default: 0)
+  --load-state <file>     Restore a JSON machine state after loading the ROM
+  --watchdog <seconds>    Stop a headless run, or restart (or skip) a kiosk
+                          ROM, once it runs this long or faults
+  --watchdog-cycles <n>   Same, once it runs this many instructions
+
+Headless (no window, runs the single given ROM):
+  --trace <file>          Write every executed instruction as a JSON line,
+                          - writes to stdout
+  --frames <n>            Frames to run, at 60 per second (default: 600)
+  --cycles <n>            Stop after this many instructions
+  --until-pc <addr>       Stop before the instruction at addr runs
+  --until-stable <frames> Stop once the screen stays the same this long
+  --until-mem <addr>=<v>  Stop once the byte at addr equals v
+  --break-at <addr>       Halt before the instruction at addr runs, can
+                          be given more than once
+  --break-on-write <addr> Halt after an instruction writes to addr, can
+                          be given more than once
+  --dump-state <file>     Write the final state as JSON, - writes to stdout
+  --json-result <file>    Write a JSON summary of the run (result, cycles,
+                          screen hash, frames with sound), - writes to stdout
+
+With a stop condition, the final state is printed as JSON. The run also
+stops if the program faults. Numbers can be given in hex (0x).
+
+Exit codes:
+  0  Success
+  1  The ROM couldn't be run
+  2  Invalid arguments
+  3  Timeout: the stop conditions weren't met before the frames ran out,
+     or the watchdog expired
+  4  A halt condition (--break-at, --break-on-write) was hit
+  5  Bad opcode: an unknown instruction or a SYS call
+  6  Stack fault: a call overflowed the stack, or a return underflowed it
+  7  Memory accessed out of bounds";
+
+pub const TRACE_GEN_USAGE: &str = "Usage: chip-8-emu trace-gen <rom> [options]
+
+Runs the ROM reproducibly (fixed seed, timers counting frames, no input)
+and writes its canonical trace: a JSON header line, then a line per
+instruction like --trace does.
+
+Options:
+  --cycles <n>            Instructions to trace (default: 10000)
+  --seed <n>              Random number generator seed (default: 0)
+  --output <file>         Write the trace there instead of to stdout";
+
+pub const TEST_USAGE: &str = "Usage: chip-8-emu test <rom> [options]
+
+Runs the tests in the tests.toml next to the ROM, each from a freshly
+loaded ROM and as fast as possible, and reports which pass. A test runs
+for some frames, pressing keys, then checks the machine's state:
+
+  frames = 600            # Frames every test runs, unless it says so
+
+  [[test]]
+  name = \"score starts at 0\"
+  frames = 120
+  seed = 1                # Random number generator seed (default: 0)
+  input = \"0+5 4-5\"       # Frame and key pressed (+) or released (-)
+  expect = [\"mem[0x3F0] == 0\", \"v3 >= 10 && i == 0x300\"]
+  screen_hash = \"...\"     # As written by --json-result
+  screen = [\"..##..\"]     # Rows from the top left, # for lit pixels
+
+Options:
+  --tests <file>          Read the tests from this file instead
+
+Exits with 0 if every test passes, 1 otherwise.";
+
+pub const BATCH_USAGE: &str = "Usage: chip-8-emu batch <rom> [options]
+
+Runs the ROM once per seed of the random number generator, as fast as
+possible and without input, then tells how the runs ended: why they
+stopped and how many ended on each screen. Every run is reproducible
+from its seed, e.g. with trace-gen.
+
+Options:
+  --runs <n>              Runs, each with the next seed (default: 100)
+  --seed <n>              Seed of the first run (default: 0)
+  --frames <n>            Frames each run lasts, unless it faults
+                          (default: 600)
+  --json-result <file>    Also write the summary as JSON, - writes to
+                          stdout";
+
+const DEFAULT_QUIT_COMBO: &str = "Ctrl+Shift+Q";
+const DEFAULT_IDLE_RESET_SECS: u64 = 120;
+const DEFAULT_HEADLESS_FRAMES: u64 = 600;
+const DEFAULT_TRACE_CYCLES: u64 = 10000;
+const DEFAULT_BATCH_RUNS: u64 = 100;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArgsError {
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { option: String, value: String },
+    KioskWithoutRom,
+    HeadlessRomCount,
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::UnknownOption(option) => write!(f, "Unknown option '{}'.", option),
+            ArgsError::MissingValue(option) => write!(f, "Missing value for '{}'.", option),
+            ArgsError::InvalidValue { option, value } => {
+                write!(f, "Invalid value '{}' for '{}'.", value, option)
+            }
+            ArgsError::KioskWithoutRom => write!(f, "Kiosk mode requires a ROM."),
+            ArgsError::HeadlessRomCount => write!(f, "Headless mode requires exactly one ROM."),
+        }
+    }
+}
+
+// A key plus the modifiers that must be held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub keycode: Keycode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyCombo {
+    // Parses combos like "Ctrl+Shift+Q" or "Alt+F12".
+    pub fn parse(combo: &str) -> Option<KeyCombo> {
+        let mut parts: Vec<&str> = combo.split('+').map(str::trim).collect();
+        let keycode = keycode_from_name(parts.pop()?)?;
+
+        let mut key_combo = KeyCombo {
+            keycode,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => key_combo.ctrl = true,
+                "shift" => key_combo.shift = true,
+                "alt" => key_combo.alt = true,
+                _ => return None,
+            }
+        }
+        Some(key_combo)
+    }
+
+    pub fn matches(&self, keycode: Keycode, keymod: Mod) -> bool {
+        keycode == self.keycode
+            && keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) == self.ctrl
+            && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) == self.shift
+            && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) == self.alt
+    }
+}
+
+// Letters, digits, F1-F12 and a few named keys. Kept independent
+// from SDL_GetKeyFromName so arguments can be parsed before SDL starts.
+fn keycode_from_name(name: &str) -> Option<Keycode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            return Keycode::from_i32(c.to_ascii_lowercase() as i32);
+        }
+    }
+
+    if let Some(number) = name.strip_prefix(['F', 'f']) {
+        let number: i32 = number.parse().ok()?;
+        if (1..=12).contains(&number) {
+            return Keycode::from_i32(Keycode::F1 as i32 + number - 1);
+        }
+        return None;
+    }
+
+    match name.to_lowercase().as_str() {
+        "escape" | "esc" => Some(Keycode::Escape),
+        "space" => Some(Keycode::Space),
+        "tab" => Some(Keycode::Tab),
+        "backspace" => Some(Keycode::Backspace),
+        "return" | "enter" => Some(Keycode::Return),
+        "delete" => Some(Keycode::Delete),
+        _ => None,
+    }
+}
+
+// When a headless run stops early. Any of them being met stops it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StopConditions {
+    pub cycles: Option<u64>,
+    pub pc: Option<u16>,
+    pub stable_frames: Option<u64>,
+    pub memory: Option<(u16, u8)>,
+    // Halt conditions, meant to fail scripts asserting a ROM never gets there
+    pub break_at: Vec<u16>,
+    pub break_on_write: Vec<u16>,
+}
+
+impl StopConditions {
+    pub fn is_empty(&self) -> bool {
+        *self == StopConditions::default()
+    }
+}
+
+// Decimal, or hex with a 0x prefix.
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Args {
+    pub roms: Vec<PathBuf>,
+    pub fullscreen: bool,
+    pub kiosk: bool,
+    pub quit_combo: KeyCombo,
+    pub idle_reset_secs: u64,
+    pub advance_secs: u64,
+    pub trace: Option<PathBuf>, // "-" for stdout
+    pub frames: u64,
+    pub stop: StopConditions,
+    pub load_state: Option<PathBuf>,
+    pub dump_state: Option<PathBuf>,  // "-" for stdout
+    pub json_result: Option<PathBuf>, // "-" for stdout
+    pub watchdog_secs: Option<u64>,
+    pub watchdog_cycles: Option<u64>,
+}
+
+impl Args {
+    // Parses the arguments, excluding the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, ArgsError> {
+        let mut parsed = Args {
+            roms: Vec::new(),
+            fullscreen: false,
+            kiosk: false,
+            quit_combo: KeyCombo::parse(DEFAULT_QUIT_COMBO).unwrap(),
+            idle_reset_secs: DEFAULT_IDLE_RESET_SECS,
+            advance_secs: 0,
+            trace: None,
+            frames: DEFAULT_HEADLESS_FRAMES,
+            stop: StopConditions::default(),
+            load_state: None,
+            dump_state: None,
+            json_result: None,
+            watchdog_secs: None,
+            watchdog_cycles: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |option: &str| {
+                args.next()
+                    .ok_or_else(|| ArgsError::MissingValue(option.to_string()))
+            };
+            let invalid = |option: &str, value: String| ArgsError::InvalidValue {
+                option: option.to_string(),
+                value,
+            };
+
+            match arg.as_str() {
+                "--fullscreen" => parsed.fullscreen = true,
+                "--kiosk" => parsed.kiosk = true,
+                "--quit-combo" => {
+                    let combo = value(&arg)?;
+                    parsed.quit_combo = KeyCombo::parse(&combo).ok_or(invalid(&arg, combo))?;
+                }
+                "--idle-reset" => {
+                    let secs = value(&arg)?;
+                    parsed.idle_reset_secs = secs.parse().map_err(|_| invalid(&arg, secs))?;
+                }
+                "--advance" => {
+                    let secs = value(&arg)?;
+                    parsed.advance_secs = secs.parse().map_err(|_| invalid(&arg, secs))?;
+                }
+                "--trace" => parsed.trace = Some(PathBuf::from(value(&arg)?)),
+                "--load-state" => parsed.load_state = Some(PathBuf::from(value(&arg)?)),
+                "--dump-state" => parsed.dump_state = Some(PathBuf::from(value(&arg)?)),
+                "--json-result" => parsed.json_result = Some(PathBuf::from(value(&arg)?)),
+                "--watchdog" => {
+                    let secs = value(&arg)?;
+                    parsed.watchdog_secs = Some(secs.parse().map_err(|_| invalid(&arg, secs))?);
+                }
+                "--watchdog-cycles" => {
+                    let cycles = value(&arg)?;
+                    let cycles = parse_number(&cycles).ok_or(invalid(&arg, cycles))?;
+                    parsed.watchdog_cycles = Some(cycles);
+                }
+                "--frames" => {
+                    let frames = value(&arg)?;
+                    parsed.frames = parse_number(&frames).ok_or(invalid(&arg, frames))?;
+                }
+                "--cycles" => {
+                    let cycles = value(&arg)?;
+                    parsed.stop.cycles = Some(parse_number(&cycles).ok_or(invalid(&arg, cycles))?);
+                }
+                "--until-pc" => {
+                    let addr = value(&arg)?;
+                    let pc = parse_number(&addr).and_then(|pc| u16::try_from(pc).ok());
+                    parsed.stop.pc = Some(pc.ok_or(invalid(&arg, addr))?);
+                }
+                "--until-stable" => {
+                    let frames = value(&arg)?;
+                    let frames = parse_number(&frames).ok_or(invalid(&arg, frames))?;
+                    parsed.stop.stable_frames = Some(frames);
+                }
+                "--until-mem" => {
+                    let condition = value(&arg)?;
+                    let parsed_condition = condition.split_once('=').and_then(|(addr, data)| {
+                        let addr = u16::try_from(parse_number(addr.trim())?).ok()?;
+                        let data = u8::try_from(parse_number(data.trim())?).ok()?;
+                        Some((addr, data))
+                    });
+                    parsed.stop.memory = Some(parsed_condition.ok_or(invalid(&arg, condition))?);
+                }
+                "--break-at" | "--break-on-write" => {
+                    let addr = value(&arg)?;
+                    let parsed_addr = parse_number(&addr).and_then(|addr| u16::try_from(addr).ok());
+                    let addr = parsed_addr.ok_or(invalid(&arg, addr))?;
+                    match arg.as_str() {
+                        "--break-at" => parsed.stop.break_at.push(addr),
+                        _ => parsed.stop.break_on_write.push(addr),
+                    }
+                }
+                _ if arg.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+                _ => parsed.roms.push(PathBuf::from(arg)),
+            }
+        }
+
+        if parsed.kiosk && parsed.roms.is_empty() {
+            return Err(ArgsError::KioskWithoutRom);
+        }
+        if parsed.is_headless() && parsed.roms.len() != 1 {
+            return Err(ArgsError::HeadlessRomCount);
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn watchdog(&self) -> Watchdog {
+        Watchdog::new(
+            self.watchdog_secs.map(Duration::from_secs),
+            self.watchdog_cycles,
+        )
+    }
+
+    // Runs without a window when only output files are asked for.
+    pub fn is_headless(&self) -> bool {
+        self.trace.is_some()
+            || self.dump_state.is_some()
+            || self.json_result.is_some()
+            || !self.stop.is_empty()
+    }
+}
+
+// Arguments of the trace-gen subcommand.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TraceGenArgs {
+    pub rom: PathBuf,
+    pub cycles: u64,
+    pub seed: u64,
+    pub output: Option<PathBuf>, // stdout if missing
+}
+
+impl TraceGenArgs {
+    // Parses the arguments following "trace-gen".
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<TraceGenArgs, ArgsError> {
+        let mut roms = Vec::new();
+        let mut cycles = DEFAULT_TRACE_CYCLES;
+        let mut seed = 0;
+        let mut output = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |option: &str| {
+                args.next()
+                    .ok_or_else(|| ArgsError::MissingValue(option.to_string()))
+            };
+            let invalid = |option: &str, value: String| ArgsError::InvalidValue {
+                option: option.to_string(),
+                value,
+            };
+
+            match arg.as_str() {
+                "--cycles" => {
+                    let value = value(&arg)?;
+                    cycles = parse_number(&value).ok_or(invalid(&arg, value))?;
+                }
+                "--seed" => {
+                    let value = value(&arg)?;
+                    seed = parse_number(&value).ok_or(invalid(&arg, value))?;
+                }
+                "--output" => output = Some(PathBuf::from(value(&arg)?)),
+                _ if arg.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+                _ => roms.push(PathBuf::from(arg)),
+            }
+        }
+
+        if roms.len() != 1 {
+            return Err(ArgsError::HeadlessRomCount);
+        }
+        Ok(TraceGenArgs {
+            rom: roms.remove(0),
+            cycles,
+            seed,
+            output,
+        })
+    }
+}
+
+// Arguments of the test subcommand.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TestArgs {
+    pub rom: PathBuf,
+    pub tests: PathBuf,
+}
+
+impl TestArgs {
+    // Parses the arguments following "test".
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<TestArgs, ArgsError> {
+        let mut roms = Vec::new();
+        let mut tests = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tests" => {
+                    let path = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    tests = Some(PathBuf::from(path));
+                }
+                _ if arg.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+                _ => roms.push(PathBuf::from(arg)),
+            }
+        }
+
+        if roms.len() != 1 {
+            return Err(ArgsError::HeadlessRomCount);
+        }
+        let rom = roms.remove(0);
+        Ok(TestArgs {
+            tests: tests.unwrap_or_else(|| rom.with_file_name("tests.toml")),
+            rom,
+        })
+    }
+}
+
+// Arguments of the batch subcommand.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BatchArgs {
+    pub rom: PathBuf,
+    pub runs: u64,
+    pub first_seed: u64,
+    pub frames: u64,
+    pub json_result: Option<PathBuf>, // "-" for stdout
+}
+
+impl BatchArgs {
+    // Parses the arguments following "batch".
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<BatchArgs, ArgsError> {
+        let mut roms = Vec::new();
+        let mut runs = DEFAULT_BATCH_RUNS;
+        let mut first_seed = 0;
+        let mut frames = DEFAULT_HEADLESS_FRAMES;
+        let mut json_result = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |option: &str| {
+                args.next()
+                    .ok_or_else(|| ArgsError::MissingValue(option.to_string()))
+            };
+            let invalid = |option: &str, value: String| ArgsError::InvalidValue {
+                option: option.to_string(),
+                value,
+            };
+
+            match arg.as_str() {
+                "--runs" => {
+                    let value = value(&arg)?;
+                    runs = parse_number(&value)
+                        .filter(|&runs| runs > 0)
+                        .ok_or(invalid(&arg, value))?;
+                }
+                "--seed" => {
+                    let value = value(&arg)?;
+                    first_seed = parse_number(&value).ok_or(invalid(&arg, value))?;
+                }
+                "--frames" => {
+                    let value = value(&arg)?;
+                    frames = parse_number(&value).ok_or(invalid(&arg, value))?;
+                }
+                "--json-result" => json_result = Some(PathBuf::from(value(&arg)?)),
+                _ if arg.starts_with("--") => return Err(ArgsError::UnknownOption(arg)),
+                _ => roms.push(PathBuf::from(arg)),
+            }
+        }
+
+        if roms.len() != 1 {
+            return Err(ArgsError::HeadlessRomCount);
+        }
+        Ok(BatchArgs {
+            rom: roms.remove(0),
+            runs,
+            first_seed,
+            frames,
+            json_result,
+        })
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use std::path::PathBuf;
+
+    use sdl2::keyboard::{Keycode, Mod};
+
+    use super::{Args, ArgsError, BatchArgs, KeyCombo, TestArgs, TraceGenArgs};
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let args = parse(&[]).unwrap();
+        assert!(args.roms.is_empty());
+        assert_eq!(args.advance_secs, 0);
+        assert!(!args.fullscreen && !args.kiosk);
+        assert_eq!(args.idle_reset_secs, 120);
+    }
+
+    #[test]
+    fn test_kiosk() {
+        let args = parse(&[
+            "--fullscreen",
+            "--kiosk",
+            "--idle-reset",
+            "30",
+            "--quit-combo",
+            "Alt+F12",
+            "game.ch8",
+        ])
+        .unwrap();
+        assert!(args.fullscreen && args.kiosk);
+        assert_eq!(args.roms, vec![PathBuf::from("game.ch8")]);
+        assert_eq!(args.idle_reset_secs, 30);
+        assert_eq!(args.quit_combo.keycode, Keycode::F12);
+        assert!(args.quit_combo.alt && !args.quit_combo.ctrl);
+
+        assert_eq!(parse(&["--kiosk"]), Err(ArgsError::KioskWithoutRom));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse(&["--turbo"]),
+            Err(ArgsError::UnknownOption("--turbo".to_string()))
+        );
+        assert_eq!(
+            parse(&["--idle-reset"]),
+            Err(ArgsError::MissingValue("--idle-reset".to_string()))
+        );
+        assert_eq!(
+            parse(&["--idle-reset", "soon"]),
+            Err(ArgsError::InvalidValue {
+                option: "--idle-reset".to_string(),
+                value: "soon".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_playlist() {
+        let args = parse(&["a.ch8", "--advance", "60", "b.ch8"]).unwrap();
+        assert_eq!(
+            args.roms,
+            vec![PathBuf::from("a.ch8"), PathBuf::from("b.ch8")]
+        );
+        assert_eq!(args.advance_secs, 60);
+    }
+
+    #[test]
+    fn test_headless() {
+        assert!(!parse(&["a.ch8"]).unwrap().is_headless());
+
+        let args = parse(&["--trace", "-", "--frames", "60", "a.ch8"]).unwrap();
+        assert!(args.is_headless());
+        assert_eq!(args.trace, Some(PathBuf::from("-")));
+        assert_eq!(args.frames, 60);
+
+        assert_eq!(
+            parse(&["--trace", "out.jsonl"]),
+            Err(ArgsError::HeadlessRomCount)
+        );
+        assert_eq!(
+            parse(&["--trace", "-", "a.ch8", "b.ch8"]),
+            Err(ArgsError::HeadlessRomCount)
+        );
+    }
+
+    #[test]
+    fn test_states() {
+        let args = parse(&["--load-state", "in.json", "a.ch8"]).unwrap();
+        assert_eq!(args.load_state, Some(PathBuf::from("in.json")));
+        assert!(!args.is_headless());
+
+        let args = parse(&["--dump-state", "-", "a.ch8"]).unwrap();
+        assert_eq!(args.dump_state, Some(PathBuf::from("-")));
+        assert!(args.is_headless());
+
+        // Not headless by itself, kiosks use it too
+        let args = parse(&["--watchdog", "30", "--watchdog-cycles", "0x100000", "a.ch8"]).unwrap();
+        assert_eq!(args.watchdog_secs, Some(30));
+        assert_eq!(args.watchdog_cycles, Some(0x100000));
+        assert!(args.watchdog().is_enabled());
+        assert!(!args.is_headless());
+
+        let args = parse(&["--json-result", "result.json", "a.ch8"]).unwrap();
+        assert_eq!(args.json_result, Some(PathBuf::from("result.json")));
+        assert!(args.is_headless());
+    }
+
+    #[test]
+    fn test_stop_conditions() {
+        let args = parse(&[
+            "--cycles",
+            "1000",
+            "--until-pc",
+            "0x2A4",
+            "--until-stable",
+            "30",
+            "--until-mem",
+            "0x300=0xFF",
+            "a.ch8",
+        ])
+        .unwrap();
+        assert!(args.is_headless());
+        assert_eq!(args.stop.cycles, Some(1000));
+        assert_eq!(args.stop.pc, Some(0x2A4));
+        assert_eq!(args.stop.stable_frames, Some(30));
+        assert_eq!(args.stop.memory, Some((0x300, 0xFF)));
+
+        let args = parse(&[
+            "--break-at",
+            "0x2A4",
+            "--break-at",
+            "0x2B0",
+            "--break-on-write",
+            "0x3F0",
+            "a.ch8",
+        ])
+        .unwrap();
+        assert!(args.is_headless());
+        assert_eq!(args.stop.break_at, vec![0x2A4, 0x2B0]);
+        assert_eq!(args.stop.break_on_write, vec![0x3F0]);
+
+        for (option, value) in [
+            ("--until-pc", "0x10000"),
+            ("--until-mem", "0x300"),
+            ("--break-on-write", "0x10000"),
+            ("--until-mem", "0x300=256"),
+            ("--cycles", "many"),
+        ] {
+            assert_eq!(
+                parse(&[option, value, "a.ch8"]),
+                Err(ArgsError::InvalidValue {
+                    option: option.to_string(),
+                    value: value.to_string()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_trace_gen() {
+        let parse = |args: &[&str]| TraceGenArgs::parse(args.iter().map(|arg| arg.to_string()));
+        let args = parse(&["game.ch8", "--cycles", "500", "--seed", "0x2A"]).unwrap();
+        assert_eq!(
+            args,
+            TraceGenArgs {
+                rom: PathBuf::from("game.ch8"),
+                cycles: 500,
+                seed: 42,
+                output: None,
+            }
+        );
+        assert_eq!(parse(&[]), Err(ArgsError::HeadlessRomCount));
+        assert_eq!(
+            parse(&["game.ch8", "--kiosk"]),
+            Err(ArgsError::UnknownOption("--kiosk".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_test_args() {
+        let parse = |args: &[&str]| TestArgs::parse(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            parse(&["roms/game.ch8"]).unwrap().tests,
+            PathBuf::from("roms/tests.toml")
+        );
+        assert_eq!(
+            parse(&["game.ch8", "--tests", "ci.toml"]).unwrap(),
+            TestArgs {
+                rom: PathBuf::from("game.ch8"),
+                tests: PathBuf::from("ci.toml"),
+            }
+        );
+        assert_eq!(
+            parse(&["game.ch8", "--tests"]),
+            Err(ArgsError::MissingValue("--tests".to_string()))
+        );
+        assert_eq!(parse(&["a.ch8", "b.ch8"]), Err(ArgsError::HeadlessRomCount));
+    }
+
+    #[test]
+    fn test_batch_args() {
+        let parse = |args: &[&str]| BatchArgs::parse(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            parse(&["game.ch8", "--runs", "1000", "--seed", "0x10"]).unwrap(),
+            BatchArgs {
+                rom: PathBuf::from("game.ch8"),
+                runs: 1000,
+                first_seed: 16,
+                frames: 600,
+                json_result: None,
+            }
+        );
+        assert_eq!(
+            parse(&["game.ch8", "--runs", "0"]),
+            Err(ArgsError::InvalidValue {
+                option: "--runs".to_string(),
+                value: "0".to_string()
+            })
+        );
+        assert_eq!(parse(&["--frames", "60"]), Err(ArgsError::HeadlessRomCount));
+    }
+
+    #[test]
+    fn test_key_combo() {
+        let combo = KeyCombo::parse("Ctrl+Shift+Q").unwrap();
+        assert!(combo.matches(Keycode::Q, Mod::LCTRLMOD | Mod::RSHIFTMOD));
+        assert!(!combo.matches(Keycode::Q, Mod::LCTRLMOD));
+        assert!(!combo.matches(Keycode::W, Mod::LCTRLMOD | Mod::LSHIFTMOD));
+        assert_eq!(
+            KeyCombo::parse("Ctrl+Esc").unwrap().keycode,
+            Keycode::Escape
+        );
+        assert_eq!(KeyCombo::parse("F1").unwrap().keycode, Keycode::F1);
+        assert_eq!(KeyCombo::parse("F13"), None);
+        assert_eq!(KeyCombo::parse("Hyper+Q"), None);
+        assert_eq!(KeyCombo::parse("Ctrl+NotAKey"), None);
+    }
+}
@@ -0,0 +1,339 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        assembler::{self, AsmError},
+        symbols::SymbolTable,
+    },
+    hex_import,
+    locale::tr,
+    rom_check::PendingRom,
+};
+
+const NEW_PROGRAM: &str = ": main\n  clear\n  loop again\n";
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+// Words of the assembly language, for highlighting.
+const KEYWORDS: [&str; 25] = [
+    "clear", "return", "jump", "jump0", "sprite", "bcd", "save", "load", "key", "-key", "hex",
+    "random", "delay", "buzzer", "if", "then", "begin", "else", "end", "loop", "while", "again",
+    "native", "bighex", "exit",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Span {
+    Text,
+    Comment,
+    Directive, // :const, :org, ... and the : of labels
+    Label,     // Label being defined
+    Keyword,
+    Register,
+    Number,
+}
+
+impl Span {
+    fn color(&self) -> [f32; 4] {
+        match self {
+            Span::Text => [0.85, 0.85, 0.85, 1.0],
+            Span::Comment => [0.5, 0.6, 0.5, 1.0],
+            Span::Directive => [0.8, 0.5, 1.0, 1.0],
+            Span::Label => [1.0, 0.85, 0.4, 1.0],
+            Span::Keyword => [0.4, 0.7, 1.0, 1.0],
+            Span::Register => [0.5, 0.9, 0.8, 1.0],
+            Span::Number => [1.0, 0.6, 0.4, 1.0],
+        }
+    }
+}
+
+fn token_span(token: &str) -> Span {
+    let lower = token.to_ascii_lowercase();
+    let is_number = lower
+        .strip_prefix('-')
+        .unwrap_or(&lower)
+        .starts_with(|c: char| c.is_ascii_digit());
+    let is_register = lower == "i"
+        || (lower.len() == 2
+            && lower.starts_with('v')
+            && lower[1..].chars().all(|c| c.is_ascii_hexdigit()));
+    // Not := and the like
+    let is_directive = token
+        .strip_prefix(':')
+        .is_some_and(|name| name.is_empty() || name.starts_with(|c: char| c.is_alphabetic()));
+    if is_directive {
+        Span::Directive
+    } else if is_number {
+        Span::Number
+    } else if is_register {
+        Span::Register
+    } else if KEYWORDS.contains(&lower.as_str()) {
+        Span::Keyword
+    } else {
+        Span::Text
+    }
+}
+
+// Splits a line into colored spans, whitespace included, so the spans
+// put back together give the line.
+fn highlight(line: &str) -> Vec<(Span, &str)> {
+    let (code, comment) = match line.find('#') {
+        Some(at) => line.split_at(at),
+        None => (line, ""),
+    };
+    let mut spans = Vec::new();
+    let mut defining_label = false;
+    let mut rest = code;
+    while !rest.is_empty() {
+        let whitespace = rest.len() - rest.trim_start().len();
+        if whitespace > 0 {
+            spans.push((Span::Text, &rest[..whitespace]));
+            rest = &rest[whitespace..];
+            continue;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..end];
+        let span = if defining_label {
+            Span::Label
+        } else {
+            token_span(token)
+        };
+        defining_label = token == ":";
+        spans.push((span, token));
+        rest = &rest[end..];
+    }
+    if !comment.is_empty() {
+        spans.push((Span::Comment, comment));
+    }
+    spans
+}
+
+// An assembled program, ready to run.
+pub struct Build {
+    pub rom: PendingRom,
+    pub symbols: SymbolTable,
+}
+
+pub struct CodeEditorState {
+    source: String,
+    path: Option<PathBuf>,
+    error: Option<AsmError>,
+    status: Option<String>,
+    pub assemble_requested: bool, // F4 was pressed
+}
+
+impl Default for CodeEditorState {
+    fn default() -> CodeEditorState {
+        CodeEditorState {
+            source: NEW_PROGRAM.to_string(),
+            path: None,
+            error: None,
+            status: None,
+            assemble_requested: false,
+        }
+    }
+}
+
+impl CodeEditorState {
+    // Assembles the source. Its labels become the debugger's symbols.
+    fn assemble(&mut self) -> Option<Build> {
+        match assembler::assemble(&self.source) {
+            Ok(program) => {
+                self.error = None;
+                self.status =
+                    Some(tr("Assembled {} bytes").replace("{}", &program.rom.len().to_string()));
+                let mut symbols = SymbolTable::new();
+                for (name, addr) in &program.labels {
+                    symbols.insert(name, *addr);
+                }
+                Some(Build {
+                    rom: hex_import::import(program.rom),
+                    symbols,
+                })
+            }
+            Err(err) => {
+                self.status = None;
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+
+    fn open(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Octo", &["8o", "txt"])
+            .pick_file()
+        else {
+            return;
+        };
+        self.load(path);
+    }
+
+    pub fn load(&mut self, path: PathBuf) {
+        match fs::read_to_string(&path) {
+            Ok(source) => {
+                self.source = source;
+                self.path = Some(path);
+                self.error = None;
+                self.status = None;
+            }
+            Err(err) => self.status = Some(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn save(&mut self, pick_path: bool) {
+        let path = match &self.path {
+            Some(path) if !pick_path => path.clone(),
+            _ => {
+                let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Octo", &["8o"])
+                    .save_file()
+                else {
+                    return;
+                };
+                path
+            }
+        };
+        self.status = Some(match fs::write(&path, &self.source) {
+            Ok(()) => {
+                let status = format!("{} {}", tr("Saved"), path.display());
+                self.path = Some(path);
+                status
+            }
+            Err(err) => format!("{}: {}", path.display(), err),
+        });
+    }
+}
+
+// A plain text editor for Octo assembly, with a highlighted listing
+// marking errors. Assembling resets the machine with the new program.
+pub fn draw(ui: &Ui, state: &mut CodeEditorState, opened: &mut bool) -> Option<Build> {
+    let mut build = None;
+    let title = match &state.path {
+        Some(path) => format!(
+            "{} - {}",
+            tr("Code editor"),
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        None => tr("Code editor").to_string(),
+    };
+    ui.window(format!("{}###code_editor", title))
+        .opened(opened)
+        .size([520.0, 480.0], Condition::FirstUseEver)
+        .build(|| {
+            if ui.button(tr("Open file...")) {
+                state.open();
+            }
+            ui.same_line();
+            if ui.button(tr("Save")) {
+                state.save(false);
+            }
+            ui.same_line();
+            if ui.button(tr("Save as...")) {
+                state.save(true);
+            }
+            ui.same_line();
+            if ui.button(tr("Assemble & Run (F4)")) || state.assemble_requested {
+                build = state.assemble();
+            }
+            state.assemble_requested = false;
+
+            match (&state.error, &state.status) {
+                (Some(err), _) => ui.text_colored(ERROR_COLOR, err.to_string()),
+                (None, Some(status)) => ui.text_disabled(status),
+                (None, None) => ui.text_disabled(tr("Octo assembly, without macros.")),
+            }
+
+            let height = ui.content_region_avail()[1] * 0.55;
+            ui.input_text_multiline("##source", &mut state.source, [-1.0, height])
+                .allow_tab_input(true)
+                .build();
+
+            ui.text(tr("Highlighted source"));
+            ui.child_window("##listing").border(true).build(|| {
+                draw_listing(ui, state);
+            });
+        });
+    build
+}
+
+fn draw_listing(ui: &Ui, state: &CodeEditorState) {
+    let error_line = state.error.as_ref().map(|err| err.line);
+    for (idx, line) in state.source.lines().enumerate() {
+        let number = idx + 1;
+        if error_line == Some(number) {
+            ui.text_colored(ERROR_COLOR, format!("{:>4}!", number));
+            if ui.is_item_hovered() {
+                if let Some(err) = &state.error {
+                    ui.tooltip_text(&err.message);
+                }
+            }
+        } else {
+            ui.text_disabled(format!("{:>4} ", number));
+        }
+        for (span, text) in highlight(line) {
+            ui.same_line_with_spacing(0.0, 0.0);
+            ui.text_colored(span.color(), text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod code_editor_tests {
+    use super::{highlight, CodeEditorState, Span};
+
+    #[test]
+    fn test_highlight() {
+        let line = ": main  v0 := 0x1F # Start";
+        let spans = highlight(line);
+        assert_eq!(
+            spans.iter().map(|(_, text)| *text).collect::<String>(),
+            line
+        );
+        let tokens: Vec<_> = spans
+            .into_iter()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (Span::Directive, ":"),
+                (Span::Label, "main"),
+                (Span::Register, "v0"),
+                (Span::Text, ":="),
+                (Span::Number, "0x1F"),
+                (Span::Comment, "# Start"),
+            ]
+        );
+        assert_eq!(highlight(":const SPEED 4")[0], (Span::Directive, ":const"));
+        assert_eq!(highlight("loop")[0], (Span::Keyword, "loop"));
+    }
+
+    #[test]
+    fn test_assemble() {
+        let mut state = CodeEditorState::default();
+        let build = state.assemble().unwrap();
+        assert_eq!(build.rom.rom, vec![0x00, 0xE0, 0x12, 0x02]);
+        assert_eq!(build.symbols.address("main"), Some(0x0200));
+
+        state.source = ": main\n  bogus := 3".to_string();
+        assert!(state.assemble().is_none());
+        assert_eq!(state.error.as_ref().map(|err| err.line), Some(2));
+    }
+}
@@ -0,0 +1,380 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{collections::BTreeMap, fs, io};
+
+use crate::{
+    audio::{DEFAULT_BUFFER_SIZE, DEFAULT_SAMPLE_RATE},
+    core::key_macro::KeyMacro,
+    graphics::{
+        Border, ColorAdjustment, Rotation, BRIGHTNESS_RANGE, CONTRAST_RANGE, DEFAULT_BORDER,
+        GAMMA_RANGE,
+    },
+    locale::Language,
+    theme::{Theme, DEFAULT_ACCENT},
+};
+
+const CONFIG_FILE: &str = "chip-8-emu.cfg";
+
+pub const MAX_SCREEN_PADDING: u32 = 64;
+
+// User settings, stored as "key = value" lines. Unknown keys and
+// invalid values are ignored, falling back to the defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub theme: Theme,
+    pub accent_color: [f32; 3],
+    pub language: Language,
+    pub welcome_shown: bool, // The first-run popup was already dismissed
+    pub audio_sample_rate: i32,
+    pub audio_buffer_size: u16, // Samples, 0 = SDL's default
+    pub display_rotation: Rotation,
+    pub display_mirrored: bool,
+    pub palette_borders: BTreeMap<String, Border>, // By palette name
+    pub screen_padding: u32,                       // Unscaled pixels around the screen
+    pub pixel_grid: f32,                           // Grid opacity, 0 = off
+    pub color_adjustment: ColorAdjustment,
+    pub reduce_flashing: bool, // Photosensitivity guard, see FlashGuard
+    pub key_macros: BTreeMap<usize, KeyMacro>, // By hotkey slot
+    pub rom_palettes: BTreeMap<u64, String>, // Palette names by ROM hash
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            theme: Theme::Dark,
+            accent_color: DEFAULT_ACCENT,
+            language: Language::English,
+            welcome_shown: false,
+            audio_sample_rate: DEFAULT_SAMPLE_RATE,
+            audio_buffer_size: DEFAULT_BUFFER_SIZE,
+            display_rotation: Rotation::None,
+            display_mirrored: false,
+            palette_borders: BTreeMap::new(),
+            screen_padding: 0,
+            pixel_grid: 0.0,
+            color_adjustment: ColorAdjustment::default(),
+            reduce_flashing: false,
+            key_macros: BTreeMap::new(),
+            rom_palettes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Config {
+        fs::read_to_string(CONFIG_FILE)
+            .map(|source| Config::parse(&source))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(CONFIG_FILE, self.serialize())
+    }
+
+    pub fn parse(source: &str) -> Config {
+        let mut config = Config::default();
+
+        for line in source.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+
+            let key = key.trim();
+            if let Some(palette) = key.strip_prefix("palette_border.") {
+                if let Some(border) = parse_border(value) {
+                    config.palette_borders.insert(palette.to_string(), border);
+                }
+                continue;
+            }
+            if let Some(rom_hash) = key.strip_prefix("rom_palette.") {
+                if let (Ok(rom_hash), false) = (u64::from_str_radix(rom_hash, 16), value.is_empty())
+                {
+                    config.rom_palettes.insert(rom_hash, value.to_string());
+                }
+                continue;
+            }
+            if let Some(slot) = key.strip_prefix("key_macro.") {
+                if let (Ok(slot), Some(key_macro)) = (slot.parse(), KeyMacro::parse(value)) {
+                    config.key_macros.insert(slot, key_macro);
+                }
+                continue;
+            }
+
+            match key {
+                "theme" => {
+                    if let Some(theme) = Theme::from_name(value) {
+                        config.theme = theme;
+                    }
+                }
+                "accent_color" => {
+                    if let Some(color) = parse_color(value) {
+                        config.accent_color = color;
+                    }
+                }
+                "language" => {
+                    if let Some(language) = Language::from_code(value) {
+                        config.language = language;
+                    }
+                }
+                "welcome_shown" => {
+                    if let Ok(shown) = value.parse() {
+                        config.welcome_shown = shown;
+                    }
+                }
+                "audio_sample_rate" => {
+                    if let Ok(sample_rate @ 8_000..=192_000) = value.parse() {
+                        config.audio_sample_rate = sample_rate;
+                    }
+                }
+                "audio_buffer_size" => {
+                    if let Ok(buffer_size) = value.parse::<u16>() {
+                        if buffer_size == 0 || buffer_size.is_power_of_two() {
+                            config.audio_buffer_size = buffer_size;
+                        }
+                    }
+                }
+                "display_rotation" => {
+                    if let Some(rotation) = value.parse().ok().and_then(Rotation::from_degrees) {
+                        config.display_rotation = rotation;
+                    }
+                }
+                "display_mirrored" => {
+                    if let Ok(mirrored) = value.parse() {
+                        config.display_mirrored = mirrored;
+                    }
+                }
+                "screen_padding" => {
+                    if let Ok(padding @ 0..=MAX_SCREEN_PADDING) = value.parse() {
+                        config.screen_padding = padding;
+                    }
+                }
+                "pixel_grid" => {
+                    if let Ok(opacity) = value.parse::<f32>() {
+                        if (0.0..=1.0).contains(&opacity) {
+                            config.pixel_grid = opacity;
+                        }
+                    }
+                }
+                "reduce_flashing" => {
+                    if let Ok(reduce) = value.parse() {
+                        config.reduce_flashing = reduce;
+                    }
+                }
+                "gamma" => {
+                    if let Some(gamma) = parse_in_range(value, GAMMA_RANGE) {
+                        config.color_adjustment.gamma = gamma;
+                    }
+                }
+                "brightness" => {
+                    if let Some(brightness) = parse_in_range(value, BRIGHTNESS_RANGE) {
+                        config.color_adjustment.brightness = brightness;
+                    }
+                }
+                "contrast" => {
+                    if let Some(contrast) = parse_in_range(value, CONTRAST_RANGE) {
+                        config.color_adjustment.contrast = contrast;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+
+    pub fn palette_border(&self, palette: &str) -> Border {
+        self.palette_borders
+            .get(palette)
+            .copied()
+            .unwrap_or(DEFAULT_BORDER)
+    }
+
+    pub fn rom_palette(&self, rom_hash: u64) -> Option<&str> {
+        self.rom_palettes.get(&rom_hash).map(String::as_str)
+    }
+
+    // Remembers the palette last used with a ROM, or forgets it with
+    // None. Returns whether anything changed.
+    pub fn set_rom_palette(&mut self, rom_hash: u64, palette: Option<&str>) -> bool {
+        if self.rom_palette(rom_hash) == palette {
+            return false;
+        }
+        match palette {
+            Some(palette) => self.rom_palettes.insert(rom_hash, palette.to_string()),
+            None => self.rom_palettes.remove(&rom_hash),
+        };
+        true
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut output = format!(
+            "theme = {}\naccent_color = {}\nlanguage = {}\nwelcome_shown = {}\n\
+             audio_sample_rate = {}\naudio_buffer_size = {}\n\
+             display_rotation = {}\ndisplay_mirrored = {}\nscreen_padding = {}\n\
+             pixel_grid = {}\ngamma = {}\nbrightness = {}\ncontrast = {}\n\
+             reduce_flashing = {}\n",
+            self.theme.name(),
+            format_color(self.accent_color),
+            self.language.code(),
+            self.welcome_shown,
+            self.audio_sample_rate,
+            self.audio_buffer_size,
+            self.display_rotation.degrees(),
+            self.display_mirrored,
+            self.screen_padding,
+            self.pixel_grid,
+            self.color_adjustment.gamma,
+            self.color_adjustment.brightness,
+            self.color_adjustment.contrast,
+            self.reduce_flashing
+        );
+        for (palette, border) in &self.palette_borders {
+            output += &format!("palette_border.{} = {}\n", palette, format_border(*border));
+        }
+        for (slot, key_macro) in &self.key_macros {
+            output += &format!("key_macro.{} = {}\n", slot, key_macro.to_text());
+        }
+        for (rom_hash, palette) in &self.rom_palettes {
+            output += &format!("rom_palette.{:016X} = {}\n", rom_hash, palette);
+        }
+        output
+    }
+}
+
+fn parse_in_range(value: &str, (min, max): (f32, f32)) -> Option<f32> {
+    value
+        .parse()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+}
+
+// Colors are stored as "#RRGGBB".
+fn format_color(color: [f32; 3]) -> String {
+    let [r, g, b] = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+fn parse_color(value: &str) -> Option<[f32; 3]> {
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some([16, 8, 0].map(|shift| ((rgb >> shift) & 0xFF) as f32 / 255.0))
+}
+
+// Borders are stored as a color, or "off_pixels".
+fn format_border(border: Border) -> String {
+    match border {
+        Border::Color(color) => format_color(color),
+        Border::OffPixels => "off_pixels".to_string(),
+    }
+}
+
+fn parse_border(value: &str) -> Option<Border> {
+    match value {
+        "off_pixels" => Some(Border::OffPixels),
+        color => parse_color(color).map(Border::Color),
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use std::collections::BTreeMap;
+
+    use super::Config;
+    use crate::{
+        core::key_macro::KeyMacro,
+        graphics::{Border, ColorAdjustment, Rotation, DEFAULT_BORDER},
+        locale::Language,
+        theme::Theme,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config {
+            theme: Theme::Light,
+            accent_color: [1.0, 0.0, 0.2],
+            language: Language::Spanish,
+            welcome_shown: true,
+            audio_sample_rate: 48_000,
+            audio_buffer_size: 512,
+            display_rotation: Rotation::Cw270,
+            display_mirrored: true,
+            palette_borders: BTreeMap::from([
+                ("Default".to_string(), Border::OffPixels),
+                ("Red".to_string(), Border::Color([1.0, 1.0, 0.0])),
+            ]),
+            screen_padding: 16,
+            pixel_grid: 0.25,
+            color_adjustment: ColorAdjustment {
+                gamma: 1.2,
+                brightness: -0.1,
+                contrast: 1.5,
+            },
+            reduce_flashing: true,
+            key_macros: BTreeMap::from([(2, KeyMacro::parse("0+5 4-5").unwrap())]),
+            rom_palettes: BTreeMap::from([(0xC8, "Brown".to_string())]),
+        };
+        let parsed = Config::parse(&config.serialize());
+        assert_eq!(parsed.theme, Theme::Light);
+        assert_eq!(parsed.language, Language::Spanish);
+        assert!(parsed.welcome_shown);
+        assert_eq!(parsed.audio_sample_rate, 48_000);
+        assert_eq!(parsed.audio_buffer_size, 512);
+        assert_eq!(parsed.display_rotation, Rotation::Cw270);
+        assert!(parsed.display_mirrored);
+        assert_eq!(parsed.palette_border("Default"), Border::OffPixels);
+        assert_eq!(parsed.palette_border("Red"), Border::Color([1.0, 1.0, 0.0]));
+        assert_eq!(parsed.palette_border("Brown"), DEFAULT_BORDER);
+        assert_eq!(parsed.screen_padding, 16);
+        assert_eq!(parsed.pixel_grid, 0.25);
+        assert_eq!(parsed.color_adjustment, config.color_adjustment);
+        assert!(parsed.reduce_flashing);
+        assert_eq!(parsed.key_macros[&2].to_text(), "0+5 4-5");
+        assert_eq!(parsed.rom_palette(0xC8), Some("Brown"));
+        assert_eq!(parsed.serialize(), config.serialize());
+        assert_eq!(parsed.accent_color[0], 1.0);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let config = Config::parse(
+            "theme = Neon\naccent_color = red\nunknown = 1\nbroken\n\
+             audio_sample_rate = 5\naudio_buffer_size = 1000\n\
+             display_rotation = 45\ndisplay_mirrored = yes\n\
+             screen_padding = 500\npalette_border.Default = blue\n\
+             pixel_grid = 2\nkey_macro.1 = 0+G\nkey_macro.x = 0+1\n\
+             rom_palette.XYZ = Red\nrom_palette.1F =\n\
+             gamma = 0\nbrightness = 1\ncontrast = high\nreduce_flashing = 1",
+        );
+        assert_eq!(config, Config::default());
+
+        let config = Config::parse("  theme=Classic  \naccent_color = #00FF00");
+        assert_eq!(config.theme, Theme::Classic);
+        assert_eq!(config.accent_color, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rom_palettes() {
+        let mut config = Config::default();
+        assert_eq!(config.rom_palette(0xAB), None);
+        assert!(config.set_rom_palette(0xAB, Some("Red")));
+        assert!(!config.set_rom_palette(0xAB, Some("Red")));
+        assert!(config.set_rom_palette(0xAB, Some("Inverted")));
+        assert_eq!(config.rom_palette(0xAB), Some("Inverted"));
+        assert_eq!(config.rom_palette(0xCD), None);
+
+        assert!(config.set_rom_palette(0xAB, None));
+        assert!(!config.set_rom_palette(0xAB, None));
+        assert_eq!(config, Config::default());
+    }
+}
@@ -0,0 +1,224 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    fs,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use imgui::Ui;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::locale::tr;
+
+// Oldest messages are dropped past this amount
+pub const MAX_ENTRIES: usize = 1000;
+
+const LEVELS: [LevelFilter; 5] = [
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+pub struct Entry {
+    pub elapsed: Duration, // Since the logger was installed
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl Entry {
+    pub fn format(&self) -> String {
+        format!(
+            "[{:>9.3}] {:<5} {}: {}",
+            self.elapsed.as_secs_f64(),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+// Keeps every message from the log facade for the console window,
+// echoing them to stderr as well.
+struct ConsoleLogger {
+    started: OnceLock<Instant>,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+static LOGGER: ConsoleLogger = ConsoleLogger {
+    started: OnceLock::new(),
+    entries: Mutex::new(VecDeque::new()),
+};
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let started = self.started.get_or_init(Instant::now);
+        let entry = Entry {
+            elapsed: started.elapsed(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        eprintln!("{}", entry.format());
+        push_entry(&mut self.entries.lock().unwrap(), entry);
+    }
+
+    fn flush(&self) {}
+}
+
+fn push_entry(entries: &mut VecDeque<Entry>, entry: Entry) {
+    if entries.len() == MAX_ENTRIES {
+        entries.pop_front();
+    }
+    entries.push_back(entry);
+}
+
+pub fn init() {
+    LOGGER.started.get_or_init(Instant::now);
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+// Every stored message up to the given level, one per line.
+pub fn export(max_level: LevelFilter) -> String {
+    let entries = LOGGER.entries.lock().unwrap();
+    let mut output = String::new();
+    for entry in entries.iter().filter(|entry| entry.level <= max_level) {
+        let _ = writeln!(output, "{}", entry.format());
+    }
+    output
+}
+
+pub struct ConsoleState {
+    pub max_level: LevelFilter,
+    pub auto_scroll: bool,
+}
+
+impl Default for ConsoleState {
+    fn default() -> ConsoleState {
+        ConsoleState {
+            max_level: LevelFilter::Info,
+            auto_scroll: true,
+        }
+    }
+}
+
+fn level_color(level: Level) -> [f32; 4] {
+    match level {
+        Level::Error => [1.0, 0.35, 0.35, 1.0],
+        Level::Warn => [1.0, 0.8, 0.3, 1.0],
+        Level::Info => [0.9, 0.9, 0.9, 1.0],
+        Level::Debug | Level::Trace => [0.6, 0.6, 0.6, 1.0],
+    }
+}
+
+pub fn draw(ui: &Ui, state: &mut ConsoleState, opened: &mut bool) {
+    ui.window(format!("{}###console", tr("Console")))
+        .opened(opened)
+        .size([520.0, 260.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            let mut level_idx = LEVELS
+                .iter()
+                .position(|&level| level == state.max_level)
+                .unwrap_or(2);
+            ui.set_next_item_width(ui.current_font_size() * 6.0);
+            let names = LEVELS.map(|level| level.as_str());
+            if ui.combo_simple_string(tr("Level"), &mut level_idx, &names) {
+                state.max_level = LEVELS[level_idx];
+                // Debug and trace messages are only produced when shown
+                log::set_max_level(state.max_level.max(LevelFilter::Info));
+            }
+            ui.same_line();
+            ui.checkbox(tr("Auto-scroll"), &mut state.auto_scroll);
+            ui.same_line();
+            if ui.button(tr("Clear")) {
+                LOGGER.entries.lock().unwrap().clear();
+            }
+            ui.same_line();
+            if ui.button(tr("Export...")) {
+                log_export_window(&export(state.max_level));
+            }
+            ui.separator();
+
+            ui.child_window("entries").build(|| {
+                let entries = LOGGER.entries.lock().unwrap();
+                for entry in entries
+                    .iter()
+                    .filter(|entry| entry.level <= state.max_level)
+                {
+                    ui.text_colored(level_color(entry.level), entry.format());
+                }
+                if state.auto_scroll && ui.scroll_y() >= ui.scroll_max_y() {
+                    ui.set_scroll_here_y_with_ratio(1.0);
+                }
+            });
+        });
+}
+
+fn log_export_window(contents: &str) {
+    let path = std::env::current_dir().unwrap();
+    let res = rfd::FileDialog::new()
+        .add_filter("log", &["log", "txt"])
+        .set_directory(&path)
+        .set_file_name("chip-8-emu.log")
+        .save_file();
+
+    if let Some(file_path) = res {
+        if let Err(err) = fs::write(file_path, contents) {
+            log::error!("Could not export the log: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod console_tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use log::Level;
+
+    use super::{push_entry, Entry, MAX_ENTRIES};
+
+    fn entry(message: &str) -> Entry {
+        Entry {
+            elapsed: Duration::from_millis(1500),
+            level: Level::Warn,
+            target: "chip_8_emu::core::cpu".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            entry("Halted").format(),
+            "[    1.500] WARN  chip_8_emu::core::cpu: Halted"
+        );
+    }
+
+    #[test]
+    fn test_drops_oldest() {
+        let mut entries = VecDeque::new();
+        for idx in 0..MAX_ENTRIES + 5 {
+            push_entry(&mut entries, entry(&idx.to_string()));
+        }
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.front().unwrap().message, "5");
+    }
+}
@@ -0,0 +1,377 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{collections::BTreeSet, fmt};
+
+use super::{
+    instruction::{Instruction, Opcode},
+    memory::{ETI_START_MEM, MEMORY_SIZE, PROGRAM_START_MEM},
+};
+
+// Fraction of printable bytes from which a file is taken as text
+const TEXT_RATIO: f32 = 0.9;
+
+// Signatures of common files that get picked by mistake
+const SIGNATURES: [(&[u8], &str); 12] = [
+    (b"\x89PNG", "PNG image"),
+    (b"\xFF\xD8\xFF", "JPEG image"),
+    (b"GIF8", "GIF image"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"\x1F\x8B", "gzip archive"),
+    (b"%PDF", "PDF document"),
+    (b"\x7FELF", "ELF executable"),
+    (b"MZ", "Windows executable"),
+    (b"C8SS", "save state"),
+    (b"C8RP", "replay"),
+    (b"PATCH", "IPS patch"),
+    (b"BPS1", "BPS patch"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Extension::SuperChip => write!(f, "SUPER-CHIP"),
+            Extension::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    Empty,
+    TooLarge { size: usize, available: usize },
+    Foreign(&'static str),
+    Text,
+    // Reachable opcodes no known variant has
+    UnknownInstructions { count: usize, first: u16 },
+    // Reachable 0nnn calls, which run machine code on real hardware
+    MachineCode { count: usize, first: u16 },
+    // The program makes more sense loaded somewhere else
+    LoadAddress(u16),
+}
+
+impl Finding {
+    // Whether the ROM can't be loaded at all.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Finding::TooLarge { .. })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Empty => write!(f, "The file is empty."),
+            Finding::TooLarge { size, available } => write!(
+                f,
+                "Too large to fit in memory ({} bytes, only {} available).",
+                size, available
+            ),
+            Finding::Foreign(kind) => write!(f, "Looks like a {}, not a ROM.", kind),
+            Finding::Text => write!(f, "Looks like a text file, not a ROM."),
+            Finding::UnknownInstructions { count, first } => write!(
+                f,
+                "{} unknown instructions can be reached, the first at 0x{:04X}.",
+                count, first
+            ),
+            Finding::MachineCode { count, first } => write!(
+                f,
+                "{} machine code calls (0nnn) are ignored, the first at 0x{:04X}.",
+                count, first
+            ),
+            Finding::LoadAddress(ETI_START_MEM) => write!(
+                f,
+                "Looks like an ETI 660 program, meant to be loaded at 0x{:04X}.",
+                ETI_START_MEM
+            ),
+            Finding::LoadAddress(base) => {
+                write!(f, "Looks like it's meant to be loaded at 0x{:04X}.", base)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub size: usize,
+    pub base: u16,        // Suggested load address
+    pub reachable: usize, // Instructions found by following the program
+    pub findings: Vec<Finding>,
+    pub extensions: Vec<Extension>,
+}
+
+impl Analysis {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty() && self.extensions.is_empty()
+    }
+
+    pub fn can_load(&self) -> bool {
+        !self.findings.iter().any(Finding::is_fatal)
+    }
+}
+
+// Checks a ROM without running it: its size, whether it's some other
+// kind of file, and the instructions reachable from the start, which
+// tell the extensions it needs.
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let mut findings = Vec::new();
+    if rom.is_empty() {
+        findings.push(Finding::Empty);
+    }
+
+    if let Some((_, kind)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| rom.starts_with(signature))
+    {
+        findings.push(Finding::Foreign(kind));
+    } else if is_text(rom) {
+        findings.push(Finding::Text);
+    }
+
+    // Programs are usually loaded at 0x200, but ones for the ETI 660
+    // jump around 0x600 instead. Picks the base where fewer jumps land
+    // outside the program and fewer unknown instructions are reached.
+    let mut base = PROGRAM_START_MEM;
+    let mut walk = walk(rom, base);
+    if walk.problems() > 0 {
+        let eti_walk = self::walk(rom, ETI_START_MEM);
+        if eti_walk.problems() < walk.problems() {
+            base = ETI_START_MEM;
+            walk = eti_walk;
+            findings.push(Finding::LoadAddress(base));
+        }
+    }
+
+    let available = MEMORY_SIZE - base as usize;
+    if rom.len() > available {
+        findings.push(Finding::TooLarge {
+            size: rom.len(),
+            available,
+        });
+    }
+
+    if let Some(&first) = walk.unknown.first() {
+        findings.push(Finding::UnknownInstructions {
+            count: walk.unknown.len(),
+            first,
+        });
+    }
+    if let Some(&first) = walk.machine_code.first() {
+        findings.push(Finding::MachineCode {
+            count: walk.machine_code.len(),
+            first,
+        });
+    }
+
+    Analysis {
+        size: rom.len(),
+        base,
+        reachable: walk.visited.len(),
+        findings,
+        extensions: walk.extensions.into_iter().collect(),
+    }
+}
+
+fn is_text(rom: &[u8]) -> bool {
+    if rom.len() < 16 || !rom.contains(&b'\n') {
+        return false;
+    }
+    let printable = rom
+        .iter()
+        .filter(|&&byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace())
+        .count();
+    printable as f32 / rom.len() as f32 >= TEXT_RATIO
+}
+
+#[derive(Default)]
+struct Walk {
+    visited: BTreeSet<u16>,
+    unknown: Vec<u16>,
+    machine_code: Vec<u16>,
+    extensions: BTreeSet<Extension>,
+    outside: usize, // Jumps and calls out of the program
+}
+
+impl Walk {
+    fn problems(&self) -> usize {
+        self.unknown.len() + self.outside
+    }
+}
+
+// Follows every path from the start of the program, loaded at base.
+// Indirect jumps (Bnnn) can't be followed.
+fn walk(rom: &[u8], base: u16) -> Walk {
+    let mut walk = Walk::default();
+    let end = (base as usize + rom.len()).min(MEMORY_SIZE);
+    let read = |addr: u16| {
+        let offset = addr as usize - base as usize;
+        u16::from_be_bytes([rom[offset], rom[offset + 1]])
+    };
+
+    let mut pending = vec![base];
+    while let Some(addr) = pending.pop() {
+        if addr < base || addr as usize + 1 >= end || !walk.visited.insert(addr) {
+            continue;
+        }
+
+        let code = read(addr);
+        let instruction = Instruction::new((
+            (code >> 12) as u8,
+            (code >> 8 & 0xF) as u8,
+            (code >> 4 & 0xF) as u8,
+            (code & 0xF) as u8,
+        ));
+        let next = addr + 2;
+
+        if let Some(extension) = extension(code) {
+            walk.extensions.insert(extension);
+            match code {
+                0x00FD => {}                      // Exit
+                0xF000 => pending.push(next + 2), // Long LD I, the address follows
+                _ => pending.push(next),
+            }
+            continue;
+        }
+
+        match instruction.opcode() {
+            Opcode::Ret | Opcode::JumpV0 => {}
+            Opcode::Jump | Opcode::Call => {
+                let target = instruction.nnn();
+                if target < base || target as usize >= end {
+                    walk.outside += 1;
+                }
+                pending.push(target);
+                if instruction.opcode() == Opcode::Call {
+                    pending.push(next);
+                }
+            }
+            Opcode::SkipEqByte
+            | Opcode::SkipNeByte
+            | Opcode::SkipEqReg
+            | Opcode::SkipNeReg
+            | Opcode::SkipKeyPressed
+            | Opcode::SkipKeyReleased => pending.extend([next, next + 2]),
+            Opcode::Sys => {
+                walk.machine_code.push(addr);
+                pending.push(next);
+            }
+            Opcode::Unknown => walk.unknown.push(addr),
+            _ => pending.push(next),
+        }
+    }
+
+    walk.unknown.sort();
+    walk.machine_code.sort();
+    walk
+}
+
+// The extension an opcode belongs to, if it isn't base CHIP-8.
+fn extension(code: u16) -> Option<Extension> {
+    match (code >> 12, code >> 8 & 0xF, code >> 4 & 0xF, code & 0xF) {
+        (0, 0, 0xC, n) if n > 0 => Some(Extension::SuperChip), // Scroll down
+        (0, 0, 0xF, 0xB..=0xF) => Some(Extension::SuperChip),  // Scroll, exit, resolution
+        (0xD, _, _, 0) => Some(Extension::SuperChip),          // 16x16 sprite
+        (0xF, _, 3, 0) | (0xF, _, 7, 5) | (0xF, _, 8, 5) => Some(Extension::SuperChip),
+        (0, 0, 0xD, n) if n > 0 => Some(Extension::XoChip), // Scroll up
+        (5, _, _, 2) | (5, _, _, 3) => Some(Extension::XoChip), // Register ranges
+        (0xF, 0, 0, 0) | (0xF, _, 0, 1) | (0xF, 0, 0, 2) | (0xF, _, 3, 0xA) => {
+            Some(Extension::XoChip)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod analyzer_tests {
+    use super::{analyze, Extension, Finding};
+
+    #[test]
+    fn test_clean_rom() {
+        // LD I, 0x20A - DRW V0, V0, 5 - SE V0, 1 - CALL 0x20C - JP 0x208 - data - RET
+        let rom = [
+            0xA2, 0x0A, 0xD0, 0x05, 0x30, 0x01, 0x22, 0x0C, 0x12, 0x08, 0xFF, 0xFF, 0x00, 0xEE,
+        ];
+        let analysis = analyze(&rom);
+        assert!(analysis.is_clean());
+        assert!(analysis.can_load());
+        assert_eq!(analysis.size, 14);
+        // The sprite data at 0x20A is never reached
+        assert_eq!(analysis.reachable, 6);
+    }
+
+    #[test]
+    fn test_extensions() {
+        // HIGH - LD F, V0 - LD I, long 0x0300 - JP 0x208
+        let rom = [0x00, 0xFF, 0xF0, 0x30, 0xF0, 0x00, 0x03, 0x00, 0x12, 0x08];
+        let analysis = analyze(&rom);
+        assert_eq!(
+            analysis.extensions,
+            vec![Extension::SuperChip, Extension::XoChip]
+        );
+        // The long address isn't decoded as an instruction
+        assert!(analysis.findings.is_empty());
+    }
+
+    #[test]
+    fn test_findings() {
+        assert_eq!(analyze(&[]).findings, vec![Finding::Empty]);
+
+        let too_large = analyze(&[0x12; 4000]);
+        assert!(!too_large.can_load());
+        assert_eq!(
+            too_large.findings,
+            vec![Finding::TooLarge {
+                size: 4000,
+                available: 3584
+            }]
+        );
+
+        let png = analyze(b"\x89PNG\r\n\x1A\n\0\0\0\x0DIHDR");
+        assert!(png.findings.contains(&Finding::Foreign("PNG image")));
+
+        let text = analyze(b"; My game\nLD V0, 1\nLD V1, 2\nJP start\n");
+        assert!(text.findings.contains(&Finding::Text));
+
+        // LD V0, 1 - SE V0, 1 - unknown 8xy8 - SYS 0x123 - JP 0x200
+        let rom = [0x60, 0x01, 0x30, 0x01, 0x81, 0x28, 0x01, 0x23, 0x12, 0x00];
+        assert_eq!(
+            analyze(&rom).findings,
+            vec![
+                Finding::UnknownInstructions {
+                    count: 1,
+                    first: 0x204
+                },
+                Finding::MachineCode {
+                    count: 1,
+                    first: 0x206
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_address() {
+        // LD V0, 1 - CALL 0x606 - JP 0x604 - RET
+        let rom = [0x60, 0x01, 0x26, 0x06, 0x16, 0x04, 0x00, 0xEE];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.base, 0x600);
+        assert_eq!(analysis.findings, vec![Finding::LoadAddress(0x600)]);
+        assert_eq!(analysis.reachable, 4);
+
+        // Jumps inside the program at 0x200 keep it there
+        let rom = [0x60, 0x01, 0x22, 0x06, 0x12, 0x04, 0x00, 0xEE];
+        assert_eq!(analyze(&rom).base, 0x200);
+        assert!(analyze(&rom).is_clean());
+    }
+}
@@ -0,0 +1,684 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use super::memory::{MEMORY_SIZE, PROGRAM_START_MEM};
+
+// Assembles the plain CHIP-8 part of Octo's language: labels, :const,
+// :alias, :org, :byte, :call, :unpack, if/then, if/begin/else/end,
+// loop/while/again and every CHIP-8 statement. Macros, :calc and the
+// SCHIP and XO-CHIP extensions aren't supported.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}.", self.line, self.message)
+    }
+}
+
+pub struct Program {
+    pub rom: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: idx + 1,
+            })
+        })
+        .collect();
+
+    let mut assembler = Assembler {
+        tokens,
+        at: 0,
+        // Room for a jump to main, dropped if main comes first
+        rom: vec![0, 0],
+        here: PROGRAM_START_MEM as usize + 2,
+        main_jump: true,
+        labels: BTreeMap::new(),
+        consts: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+    };
+    while assembler.at < assembler.tokens.len() {
+        let token = assembler.next()?;
+        assembler.statement(token)?;
+    }
+    assembler.finish()
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+enum Fixup {
+    Address,    // The nnn of the instruction
+    Unpack(u8), // v0 := nibble and high address, v1 := low address
+}
+
+enum Block {
+    If(usize), // Address of the jump past the block
+    Else(usize),
+    Loop(usize, Vec<usize>), // Start, and the jumps out of it
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    at: usize,
+    rom: Vec<u8>,
+    here: usize,
+    main_jump: bool,
+    labels: BTreeMap<String, u16>,
+    consts: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    fixups: Vec<(usize, Fixup, Token<'a>)>,
+    blocks: Vec<(Block, usize)>,
+}
+
+fn error(line: usize, message: impl Into<String>) -> AsmError {
+    AsmError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+impl<'a> Assembler<'a> {
+    fn next(&mut self) -> Result<Token<'a>, AsmError> {
+        let token = self.tokens.get(self.at).copied().ok_or_else(|| {
+            let line = self.tokens.last().map_or(1, |token| token.line);
+            error(line, "Unexpected end of the program")
+        })?;
+        self.at += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(error(
+                token.line,
+                format!("Expected '{}', found '{}'", text, token.text),
+            ));
+        }
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, byte: u8, line: usize) -> Result<(), AsmError> {
+        if self.here >= MEMORY_SIZE {
+            return Err(error(line, "The program doesn't fit in memory"));
+        }
+        let idx = self.here - PROGRAM_START_MEM as usize;
+        if idx >= self.rom.len() {
+            self.rom.resize(idx + 1, 0);
+        }
+        self.rom[idx] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit(&mut self, opcode: u16, line: usize) -> Result<(), AsmError> {
+        self.emit_byte((opcode >> 8) as u8, line)?;
+        self.emit_byte(opcode as u8, line)
+    }
+
+    // Sets the nnn of an already emitted instruction.
+    fn patch(&mut self, addr: usize, nnn: u16) {
+        let idx = addr - PROGRAM_START_MEM as usize;
+        self.rom[idx] = (self.rom[idx] & 0xF0) | (nnn >> 8) as u8;
+        self.rom[idx + 1] = nnn as u8;
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        self.aliases.contains_key(text)
+            || (text.len() == 2
+                && text.starts_with(['v', 'V'])
+                && text[1..].chars().all(|c| c.is_ascii_hexdigit()))
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        if !self.is_register(token.text) {
+            return Err(error(
+                token.line,
+                format!("Expected a register, found '{}'", token.text),
+            ));
+        }
+        Ok(match self.aliases.get(token.text) {
+            Some(&register) => register as u16,
+            None => u16::from_str_radix(&token.text[1..], 16).unwrap(),
+        })
+    }
+
+    fn number(&self, text: &str) -> Option<i64> {
+        self.consts
+            .get(text)
+            .copied()
+            .or_else(|| parse_number(text))
+    }
+
+    fn value(&mut self, min: i64, max: i64) -> Result<i64, AsmError> {
+        let token = self.next()?;
+        match self.number(token.text) {
+            Some(value) if (min..=max).contains(&value) => Ok(value),
+            Some(value) => Err(error(
+                token.line,
+                format!("{} is out of range, from {} to {}", value, min, max),
+            )),
+            None => Err(error(
+                token.line,
+                format!("Expected a number, found '{}'", token.text),
+            )),
+        }
+    }
+
+    // Negative bytes are allowed, e.g. "v0 += -1".
+    fn byte(&mut self) -> Result<u16, AsmError> {
+        Ok(self.value(-128, 255)? as u8 as u16)
+    }
+
+    // A number, constant or label, resolved later if it's defined
+    // further down.
+    fn address(&mut self, opcode: u16, fixup: Fixup) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        if let Some(value) = self.number(token.text) {
+            if !(0..=0xFFF).contains(&value) {
+                return Err(error(token.line, format!("{:#X} is not an address", value)));
+            }
+            return Ok(opcode | value as u16);
+        }
+        if let Some(&addr) = self.labels.get(token.text) {
+            return Ok(opcode | addr);
+        }
+        if !is_identifier(token.text) {
+            return Err(error(
+                token.line,
+                format!("Expected an address, found '{}'", token.text),
+            ));
+        }
+        self.fixups.push((self.here, fixup, token));
+        Ok(opcode)
+    }
+
+    fn define_label(&mut self, token: Token<'a>) -> Result<(), AsmError> {
+        if !is_identifier(token.text)
+            || self.labels.contains_key(token.text)
+            || self.consts.contains_key(token.text)
+        {
+            return Err(error(
+                token.line,
+                format!("'{}' can't be used as a label", token.text),
+            ));
+        }
+        if token.text == "main" && self.here == PROGRAM_START_MEM as usize + 2 {
+            self.rom.clear();
+            self.here = PROGRAM_START_MEM as usize;
+            self.main_jump = false;
+        }
+        self.labels.insert(token.text.to_string(), self.here as u16);
+        Ok(())
+    }
+
+    // The instructions skipping the next one when the condition is
+    // true, and when it's false.
+    fn condition(&mut self) -> Result<(u16, u16), AsmError> {
+        let x = self.register()? << 8;
+        let op = self.next()?;
+        let (skip_if_equal, skip_if_not_equal) = match op.text {
+            "key" => return Ok((0xE09E | x, 0xE0A1 | x)),
+            "-key" => return Ok((0xE0A1 | x, 0xE09E | x)),
+            "==" | "!=" => {
+                let operand = self.tokens.get(self.at).map(|token| token.text);
+                if operand.is_some_and(|text| self.is_register(text)) {
+                    let y = self.register()? << 4;
+                    (0x5000 | x | y, 0x9000 | x | y)
+                } else {
+                    let kk = self.byte()?;
+                    (0x3000 | x | kk, 0x4000 | x | kk)
+                }
+            }
+            _ => {
+                return Err(error(
+                    op.line,
+                    format!("Unsupported comparison '{}'", op.text),
+                ))
+            }
+        };
+        if op.text == "==" {
+            Ok((skip_if_equal, skip_if_not_equal))
+        } else {
+            Ok((skip_if_not_equal, skip_if_equal))
+        }
+    }
+
+    fn close_block(&mut self, token: Token<'a>) -> Result<(Block, usize), AsmError> {
+        self.blocks
+            .pop()
+            .ok_or_else(|| error(token.line, format!("'{}' without a block", token.text)))
+    }
+
+    fn statement(&mut self, token: Token<'a>) -> Result<(), AsmError> {
+        let line = token.line;
+        match token.text {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(name)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value(i64::MIN, i64::MAX)?;
+                if !is_identifier(name.text) || self.labels.contains_key(name.text) {
+                    return Err(error(
+                        line,
+                        format!("'{}' can't be used as a constant", name.text),
+                    ));
+                }
+                self.consts.insert(name.text.to_string(), value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text.to_string(), register as u8);
+            }
+            ":org" => {
+                let addr = self.value(PROGRAM_START_MEM as i64, MEMORY_SIZE as i64 - 1)?;
+                self.here = addr as usize;
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit_byte(byte as u8, line)?;
+            }
+            ":call" => {
+                let opcode = self.address(0x2000, Fixup::Address)?;
+                self.emit(opcode, line)?;
+            }
+            ":unpack" => {
+                let nibble = self.value(0, 15)? as u8;
+                let addr = self.address(0, Fixup::Unpack(nibble))?;
+                self.emit(0x6000 | (nibble as u16) << 4 | addr >> 8, line)?;
+                self.emit(0x6100 | (addr & 0xFF), line)?;
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.emit(0x00E0, line)?,
+            "return" | ";" => self.emit(0x00EE, line)?,
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(0xF033 | x << 8, line)?;
+            }
+            "save" => {
+                let x = self.register()?;
+                self.emit(0xF055 | x << 8, line)?;
+            }
+            "load" => {
+                let x = self.register()?;
+                self.emit(0xF065 | x << 8, line)?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value(0, 15)? as u16;
+                self.emit(0xD000 | x << 8 | y << 4 | n, line)?;
+            }
+            "jump" => {
+                let opcode = self.address(0x1000, Fixup::Address)?;
+                self.emit(opcode, line)?;
+            }
+            "jump0" => {
+                let opcode = self.address(0xB000, Fixup::Address)?;
+                self.emit(opcode, line)?;
+            }
+            "native" => {
+                let opcode = self.address(0x0000, Fixup::Address)?;
+                self.emit(opcode, line)?;
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let opcode = if token.text == "delay" {
+                    0xF015
+                } else {
+                    0xF018
+                };
+                self.emit(opcode | x << 8, line)?;
+            }
+            "i" => {
+                let op = self.next()?;
+                match op.text {
+                    ":=" if self
+                        .tokens
+                        .get(self.at)
+                        .is_some_and(|next| next.text == "hex") =>
+                    {
+                        self.next()?;
+                        let x = self.register()?;
+                        self.emit(0xF029 | x << 8, line)?;
+                    }
+                    ":=" => {
+                        let opcode = self.address(0xA000, Fixup::Address)?;
+                        self.emit(opcode, line)?;
+                    }
+                    "+=" => {
+                        let x = self.register()?;
+                        self.emit(0xF01E | x << 8, line)?;
+                    }
+                    _ => return Err(error(line, format!("Unknown operator '{}'", op.text))),
+                }
+            }
+            "loop" => self.blocks.push((Block::Loop(self.here, Vec::new()), line)),
+            "again" => match self.close_block(token)? {
+                (Block::Loop(start, breaks), _) => {
+                    self.emit(0x1000 | start as u16, line)?;
+                    for jump in breaks {
+                        self.patch(jump, self.here as u16);
+                    }
+                }
+                _ => return Err(error(line, "'again' without 'loop'")),
+            },
+            "while" => {
+                let (skip_if_true, _) = self.condition()?;
+                self.emit(skip_if_true, line)?;
+                let jump = self.here;
+                self.emit(0x1000, line)?;
+                let Some(breaks) =
+                    self.blocks
+                        .iter_mut()
+                        .rev()
+                        .find_map(|(block, _)| match block {
+                            Block::Loop(_, breaks) => Some(breaks),
+                            _ => None,
+                        })
+                else {
+                    return Err(error(line, "'while' outside of a loop"));
+                };
+                breaks.push(jump);
+            }
+            "if" => {
+                let (skip_if_true, skip_if_false) = self.condition()?;
+                let then = self.next()?;
+                match then.text {
+                    "then" => self.emit(skip_if_false, line)?,
+                    "begin" => {
+                        self.emit(skip_if_true, line)?;
+                        self.blocks.push((Block::If(self.here), line));
+                        self.emit(0x1000, line)?;
+                    }
+                    _ => {
+                        return Err(error(
+                            then.line,
+                            format!("Expected 'then' or 'begin', found '{}'", then.text),
+                        ))
+                    }
+                }
+            }
+            "else" => match self.close_block(token)? {
+                (Block::If(jump), _) => {
+                    let end_jump = self.here;
+                    self.emit(0x1000, line)?;
+                    self.patch(jump, self.here as u16);
+                    self.blocks.push((Block::Else(end_jump), line));
+                }
+                _ => return Err(error(line, "'else' without 'if'")),
+            },
+            "end" => match self.close_block(token)? {
+                (Block::If(jump), _) | (Block::Else(jump), _) => {
+                    self.patch(jump, self.here as u16);
+                }
+                _ => return Err(error(line, "'end' without 'if'")),
+            },
+            text if self.is_register(text) => {
+                self.at -= 1;
+                self.register_statement()?;
+            }
+            text if text.starts_with(':') => {
+                return Err(error(line, format!("{} is not supported", text)))
+            }
+            text => match self.number(text) {
+                // Bare numbers are data, e.g. sprites
+                Some(value) if (-128..=255).contains(&value) => {
+                    self.emit_byte(value as u8, line)?
+                }
+                Some(value) => return Err(error(line, format!("{} is not a byte", value))),
+                None => {
+                    self.at -= 1;
+                    let opcode = self.address(0x2000, Fixup::Address)?;
+                    self.emit(opcode, line)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self) -> Result<(), AsmError> {
+        let x = self.register()? << 8;
+        let op = self.next()?;
+        let line = op.line;
+        let operand = self.tokens.get(self.at).map(|token| token.text);
+        let y = if operand.is_some_and(|text| self.is_register(text)) {
+            Some(self.register()? << 4)
+        } else {
+            None
+        };
+
+        let opcode = match (op.text, y) {
+            (":=", Some(y)) => 0x8000 | x | y,
+            ("|=", Some(y)) => 0x8001 | x | y,
+            ("&=", Some(y)) => 0x8002 | x | y,
+            ("^=", Some(y)) => 0x8003 | x | y,
+            ("+=", Some(y)) => 0x8004 | x | y,
+            ("-=", Some(y)) => 0x8005 | x | y,
+            (">>=", Some(y)) => 0x8006 | x | y,
+            ("=-", Some(y)) => 0x8007 | x | y,
+            ("<<=", Some(y)) => 0x800E | x | y,
+            (":=", None) => match operand {
+                Some("random") => {
+                    self.next()?;
+                    0xC000 | x | self.byte()?
+                }
+                Some("delay") => {
+                    self.next()?;
+                    0xF007 | x
+                }
+                Some("key") => {
+                    self.next()?;
+                    0xF00A | x
+                }
+                _ => 0x6000 | x | self.byte()?,
+            },
+            ("+=", None) => 0x7000 | x | self.byte()?,
+            ("-=", None) => 0x7000 | x | (self.byte()? as u8).wrapping_neg() as u16,
+            _ => return Err(error(line, format!("Unknown operator '{}'", op.text))),
+        };
+        self.emit(opcode, line)
+    }
+
+    fn finish(mut self) -> Result<Program, AsmError> {
+        if let Some((block, line)) = self.blocks.last() {
+            let message = match block {
+                Block::Loop(..) => "'loop' without 'again'",
+                _ => "'if' without 'end'",
+            };
+            return Err(error(*line, message));
+        }
+
+        for (addr, fixup, token) in std::mem::take(&mut self.fixups) {
+            let Some(&target) = self.labels.get(token.text) else {
+                return Err(error(token.line, format!("Unknown label '{}'", token.text)));
+            };
+            match fixup {
+                Fixup::Address => self.patch(addr, target),
+                Fixup::Unpack(nibble) => {
+                    let idx = addr - PROGRAM_START_MEM as usize;
+                    self.rom[idx + 1] = nibble << 4 | (target >> 8) as u8;
+                    self.rom[idx + 3] = target as u8;
+                }
+            }
+        }
+
+        if self.main_jump {
+            let Some(&main) = self.labels.get("main") else {
+                let line = self.tokens.last().map_or(1, |token| token.line);
+                return Err(error(line, "The program has no main label"));
+            };
+            self.rom[0] = 0x10 | (main >> 8) as u8;
+            self.rom[1] = main as u8;
+        }
+
+        Ok(Program {
+            rom: self.rom,
+            labels: self.labels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod assembler_tests {
+    use super::assemble;
+
+    fn words(source: &str) -> Vec<u16> {
+        assemble(source)
+            .unwrap()
+            .rom
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_statements() {
+        let source = "
+            : main
+              clear
+              v0 := 5        # Comments are ignored
+              v1 += -1
+              v2 := random 0x0F
+              v3 := delay
+              v0 += v1
+              v0 <<= v0
+              i := hex v0
+              i += v2
+              sprite v0 v1 5
+              delay := v3
+              buzzer := v3
+              bcd v2
+              save v2
+              load vF
+              return";
+        assert_eq!(
+            words(source),
+            vec![
+                0x00E0, 0x6005, 0x71FF, 0xC20F, 0xF307, 0x8014, 0x800E, 0xF029, 0xF21E, 0xD015,
+                0xF315, 0xF318, 0xF233, 0xF255, 0xFF65, 0x00EE
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let source = "
+            :const SPEED 2
+            :alias x v4
+            : draw
+              i := dot
+              sprite x x 1
+            ;
+            : main
+              x := SPEED
+              draw
+              jump main
+            : dot
+              0x80 0b1";
+        let program = assemble(source).unwrap();
+        assert_eq!(program.labels["main"], 0x208);
+        assert_eq!(
+            program.rom,
+            vec![
+                0x12, 0x08, 0xA2, 0x0E, 0xD4, 0x41, 0x00, 0xEE, 0x64, 0x02, 0x22, 0x02, 0x12, 0x08,
+                0x80, 0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            : main
+              loop
+                if v0 == 3 then v1 := 0
+                if v1 != v2 begin
+                  v0 += 1
+                else
+                  v0 := 0
+                end
+                while v2 key
+              again";
+        assert_eq!(
+            words(source),
+            vec![0x4003, 0x6100, 0x9120, 0x120C, 0x7001, 0x120E, 0x6000, 0xE29E, 0x1214, 0x1200]
+        );
+    }
+
+    #[test]
+    fn test_unpack() {
+        let program = assemble(": main :unpack 0xA data : data").unwrap();
+        assert_eq!(program.rom, vec![0x60, 0xA2, 0x61, 0x04]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let line = |source| assemble(source).err().map(|err| err.line);
+        assert_eq!(line(": main\n  v0 := 256"), Some(2));
+        assert_eq!(line(": main\n\n  jump nowhere"), Some(3));
+        assert_eq!(line(": main\n  loop\n  v0 += 1"), Some(2));
+        assert_eq!(line("clear"), Some(1));
+        assert_eq!(line(": main\n  v0 < v1"), Some(2));
+        assert_eq!(line(": main\n  :calc x { 1 }"), Some(2));
+    }
+}
@@ -7,7 +7,233 @@
 //
 // https://github.com/keelus/chip-8-emu
 
-pub trait BeepHandler {
-    fn start(&mut self);
-    fn stop(&mut self);
+use std::{cell::RefCell, rc::Rc};
+
+// Receives the buzzer's audio as it's rendered, one emulated frame
+// at a time (see AudioOutput).
+pub trait AudioSink {
+    fn sample_rate(&self) -> i32;
+    fn push(&mut self, samples: &[f32]);
+}
+
+// Keeps every sample pushed into it, e.g. for tests listening to a
+// ROM. Clones share the samples, so one can be given to a cpu while
+// the other reads them.
+#[derive(Clone)]
+#[allow(dead_code)]
+pub struct BufferSink {
+    sample_rate: i32,
+    samples: Rc<RefCell<Vec<f32>>>,
+}
+
+#[allow(dead_code)]
+impl BufferSink {
+    pub fn new(sample_rate: i32) -> BufferSink {
+        BufferSink {
+            sample_rate,
+            samples: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples.borrow().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.borrow().is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.borrow_mut().clear();
+    }
+}
+
+impl AudioSink for BufferSink {
+    fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        self.samples.borrow_mut().extend_from_slice(samples);
+    }
+}
+
+// Emulated frames per second, which the timers count down at.
+pub const FRAME_RATE: f64 = 60.0;
+
+pub const BUZZER_FREQUENCY: f32 = 250.0;
+pub const BUZZER_VOLUME: f32 = 0.12;
+
+// Attack and release time. Starting or stopping the square wave at
+// full volume makes the speaker pop.
+pub const RAMP_SECS: f32 = 0.005;
+
+// Square wave generator for the buzzer, fading in and out over
+// RAMP_SECS whenever it's switched on or off.
+pub struct Buzzer {
+    phase: f32,
+    phase_inc: f32,
+    volume: f32,
+    level: f32, // Current envelope level, 0.0 - 1.0
+    ramp_step: f32,
+}
+
+impl Buzzer {
+    pub fn new(sample_rate: i32) -> Buzzer {
+        let sample_rate = sample_rate.max(1) as f32;
+        Buzzer {
+            phase: 0.0,
+            phase_inc: BUZZER_FREQUENCY / sample_rate,
+            volume: BUZZER_VOLUME,
+            level: 0.0,
+            ramp_step: 1.0 / (RAMP_SECS * sample_rate).max(1.0),
+        }
+    }
+
+    // Fills out with samples, ramping towards full volume if on, or
+    // towards silence otherwise.
+    pub fn fill(&mut self, out: &mut [f32], on: bool) {
+        let target = if on { 1.0 } else { 0.0 };
+        for sample in out.iter_mut() {
+            if self.level < target {
+                self.level = (self.level + self.ramp_step).min(target);
+            } else if self.level > target {
+                self.level = (self.level - self.ramp_step).max(target);
+            }
+
+            let wave = if self.phase <= 0.5 {
+                self.volume
+            } else {
+                -self.volume
+            };
+            *sample = wave * self.level;
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_silent(&self) -> bool {
+        self.level == 0.0
+    }
+}
+
+// Renders a frame's worth of buzzer audio into a sink for every
+// emulated frame. Audio follows emulated time, so fast-forwarding
+// produces more of it and slow motion less.
+pub struct AudioOutput {
+    sink: Box<dyn AudioSink>,
+    buzzer: Buzzer,
+    samples_per_frame: f64,
+    pending: f64, // Fraction of a sample carried over between frames
+    buffer: Vec<f32>,
+}
+
+impl AudioOutput {
+    pub fn new(sink: Box<dyn AudioSink>) -> AudioOutput {
+        let sample_rate = sink.sample_rate();
+        AudioOutput {
+            sink,
+            buzzer: Buzzer::new(sample_rate),
+            samples_per_frame: sample_rate.max(1) as f64 / FRAME_RATE,
+            pending: 0.0,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn render_frame(&mut self, on: bool) {
+        self.pending += self.samples_per_frame;
+        let count = self.pending as usize;
+        self.pending -= count as f64;
+
+        self.buffer.resize(count, 0.0);
+        self.buzzer.fill(&mut self.buffer, on);
+        self.sink.push(&self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod beep_tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{AudioOutput, AudioSink, BufferSink, Buzzer, BUZZER_VOLUME};
+
+    const SAMPLE_RATE: i32 = 48_000;
+    const RAMP_SAMPLES: usize = 240; // 5 ms at 48 kHz
+
+    #[test]
+    fn test_attack() {
+        let mut buzzer = Buzzer::new(SAMPLE_RATE);
+        let mut out = [0.0; RAMP_SAMPLES];
+        buzzer.fill(&mut out, true);
+        assert!(out[0].abs() < BUZZER_VOLUME * 0.01);
+        assert!((out[RAMP_SAMPLES - 1].abs() - BUZZER_VOLUME).abs() < 1e-6);
+        assert!(!buzzer.is_silent());
+    }
+
+    #[test]
+    fn test_release() {
+        let mut buzzer = Buzzer::new(SAMPLE_RATE);
+        let mut out = [0.0; RAMP_SAMPLES * 4];
+        buzzer.fill(&mut out, true);
+
+        // Stopping mid-cycle fades out instead of dropping to 0
+        let mut out = [0.0; RAMP_SAMPLES + 10];
+        buzzer.fill(&mut out, false);
+        assert!(out[0].abs() > BUZZER_VOLUME * 0.9);
+        let mid = out[RAMP_SAMPLES / 2].abs();
+        assert!(mid > BUZZER_VOLUME * 0.4 && mid < BUZZER_VOLUME * 0.6);
+        assert_eq!(out[RAMP_SAMPLES + 5], 0.0);
+        assert!(buzzer.is_silent());
+    }
+
+    #[test]
+    fn test_silent_when_off() {
+        let mut buzzer = Buzzer::new(SAMPLE_RATE);
+        let mut out = [1.0; 64];
+        buzzer.fill(&mut out, false);
+        assert!(out.iter().all(|&sample| sample == 0.0));
+    }
+
+    struct TestSink(Rc<RefCell<Vec<usize>>>);
+
+    impl AudioSink for TestSink {
+        fn sample_rate(&self) -> i32 {
+            22_050
+        }
+
+        fn push(&mut self, samples: &[f32]) {
+            self.0.borrow_mut().push(samples.len());
+        }
+    }
+
+    #[test]
+    fn test_samples_per_frame() {
+        let pushed = Rc::new(RefCell::new(Vec::new()));
+        let mut output = AudioOutput::new(Box::new(TestSink(pushed.clone())));
+        for _ in 0..4 {
+            output.render_frame(true);
+        }
+        // 367.5 samples per frame at 22050 Hz
+        assert_eq!(*pushed.borrow(), vec![367, 368, 367, 368]);
+    }
+
+    #[test]
+    fn test_buffer_sink() {
+        let sink = BufferSink::new(6_000);
+        let mut output = AudioOutput::new(Box::new(sink.clone()));
+        output.render_frame(false);
+        assert_eq!(sink.samples(), vec![0.0; 100]);
+        output.render_frame(true);
+        assert_eq!(sink.len(), 200);
+        assert!(sink.samples()[100..].iter().any(|&sample| sample != 0.0));
+
+        let mut sink = sink;
+        sink.clear();
+        output.render_frame(true);
+        assert_eq!(sink.len(), 100);
+    }
 }
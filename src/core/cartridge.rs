@@ -0,0 +1,158 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{fmt, path::Path};
+
+use super::{
+    assembler::{self, AsmError},
+    gif::{self, GifError},
+    json::Json,
+};
+
+// Octo cartridges are GIFs with a payload hidden in the lowest 2 bits
+// of each pixel of the first frame, 4 pixels per byte, high bits first.
+// The payload is a big endian 32 bit length, then that many bytes of
+// JSON with the program's Octo source and its options.
+const LENGTH_BYTES: usize = 4;
+
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError {
+    Gif(GifError),
+    NoPayload,
+    InvalidPayload,
+    Assembly(AsmError),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CartridgeError::Gif(err) => write!(f, "{}", err),
+            CartridgeError::NoPayload => write!(f, "Image isn't an Octo cartridge."),
+            CartridgeError::InvalidPayload => write!(f, "Cartridge data is corrupted."),
+            CartridgeError::Assembly(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// Cartridges are told apart from ROMs by their extension.
+pub fn is_cartridge(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"))
+}
+
+// Extracts and assembles the program of a cartridge.
+pub fn read_rom(gif: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    Cartridge::read(gif)?.rom()
+}
+
+pub struct Cartridge {
+    pub source: String,
+    #[allow(dead_code)]
+    pub options: Json,
+}
+
+impl Cartridge {
+    pub fn read(gif: &[u8]) -> Result<Cartridge, CartridgeError> {
+        let frame = gif::decode_first_frame(gif).map_err(CartridgeError::Gif)?;
+        let bytes: Vec<u8> = frame
+            .pixels
+            .chunks_exact(4)
+            .map(|pixels| {
+                pixels
+                    .iter()
+                    .fold(0, |byte, pixel| (byte << 2) | (pixel & 0x03))
+            })
+            .collect();
+
+        let length = bytes
+            .get(..LENGTH_BYTES)
+            .map(|length| u32::from_be_bytes([length[0], length[1], length[2], length[3]]))
+            .ok_or(CartridgeError::NoPayload)? as usize;
+        let payload = bytes
+            .get(LENGTH_BYTES..LENGTH_BYTES + length)
+            .ok_or(CartridgeError::NoPayload)?;
+        let payload = std::str::from_utf8(payload).map_err(|_| CartridgeError::InvalidPayload)?;
+        let json = Json::parse(payload).map_err(|_| CartridgeError::InvalidPayload)?;
+
+        let source = json
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or(CartridgeError::InvalidPayload)?
+            .to_string();
+        let options = json.get("options").cloned().unwrap_or(Json::Null);
+        Ok(Cartridge { source, options })
+    }
+
+    // The program assembled, ready to be loaded.
+    pub fn rom(&self) -> Result<Vec<u8>, CartridgeError> {
+        assembler::assemble(&self.source)
+            .map(|program| program.rom)
+            .map_err(CartridgeError::Assembly)
+    }
+}
+
+#[cfg(test)]
+mod cartridge_tests {
+    use std::path::Path;
+
+    use super::{is_cartridge, read_rom, Cartridge, CartridgeError};
+    use crate::core::gif::gif_tests::encode;
+
+    // Hides the payload in the pixels of a gray image.
+    fn cartridge(payload: &str) -> Vec<u8> {
+        let mut data = (payload.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(payload.as_bytes());
+        let mut pixels = vec![0x80; 128 * 64];
+        for (idx, byte) in data.iter().enumerate() {
+            for part in 0..4 {
+                pixels[idx * 4 + part] |= (byte >> (6 - part * 2)) & 0x03;
+            }
+        }
+        encode(128, 64, &pixels)
+    }
+
+    #[test]
+    fn test_read() {
+        let gif =
+            cartridge(r#"{"program":": main\n  clear\n  loop again","options":{"tickrate":20}}"#);
+        let cartridge = Cartridge::read(&gif).unwrap();
+        assert_eq!(cartridge.source, ": main\n  clear\n  loop again");
+        assert_eq!(
+            cartridge
+                .options
+                .get("tickrate")
+                .and_then(|rate| rate.as_u64()),
+            Some(20)
+        );
+        assert_eq!(cartridge.rom().unwrap(), vec![0x00, 0xE0, 0x12, 0x02]);
+    }
+
+    #[test]
+    fn test_is_cartridge() {
+        assert!(is_cartridge(Path::new("roms/octopeg.GIF")));
+        assert!(!is_cartridge(Path::new("roms/pong.ch8")));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Cartridge::read(&encode(4, 4, &[0; 16])).err(),
+            Some(CartridgeError::InvalidPayload)
+        );
+        assert_eq!(
+            Cartridge::read(&encode(2, 2, &[0; 4])).err(),
+            Some(CartridgeError::NoPayload)
+        );
+        assert!(matches!(
+            read_rom(&cartridge(r#"{"program":": main\n  bogus := 3"}"#)),
+            Err(CartridgeError::Assembly(_))
+        ));
+    }
+}
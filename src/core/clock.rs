@@ -0,0 +1,75 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::beep::FRAME_RATE;
+
+// Emulated time, counted in executed instructions and ended frames
+// instead of read off the wall clock. The timers, draw pacing, replays
+// and the speedrun timer all go by it, so they agree with each other
+// and a run plays back the same however fast the host is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EmuClock {
+    cycles: u64,
+    frame: u64,
+}
+
+impl EmuClock {
+    pub fn new() -> EmuClock {
+        EmuClock::default()
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // An instruction ran, or waited.
+    pub fn step(&mut self) {
+        self.cycles += 1;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    #[allow(dead_code)]
+    pub fn seconds(&self) -> f64 {
+        self.frame as f64 / FRAME_RATE
+    }
+
+    // Cycles run since an earlier reading, None if the clock went back
+    // to before it (e.g. rewinding).
+    #[allow(dead_code)]
+    pub fn cycles_since(&self, earlier: u64) -> Option<u64> {
+        self.cycles.checked_sub(earlier)
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::EmuClock;
+
+    #[test]
+    fn test_clock() {
+        let mut clock = EmuClock::new();
+        for _ in 0..30 {
+            for _ in 0..10 {
+                clock.step();
+            }
+            clock.end_frame();
+        }
+        assert_eq!((clock.cycles(), clock.frame()), (300, 30));
+        assert_eq!(clock.seconds(), 0.5);
+        assert_eq!(clock.cycles_since(290), Some(10));
+        assert_eq!(clock.cycles_since(301), None);
+    }
+}
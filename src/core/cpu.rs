@@ -7,40 +7,229 @@
 //
 // https://github.com/keelus/chip-8-emu
 
-#![allow(dead_code)]
-use std::{borrow::BorrowMut, ops::Shr, time::Instant};
-
-use rand::Rng;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+    hash::Hasher,
+};
 
-use crate::core::screen;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use super::{
-    beep::BeepHandler,
+    beep::{AudioOutput, AudioSink},
+    clock::EmuClock,
+    hash::{self, Fnv1a},
+    hooks::Hooks,
+    instruction::{Opcode, Quirk},
     keypad::Keypad,
-    memory::{Memory, HEX_SPRITES_HEIGHT, HEX_SPRITES_START_MEM},
-    registers::{Registers, DELAY_TIMER, SOUND_TIMER},
-    screen::Screen,
+    memory::{
+        BoundsPolicy, LoadError, Memory, HEX_SPRITES_HEIGHT, HEX_SPRITES_START_MEM,
+        INTERPRETER_START_MEM, MEMORY_SIZE,
+    },
+    memory_access::MemoryAccesses,
+    profile::MachineProfile,
+    register_log::{written_registers, RegisterLog},
+    registers::{Registers, DELAY_TIMER, SOUND_TIMER, STACK_CAPACITY},
+    rewind::RewindBuffer,
+    rng_stats::RngStats,
+    screen::{DrawRegion, Screen},
+    stats::Stats,
+    timeline::{EventKind, Timeline},
+    timing::TimingModel,
 };
 
+// Copy of the whole machine state, used to rewind and seek.
+#[derive(Clone)]
+pub struct Snapshot {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u8,
+    stack: [u16; STACK_CAPACITY],
+    timers: [u8; 2],
+    memory: Memory,
+    screen: Screen,
+    keypad: Keypad,
+    rng: StdRng,
+    clock: EmuClock,
+    draws: u64,
+    sound_frames: u64,
+}
+
+impl Snapshot {
+    pub fn frame(&self) -> u64 {
+        self.clock.frame()
+    }
+}
+
+// What happens when an instruction writes into the built-in font,
+// usually a sign of a wrong load address or a ROM expecting its own
+// font layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontWriteAction {
+    Ignore,
+    Warn, // Once per run, see take_font_write
+    Break,
+}
+
+pub const FONT_WRITE_ACTIONS: [FontWriteAction; 3] = [
+    FontWriteAction::Ignore,
+    FontWriteAction::Warn,
+    FontWriteAction::Break,
+];
+
+impl FontWriteAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FontWriteAction::Ignore => "Ignore",
+            FontWriteAction::Warn => "Warn",
+            FontWriteAction::Break => "Halt",
+        }
+    }
+}
+
+// Errors that halt the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    BadOpcode, // Unknown instructions and SYS calls
+    StackOverflow,
+    StackUnderflow,
+    BadAddress, // Memory accessed out of bounds with the Error policy
+}
+
+impl Fault {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fault::BadOpcode => "bad_opcode",
+            Fault::StackOverflow => "stack_overflow",
+            Fault::StackUnderflow => "stack_underflow",
+            Fault::BadAddress => "bad_address",
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::BadOpcode => write!(f, "Unknown instruction"),
+            Fault::StackOverflow => write!(f, "Stack overflow"),
+            Fault::StackUnderflow => write!(f, "Stack underflow"),
+            Fault::BadAddress => write!(f, "Memory access out of bounds"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmuError {
+    NoRom,
+    Fault { fault: Fault, pc: u16 },
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmuError::NoRom => write!(f, "No ROM loaded."),
+            EmuError::Fault { fault, pc } => write!(f, "{} at {:#05X}.", fault, pc),
+        }
+    }
+}
+
+// An instruction run by step(), and what it did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutedInstruction {
+    pub pc: u16,
+    pub data: u16, // The instruction's two bytes
+    pub opcode: Opcode,
+    pub registers: u32, // Written registers, as in the register log
+    pub memory_written: Vec<u16>,
+    pub pixels_flipped: u32,
+}
+
+// A write into an address that ran as an instruction since it was
+// last written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModification {
+    pub pc: u16,   // Of the writing instruction
+    pub addr: u16, // Written address
+}
+
 pub struct Cpu {
     // Main parts
     pub registers: Registers,
     pub memory: Memory,
-    pub screen: Screen,
+    pub screen: Screen, // Drawn into by instructions
+    front: Screen,      // The last finished frame, see front_screen
     pub keypad: Keypad,
+    pub hooks: Hooks, // Callbacks for embedders
 
     // Sound related
-    beep_handler: Option<Box<dyn BeepHandler>>,
+    audio: Option<AudioOutput>,
     beep_enabled: bool,
 
     // Misc
     rom_loaded: bool,
-    last_draw: Option<Instant>,
+    rom_hash: Option<u64>, // Of the program as loaded
+    frame_ready: bool,     // A frame ran since take_frame_ready
+    draws: u64,            // DXYN run since the ROM was loaded
+    sound_frames: u64,     // Frames sounding since the ROM was loaded
+    last_draw_region: Option<DrawRegion>,
+    last_self_modification: Option<SelfModification>,
+    font_written: bool,             // Warned about a font write this run
+    font_write: Option<(u16, u16)>, // PC and address of it, until taken
+    bad_digit_warned: bool,         // Warned about Fx29 past 0xF this run
+    bad_digit: Option<(u16, u8)>,   // PC and Vx of it, until taken
+    i_overflowed: bool,             // Warned about I overflowing this run
+    i_overflow: Option<(u16, u32)>, // PC and unmasked I of it, until taken
     halted: bool,
+    resumed: bool,        // Runs the next instruction past its breakpoint
+    fault: Option<Fault>, // Why it halted on its own, if it did
+    clock: EmuClock,      // Time since the ROM was loaded
+
+    // Per instruction snapshots, used to step backwards
+    history: Option<RewindBuffer>,
+
+    // Registers written by each instruction, for the debugger's heatmap
+    register_log: Option<RegisterLog>,
+
+    // Reads and writes per address, for the memory heatmap
+    memory_accesses: Option<MemoryAccesses>,
+
+    // Addresses written by the instruction step() is running
+    step_writes: Option<Vec<u16>>,
+
+    // Key presses, sounds, clears and save states by frame
+    timeline: Option<Timeline>,
+
+    // Screen as it was when the current frame started, kept while the
+    // debugger shows it as a ghost under the current one
+    previous_screen: Option<Screen>,
+
+    // Hardware being emulated
+    profile: MachineProfile,
+
+    // Random number generation (cxkk)
+    rng: StdRng,
+    rng_seed: u64,
+    rng_stats: RngStats,
+
+    // Addresses fetched as (part of) an instruction since they were
+    // last written, to detect self-modifying code
+    executed: Vec<bool>,
+
+    // Debugging
+    pub break_on_draw: bool,        // Halt right after every DXYN
+    pub break_on_clear: bool,       // Halt right after every CLS
+    pub break_on_self_modify: bool, // Halt right after writing executed code
+    pub log_self_modify: bool,      // Log every write into executed code
+    pub font_write_action: FontWriteAction,
+    pub breakpoints: BTreeSet<u16>, // Halt before executing these addresses
+    pub break_opcodes: HashSet<Opcode>, // Halt before executing these opcodes
+    pub forced_random: Option<u8>,  // Used by CXKK instead of a random byte
 
     // Speed configuration
-    pub draws_per_second: u32,
     pub ticks_per_frame: u32,
+    pub timing_model: TimingModel, // Cost of each instruction
+    cycles_overrun: u32,           // Taken from the next frame's cycles
 
     // Quirks
 
@@ -54,90 +243,765 @@ pub struct Cpu {
     pub memory_load_save_increment_i: bool, // Default: true
 
     // If clipping is disabled, sprites will wrap around.
-    pub sprite_clipping: bool, // Default: true
+    // Each axis can be configured independently, as some
+    // variants clip horizontally but wrap vertically.
+    pub sprite_clipping_x: bool, // Default: true
+    pub sprite_clipping_y: bool, // Default: true
 
     // If true -> NNN (JP to NNN + V0)
     // If false -> xNN (JP to NN + Vx) // Use with care!
     pub jump_to_nnn: bool, // Default: True
+
+    // If true, the buzzer only sounds while ST >= 2, like on the
+    // COSMAC VIP. If false, it sounds for any ST > 0.
+    pub sound_timer_threshold: bool, // Default: false
+
+    // 8xy4 - 8xyE write both a result to Vx and a flag to VF, so with
+    // x = 0xF one of them is lost. If false, the flag is written last
+    // and VF keeps it, like the COSMAC VIP. If true, VF keeps the result.
+    pub vf_result_wins: bool, // Default: false
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
+        let rng_seed = rand::thread_rng().gen();
+
         Cpu {
             registers: Registers::new(),
             memory: Memory::new(),
             screen: Screen::new(),
+            front: Screen::new(),
             keypad: Keypad::new(),
+            hooks: Hooks::default(),
 
-            beep_handler: None,
+            audio: None,
             beep_enabled: true,
 
             rom_loaded: false,
-            last_draw: None,
+            rom_hash: None,
+            frame_ready: false,
+            draws: 0,
+            sound_frames: 0,
+            last_draw_region: None,
+            last_self_modification: None,
+            font_written: false,
+            font_write: None,
+            bad_digit_warned: false,
+            bad_digit: None,
+            i_overflowed: false,
+            i_overflow: None,
             halted: false,
+            resumed: false,
+            fault: None,
+            clock: EmuClock::new(),
+
+            history: None,
+            register_log: None,
+            memory_accesses: None,
+            step_writes: None,
+            timeline: None,
+            previous_screen: None,
+
+            profile: MachineProfile::Modern,
+
+            rng: StdRng::seed_from_u64(rng_seed),
+            rng_seed,
+            rng_stats: RngStats::default(),
+
+            executed: vec![false; MEMORY_SIZE],
+
+            break_on_draw: false,
+            break_on_clear: false,
+            break_on_self_modify: false,
+            log_self_modify: false,
+            font_write_action: FontWriteAction::Warn,
+            breakpoints: BTreeSet::new(),
+            break_opcodes: HashSet::new(),
+            forced_random: None,
 
-            draws_per_second: 60,
             ticks_per_frame: 10,
+            timing_model: TimingModel::Uniform,
+            cycles_overrun: 0,
 
             shifts_against_vy: true,
             memory_load_save_increment_i: true,
-            sprite_clipping: true,
+            sprite_clipping_x: true,
+            sprite_clipping_y: true,
             jump_to_nnn: true,
+            sound_timer_threshold: false,
+            vf_result_wins: false,
         }
     }
 
-    pub fn load_rom(&mut self, program: Vec<u8>, program_begin: u16) {
-        self.memory.load_rom(program, program_begin);
+    pub fn load_rom(&mut self, program: &[u8], program_begin: u16) -> Result<(), LoadError> {
+        self.memory.load_rom(program, program_begin)?;
+        self.executed.fill(false);
+        self.resumed = false;
+        self.fault = None;
         self.registers.pc = program_begin;
         self.rom_loaded = true;
+        self.rom_hash = Some(hash::hash_bytes(program));
+        Ok(())
     }
 
     pub fn clear(&mut self) {
         self.registers = Registers::new();
-        self.memory = Memory::new();
+        self.memory.reset();
         self.screen = Screen::new();
-        self.keypad = Keypad::new();
+        self.front = Screen::new();
+        self.keypad = Keypad::with_input_mode(self.keypad.input_mode());
         self.rom_loaded = false;
-        self.last_draw = None;
-        self.handle_beep();
+        self.rom_hash = None;
+        self.frame_ready = true;
+        self.draws = 0;
+        self.sound_frames = 0;
+        self.last_draw_region = None;
+        self.last_self_modification = None;
+        self.font_written = false;
+        self.font_write = None;
+        self.bad_digit_warned = false;
+        self.bad_digit = None;
+        self.i_overflowed = false;
+        self.i_overflow = None;
+        self.resumed = false;
+        self.cycles_overrun = 0;
+        self.fault = None;
+        self.executed.fill(false);
+        self.clock = EmuClock::new();
+        self.rng_stats.clear();
+        self.hooks.reset();
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        if let Some(register_log) = self.register_log.as_mut() {
+            register_log.clear();
+        }
+        if let Some(accesses) = self.memory_accesses.as_mut() {
+            accesses.clear();
+        }
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.clear();
+        }
+        if let Some(previous_screen) = self.previous_screen.as_mut() {
+            *previous_screen = Screen::new();
+        }
+    }
+
+    // Every emulated frame renders its buzzer audio into the sink.
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.audio = Some(AudioOutput::new(sink));
+    }
+
+    pub fn remove_audio_sink(&mut self) {
+        self.audio = None
+    }
+
+    // Moves the audio output between machines, e.g. when switching tabs.
+    pub fn take_audio(&mut self) -> Option<AudioOutput> {
+        self.audio.take()
+    }
+
+    // Whether the screen holds a new frame to show: one ran to its end,
+    // an instruction was stepped or a state restored since last asked.
+    // Renderers pick how many of them to upload, so drawing never
+    // slows down the program.
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    // The screen as it was at the end of the last frame, so renderers
+    // never show a frame with half its sprites drawn. Instructions draw
+    // into the screen field instead.
+    pub fn front_screen(&self) -> &Screen {
+        &self.front
+    }
+
+    // Has renderers upload both screens again, e.g. with a new palette.
+    pub fn mark_screens_dirty(&mut self) {
+        self.screen.mark_dirty();
+        self.front.mark_dirty();
+    }
+
+    // Whether the front screen changed since last asked.
+    pub fn take_front_dirty(&mut self) -> bool {
+        self.front.take_dirty()
     }
 
-    pub fn add_beep_handler(&mut self, audio_manager: Box<dyn BeepHandler>) {
-        self.beep_handler = Some(audio_manager);
+    // Vblank: the screen drawn so far becomes the front one.
+    fn present(&mut self) {
+        if self.front.width() != self.screen.width() || self.front.words() != self.screen.words() {
+            self.front.clone_from(&self.screen);
+            self.front.mark_dirty();
+        }
     }
 
-    pub fn remove_beep_handler(&mut self) {
-        self.beep_handler = None
+    pub fn set_audio(&mut self, audio: Option<AudioOutput>) {
+        self.audio = audio;
     }
 
     pub fn tick(&mut self) {
+        self.tick_with(|_| true);
+    }
+
+    // Same as tick, calling back after every executed instruction. The
+    // frame ends early if the callback returns false.
+    pub fn tick_with(&mut self, after_step: impl FnMut(&Cpu) -> bool) {
         if !self.halted && self.rom_loaded {
-            for _i in 0..self.ticks_per_frame {
-                self.do_tick();
+            self.run_frame(after_step);
+        }
+    }
+
+    // Runs a whole frame while halted, audio included, then halts
+    // again (unless a breakpoint already did it earlier).
+    pub fn step_frame(&mut self) {
+        if self.rom_loaded {
+            self.halted = false;
+            self.resumed = true;
+            self.run_frame(|_| true);
+            self.halted = true;
+        }
+    }
+
+    fn run_frame(&mut self, mut after_step: impl FnMut(&Cpu) -> bool) {
+        if let Some(previous_screen) = self.previous_screen.as_mut() {
+            if previous_screen.words() != self.screen.words() {
+                previous_screen.clone_from(&self.screen);
+                self.screen.mark_dirty();
+                self.front.mark_dirty();
+            }
+        }
+
+        self.keypad.begin_frame();
+        if let Some(timeline) = self.timeline.as_mut() {
+            let keys = self.keypad.pressed_mask() | self.keypad.pressed_this_frame();
+            timeline.update_keys(self.clock.frame(), keys);
+        }
+        // Instructions run until the frame's cycles are spent. The last
+        // one may overrun them (a VIP draw takes longer than a frame),
+        // delaying the next frame's instructions by as much.
+        let budget = self.timing_model.cycles_per_frame(self.ticks_per_frame);
+        let mut spent = std::mem::take(&mut self.cycles_overrun);
+        while spent < budget {
+            // Checked before executing, so the entry point can break too
+            let resumed = std::mem::take(&mut self.resumed);
+            if !resumed
+                && (self.breakpoints.contains(&self.registers.pc) || self.breaks_on_next_opcode())
+            {
+                self.halted = true;
+                break;
+            }
+            spent += self.next_cycles();
+            self.do_tick();
+            if !after_step(self) || self.halted {
+                break;
+            }
+        }
+        self.cycles_overrun = spent.saturating_sub(budget);
+        // The frame sounds if ST was above 0 during it, so ST = n sounds
+        // for n frames
+        let sounding = self.sound_timer_active();
+        if sounding {
+            self.sound_frames += 1;
+        }
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.update_buzzer(self.clock.frame(), sounding);
+        }
+        self.clock.end_frame();
+        self.keypad.end_frame();
+        for timer in &mut self.registers.timers {
+            timer.tick_frame();
+        }
+
+        self.hooks.sound(sounding);
+        self.present();
+        self.frame_ready = true;
+        let buzzer_on = self.beep_enabled && sounding;
+        if let Some(audio) = self.audio.as_mut() {
+            audio.render_frame(buzzer_on);
+        }
+    }
+
+    // Executes a single instruction, even if halted, returning what it
+    // did. A fault halts the machine, as when running.
+    pub fn step(&mut self) -> Result<ExecutedInstruction, EmuError> {
+        if !self.rom_loaded {
+            return Err(EmuError::NoRom);
+        }
+        let pc = self.registers.pc;
+        let data = self.memory.read_u16(pc);
+        let instruction = self.memory.read_instruction(pc);
+        let opcode = instruction.opcode();
+        let registers = written_registers(&instruction, self.memory_load_save_increment_i);
+        // Only clears and draws change the screen
+        let screen =
+            matches!(opcode, Opcode::Cls | Opcode::Draw).then(|| self.screen.words().to_vec());
+
+        self.fault = None;
+        self.step_writes = Some(Vec::new());
+        self.do_tick();
+        self.frame_ready = true;
+        let memory_written = self.step_writes.take().unwrap_or_default();
+        if let Some(fault) = self.fault {
+            return Err(EmuError::Fault { fault, pc });
+        }
+
+        let pixels_flipped = screen.map_or(0, |before| {
+            before
+                .iter()
+                .zip(self.screen.words())
+                .map(|(before, after)| (before ^ after).count_ones())
+                .sum()
+        });
+        Ok(ExecutedInstruction {
+            pc,
+            data,
+            opcode,
+            registers,
+            memory_written,
+            pixels_flipped,
+        })
+    }
+
+    // Undoes the last executed instruction. Returns false if
+    // there's no history left to go back to.
+    pub fn step_back(&mut self) -> bool {
+        let snapshot = self.history.as_mut().and_then(|history| history.pop());
+        if let Some(snapshot) = snapshot {
+            if let Some(register_log) = self.register_log.as_mut() {
+                register_log.pop();
+            }
+            self.restore(&snapshot);
+            return true;
+        }
+        false
+    }
+
+    // Goes back to the start of an earlier frame, as far as the history
+    // reaches. Returns false if it doesn't reach that far.
+    pub fn rewind_to_frame(&mut self, frame: u64) -> bool {
+        let Some(history) = self.history.as_mut() else {
+            return false;
+        };
+        let len = history.len();
+        let Some(snapshot) = history.rewind_to_frame(frame) else {
+            return false;
+        };
+        if let Some(register_log) = self.register_log.as_mut() {
+            for _ in history.len()..len {
+                register_log.pop();
+            }
+        }
+        self.restore(&snapshot);
+        true
+    }
+
+    pub fn can_rewind_to(&self, frame: u64) -> bool {
+        frame < self.clock.frame()
+            && self
+                .history
+                .as_ref()
+                .and_then(|history| history.oldest_frame())
+                .is_some_and(|oldest| oldest <= frame)
+    }
+
+    // Sets how many instructions can be stepped back. 0 disables
+    // the history, avoiding a snapshot per executed instruction.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history = if capacity > 0 {
+            Some(RewindBuffer::new(capacity))
+        } else {
+            None
+        };
+    }
+
+    // Sets how many instructions the register log keeps. 0 disables it.
+    pub fn set_register_log_capacity(&mut self, capacity: usize) {
+        self.register_log = (capacity > 0).then(|| RegisterLog::new(capacity));
+    }
+
+    pub fn register_log(&self) -> Option<&RegisterLog> {
+        self.register_log.as_ref()
+    }
+
+    pub fn set_memory_access_counting(&mut self, enabled: bool) {
+        if enabled != self.memory_accesses.is_some() {
+            self.memory_accesses = enabled.then(|| MemoryAccesses::new(self.memory.size()));
+        }
+    }
+
+    pub fn memory_accesses(&self) -> Option<&MemoryAccesses> {
+        self.memory_accesses.as_ref()
+    }
+
+    pub fn clear_memory_accesses(&mut self) {
+        if let Some(accesses) = self.memory_accesses.as_mut() {
+            accesses.clear();
+        }
+    }
+
+    // Sets how many events the timeline keeps. 0 disables it.
+    pub fn set_timeline_capacity(&mut self, capacity: usize) {
+        self.timeline = (capacity > 0).then(|| Timeline::new(capacity));
+    }
+
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    // Marks the current frame as saved on the timeline.
+    pub fn mark_save_state(&mut self) {
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.push(self.clock.frame(), EventKind::SaveState);
+        }
+    }
+
+    pub fn profile(&self) -> MachineProfile {
+        self.profile
+    }
+
+    // Switching to a profile with another memory size keeps the
+    // memory contents that still fit.
+    pub fn set_profile(&mut self, profile: MachineProfile) {
+        self.profile = profile;
+        let size = profile.memory_size();
+        if self.memory.size() != size {
+            self.memory.set_size(size);
+            self.executed.resize(size, false);
+            if self.memory_accesses.is_some() {
+                self.memory_accesses = Some(MemoryAccesses::new(size));
+            }
+        }
+    }
+
+    pub fn stack_size(&self) -> usize {
+        self.profile.stack_size()
+    }
+
+    pub fn history_capacity(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.capacity())
+    }
+
+    pub fn set_ghosting(&mut self, enabled: bool) {
+        if enabled != self.previous_screen.is_some() {
+            self.previous_screen = enabled.then(|| self.screen.clone());
+            self.mark_screens_dirty();
+        }
+    }
+
+    pub fn previous_screen(&self) -> Option<&Screen> {
+        self.previous_screen.as_ref()
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| history.len())
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.clock.frame()
+    }
+
+    pub fn clock(&self) -> &EmuClock {
+        &self.clock
+    }
+
+    // Tells ROMs apart however they were loaded, for settings kept per game.
+    pub fn rom_hash(&self) -> Option<u64> {
+        self.rom_hash
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            cycles: self.clock.cycles(),
+            frames: self.clock.frame(),
+            draws: self.draws,
+            key_events: self.keypad.events(),
+            sound_frames: self.sound_frames,
+        }
+    }
+
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.rng_seed = seed;
+    }
+
+    pub fn rng_stats(&self) -> &RngStats {
+        &self.rng_stats
+    }
+
+    pub fn clear_rng_stats(&mut self) {
+        self.rng_stats.clear();
+    }
+
+    // Quirks packed as bit flags, in declaration order.
+    pub fn quirk_bits(&self) -> u8 {
+        (self.shifts_against_vy as u8)
+            | (self.memory_load_save_increment_i as u8) << 1
+            | (self.sprite_clipping_x as u8) << 2
+            | (self.jump_to_nnn as u8) << 3
+            | (self.sprite_clipping_y as u8) << 4
+            | (self.sound_timer_threshold as u8) << 5
+            | (self.vf_result_wins as u8) << 6
+    }
+
+    pub fn set_quirk_bits(&mut self, bits: u8) {
+        self.shifts_against_vy = bits & 0x1 != 0;
+        self.memory_load_save_increment_i = bits & 0x2 != 0;
+        self.sprite_clipping_x = bits & 0x4 != 0;
+        self.jump_to_nnn = bits & 0x8 != 0;
+        self.sprite_clipping_y = bits & 0x10 != 0;
+        self.sound_timer_threshold = bits & 0x20 != 0;
+        self.vf_result_wins = bits & 0x40 != 0;
+    }
+
+    pub fn quirk_enabled(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::ShiftsAgainstVy => self.shifts_against_vy,
+            Quirk::MemoryLoadSaveIncrementI => self.memory_load_save_increment_i,
+            Quirk::SpriteClipping => self.sprite_clipping_x,
+            Quirk::JumpToNnn => self.jump_to_nnn,
+            Quirk::SoundTimerThreshold => self.sound_timer_threshold,
+            Quirk::VfResultWins => self.vf_result_wins,
+        }
+    }
+
+    // Writes the result and flag of 8xy4 - 8xyE, in the order the
+    // vf_result_wins quirk asks for.
+    fn write_with_flag(&mut self, x: u8, value: u8, flag: u8) {
+        if self.vf_result_wins {
+            self.registers.v[0xF] = flag;
+            self.registers.v[x as usize] = value;
+        } else {
+            self.registers.v[x as usize] = value;
+            self.registers.v[0xF] = flag;
+        }
+    }
+
+    // What the instruction at PC costs, at least a cycle so unknown ones
+    // can't stall a frame.
+    fn next_cycles(&self) -> u32 {
+        if self.timing_model == TimingModel::Uniform {
+            return 1;
+        }
+        let opcode = self.memory.read_instruction(self.registers.pc).opcode();
+        opcode.cycles(&self.timing_model).max(1)
+    }
+
+    fn breaks_on_next_opcode(&self) -> bool {
+        !self.break_opcodes.is_empty()
+            && self
+                .break_opcodes
+                .contains(&self.memory.read_instruction(self.registers.pc).opcode())
+    }
+
+    // Memory accesses made by instructions go through these.
+    fn read_memory(&mut self, addr: u16) -> u8 {
+        if let Some(accesses) = self.memory_accesses.as_mut() {
+            accesses.record_read(addr);
+        }
+        self.memory.read(addr)
+    }
+
+    fn write_memory(&mut self, addr: u16, data: u8) {
+        if let Some(accesses) = self.memory_accesses.as_mut() {
+            accesses.record_write(addr);
+        }
+        if let Some(writes) = self.step_writes.as_mut() {
+            writes.push(addr);
+        }
+        if let Ok(resolved) = self.memory.resolve(addr as usize) {
+            if resolved < INTERPRETER_START_MEM as usize {
+                self.on_font_write(resolved as u16);
+            }
+            if self.executed[resolved] {
+                // Reported again only once it runs again
+                self.executed[resolved] = false;
+                self.on_self_modification(addr);
+            }
+        }
+        self.memory.write(addr, data);
+    }
+
+    fn on_self_modification(&mut self, addr: u16) {
+        let pc = self.registers.pc;
+        self.last_self_modification = Some(SelfModification { pc, addr });
+        if self.log_self_modify {
+            log::warn!(
+                "Self-modifying code: {:#05X} wrote to {:#05X}, which already ran.",
+                pc,
+                addr
+            );
+        }
+        if self.break_on_self_modify {
+            self.halted = true;
+        }
+    }
+
+    fn on_font_write(&mut self, addr: u16) {
+        match self.font_write_action {
+            FontWriteAction::Ignore => {}
+            FontWriteAction::Warn if self.font_written => {}
+            FontWriteAction::Warn => {
+                self.font_written = true;
+                self.font_write = Some((self.registers.pc, addr));
+                log::warn!(
+                    "{:#05X} wrote into the font at {:#05X}. Is the ROM loaded at the right address?",
+                    self.registers.pc,
+                    addr
+                );
+            }
+            FontWriteAction::Break => {
+                self.font_write = Some((self.registers.pc, addr));
+                self.halted = true;
+            }
+        }
+    }
+
+    // Returns (and clears) the PC and address of a reported write
+    // into the font.
+    pub fn take_font_write(&mut self) -> Option<(u16, u16)> {
+        self.font_write.take()
+    }
+
+    // Returns (and clears) the PC and Vx of an Fx29 asking for a digit
+    // past 0xF.
+    pub fn take_bad_digit(&mut self) -> Option<(u16, u8)> {
+        self.bad_digit.take()
+    }
+
+    // The font sprite Fx29 points I to for vx. Digits past 0xF have no
+    // sprite, so they follow the bounds policy as if the font were the
+    // whole memory: None means halting.
+    fn font_digit(&mut self, vx: u8) -> Option<u8> {
+        if vx <= 0xF {
+            return Some(vx);
+        }
+        let pc = self.registers.pc;
+        let digit = match self.memory.bounds_policy() {
+            BoundsPolicy::Wrap => vx & 0xF,
+            BoundsPolicy::Clamp => 0xF,
+            BoundsPolicy::Error => {
+                log::error!("{:#05X} asked for font digit {:#04X}, halting.", pc, vx);
+                return None;
             }
+        };
+        if !self.bad_digit_warned {
+            self.bad_digit_warned = true;
+            self.bad_digit = Some((pc, vx));
+            log::warn!(
+                "{:#05X} asked for font digit {:#04X}, using {:X}.",
+                pc,
+                vx,
+                digit
+            );
+        }
+        Some(digit)
+    }
+
+    // Returns (and clears) the PC and unmasked value of an instruction
+    // that moved I past what the profile can address.
+    pub fn take_i_overflow(&mut self) -> Option<(u16, u32)> {
+        self.i_overflow.take()
+    }
+
+    // Sets I to i, keeping the bits the profile's I register has. Games
+    // relying on I wrapping around are reported once per run, as they
+    // only work on machines with the same register size.
+    fn set_i(&mut self, i: u32) {
+        let mask = self.profile.i_mask();
+        if i > mask as u32 && !self.i_overflowed {
+            self.i_overflowed = true;
+            self.i_overflow = Some((self.registers.pc, i));
+            log::warn!(
+                "{:#05X} moved I past {:#06X} (to {:#06X}), wrapping around.",
+                self.registers.pc,
+                mask,
+                i
+            );
         }
+        self.registers.i = i as u16 & mask;
+    }
+
+    // The most recent write into executed code.
+    pub fn last_self_modification(&self) -> Option<SelfModification> {
+        self.last_self_modification
     }
 
     fn do_tick(&mut self) {
-        let instruction = self.memory.read_instruction(self.registers.pc);
+        if let Some(mut history) = self.history.take() {
+            history.push(self.snapshot());
+            self.history = Some(history);
+        }
+        self.clock.step();
+
+        let pc = self.registers.pc;
+        if let Err(err) = self.memory.resolve(pc as usize + 1) {
+            log::error!("{} fetching at {:#05X}, halting.", err, pc);
+            self.fault(Fault::BadAddress);
+            return;
+        }
+        let instruction = self.memory.read_instruction(pc);
+        for addr in [pc as usize, pc as usize + 1] {
+            if let Ok(addr) = self.memory.resolve(addr) {
+                self.executed[addr] = true;
+            }
+        }
+        if let Some(register_log) = self.register_log.as_mut() {
+            register_log.push(written_registers(
+                &instruction,
+                self.memory_load_save_increment_i,
+            ));
+        }
 
         match instruction.parts() {
             (0, 0, 0xE, 0) => {
                 // CLS - 00e0
+                let before = self
+                    .hooks
+                    .wants_pixel_flips()
+                    .then(|| self.screen.words().to_vec());
                 self.screen.clear();
+                if let Some(before) = before {
+                    self.hooks.pixel_flips(&self.screen, &before);
+                }
+                if let Some(timeline) = self.timeline.as_mut() {
+                    timeline.push(self.clock.frame(), EventKind::Clear);
+                }
+                if self.break_on_clear {
+                    self.halted = true;
+                }
             }
             (0, 0, 0xE, 0xE) => {
                 // RET - 00ee
-                let (mut sp, overflows) = self.registers.sp.overflowing_sub(1);
-                if overflows {
-                    sp = 0xF;
+                let sp = self.registers.sp as usize;
+                if sp == 0 || sp > self.stack_size() {
+                    log::error!(
+                        "Stack underflow at {:#05X} (RET with an empty stack), halting.",
+                        self.registers.pc
+                    );
+                    self.fault(Fault::StackUnderflow);
+                    return;
                 }
-                let pc = self.registers.stack[sp as usize];
+                let sp = sp - 1;
 
-                self.registers.sp = sp;
-                self.registers.pc = pc;
+                self.registers.sp = sp as u8;
+                self.registers.pc = self.registers.stack[sp];
+            }
+            (0, _, _, _) => {
+                // SYS - 0nnn, machine code routines can't be run
+                log::error!(
+                    "SYS {:#05X} at {:#05X} is not supported, halting.",
+                    instruction.nnn(),
+                    self.registers.pc
+                );
+                self.fault(Fault::BadOpcode);
+                return;
             }
-            (0, _, _, _) => panic!("SYS not implemented."),
             (1, _, _, _) => {
                 // JP - 1nnn
                 let nnn = instruction.nnn();
@@ -147,15 +1011,19 @@ impl Cpu {
                 // CALL - 2nnn
                 let nnn = instruction.nnn();
 
-                let sp = self.registers.sp;
-                self.registers.stack[sp as usize] = self.registers.pc;
-
-                let mut sp = sp + 1;
-                if sp > 0xF {
-                    sp = 0x0;
+                let sp = self.registers.sp as usize;
+                if sp >= self.stack_size() {
+                    log::error!(
+                        "Stack overflow at {:#05X} ({} nested calls), halting.",
+                        self.registers.pc,
+                        sp
+                    );
+                    self.fault(Fault::StackOverflow);
+                    return;
                 }
+                self.registers.stack[sp] = self.registers.pc;
 
-                self.registers.sp = sp;
+                self.registers.sp = sp as u8 + 1;
                 self.registers.pc = nnn.wrapping_sub(2);
             }
             (3, _, _, _) => {
@@ -165,7 +1033,7 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
 
                 if vx == kk {
-                    self.registers.pc += 2
+                    self.registers.pc = self.registers.pc.wrapping_add(2)
                 }
             }
             (4, _, _, _) => {
@@ -175,7 +1043,7 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
 
                 if vx != kk {
-                    self.registers.pc += 2
+                    self.registers.pc = self.registers.pc.wrapping_add(2)
                 }
             }
             (5, _, _, 0) => {
@@ -186,7 +1054,7 @@ impl Cpu {
                 let vy = self.registers.v[y as usize];
 
                 if vx == vy {
-                    self.registers.pc += 2
+                    self.registers.pc = self.registers.pc.wrapping_add(2)
                 }
             }
             (6, _, _, _) => {
@@ -216,7 +1084,7 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
                 let vy = self.registers.v[y as usize];
                 self.registers.v[x as usize] = vx | vy;
-                self.registers.v[0xF as usize] = 0;
+                self.registers.v[0xF] = 0;
             }
             (8, _, _, 2) => {
                 // AND - 8xy2
@@ -225,7 +1093,7 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
                 let vy = self.registers.v[y as usize];
                 self.registers.v[x as usize] = vx & vy;
-                self.registers.v[0xF as usize] = 0;
+                self.registers.v[0xF] = 0;
             }
             (8, _, _, 3) => {
                 // XOR - 8xy3
@@ -234,7 +1102,7 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
                 let vy = self.registers.v[y as usize];
                 self.registers.v[x as usize] = vx ^ vy;
-                self.registers.v[0xF as usize] = 0;
+                self.registers.v[0xF] = 0;
             }
             (8, _, _, 4) => {
                 // ADD - 8xy4
@@ -243,8 +1111,7 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
                 let vy = self.registers.v[y as usize];
                 let (vx, overflows) = vx.overflowing_add(vy);
-                self.registers.v[x as usize] = vx;
-                self.registers.v[0x0F] = if overflows { 1 } else { 0 };
+                self.write_with_flag(x, vx, if overflows { 1 } else { 0 });
             }
             (8, _, _, 5) => {
                 // SUB - 8xy5
@@ -253,28 +1120,24 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
                 let vy = self.registers.v[y as usize];
                 let (vx, underflows) = vx.overflowing_sub(vy);
-                self.registers.v[x as usize] = vx;
-                self.registers.v[0x0F] = if underflows { 0 } else { 1 };
+                self.write_with_flag(x, vx, if underflows { 0 } else { 1 });
             }
             (8, _, _, 6) => {
                 // SHR - 8xy6
                 let x = instruction.x();
 
-                let value;
                 let mut vx = self.registers.v[x as usize];
 
-                if self.shifts_against_vy {
+                let value = if self.shifts_against_vy {
                     let y = instruction.y();
-                    let vy = self.registers.v[y as usize];
-                    value = vy;
+                    self.registers.v[y as usize]
                 } else {
-                    value = vx;
-                }
+                    vx
+                };
 
                 let vf = if value & 0x1 != 0 { 1 } else { 0 };
                 vx = value >> 1;
-                self.registers.v[x as usize] = vx;
-                self.registers.v[0x0F] = vf;
+                self.write_with_flag(x, vx, vf);
             }
             (8, _, _, 7) => {
                 // SUBN - 8xy7
@@ -283,28 +1146,24 @@ impl Cpu {
                 let vx = self.registers.v[x as usize];
                 let vy = self.registers.v[y as usize];
                 let (vx, underflows) = vy.overflowing_sub(vx);
-                self.registers.v[x as usize] = vx;
-                self.registers.v[0x0F] = if underflows { 0 } else { 1 };
+                self.write_with_flag(x, vx, if underflows { 0 } else { 1 });
             }
             (8, _, _, 0xE) => {
                 // SHL - 8xye
                 let x = instruction.x();
 
-                let value;
                 let mut vx = self.registers.v[x as usize];
 
-                if self.shifts_against_vy {
+                let value = if self.shifts_against_vy {
                     let y = instruction.y();
-                    let vy = self.registers.v[y as usize];
-                    value = vy;
+                    self.registers.v[y as usize]
                 } else {
-                    value = vx;
-                }
+                    vx
+                };
 
                 let vf = if value & 0x80 != 0 { 1 } else { 0 };
                 vx = value << 1;
-                self.registers.v[x as usize] = vx;
-                self.registers.v[0x0F] = vf;
+                self.write_with_flag(x, vx, vf);
             }
             (9, _, _, 0) => {
                 // SNE - 9xy0
@@ -314,52 +1173,48 @@ impl Cpu {
                 let vy = self.registers.v[y as usize];
 
                 if vx != vy {
-                    self.registers.pc += 2
+                    self.registers.pc = self.registers.pc.wrapping_add(2)
                 }
             }
             (0xA, _, _, _) => {
                 // LD - annn
                 let nnn = instruction.nnn();
-                self.registers.i = nnn;
+                self.set_i(nnn as u32);
             }
             (0xB, _, _, _) => {
                 // JP - bnnn
 
-                let pc: u16;
-
-                if self.jump_to_nnn {
+                let pc = if self.jump_to_nnn {
                     let nnn = instruction.nnn();
                     let v0 = self.registers.v[0] as u16;
-                    pc = nnn.wrapping_add(v0);
+                    nnn.wrapping_add(v0)
                 } else {
                     let kk = instruction.kk() as u16;
                     let x = instruction.x();
                     let vx = self.registers.v[x as usize] as u16;
-                    pc = kk.wrapping_add(vx);
-                }
+                    kk.wrapping_add(vx)
+                };
                 self.registers.pc = pc.wrapping_sub(2);
             }
             (0xC, _, _, _) => {
                 // RND - cxkk
                 let x = instruction.x();
                 let kk = instruction.kk();
-                let rnd: u8 = rand::thread_rng().gen_range(0..=255);
-                let rnd = rnd & kk;
-                self.registers.v[x as usize] = rnd;
+                // A forced value leaves the generator as it was
+                let rnd: u8 = match self.forced_random {
+                    Some(forced) => forced,
+                    None => self.rng.gen_range(0..=255),
+                };
+                self.rng_stats.record(rnd, rnd & kk);
+                self.registers.v[x as usize] = rnd & kk;
             }
             (0xD, _, _, _) => {
                 // DRW - dxyn
-                let now = Instant::now();
-                if let Some(last_draw) = self.last_draw {
-                    let draw_diff = now.duration_since(last_draw).as_secs_f64();
-                    let max = 1.0 / self.draws_per_second as f64;
-                    if draw_diff < max {
-                        // Revise thread sleep and other alternatives
-                        //std::thread::sleep(Duration::from_secs_f64(max - draw_diff));
-                        return;
-                    }
-                }
-                self.last_draw = Some(now);
+                self.draws += 1;
+                let before = self
+                    .hooks
+                    .wants_pixel_flips()
+                    .then(|| self.screen.words().to_vec());
 
                 let i = self.registers.i;
                 let x = instruction.x();
@@ -368,26 +1223,35 @@ impl Cpu {
                 let vy = self.registers.v[y as usize];
                 let n = instruction.n() as u16;
 
-                let x = vx % screen::WIDTH as u8;
-                let mut y = vy % screen::HEIGHT as u8;
+                let screen_width = self.screen.width();
+                let screen_height = self.screen.height();
+                let x = vx as usize % screen_width;
+                let mut y = vy as usize % screen_height;
+
+                let width = if self.sprite_clipping_x {
+                    8.min(screen_width - x)
+                } else {
+                    8
+                };
+                let height = if self.sprite_clipping_y {
+                    (n as usize).min(screen_height - y)
+                } else {
+                    n as usize
+                };
+                let region_y = y;
 
                 let mut collision = false;
                 for idx in 0..n {
                     let addr = i.wrapping_add(idx);
-                    let mut data = (self.memory.read(addr) as u64) << 56;
-
-                    if self.sprite_clipping {
-                        data = data.shr(x as u32);
-                    } else {
-                        data = data.rotate_right(x as u32);
-                    }
+                    let data = self.read_memory(addr);
 
-                    collision |= (self.screen.0[y as usize] & data) != 0;
-                    self.screen.0[y as usize] ^= data;
+                    collision |= self
+                        .screen
+                        .draw_sprite_row(x, y, data, !self.sprite_clipping_x);
 
                     y += 1;
-                    if y >= screen::HEIGHT as u8 {
-                        if self.sprite_clipping {
+                    if y >= screen_height {
+                        if self.sprite_clipping_y {
                             break;
                         } else {
                             y = 0;
@@ -396,6 +1260,19 @@ impl Cpu {
                 }
 
                 self.registers.v[0xF] = if collision { 1 } else { 0 };
+                if let Some(before) = before {
+                    self.hooks.pixel_flips(&self.screen, &before);
+                }
+                self.last_draw_region = Some(DrawRegion {
+                    x,
+                    y: region_y,
+                    width,
+                    height,
+                    collision,
+                });
+                if self.break_on_draw {
+                    self.halted = true;
+                }
             }
             (0xE, _, 9, 0xE) => {
                 // SKP - ex9e
@@ -404,7 +1281,7 @@ impl Cpu {
                 let is_down = self.keypad.get_key_state(vx);
 
                 if is_down {
-                    self.registers.pc += 2;
+                    self.registers.pc = self.registers.pc.wrapping_add(2);
                 }
             }
             (0xE, _, 0xA, 1) => {
@@ -414,7 +1291,7 @@ impl Cpu {
                 let is_down = self.keypad.get_key_state(vx);
 
                 if !is_down {
-                    self.registers.pc += 2;
+                    self.registers.pc = self.registers.pc.wrapping_add(2);
                 }
             }
             (0xF, _, 0, 7) => {
@@ -430,8 +1307,10 @@ impl Cpu {
                 if let Some(key) = key {
                     let x = instruction.x();
                     self.registers.v[x as usize] = key;
+                    self.hooks.key_wait_done();
                 } else {
-                    self.registers.pc -= 2;
+                    self.hooks.key_wait(self.registers.pc);
+                    self.registers.pc = self.registers.pc.wrapping_sub(2);
                 }
             }
             (0xF, _, 1, 5) => {
@@ -449,17 +1328,18 @@ impl Cpu {
             (0xF, _, 1, 0xE) => {
                 // ADD (no carry) - fx1e
                 let x = instruction.x();
-                let vx = self.registers.v[x as usize] as u16;
-                let i = self.registers.i;
-                let i = i.wrapping_add(vx);
-                self.registers.i = i;
+                let vx = self.registers.v[x as usize] as u32;
+                self.set_i(self.registers.i as u32 + vx);
             }
             (0xF, _, 2, 9) => {
                 // LD - fx29
                 let x = instruction.x();
                 let vx = self.registers.v[x as usize];
-                let addr = HEX_SPRITES_START_MEM.wrapping_add((vx * HEX_SPRITES_HEIGHT) as u16);
-                self.registers.i = addr;
+                let Some(digit) = self.font_digit(vx) else {
+                    self.fault(Fault::BadAddress);
+                    return;
+                };
+                self.registers.i = HEX_SPRITES_START_MEM + digit as u16 * HEX_SPRITES_HEIGHT as u16;
             }
             (0xF, _, 3, 3) => {
                 // LD - fx33
@@ -471,9 +1351,9 @@ impl Cpu {
                 let tens = (vx / 10) % 10;
                 let ones = vx % 10;
 
-                self.memory.write(i, hundreds);
-                self.memory.write(i + 1, tens);
-                self.memory.write(i + 2, ones);
+                self.write_memory(i, hundreds);
+                self.write_memory(i.wrapping_add(1), tens);
+                self.write_memory(i.wrapping_add(2), ones);
             }
             (0xF, _, 5, 5) => {
                 // LD [x inclusive] - fx55
@@ -481,12 +1361,12 @@ impl Cpu {
                 let mut addr = self.registers.i;
                 for idx in 0..=x {
                     let v = self.registers.v[idx as usize];
-                    self.memory.write(addr, v);
-                    addr += 1;
+                    self.write_memory(addr, v);
+                    addr = addr.wrapping_add(1);
                 }
 
                 if self.memory_load_save_increment_i {
-                    self.registers.i = addr;
+                    self.set_i(self.registers.i as u32 + x as u32 + 1);
                 }
             }
             (0xF, _, 6, 5) => {
@@ -494,20 +1374,113 @@ impl Cpu {
                 let x = instruction.x();
                 let mut addr = self.registers.i;
                 for idx in 0..=x {
-                    let v = self.memory.read(addr);
+                    let v = self.read_memory(addr);
                     self.registers.v[idx as usize] = v;
-                    addr += 1;
+                    addr = addr.wrapping_add(1);
                 }
 
                 if self.memory_load_save_increment_i {
-                    self.registers.i = addr;
+                    self.set_i(self.registers.i as u32 + x as u32 + 1);
                 }
             }
-            _ => panic!("Unknown instruction."),
+            _ => {
+                log::error!(
+                    "Unknown instruction {:04X} at {:#05X}, halting.",
+                    self.memory.read_u16(self.registers.pc),
+                    self.registers.pc
+                );
+                self.fault(Fault::BadOpcode);
+                return;
+            }
+        }
+
+        // Out of bounds accesses with the Error policy. The instruction
+        // may have partially run, but the PC stays on it.
+        if let Some(err) = self.memory.take_error() {
+            log::error!(
+                "{} running {:04X} at {:#05X}, halting.",
+                err,
+                self.memory.read_u16(pc),
+                pc
+            );
+            self.fault(Fault::BadAddress);
+            return;
+        }
+
+        self.registers.pc = self.registers.pc.wrapping_add(2);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            v: self.registers.v,
+            i: self.registers.i,
+            pc: self.registers.pc,
+            sp: self.registers.sp,
+            stack: self.registers.stack,
+            timers: [
+                self.registers.timers[DELAY_TIMER].read(),
+                self.registers.timers[SOUND_TIMER].read(),
+            ],
+            memory: self.memory.clone(),
+            screen: self.screen.clone(),
+            keypad: self.keypad.clone(),
+            rng: self.rng.clone(),
+            clock: self.clock,
+            draws: self.draws,
+            sound_frames: self.sound_frames,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.registers.v = snapshot.v;
+        self.registers.i = snapshot.i;
+        self.registers.pc = snapshot.pc;
+        self.registers.sp = snapshot.sp;
+        self.registers.stack = snapshot.stack;
+        self.registers.timers[DELAY_TIMER].write(snapshot.timers[DELAY_TIMER]);
+        self.registers.timers[SOUND_TIMER].write(snapshot.timers[SOUND_TIMER]);
+        self.memory = snapshot.memory.clone();
+        self.memory.set_size(self.profile.memory_size());
+        self.screen = snapshot.screen.clone();
+        self.screen.mark_dirty();
+        self.present();
+        self.frame_ready = true;
+        self.keypad = snapshot.keypad.clone();
+        self.rng = snapshot.rng.clone();
+        self.clock = snapshot.clock;
+        self.draws = snapshot.draws;
+        self.sound_frames = snapshot.sound_frames;
+    }
+
+    // Hash of the registers, memory and screen. Two machines
+    // with the same state hash are (almost certainly) identical.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+
+        hasher.write(&self.registers.v);
+        hasher.write_u16(self.registers.i);
+        hasher.write_u16(self.registers.pc);
+        hasher.write_u8(self.registers.sp);
+        for addr in self.registers.stack {
+            hasher.write_u16(addr);
         }
+        for timer in &self.registers.timers {
+            hasher.write_u8(timer.read());
+        }
+
+        hasher.write(&self.memory.dump());
+        hasher.write_usize(self.screen.width());
+        hasher.write_usize(self.screen.height());
+        for word in self.screen.words() {
+            hasher.write_u64(*word);
+        }
+
+        hasher.finish()
+    }
 
-        self.registers.pc += 2;
-        self.handle_beep();
+    // Area affected by the most recent DXYN.
+    pub fn last_draw_region(&self) -> Option<DrawRegion> {
+        self.last_draw_region
     }
 
     pub fn is_rom_loaded(&self) -> bool {
@@ -524,23 +1497,37 @@ impl Cpu {
 
     pub fn resume(&mut self) {
         self.halted = false;
+        self.resumed = true;
+        self.fault = None;
     }
 
-    pub fn toggle_halt(&mut self) {
-        self.halted = !self.halted;
+    fn fault(&mut self, fault: Fault) {
+        self.halted = true;
+        self.fault = Some(fault);
     }
 
-    pub fn is_beep_enabled(&mut self) -> bool {
-        self.beep_enabled
+    // The error that halted the program, the PC still on its instruction.
+    pub fn last_fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    pub fn toggle_halt(&mut self) {
+        if self.halted {
+            self.resume();
+        } else {
+            self.halted = true;
+        }
+    }
+
+    pub fn is_beep_enabled(&self) -> bool {
+        self.beep_enabled
     }
 
     pub fn enable_beep(&mut self) {
         self.beep_enabled = true;
-        self.handle_beep();
     }
     pub fn disable_beep(&mut self) {
         self.beep_enabled = false;
-        self.handle_beep();
     }
 
     pub fn toggle_beep_enabled(&mut self) {
@@ -551,63 +1538,108 @@ impl Cpu {
         }
     }
 
-    pub fn handle_beep(&mut self) {
-        if let Some(beep_handler) = self.beep_handler.borrow_mut() {
-            if self.registers.timers[SOUND_TIMER].read() > 0 && self.beep_enabled {
-                beep_handler.start()
-            } else {
-                beep_handler.stop()
-            }
-        }
+    #[allow(dead_code)]
+    pub fn is_buzzer_on(&self) -> bool {
+        self.beep_enabled && self.sound_timer_active()
+    }
+
+    // Whether the program is sounding the buzzer, even if muted.
+    pub fn sound_timer_active(&self) -> bool {
+        let threshold = if self.sound_timer_threshold { 2 } else { 1 };
+        self.registers.timers[SOUND_TIMER].read() >= threshold
     }
 }
 
 #[cfg(test)]
 mod instruction_tests {
-    use std::u64;
-
     use rand::Rng;
 
-    use crate::core::cpu::Cpu;
+    use crate::core::{
+        beep::{BufferSink, BUZZER_VOLUME},
+        cpu::{Cpu, EmuError, Fault, FontWriteAction, SelfModification},
+        instruction::Opcode,
+        keypad::InputMode,
+        memory::{BoundsPolicy, HEX_SPRITES_START_MEM},
+        profile::MachineProfile,
+        registers::{DELAY_TIMER, SOUND_TIMER},
+        screen,
+        stats::Stats,
+        timing::TimingModel,
+    };
+
+    // 100 samples per frame, 30 of them to fade in or out
+    const SAMPLE_RATE: i32 = 6_000;
+    const RAMP_SAMPLES: usize = 30;
 
     #[test]
     fn test_cls_00e0() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x00, 0xE0], 0x0200);
-        cpu.screen
-            .0
-            .iter_mut()
-            .for_each(|row| *row = rand::thread_rng().gen_range(0..=u64::MAX));
+        cpu.load_rom(&[0x00, 0xE0], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        for y in 0..screen::HEIGHT {
+            for x in 0..screen::WIDTH {
+                cpu.screen.set(x, y, rand::thread_rng().gen_bool(0.5));
+            }
+        }
         cpu.tick();
-        assert!(cpu.screen.0.iter().all(|row| *row == 0));
+        assert!(cpu.screen.iter().all(|(_, _, on)| !on));
     }
     // SYS
 
     #[test]
     fn test_ret_00ee() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x00, 0xEE], 0x0200);
-        cpu.registers.sp = 0;
-        cpu.registers.stack[0xF] = 0x0300;
+        cpu.load_rom(&[0x00, 0xEE], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.registers.sp = 0x1;
+        cpu.registers.stack[0x0] = 0x0300;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x302);
-        assert_eq!(cpu.registers.sp, 0xF);
+        assert_eq!(cpu.registers.sp, 0x0);
     }
     #[test]
-    fn test_ret_00ee_full() {
+    fn test_ret_00ee_empty() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x00, 0xEE], 0x0200);
-        cpu.registers.sp = 0x1;
-        cpu.registers.stack[0x0] = 0x0300;
+        cpu.load_rom(&[0x00, 0xEE], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.registers.sp = 0;
         cpu.tick();
-        assert_eq!(cpu.registers.pc, 0x302);
-        assert_eq!(cpu.registers.sp, 0x0);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.last_fault(), Some(Fault::StackUnderflow));
+        assert_eq!(cpu.registers.pc, 0x200);
+        assert_eq!(cpu.registers.sp, 0);
+    }
+
+    #[test]
+    fn test_sys_0nnn_halts() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x01, 0x23], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x200);
+    }
+
+    #[test]
+    fn test_unknown_instruction_halts() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x51, 0x21], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.last_fault(), Some(Fault::BadOpcode));
+        assert_eq!(cpu.registers.pc, 0x200);
+
+        // Resuming forgets it, until it happens again
+        cpu.resume();
+        assert_eq!(cpu.last_fault(), None);
     }
 
     #[test]
     fn test_jp_1nnn() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x11, 0x23], 0x0200);
+        cpu.load_rom(&[0x11, 0x23], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x123);
     }
@@ -615,7 +1647,8 @@ mod instruction_tests {
     #[test]
     fn test_call_2nnn() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x21, 0x23], 0x0200);
+        cpu.load_rom(&[0x21, 0x23], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x123);
         assert_eq!(cpu.registers.sp, 1);
@@ -624,18 +1657,40 @@ mod instruction_tests {
     #[test]
     fn test_call_2nnn_full() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x21, 0x23], 0x0200);
+        cpu.load_rom(&[0x21, 0x23], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.sp = 0xF;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x123);
-        assert_eq!(cpu.registers.sp, 0);
+        assert_eq!(cpu.registers.sp, 16);
         assert_eq!(cpu.registers.stack[0xF], 0x0200);
     }
+    #[test]
+    fn test_call_2nnn_overflow() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x21, 0x23], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.registers.sp = 16;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.last_fault(), Some(Fault::StackOverflow));
+        assert_eq!(cpu.registers.pc, 0x200);
+
+        // The VIP only has room for 12 calls
+        cpu.clear();
+        cpu.load_rom(&[0x21, 0x23], 0x0200).unwrap();
+        cpu.set_profile(MachineProfile::CosmacVip);
+        cpu.registers.sp = 12;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.sp, 12);
+    }
 
     #[test]
     fn test_se_3xkk_no_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x30, 0x55], 0x0200);
+        cpu.load_rom(&[0x30, 0x55], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x15;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0202);
@@ -643,7 +1698,8 @@ mod instruction_tests {
     #[test]
     fn test_se_3xkk_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x30, 0x55], 0x0200);
+        cpu.load_rom(&[0x30, 0x55], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x55;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0204);
@@ -652,7 +1708,8 @@ mod instruction_tests {
     #[test]
     fn test_sne_3xkk_no_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x40, 0x55], 0x0200);
+        cpu.load_rom(&[0x40, 0x55], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x55;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0202);
@@ -660,7 +1717,8 @@ mod instruction_tests {
     #[test]
     fn test_sne_4xkk_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x40, 0x55], 0x0200);
+        cpu.load_rom(&[0x40, 0x55], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x15;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0204);
@@ -669,7 +1727,8 @@ mod instruction_tests {
     #[test]
     fn test_se_5xy0_no_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x50, 0x10], 0x0200);
+        cpu.load_rom(&[0x50, 0x10], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x28;
         cpu.registers.v[0x1] = 0x55;
         cpu.tick();
@@ -678,7 +1737,8 @@ mod instruction_tests {
     #[test]
     fn test_se_5xy0_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x50, 0x10], 0x0200);
+        cpu.load_rom(&[0x50, 0x10], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x15;
         cpu.registers.v[0x1] = 0x15;
         cpu.tick();
@@ -688,7 +1748,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_6xkk() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x60, 0x12], 0x0200);
+        cpu.load_rom(&[0x60, 0x12], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.tick();
         assert_eq!(cpu.registers.v[0x0], 0x12);
     }
@@ -696,7 +1757,8 @@ mod instruction_tests {
     #[test]
     fn test_add_7xkk() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x70, 0x12], 0x0200);
+        cpu.load_rom(&[0x70, 0x12], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x33;
         cpu.tick();
         assert_eq!(cpu.registers.v[0x0], 0x45);
@@ -706,7 +1768,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_8xy0() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x10], 0x0200);
+        cpu.load_rom(&[0x80, 0x10], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x12;
         cpu.registers.v[0x1] = 0x34;
         cpu.tick();
@@ -717,7 +1780,8 @@ mod instruction_tests {
     #[test]
     fn test_or_8xy1() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x11], 0x0200);
+        cpu.load_rom(&[0x80, 0x11], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0b10101010;
         cpu.registers.v[0x1] = 0b01010101;
         cpu.tick();
@@ -728,7 +1792,8 @@ mod instruction_tests {
     #[test]
     fn test_and_8xy2() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x12], 0x0200);
+        cpu.load_rom(&[0x80, 0x12], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0b10101010;
         cpu.registers.v[0x1] = 0b01010101;
         cpu.tick();
@@ -739,7 +1804,8 @@ mod instruction_tests {
     #[test]
     fn test_xor_8xy3() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x13], 0x0200);
+        cpu.load_rom(&[0x80, 0x13], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0b10101111;
         cpu.registers.v[0x1] = 0b01011111;
         cpu.tick();
@@ -750,7 +1816,8 @@ mod instruction_tests {
     #[test]
     fn test_add_8xy4_no_carry() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x14], 0x0200);
+        cpu.load_rom(&[0x80, 0x14], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x22;
         cpu.registers.v[0x1] = 0x41;
         cpu.tick();
@@ -761,7 +1828,8 @@ mod instruction_tests {
     #[test]
     fn test_add_8xy4_carry() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x14], 0x0200);
+        cpu.load_rom(&[0x80, 0x14], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0xF3;
         cpu.registers.v[0x1] = 0x41;
         cpu.tick();
@@ -773,7 +1841,8 @@ mod instruction_tests {
     #[test]
     fn test_sub_8xy5_no_borrow() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x15], 0x0200);
+        cpu.load_rom(&[0x80, 0x15], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0xF3;
         cpu.registers.v[0x1] = 0x20;
         cpu.tick();
@@ -784,7 +1853,8 @@ mod instruction_tests {
     #[test]
     fn test_sub_8xy5_borrow() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x15], 0x0200);
+        cpu.load_rom(&[0x80, 0x15], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x25;
         cpu.registers.v[0x1] = 0x80;
         cpu.tick();
@@ -796,7 +1866,8 @@ mod instruction_tests {
     #[test]
     fn test_shr_8xy6_no_carry() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x16], 0x0200);
+        cpu.load_rom(&[0x80, 0x16], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         if cpu.shifts_against_vy {
             cpu.registers.v[0x1] = 0b01111110;
         } else {
@@ -810,7 +1881,8 @@ mod instruction_tests {
     #[test]
     fn test_shr_8xy6_carry() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x16], 0x0200);
+        cpu.load_rom(&[0x80, 0x16], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         if cpu.shifts_against_vy {
             cpu.registers.v[0x1] = 0b00111111;
         } else {
@@ -824,7 +1896,8 @@ mod instruction_tests {
     #[test]
     fn test_subn_8xy7_no_borrow() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x17], 0x0200);
+        cpu.load_rom(&[0x80, 0x17], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x25;
         cpu.registers.v[0x1] = 0x80;
         cpu.tick();
@@ -835,7 +1908,8 @@ mod instruction_tests {
     #[test]
     fn test_subn_8xy7_borrow() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x17], 0x0200);
+        cpu.load_rom(&[0x80, 0x17], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0xF3;
         cpu.registers.v[0x1] = 0x20;
         cpu.tick();
@@ -847,7 +1921,8 @@ mod instruction_tests {
     #[test]
     fn test_shl_8xye_no_carry() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x1E], 0x0200);
+        cpu.load_rom(&[0x80, 0x1E], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         if cpu.shifts_against_vy {
             cpu.registers.v[0x1] = 0b01111110;
         } else {
@@ -860,7 +1935,8 @@ mod instruction_tests {
     #[test]
     fn test_shl_8xye_carry() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x80, 0x1E], 0x0200);
+        cpu.load_rom(&[0x80, 0x1E], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         if cpu.shifts_against_vy {
             cpu.registers.v[0x1] = 0b11111100;
         } else {
@@ -874,7 +1950,8 @@ mod instruction_tests {
     #[test]
     fn test_sne_9xy0_no_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x90, 0x10], 0x0200);
+        cpu.load_rom(&[0x90, 0x10], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x12;
         cpu.registers.v[0x1] = 0x12;
         cpu.tick();
@@ -883,7 +1960,8 @@ mod instruction_tests {
     #[test]
     fn test_sne_9xy0_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0x90, 0x10], 0x0200);
+        cpu.load_rom(&[0x90, 0x10], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0x0] = 0x12;
         cpu.registers.v[0x1] = 0x93;
         cpu.tick();
@@ -893,7 +1971,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_annn() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xa1, 0x23], 0x0200);
+        cpu.load_rom(&[0xa1, 0x23], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.tick();
         assert_eq!(cpu.registers.i, 0x123);
     }
@@ -901,7 +1980,8 @@ mod instruction_tests {
     #[test]
     fn test_jp_bnnn() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xb4, 0x03], 0x0200);
+        cpu.load_rom(&[0xb4, 0x03], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x53;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x456);
@@ -910,7 +1990,8 @@ mod instruction_tests {
     #[test]
     fn test_skp_ex9e_no_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xE0, 0x9E], 0x0200);
+        cpu.load_rom(&[0xE0, 0x9E], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x6;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0202);
@@ -918,7 +1999,8 @@ mod instruction_tests {
     #[test]
     fn test_skp_ex9e_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xE0, 0x9E], 0x0200);
+        cpu.load_rom(&[0xE0, 0x9E], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x6;
         cpu.keypad.set_key(0x06, true);
         cpu.tick();
@@ -928,7 +2010,8 @@ mod instruction_tests {
     #[test]
     fn test_skp_exa1_no_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xE0, 0xA1], 0x0200);
+        cpu.load_rom(&[0xE0, 0xA1], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x6;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0204);
@@ -936,17 +2019,39 @@ mod instruction_tests {
     #[test]
     fn test_skp_exa1_skip() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xE0, 0xA1], 0x0200);
+        cpu.load_rom(&[0xE0, 0xA1], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x6;
         cpu.keypad.set_key(0x06, true);
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0202);
     }
 
+    #[test]
+    fn test_latched_keypad() {
+        // SKP V0 - JP 0x200
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xE0, 0x9E, 0x12, 0x00], 0x0200).unwrap();
+        cpu.ticks_per_frame = 2;
+        cpu.keypad.set_input_mode(InputMode::Latched);
+        cpu.tick();
+
+        // Changes between frames show up in the next one
+        cpu.keypad.set_key(0x0, true);
+        cpu.keypad.set_key(0x0, false);
+        cpu.ticks_per_frame = 1;
+        cpu.tick();
+        assert_eq!(cpu.registers.pc, 0x0204);
+
+        cpu.clear();
+        assert_eq!(cpu.keypad.input_mode(), InputMode::Latched);
+    }
+
     #[test]
     fn test_ld_fx0a() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF0, 0x0A], 0x0200);
+        cpu.load_rom(&[0xF0, 0x0A], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.tick();
         assert_eq!(cpu.registers.pc, 0x0200);
         assert_eq!(cpu.registers.v[0], 0x0);
@@ -963,7 +2068,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_fx1e() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF0, 0x1E], 0x0200);
+        cpu.load_rom(&[0xF0, 0x1E], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x20;
         cpu.registers.i = 0x94;
         cpu.tick();
@@ -974,7 +2080,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_fx33() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF0, 0x33], 0x0200);
+        cpu.load_rom(&[0xF0, 0x33], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0xC4; // 196
         cpu.registers.i = 0x500;
         cpu.tick();
@@ -986,7 +2093,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_fx55_first_four() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF3, 0x55], 0x0200);
+        cpu.load_rom(&[0xF3, 0x55], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x12;
         cpu.registers.v[1] = 0x34;
         cpu.registers.v[2] = 0x56;
@@ -1003,7 +2111,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_fx55_one() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF0, 0x55], 0x0200);
+        cpu.load_rom(&[0xF0, 0x55], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.registers.v[0] = 0x12;
         cpu.registers.v[1] = 0x34;
         cpu.registers.i = 0x500;
@@ -1015,7 +2124,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_fx65_first_four() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF3, 0x65], 0x0200);
+        cpu.load_rom(&[0xF3, 0x65], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.memory.write(0x500, 0x12);
         cpu.memory.write(0x501, 0x34);
         cpu.memory.write(0x502, 0x56);
@@ -1032,7 +2142,8 @@ mod instruction_tests {
     #[test]
     fn test_ld_fx65_one() {
         let mut cpu = Cpu::new();
-        cpu.load_rom(vec![0xF0, 0x65], 0x0200);
+        cpu.load_rom(&[0xF0, 0x65], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
         cpu.memory.write(0x500, 0x12);
         cpu.memory.write(0x501, 0x34);
         cpu.registers.i = 0x500;
@@ -1040,4 +2151,1098 @@ mod instruction_tests {
         assert_eq!(cpu.registers.v[0x0], 0x12);
         assert_eq!(cpu.registers.v[0x1], 0x0);
     }
+
+    // Runs one instruction with I at addr and the given bounds policy.
+    fn run_at_boundary(rom: &[u8], addr: u16, policy: BoundsPolicy) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(rom, 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.memory.set_bounds_policy(policy);
+        cpu.registers.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+        cpu.registers.i = addr;
+        cpu.tick();
+        cpu
+    }
+
+    #[test]
+    fn test_bounds_fx55_fx65() {
+        // LD [I], V3 from 0xFFE
+        let cpu = run_at_boundary(&[0xF3, 0x55], 0xFFE, BoundsPolicy::Wrap);
+        assert_eq!(cpu.memory.peek(0xFFF), Some(2));
+        assert_eq!(cpu.memory.peek(0x001), Some(4));
+        assert_eq!(cpu.registers.i, 0x002); // 12 bits wrap
+
+        let cpu = run_at_boundary(&[0xF3, 0x55], 0xFFE, BoundsPolicy::Clamp);
+        assert_eq!(cpu.memory.peek(0xFFF), Some(4));
+
+        let cpu = run_at_boundary(&[0xF3, 0x55], 0xFFE, BoundsPolicy::Error);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x200);
+        assert_eq!(cpu.memory.peek(0xFFF), Some(2));
+
+        // LD V3, [I] from 0xFFF
+        let cpu = run_at_boundary(&[0xF3, 0x65], 0xFFF, BoundsPolicy::Wrap);
+        assert_eq!(cpu.registers.v[1], 0xF0); // Font's first byte
+        let cpu = run_at_boundary(&[0xF3, 0x65], 0xFFF, BoundsPolicy::Error);
+        assert!(cpu.is_halted());
+        let cpu = run_at_boundary(&[0xF3, 0x65], 0xFFC, BoundsPolicy::Error);
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn test_bounds_fx33_dxyn() {
+        // LD B, V0 with V0 = 123 at 0xFFE
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xF0, 0x33], 0x0200).unwrap();
+        cpu.memory.set_bounds_policy(BoundsPolicy::Wrap);
+        cpu.registers.v[0] = 123;
+        cpu.registers.i = 0xFFE;
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.peek(0xFFF), Some(2));
+        assert_eq!(cpu.memory.peek(0x000), Some(3));
+
+        // V0 = 1 there, its ones digit ending up in the last byte
+        let cpu = run_at_boundary(&[0xF0, 0x33], 0xFFE, BoundsPolicy::Clamp);
+        assert_eq!(cpu.memory.peek(0xFFF), Some(1));
+        let cpu = run_at_boundary(&[0xF0, 0x33], 0xFFE, BoundsPolicy::Error);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.last_fault(), Some(Fault::BadAddress));
+
+        // DRW V0, V0, 5 from 0xFFD, reading 2 bytes past the end
+        let cpu = run_at_boundary(&[0xD0, 0x05], 0xFFD, BoundsPolicy::Wrap);
+        assert!(!cpu.is_halted());
+        assert!(cpu.screen.get(1, 4)); // 0x000, the font's 0xF0
+        let cpu = run_at_boundary(&[0xD0, 0x05], 0xFFD, BoundsPolicy::Error);
+        assert!(cpu.is_halted());
+        let cpu = run_at_boundary(&[0xD0, 0x03], 0xFFD, BoundsPolicy::Error);
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn test_bounds_fx29() {
+        // LD F, V0 with V0 = 0x5A, which has no sprite
+        let digit = |policy| {
+            let mut cpu = Cpu::new();
+            cpu.load_rom(&[0xF0, 0x29], 0x0200).unwrap();
+            cpu.ticks_per_frame = 1;
+            cpu.memory.set_bounds_policy(policy);
+            cpu.registers.v[0] = 0x5A;
+            cpu.tick();
+            cpu
+        };
+        let mut cpu = digit(BoundsPolicy::Wrap);
+        assert_eq!(cpu.registers.i, HEX_SPRITES_START_MEM + 0xA * 5);
+        assert_eq!(cpu.take_bad_digit(), Some((0x200, 0x5A)));
+        assert_eq!(cpu.take_bad_digit(), None);
+
+        let cpu = digit(BoundsPolicy::Clamp);
+        assert_eq!(cpu.registers.i, HEX_SPRITES_START_MEM + 0xF * 5);
+
+        let mut cpu = digit(BoundsPolicy::Error);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.last_fault(), Some(Fault::BadAddress));
+        assert_eq!((cpu.registers.pc, cpu.registers.i), (0x200, 0));
+        assert_eq!(cpu.take_bad_digit(), None);
+
+        // Reported once per run
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xF0, 0x29, 0xF0, 0x29], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.registers.v[0] = 0xFF;
+        cpu.tick();
+        assert_eq!(cpu.take_bad_digit(), Some((0x200, 0xFF)));
+        cpu.tick();
+        assert_eq!(cpu.take_bad_digit(), None);
+    }
+
+    #[test]
+    fn test_bounds_fetch() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x1F, 0xFF], 0x0200).unwrap(); // JP 0xFFF
+        cpu.memory.set_bounds_policy(BoundsPolicy::Error);
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.step(),
+            Err(EmuError::Fault {
+                fault: Fault::BadAddress,
+                pc: 0xFFF
+            })
+        );
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0xFFF);
+    }
+
+    #[test]
+    fn test_profile_memory() {
+        // ADD I, V0 - LD [I], V0
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xF0, 0x1E, 0xF0, 0x55], 0x0200).unwrap();
+        cpu.set_profile(MachineProfile::XoChip);
+        assert_eq!(cpu.memory.size(), 0x10000);
+        assert_eq!(cpu.memory.peek(0x0200), Some(0xF0));
+        cpu.registers.i = 0xFFF0;
+        cpu.registers.v[0] = 0x20;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.i, 0x0010); // 16 bits wrap
+        cpu.registers.i = 0x8000;
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.peek(0x8000), Some(0x20));
+
+        // The PC wraps past the end of the 64K, skips included:
+        // SE V0, 0x20 at 0xFFFC
+        cpu.memory.load_slice(0xFFFC, &[0x30, 0x20]).unwrap();
+        cpu.registers.pc = 0xFFFC;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.pc, 0x0000);
+
+        // The VIP keeps 12 bits of I
+        cpu.clear();
+        cpu.load_rom(&[0xF0, 0x1E], 0x0200).unwrap();
+        cpu.set_profile(MachineProfile::CosmacVip);
+        assert_eq!(cpu.memory.size(), 0x1000);
+        cpu.registers.i = 0xFF0;
+        cpu.registers.v[0] = 0x20;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.i, 0x010);
+        assert_eq!(cpu.take_i_overflow(), Some((0x200, 0x1010)));
+    }
+
+    #[test]
+    fn test_vip_timing() {
+        // ADD V1, 1 - JP 0x200: 150 cycles a loop
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x71, 0x01, 0x12, 0x00], 0x0200).unwrap();
+        cpu.timing_model = TimingModel::CosmacVip;
+        cpu.tick();
+        assert_eq!(cpu.registers.v[1], 112);
+
+        // A draw alone overruns the frame, the next one starts late
+        cpu.clear();
+        // DRW V0, V0, 1 - ADD V1, 1 - JP 0x202
+        cpu.load_rom(&[0xD0, 0x01, 0x71, 0x01, 0x12, 0x02], 0x0200)
+            .unwrap();
+        cpu.tick();
+        assert_eq!(cpu.registers.pc, 0x0202);
+        cpu.tick();
+        assert_eq!(cpu.registers.v[1], 71);
+    }
+
+    #[test]
+    fn test_i_overflow() {
+        // ADD I, V0 twice
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xF0, 0x1E, 0xF0, 0x1E], 0x0200).unwrap();
+        cpu.registers.i = 0xFE0;
+        cpu.registers.v[0] = 0x10;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.i, 0xFF0);
+        assert_eq!(cpu.take_i_overflow(), None);
+        cpu.registers.v[0] = 0x20;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.i, 0x010);
+        assert_eq!(cpu.take_i_overflow(), Some((0x202, 0x1010)));
+
+        // Only reported once per run
+        cpu.registers.pc = 0x200;
+        cpu.registers.i = 0xFF0;
+        cpu.step().unwrap();
+        assert_eq!(cpu.take_i_overflow(), None);
+
+        // LD [I], V3 at 0xFFE
+        cpu.clear();
+        cpu.load_rom(&[0xF3, 0x55], 0x0200).unwrap();
+        cpu.registers.i = 0xFFE;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.i, 0x002);
+        assert_eq!(cpu.take_i_overflow(), Some((0x200, 0x1002)));
+    }
+
+    #[test]
+    fn test_self_modification() {
+        // LD I, 0x200 - LD V0, 0x12 - LD [I], V0 - JP 0x206
+        let rom = [0xA2, 0x00, 0x60, 0x12, 0xF0, 0x55, 0x12, 0x06];
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&rom, 0x0200).unwrap();
+        cpu.ticks_per_frame = 4;
+        cpu.tick();
+        assert!(!cpu.is_halted());
+        assert_eq!(
+            cpu.last_self_modification(),
+            Some(SelfModification {
+                pc: 0x204,
+                addr: 0x200
+            })
+        );
+
+        // Halts after the writing instruction
+        cpu.clear();
+        assert_eq!(cpu.last_self_modification(), None);
+        cpu.load_rom(&rom, 0x0200).unwrap();
+        cpu.break_on_self_modify = true;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x206);
+
+        // Writes into data that never ran aren't reported
+        let mut cpu = Cpu::new();
+        cpu.break_on_self_modify = true;
+        cpu.load_rom(&[0xA3, 0x00, 0xF0, 0x55, 0x12, 0x04], 0x0200)
+            .unwrap();
+        cpu.tick();
+        assert!(!cpu.is_halted());
+        assert_eq!(cpu.last_self_modification(), None);
+    }
+
+    #[test]
+    fn test_font_write() {
+        // LD I, 0x010 - LD [I], V0 - JP 0x200
+        let rom = [0xA0, 0x10, 0xF0, 0x55, 0x12, 0x00];
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&rom, 0x0200).unwrap();
+        cpu.tick();
+        assert!(!cpu.is_halted());
+        assert_eq!(cpu.take_font_write(), Some((0x202, 0x010)));
+        cpu.tick();
+        assert_eq!(cpu.take_font_write(), None); // Once per run
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&rom, 0x0200).unwrap();
+        cpu.font_write_action = FontWriteAction::Break;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x204);
+        assert_eq!(cpu.take_font_write(), Some((0x202, 0x010)));
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&rom, 0x0200).unwrap();
+        cpu.font_write_action = FontWriteAction::Ignore;
+        cpu.tick();
+        assert_eq!(cpu.take_font_write(), None);
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut cpu_a = Cpu::new();
+        let mut cpu_b = Cpu::new();
+        cpu_a.load_rom(&[0x60, 0x12], 0x0200).unwrap();
+        cpu_b.load_rom(&[0x60, 0x12], 0x0200).unwrap();
+        assert_eq!(cpu_a.state_hash(), cpu_b.state_hash());
+
+        cpu_a.ticks_per_frame = 1;
+        cpu_a.tick();
+        assert_ne!(cpu_a.state_hash(), cpu_b.state_hash());
+
+        cpu_b.ticks_per_frame = 1;
+        cpu_b.tick();
+        assert_eq!(cpu_a.state_hash(), cpu_b.state_hash());
+
+        cpu_b.screen.set(0, 0, true);
+        assert_ne!(cpu_a.state_hash(), cpu_b.state_hash());
+    }
+
+    #[test]
+    fn test_rnd_cxkk_seeded() {
+        let mut cpu_a = Cpu::new();
+        let mut cpu_b = Cpu::new();
+        cpu_a.load_rom(&[0xC0, 0xFF], 0x0200).unwrap();
+        cpu_b.load_rom(&[0xC0, 0xFF], 0x0200).unwrap();
+        cpu_a.ticks_per_frame = 1;
+        cpu_b.ticks_per_frame = 1;
+        cpu_a.seed_rng(1234);
+        cpu_b.seed_rng(1234);
+        cpu_a.tick();
+        cpu_b.tick();
+        assert_eq!(cpu_a.registers.v[0x0], cpu_b.registers.v[0x0]);
+        assert_eq!(cpu_a.rng_seed(), 1234);
+    }
+
+    #[test]
+    fn test_forced_random() {
+        // RND V0, 0x0F - RND V1, 0xFF - JP 0x200
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xC0, 0x0F, 0xC1, 0xFF, 0x12, 0x00], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 3;
+        cpu.forced_random = Some(0xA5);
+        cpu.tick();
+        assert_eq!((cpu.registers.v[0x0], cpu.registers.v[0x1]), (0x05, 0xA5));
+        let stats = cpu.rng_stats();
+        assert_eq!(stats.total(), 2);
+        assert_eq!((stats.random()[0xA5], stats.results()[0x05]), (2, 1));
+
+        cpu.forced_random = None;
+        cpu.tick();
+        assert_eq!(cpu.rng_stats().total(), 4);
+        cpu.clear();
+        assert_eq!(cpu.rng_stats().total(), 0);
+    }
+
+    #[test]
+    fn test_hooks() {
+        use std::{cell::RefCell, rc::Rc};
+
+        // LD V0, K - CLS - LD ST, V1 - DRW V2, V2, 1 - JP 0x208
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            &[0xF0, 0x0A, 0x00, 0xE0, 0xF1, 0x18, 0xD2, 0x21, 0x12, 0x08],
+            0x0200,
+        )
+        .unwrap();
+        cpu.registers.v[1] = 2;
+        cpu.registers.v[2] = 62;
+        cpu.registers.i = 0x0200; // 0xF0, lighting 4 pixels
+        cpu.screen.set(5, 5, true);
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let log = events.clone();
+        cpu.hooks
+            .set_key_wait(move |pc| log.borrow_mut().push(format!("wait {:03X}", pc)));
+        let log = events.clone();
+        cpu.hooks
+            .set_sound(move |on| log.borrow_mut().push(format!("sound {}", on)));
+        let log = events.clone();
+        cpu.hooks
+            .set_pixel_flip(move |x, y, on| log.borrow_mut().push(format!("{},{} {}", x, y, on)));
+
+        cpu.tick();
+        cpu.tick();
+        assert_eq!(*events.borrow(), vec!["wait 200"]);
+        events.borrow_mut().clear();
+
+        cpu.keypad.set_key(1, true);
+        cpu.tick();
+        cpu.keypad.set_key(1, false);
+        for _ in 0..4 {
+            cpu.tick();
+        }
+        // At 62,30, clipped instead of lighting 0,30 and 1,30 too
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "5,5 false",
+                "62,30 true",
+                "63,30 true",
+                "sound true",
+                "sound false"
+            ]
+        );
+
+        // Unset, nothing is reported
+        cpu.hooks.clear();
+        cpu.tick();
+        assert_eq!(events.borrow().len(), 5);
+    }
+
+    #[test]
+    fn test_quirk_bits() {
+        let mut cpu = Cpu::new();
+        let bits = cpu.quirk_bits();
+        assert_eq!(bits, 0x1F);
+        cpu.set_quirk_bits(0b100101);
+        assert!(cpu.shifts_against_vy);
+        assert!(!cpu.memory_load_save_increment_i);
+        assert!(cpu.sprite_clipping_x);
+        assert!(!cpu.jump_to_nnn);
+        assert!(!cpu.sprite_clipping_y);
+        assert!(cpu.sound_timer_threshold);
+        assert!(!cpu.vf_result_wins);
+        assert_eq!(cpu.quirk_bits(), 0b100101);
+        cpu.set_quirk_bits(0x40);
+        assert!(cpu.vf_result_wins);
+        assert_eq!(cpu.quirk_bits(), 0x40);
+    }
+
+    fn vf_after(rom: &[u8], vf_result_wins: bool) -> u8 {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(rom, 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.vf_result_wins = vf_result_wins;
+        cpu.registers.v[0x1] = 0x05;
+        cpu.registers.v[0xF] = 0xFE;
+        cpu.tick();
+        cpu.registers.v[0xF]
+    }
+
+    #[test]
+    fn test_vf_destination() {
+        // ADD VF, V1: 0xFE + 0x05 = 0x03 with carry
+        assert_eq!(vf_after(&[0x8F, 0x14], false), 0x01);
+        assert_eq!(vf_after(&[0x8F, 0x14], true), 0x03);
+        // SUB VF, V1: 0xF9 without borrow
+        assert_eq!(vf_after(&[0x8F, 0x15], false), 0x01);
+        assert_eq!(vf_after(&[0x8F, 0x15], true), 0xF9);
+        // SUBN VF, V1: 0x07 with borrow
+        assert_eq!(vf_after(&[0x8F, 0x17], false), 0x00);
+        assert_eq!(vf_after(&[0x8F, 0x17], true), 0x07);
+        // SHL VF, VF: 0xFC, shifting out a 1
+        assert_eq!(vf_after(&[0x8F, 0xFE], false), 0x01);
+        assert_eq!(vf_after(&[0x8F, 0xFE], true), 0xFC);
+        // SHR VF, V1: 0x02, shifting out a 1
+        assert_eq!(vf_after(&[0x8F, 0x16], false), 0x01);
+        assert_eq!(vf_after(&[0x8F, 0x16], true), 0x02);
+    }
+
+    fn beeps_with_st(st: u8, threshold: bool) -> bool {
+        let mut cpu = Cpu::new();
+        cpu.sound_timer_threshold = threshold;
+        cpu.registers.timers[SOUND_TIMER].write(st);
+        cpu.is_buzzer_on()
+    }
+
+    #[test]
+    fn test_audio_per_frame() {
+        let samples = BufferSink::new(SAMPLE_RATE);
+        let mut cpu = Cpu::new();
+        cpu.set_audio_sink(Box::new(samples.clone()));
+        // LD V0, 0xFF - LD ST, V0 - JP 0x204
+        cpu.load_rom(&[0x60, 0xFF, 0xF0, 0x18, 0x12, 0x04], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 3;
+
+        cpu.tick();
+        assert_eq!(samples.len(), 100);
+        assert!(samples.samples().iter().any(|&sample| sample != 0.0));
+
+        // Muting fades out, then stays silent
+        cpu.disable_beep();
+        cpu.tick();
+        cpu.tick();
+        assert_eq!(samples.len(), 300);
+        assert!(samples.samples()[200..].iter().all(|&sample| sample == 0.0));
+
+        // Halted machines don't produce audio
+        cpu.halt();
+        cpu.tick();
+        assert_eq!(samples.len(), 300);
+    }
+
+    #[test]
+    fn test_step_frame_audio() {
+        let samples = BufferSink::new(SAMPLE_RATE);
+        let mut cpu = Cpu::new();
+        cpu.set_audio_sink(Box::new(samples.clone()));
+        // LD V0, 0xFF - LD ST, V0 - JP 0x204
+        cpu.load_rom(&[0x60, 0xFF, 0xF0, 0x18, 0x12, 0x04], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 2;
+        cpu.halt();
+
+        cpu.step_frame();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x204);
+        assert_eq!(cpu.frame(), 1);
+        assert_eq!(samples.len(), 100);
+        assert!(samples.samples().iter().any(|&sample| sample != 0.0));
+    }
+
+    // Samples of each frame, run one instruction per frame
+    fn frame_audio(rom: &[u8], frames: usize, threshold: bool) -> Vec<Vec<f32>> {
+        let samples = BufferSink::new(SAMPLE_RATE);
+        let mut cpu = Cpu::new();
+        cpu.set_audio_sink(Box::new(samples.clone()));
+        cpu.load_rom(rom, 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.sound_timer_threshold = threshold;
+        for _ in 0..frames {
+            cpu.tick();
+        }
+        samples
+            .samples()
+            .chunks(SAMPLE_RATE as usize / 60)
+            .map(<[f32]>::to_vec)
+            .collect()
+    }
+
+    fn is_silent(samples: &[f32]) -> bool {
+        samples.iter().all(|&sample| sample == 0.0)
+    }
+
+    #[test]
+    fn test_sound_starts_and_stops_with_st() {
+        // LD V0, 3 - LD ST, V0 - JP 0x204
+        let frames = frame_audio(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04], 6, false);
+        assert!(is_silent(&frames[0]));
+        // ST = 3 sounds for 3 frames, at full volume once faded in
+        for frame in &frames[1..4] {
+            assert!(!is_silent(frame));
+        }
+        assert_eq!(frames[3][99].abs(), BUZZER_VOLUME);
+        // The frame after ST reaches 0 fades out, then it stays silent
+        assert!(frames[4][0] != 0.0);
+        assert!(is_silent(&frames[4][RAMP_SAMPLES..]));
+        assert!(is_silent(&frames[5]));
+    }
+
+    #[test]
+    fn test_sound_for_st_1() {
+        // LD V0, 1 - LD ST, V0 - JP 0x204
+        let rom = [0x60, 0x01, 0xF0, 0x18, 0x12, 0x04];
+        let frames = frame_audio(&rom, 3, false);
+        assert!(is_silent(&frames[0]));
+        assert!(!is_silent(&frames[1]));
+        assert!(is_silent(&frames[2][RAMP_SAMPLES..]));
+        // Too short to be heard on the COSMAC VIP
+        let frames = frame_audio(&rom, 3, true);
+        assert!(frames.iter().all(|frame| is_silent(frame)));
+    }
+
+    #[test]
+    fn test_timers_count_frames() {
+        let mut cpu = Cpu::new();
+        // LD V0, 3 - LD DT, V0 - JP 0x204
+        cpu.load_rom(&[0x60, 0x03, 0xF0, 0x15, 0x12, 0x04], 0x0200)
+            .unwrap();
+
+        cpu.tick();
+        assert_eq!(cpu.registers.timers[DELAY_TIMER].read(), 2);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(cpu.registers.timers[DELAY_TIMER].read(), 2);
+        cpu.tick();
+        cpu.tick();
+        cpu.tick();
+        assert_eq!(cpu.registers.timers[DELAY_TIMER].read(), 0);
+    }
+
+    #[test]
+    fn test_ghosting() {
+        let mut cpu = Cpu::new();
+        // LD I, font 0 - DRW V0, V0, 5 - JP 0x202
+        cpu.load_rom(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x02], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 2;
+        assert!(cpu.previous_screen().is_none());
+        cpu.set_ghosting(true);
+
+        // The ghost is the screen as it was before the frame
+        cpu.tick();
+        assert!(cpu.screen.get(0, 0));
+        assert!(!cpu.previous_screen().unwrap().get(0, 0));
+
+        // The sprite gets erased, the ghost keeps it
+        cpu.tick();
+        assert!(!cpu.screen.get(0, 0));
+        assert!(cpu.previous_screen().unwrap().get(0, 0));
+
+        cpu.set_ghosting(false);
+        assert!(cpu.previous_screen().is_none());
+    }
+
+    #[test]
+    fn test_sound_timer_threshold() {
+        assert!(beeps_with_st(1, false));
+        assert!(!beeps_with_st(1, true));
+        assert!(beeps_with_st(2, true));
+        assert!(!beeps_with_st(0, false));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x70, 0x01, 0xC1, 0xFF, 0x12, 0x00], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 3;
+        cpu.tick();
+
+        let snapshot = cpu.snapshot();
+        let hash = cpu.state_hash();
+        cpu.tick();
+        let next_hash = cpu.state_hash();
+        assert_ne!(hash, next_hash);
+
+        cpu.restore(&snapshot);
+        assert_eq!(cpu.frame(), 1);
+        assert_eq!(cpu.state_hash(), hash);
+
+        // The rng is restored too, so the run repeats exactly
+        cpu.tick();
+        assert_eq!(cpu.state_hash(), next_hash);
+    }
+
+    #[test]
+    fn test_step_result() {
+        // LD I, 0x300 - LD V0, 0x12 - LD [I], V0 - LD I, font 0 - DRW V0, V0, 5
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.step(), Err(EmuError::NoRom));
+        cpu.load_rom(
+            &[
+                0xA3, 0x00, 0x60, 0x12, 0xF0, 0x55, 0xA0, 0x00, 0xD0, 0x05, 0x51, 0x21,
+            ],
+            0x0200,
+        )
+        .unwrap();
+        cpu.step().unwrap();
+        let load = cpu.step().unwrap();
+        assert_eq!(
+            (load.pc, load.data, load.opcode),
+            (0x202, 0x6012, Opcode::LoadByte)
+        );
+        assert_eq!(load.registers, 1);
+
+        let store = cpu.step().unwrap();
+        assert_eq!(store.memory_written, vec![0x300]);
+        assert_eq!(store.pixels_flipped, 0);
+
+        cpu.step().unwrap();
+        // The 0 of the font: 4 + 2 + 2 + 2 + 4 pixels
+        let draw = cpu.step().unwrap();
+        assert_eq!(draw.pixels_flipped, 14);
+        assert!(draw.memory_written.is_empty());
+
+        assert_eq!(
+            cpu.step(),
+            Err(EmuError::Fault {
+                fault: Fault::BadOpcode,
+                pc: 0x20A
+            })
+        );
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x60, 0x12, 0x61, 0x34, 0x80, 0x14], 0x0200)
+            .unwrap();
+        cpu.set_history_capacity(16);
+        cpu.halt();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.pc, 0x0206);
+        assert_eq!(cpu.registers.v[0x0], 0x46);
+        assert_eq!(cpu.history_len(), 3);
+
+        assert!(cpu.step_back());
+        assert_eq!(cpu.registers.pc, 0x0204);
+        assert_eq!(cpu.registers.v[0x0], 0x12);
+        assert_eq!(cpu.registers.v[0x1], 0x34);
+
+        assert!(cpu.step_back());
+        assert!(cpu.step_back());
+        assert_eq!(cpu.registers.pc, 0x0200);
+        assert_eq!(cpu.registers.v[0x0], 0x00);
+        assert!(!cpu.step_back());
+    }
+
+    #[test]
+    fn test_break_on_draw() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xD0, 0x01, 0x60, 0x05, 0x00, 0xE0, 0x61, 0x05], 0x0200)
+            .unwrap();
+        cpu.break_on_draw = true;
+        cpu.break_on_clear = true;
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x0202);
+
+        cpu.resume();
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x0206);
+        assert_eq!(cpu.registers.v[0x0], 0x05);
+        assert_eq!(cpu.registers.v[0x1], 0x00);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut cpu = Cpu::new();
+        // 0x200: LD V0, 1 / 0x202: LD V1, 2 / 0x204: JP 0x200
+        cpu.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x00], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 10;
+        cpu.breakpoints.insert(0x0204);
+
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x0204);
+        assert_eq!(cpu.registers.v[1], 0x02);
+
+        // Resuming runs the instruction at the breakpoint
+        cpu.resume();
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x0204);
+
+        // A breakpoint on the entry point halts before its instruction
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x60, 0x01, 0x12, 0x00], 0x0200).unwrap();
+        cpu.breakpoints.insert(0x0200);
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x0200);
+        assert_eq!(cpu.registers.v[0], 0x00);
+    }
+
+    #[test]
+    fn test_break_opcodes() {
+        let mut cpu = Cpu::new();
+        // LD V0, 1 - LD I, 0x300 - JP 0x200
+        cpu.load_rom(&[0x60, 0x01, 0xA3, 0x00, 0x12, 0x00], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 10;
+        cpu.break_opcodes.insert(Opcode::LoadI);
+
+        cpu.tick();
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.registers.pc, 0x0202);
+        assert_eq!(cpu.registers.i, 0);
+    }
+
+    #[test]
+    fn test_last_draw_region() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xD0, 0x15, 0xD0, 0x15, 0xD0, 0x15], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.registers.v[0x0] = 60;
+        cpu.registers.v[0x1] = 30;
+        assert_eq!(cpu.last_draw_region(), None);
+
+        cpu.tick();
+        let region = cpu.last_draw_region().unwrap();
+        assert_eq!(
+            (region.x, region.y, region.width, region.height),
+            (60, 30, 4, 2)
+        );
+        assert!(!region.collision);
+
+        cpu.tick();
+        assert!(cpu.last_draw_region().unwrap().collision);
+
+        cpu.sprite_clipping_x = false;
+        cpu.sprite_clipping_y = false;
+        cpu.tick();
+        let region = cpu.last_draw_region().unwrap();
+        assert_eq!(
+            (region.x, region.y, region.width, region.height),
+            (60, 30, 8, 5)
+        );
+    }
+
+    // Draws a 2x2 sprite at (63, 31), so it overflows both edges
+    fn draw_corner_sprite(clipping_x: bool, clipping_y: bool) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xD0, 0x12, 0xC0, 0xC0], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.sprite_clipping_x = clipping_x;
+        cpu.sprite_clipping_y = clipping_y;
+        cpu.registers.i = 0x0202;
+        cpu.registers.v[0x0] = 63;
+        cpu.registers.v[0x1] = 31;
+        cpu.tick();
+        cpu
+    }
+
+    fn lit_pixels(cpu: &Cpu) -> Vec<(usize, usize)> {
+        cpu.screen
+            .iter()
+            .filter(|(_, _, on)| *on)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn test_drw_every_time() {
+        // LD I, 0x206 - DRW V0, V0, 1 - DRW V0, V0, 1 - (0x80, a pixel):
+        // both draws of the frame run, erasing the pixel again
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xA2, 0x06, 0xD0, 0x01, 0xD0, 0x01, 0x80], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 3;
+        assert!(!cpu.take_frame_ready());
+        cpu.tick();
+        assert_eq!(cpu.registers.pc, 0x206);
+        assert_eq!(cpu.registers.v[0xF], 1);
+        assert!(!cpu.screen.get(0, 0));
+        assert!(cpu.take_frame_ready());
+        assert!(!cpu.take_frame_ready());
+
+        // Stepped instructions are shown too
+        cpu.registers.pc = 0x202;
+        cpu.step().unwrap();
+        assert!(cpu.screen.get(0, 0));
+        assert!(cpu.take_frame_ready());
+    }
+
+    #[test]
+    fn test_front_screen() {
+        // DRW V0, V0, 1 - JP 0x200, with I = 0x200 (0xD0, 3 pixels)
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xD0, 0x01, 0x12, 0x00], 0x0200).unwrap();
+        cpu.registers.i = 0x0200;
+        cpu.ticks_per_frame = 2;
+        assert!(cpu.take_front_dirty());
+
+        // Halfway through a frame, only the screen has the sprite
+        cpu.step().unwrap();
+        assert!(cpu.screen.get(0, 0));
+        assert!(!cpu.front_screen().get(0, 0));
+        assert!(!cpu.take_front_dirty());
+
+        // Erased again by the end of the frame: no change
+        cpu.tick();
+        assert!(!cpu.screen.get(0, 0));
+        assert!(!cpu.take_front_dirty());
+
+        // Lit again at the end of this one
+        cpu.tick();
+        assert!(cpu.front_screen().get(0, 0));
+        assert!(cpu.take_front_dirty());
+    }
+
+    #[test]
+    fn test_rom_hash() {
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.rom_hash(), None);
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        let hash = cpu.rom_hash();
+        assert!(hash.is_some());
+
+        // Running and writing into the program doesn't change it
+        cpu.memory.write(0x0200, 0x13);
+        cpu.tick();
+        assert_eq!(cpu.rom_hash(), hash);
+
+        cpu.clear();
+        assert_eq!(cpu.rom_hash(), None);
+        cpu.load_rom(&[0x12, 0x02], 0x0200).unwrap();
+        assert_ne!(cpu.rom_hash(), hash);
+    }
+
+    #[test]
+    fn test_stats() {
+        // LD V0, 3 - LD ST, V0 - DRW V1, V1, 1 - JP 0x204
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x60, 0x03, 0xF0, 0x18, 0xD1, 0x11, 0x12, 0x04], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 4;
+        assert_eq!(cpu.stats(), Stats::default());
+
+        for _ in 0..5 {
+            cpu.tick();
+        }
+        cpu.keypad.set_key(1, true);
+        cpu.keypad.set_key(1, true);
+        cpu.keypad.set_key(1, false);
+        let stats = cpu.stats();
+        assert_eq!((stats.cycles, stats.frames), (20, 5));
+        assert_eq!(stats.draws, 9);
+        assert_eq!(stats.key_events, 2);
+        assert_eq!(stats.sound_frames, 3);
+        assert_eq!(stats.sound_seconds(), 3.0 / 60.0);
+
+        // Rewinding takes the counts back too
+        let snapshot = cpu.snapshot();
+        cpu.tick();
+        assert_eq!(cpu.stats().draws, 11);
+        cpu.restore(&snapshot);
+        assert_eq!(cpu.stats(), stats);
+
+        cpu.clear();
+        assert_eq!(cpu.stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_drw_dxyn_clip_both() {
+        let cpu = draw_corner_sprite(true, true);
+        assert_eq!(lit_pixels(&cpu), vec![(63, 31)]);
+    }
+
+    #[test]
+    fn test_drw_dxyn_wrap_x_clip_y() {
+        let cpu = draw_corner_sprite(false, true);
+        assert_eq!(lit_pixels(&cpu), vec![(0, 31), (63, 31)]);
+    }
+
+    #[test]
+    fn test_drw_dxyn_clip_x_wrap_y() {
+        let cpu = draw_corner_sprite(true, false);
+        assert_eq!(lit_pixels(&cpu), vec![(63, 0), (63, 31)]);
+    }
+
+    #[test]
+    fn test_drw_dxyn_wrap_both() {
+        let cpu = draw_corner_sprite(false, false);
+        assert_eq!(lit_pixels(&cpu), vec![(0, 0), (63, 0), (0, 31), (63, 31)]);
+    }
+}
+
+// Property tests for the 8xyN arithmetic, checked against a plain
+// reference model. They use a seeded StdRng rather than proptest, which
+// isn't available offline here, so there's no input shrinking: the
+// failing Vx and Vy are printed instead.
+#[cfg(test)]
+mod arithmetic_property_tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::core::cpu::Cpu;
+
+    // Random cases per opcode and quirk setting, seeded so failures repeat
+    const CASES: usize = 2_000;
+    const SEED: u64 = 0xC8;
+
+    #[derive(Debug, Clone, Copy)]
+    struct Quirks {
+        shifts_against_vy: bool,
+        vf_result_wins: bool,
+    }
+
+    const ALL_QUIRKS: [Quirks; 4] = [
+        Quirks {
+            shifts_against_vy: true,
+            vf_result_wins: false,
+        },
+        Quirks {
+            shifts_against_vy: false,
+            vf_result_wins: false,
+        },
+        Quirks {
+            shifts_against_vy: true,
+            vf_result_wins: true,
+        },
+        Quirks {
+            shifts_against_vy: false,
+            vf_result_wins: true,
+        },
+    ];
+
+    // Runs 8xyN once on the given registers, returning them afterwards.
+    fn run(n: u8, x: u8, y: u8, v: [u8; 16], quirks: Quirks) -> [u8; 16] {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x80 | x, (y << 4) | n], 0x0200).unwrap();
+        cpu.ticks_per_frame = 1;
+        cpu.shifts_against_vy = quirks.shifts_against_vy;
+        cpu.vf_result_wins = quirks.vf_result_wins;
+        cpu.registers.v = v;
+        cpu.tick();
+        assert_eq!(cpu.registers.pc, 0x202);
+        cpu.registers.v
+    }
+
+    // What 8xyN should give for the operands: the result and VF.
+    fn expected(n: u8, vx: u8, vy: u8, shifts_against_vy: bool) -> (u8, u8) {
+        let source = if shifts_against_vy { vy } else { vx };
+        match n {
+            0x4 => {
+                let sum = vx as u16 + vy as u16;
+                (sum as u8, (sum > 0xFF) as u8)
+            }
+            0x5 => (vx.wrapping_sub(vy), (vx >= vy) as u8),
+            0x6 => (source >> 1, source & 0x1),
+            0x7 => (vy.wrapping_sub(vx), (vy >= vx) as u8),
+            0xE => (source << 1, source >> 7),
+            _ => unreachable!(),
+        }
+    }
+
+    // Checks one case: Vx gets the result and VF the flag, or whichever
+    // the quirk picks when x is 0xF, and no other register changes.
+    fn check(n: u8, x: u8, y: u8, v: [u8; 16], quirks: Quirks) {
+        let after = run(n, x, y, v, quirks);
+        let (result, flag) = expected(n, v[x as usize], v[y as usize], quirks.shifts_against_vy);
+        let context = format!("8{:X}{:X}{:X} on {:02X?}, {:?}", x, y, n, v, quirks);
+        if x == 0xF && quirks.vf_result_wins {
+            assert_eq!(after[0xF], result, "VF after {}", context);
+        } else {
+            assert_eq!(after[0xF], flag, "VF after {}", context);
+        }
+        if x != 0xF {
+            assert_eq!(after[x as usize], result, "V{:X} after {}", x, context);
+        }
+        for idx in (0..0xF).filter(|&idx| idx != x as usize) {
+            assert_eq!(after[idx], v[idx], "V{:X} after {}", idx, context);
+        }
+    }
+
+    fn for_random_cases(mut case: impl FnMut(u8, u8, [u8; 16], Quirks)) {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for quirks in ALL_QUIRKS {
+            for _ in 0..CASES {
+                let x = rng.gen_range(0..16);
+                let y = rng.gen_range(0..16);
+                case(x, y, rng.gen(), quirks);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_operands() {
+        for n in [0x4, 0x5, 0x6, 0x7, 0xE] {
+            for_random_cases(|x, y, v, quirks| check(n, x, y, v, quirks));
+        }
+    }
+
+    #[test]
+    fn test_all_register_pairs() {
+        // Every x and y, aliasing included, with operands near the
+        // carry and borrow edges
+        let edges = [0x00, 0x01, 0x7F, 0x80, 0xFF];
+        for n in [0x4, 0x5, 0x6, 0x7, 0xE] {
+            for quirks in ALL_QUIRKS {
+                for x in 0..16 {
+                    for y in 0..16 {
+                        for &vx in &edges {
+                            for &vy in &edges {
+                                let mut v = [0x5A; 16];
+                                v[y as usize] = vy;
+                                v[x as usize] = vx;
+                                check(n, x, y, v, quirks);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_carries_the_whole_sum() {
+        // VF * 256 + Vx is always the real sum
+        for_random_cases(|x, y, v, quirks| {
+            if x == 0xF {
+                return;
+            }
+            let after = run(0x4, x, y, v, quirks);
+            assert_eq!(
+                after[0xF] as u16 * 0x100 + after[x as usize] as u16,
+                v[x as usize] as u16 + v[y as usize] as u16
+            );
+        });
+    }
+
+    #[test]
+    fn test_sub_and_subn_negate() {
+        // Vx - Vy and Vy - Vx add up to 0, and exactly one borrows
+        // unless they're equal
+        for_random_cases(|x, y, v, quirks| {
+            if x == 0xF {
+                return;
+            }
+            let sub = run(0x5, x, y, v, quirks);
+            let subn = run(0x7, x, y, v, quirks);
+            assert_eq!(sub[x as usize].wrapping_add(subn[x as usize]), 0);
+            let equal = v[x as usize] == v[y as usize];
+            assert_eq!(sub[0xF] + subn[0xF], if equal { 2 } else { 1 });
+        });
+    }
+
+    #[test]
+    fn test_shifts_lose_only_the_flag_bit() {
+        // Shifting back the other way restores the operand, but for the
+        // bit that went to VF
+        for_random_cases(|x, y, v, quirks| {
+            if x == 0xF {
+                return;
+            }
+            let source = if quirks.shifts_against_vy {
+                v[y as usize]
+            } else {
+                v[x as usize]
+            };
+            let right = run(0x6, x, y, v, quirks);
+            assert_eq!((right[x as usize] << 1) | right[0xF], source);
+            let left = run(0xE, x, y, v, quirks);
+            assert_eq!((left[x as usize] >> 1) | (left[0xF] << 7), source);
+        });
+    }
 }
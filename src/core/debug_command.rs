@@ -0,0 +1,274 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::Cpu,
+    expr::{Expr, ExprError},
+    symbols::SymbolTable,
+};
+
+// Debugger commands, gdb style:
+//   b <addr>        breakpoint at a label or hex address
+//   x/<n> <addr>    n bytes of memory from addr (16 by default)
+//   p <expr>        value of a watch expression, e.g. "p v5" or "p mem[i]"
+//   c               continue running
+//   si [n]          step n instructions (1 by default)
+//   help
+// Addresses can also be expressions, e.g. "x/8 i" or "b pc+2".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Break(u16),
+    Examine { addr: u16, count: usize },
+    Print { text: String, expr: Expr },
+    Continue,
+    Step(u32),
+    Help,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    Unknown(String),
+    MissingArgument(&'static str),
+    InvalidCount(String),
+    InvalidAddress(String),
+    Expr(ExprError),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Unknown(name) => {
+                write!(f, "Unknown command \"{}\", try \"help\".", name)
+            }
+            CommandError::MissingArgument(argument) => write!(f, "Missing {}.", argument),
+            CommandError::InvalidCount(count) => write!(f, "Invalid count \"{}\".", count),
+            CommandError::InvalidAddress(addr) => write!(f, "Invalid address \"{}\".", addr),
+            CommandError::Expr(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+const HELP: [&str; 6] = [
+    "b <addr>      set a breakpoint",
+    "x/<n> <addr>  show n bytes of memory",
+    "p <expr>      print an expression, e.g. p v5",
+    "c             continue",
+    "si [n]        step n instructions",
+    "help          show this list",
+];
+
+const DEFAULT_EXAMINE_COUNT: usize = 16;
+const EXAMINE_ROW: usize = 8;
+// Steps printed one per line, longer runs only print where they ended
+const MAX_STEP_LINES: u32 = 16;
+// Longer runs are what breakpoints and "c" are for
+const MAX_STEPS: u32 = 10_000;
+
+// Labels, hex addresses, then any expression over the machine state.
+fn address(input: &str, symbols: &SymbolTable, cpu: &Cpu) -> Result<u16, CommandError> {
+    if let Some(addr) = symbols.resolve(input) {
+        return Ok(addr);
+    }
+    let expr = Expr::parse(input).map_err(|_| CommandError::InvalidAddress(input.to_string()))?;
+    u16::try_from(expr.eval(cpu)).map_err(|_| CommandError::InvalidAddress(input.to_string()))
+}
+
+fn count<T: std::str::FromStr + PartialOrd>(input: &str, max: T) -> Result<T, CommandError> {
+    input
+        .parse()
+        .ok()
+        .filter(|count| *count <= max)
+        .ok_or_else(|| CommandError::InvalidCount(input.to_string()))
+}
+
+impl Command {
+    pub fn parse(input: &str, symbols: &SymbolTable, cpu: &Cpu) -> Result<Command, CommandError> {
+        let input = input.trim();
+        let (name, argument) = input
+            .split_once(char::is_whitespace)
+            .map_or((input, ""), |(name, argument)| (name, argument.trim()));
+        // "x/16" carries its count in the name
+        let (name, suffix) = name.split_once('/').unwrap_or((name, ""));
+        let required = |what: &'static str| {
+            if argument.is_empty() {
+                Err(CommandError::MissingArgument(what))
+            } else {
+                Ok(argument)
+            }
+        };
+
+        match name {
+            "b" | "break" => Ok(Command::Break(address(required("address")?, symbols, cpu)?)),
+            "x" => Ok(Command::Examine {
+                addr: address(required("address")?, symbols, cpu)?,
+                count: if suffix.is_empty() {
+                    DEFAULT_EXAMINE_COUNT
+                } else {
+                    count(suffix, cpu.memory.size())?
+                },
+            }),
+            "p" | "print" => {
+                let text = required("expression")?;
+                Ok(Command::Print {
+                    text: text.to_string(),
+                    expr: Expr::parse(text).map_err(CommandError::Expr)?,
+                })
+            }
+            "c" | "continue" => Ok(Command::Continue),
+            "si" | "stepi" => Ok(Command::Step(if argument.is_empty() {
+                1
+            } else {
+                count(argument, MAX_STEPS)?
+            })),
+            "help" | "h" => Ok(Command::Help),
+            _ => Err(CommandError::Unknown(name.to_string())),
+        }
+    }
+
+    // Runs the command, returning the lines to print.
+    pub fn execute(&self, cpu: &mut Cpu, symbols: &SymbolTable) -> Vec<String> {
+        match self {
+            Command::Break(addr) => {
+                cpu.breakpoints.insert(*addr);
+                vec![format!("Breakpoint at {}", symbols.describe(*addr))]
+            }
+            Command::Examine { addr, count } => (0..*count)
+                .step_by(EXAMINE_ROW)
+                .map(|offset| {
+                    let start = addr.wrapping_add(offset as u16);
+                    let bytes: Vec<String> = (0..EXAMINE_ROW.min(count - offset))
+                        .map(
+                            |idx| match cpu.memory.peek(start.wrapping_add(idx as u16)) {
+                                Some(data) => format!("{:02X}", data),
+                                None => "--".to_string(),
+                            },
+                        )
+                        .collect();
+                    format!("{:#06X}: {}", start, bytes.join(" "))
+                })
+                .collect(),
+            Command::Print { text, expr } => {
+                let value = expr.eval(cpu);
+                vec![format!("{} = {} ({:#X})", text, value, value)]
+            }
+            Command::Continue => {
+                cpu.resume();
+                vec!["Continuing.".to_string()]
+            }
+            Command::Step(count) => {
+                cpu.halt();
+                let mut lines = Vec::new();
+                for step in 0..*count {
+                    match cpu.step() {
+                        Ok(executed) if *count <= MAX_STEP_LINES || step + 1 == *count => {
+                            lines.push(format!(
+                                "{}: {:04X} {:?}",
+                                symbols.describe(executed.pc),
+                                executed.data,
+                                executed.opcode
+                            ));
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            lines.push(err.to_string());
+                            break;
+                        }
+                    }
+                }
+                lines.push(format!("PC = {}", symbols.describe(cpu.registers.pc)));
+                lines
+            }
+            Command::Help => HELP.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod debug_command_tests {
+    use super::{Command, CommandError};
+    use crate::core::{cpu::Cpu, symbols::SymbolTable};
+
+    fn cpu() -> Cpu {
+        // LD V5, 0x2A - LD I, 0x300 - JP 0x204
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x65, 0x2A, 0xA3, 0x00, 0x12, 0x04], 0x0200)
+            .unwrap();
+        cpu
+    }
+
+    #[test]
+    fn test_parse() {
+        let cpu = cpu();
+        let mut symbols = SymbolTable::new();
+        symbols.insert("main", 0x0200);
+        let parse = |input| Command::parse(input, &symbols, &cpu);
+        assert_eq!(parse("b 0x234"), Ok(Command::Break(0x0234)));
+        assert_eq!(parse("b main"), Ok(Command::Break(0x0200)));
+        assert_eq!(parse("b pc+2"), Ok(Command::Break(0x0202)));
+        assert_eq!(
+            parse("x/16 0x300"),
+            Ok(Command::Examine {
+                addr: 0x0300,
+                count: 16
+            })
+        );
+        assert_eq!(parse("  c "), Ok(Command::Continue));
+        assert_eq!(parse("si"), Ok(Command::Step(1)));
+        assert_eq!(parse("si 20"), Ok(Command::Step(20)));
+        assert!(matches!(parse("p V5"), Ok(Command::Print { .. })));
+
+        assert_eq!(parse("jump"), Err(CommandError::Unknown("jump".into())));
+        assert_eq!(parse("b"), Err(CommandError::MissingArgument("address")));
+        assert_eq!(
+            parse("x/lots 0x300"),
+            Err(CommandError::InvalidCount("lots".into()))
+        );
+        assert_eq!(
+            parse("x/4097 0x300"),
+            Err(CommandError::InvalidCount("4097".into()))
+        );
+        assert_eq!(
+            parse("si 4000000000"),
+            Err(CommandError::InvalidCount("4000000000".into()))
+        );
+        assert_eq!(
+            parse("b nowhere"),
+            Err(CommandError::InvalidAddress("nowhere".into()))
+        );
+        assert!(matches!(parse("p v5 +"), Err(CommandError::Expr(_))));
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut cpu = cpu();
+        let symbols = SymbolTable::new();
+        let mut run = |input| {
+            Command::parse(input, &symbols, &cpu)
+                .unwrap()
+                .execute(&mut cpu, &symbols)
+        };
+
+        assert_eq!(
+            run("si 2"),
+            vec!["0x0200: 652A LoadByte", "0x0202: A300 LoadI", "PC = 0x0204"]
+        );
+        assert_eq!(run("p V5"), vec!["V5 = 42 (0x2A)"]);
+        assert_eq!(
+            run("x/10 0x200"),
+            vec!["0x0200: 65 2A A3 00 12 04 00 00", "0x0208: 00 00"]
+        );
+        assert_eq!(run("x/2 0xFFF"), vec!["0x0FFF: 00 --"]);
+        assert_eq!(run("b 0x204"), vec!["Breakpoint at 0x0204"]);
+        assert_eq!(run("c"), vec!["Continuing."]);
+        assert!(cpu.breakpoints.contains(&0x0204));
+        assert!(!cpu.is_halted());
+    }
+}
@@ -0,0 +1,343 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::Cpu,
+    registers::{DELAY_TIMER, SOUND_TIMER},
+};
+
+// Watch expressions over the machine state, e.g. "mem[0x3F0] == 5" or
+// "v3 >= 10 && frame > 600". Operands:
+//   numbers (decimal or 0x hex), v0-vf, i, pc, sp, dt, st, frame,
+//   mem[expr] (0 outside memory), (expr)
+// Operators, from loosest to tightest binding:
+//   ||  &&  == != < <= > >=  |  &  + -  ! (unary)
+// Comparisons and logic operators give 1 or 0, like in C.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Operand(Operand),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Frame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExprError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid expression at column {}: {}.",
+            self.offset + 1,
+            self.message
+        )
+    }
+}
+
+// Binary operators by precedence level, loosest first.
+const LEVELS: [&[(&str, BinaryOp)]; 6] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Eq),
+        ("!=", BinaryOp::Ne),
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+    ],
+    &[("|", BinaryOp::BitOr)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+];
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, ExprError> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            at: 0,
+        };
+        let expr = parser.binary(0)?;
+        parser.skip_whitespace();
+        if parser.at != parser.bytes.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(expr)
+    }
+
+    pub fn eval(&self, cpu: &Cpu) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Operand(operand) => {
+                let registers = &cpu.registers;
+                match operand {
+                    Operand::V(idx) => registers.v[*idx] as i64,
+                    Operand::I => registers.i as i64,
+                    Operand::Pc => registers.pc as i64,
+                    Operand::Sp => registers.sp as i64,
+                    Operand::Dt => registers.timers[DELAY_TIMER].read() as i64,
+                    Operand::St => registers.timers[SOUND_TIMER].read() as i64,
+                    Operand::Frame => cpu.frame() as i64,
+                }
+            }
+            Expr::Memory(addr) => u16::try_from(addr.eval(cpu))
+                .ok()
+                .and_then(|addr| cpu.memory.peek(addr))
+                .map_or(0, |data| data as i64),
+            Expr::Not(expr) => (expr.eval(cpu) == 0) as i64,
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(cpu);
+                // Logic operators short circuit
+                match op {
+                    BinaryOp::Or if left != 0 => return 1,
+                    BinaryOp::And if left == 0 => return 0,
+                    _ => {}
+                }
+                let right = right.eval(cpu);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (right != 0) as i64,
+                    BinaryOp::Eq => (left == right) as i64,
+                    BinaryOp::Ne => (left != right) as i64,
+                    BinaryOp::Lt => (left < right) as i64,
+                    BinaryOp::Le => (left <= right) as i64,
+                    BinaryOp::Gt => (left > right) as i64,
+                    BinaryOp::Ge => (left >= right) as i64,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                }
+            }
+        }
+    }
+
+    pub fn is_true(&self, cpu: &Cpu) -> bool {
+        self.eval(cpu) != 0
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> ExprError {
+        ExprError {
+            offset: self.at,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    // Eats the operator, unless it's the start of a longer one
+    // (& of &&, < of <=, ...).
+    fn eat(&mut self, operator: &str) -> bool {
+        self.skip_whitespace();
+        let rest = &self.bytes[self.at..];
+        if !rest.starts_with(operator.as_bytes()) {
+            return false;
+        }
+        let next = rest.get(operator.len()).copied();
+        let longer = match operator {
+            "|" => next == Some(b'|'),
+            "&" => next == Some(b'&'),
+            "<" | ">" | "!" => next == Some(b'='),
+            _ => false,
+        };
+        if longer {
+            return false;
+        }
+        self.at += operator.len();
+        true
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ExprError> {
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            for (text, op) in operators.iter() {
+                if self.eat(text) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.at) {
+            Some(b'(') => {
+                self.at += 1;
+                let expr = self.binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(b'0'..=b'9') => self.number(),
+            Some(byte) if byte.is_ascii_alphabetic() => self.name(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn expect(&mut self, operator: &'static str) -> Result<(), ExprError> {
+        if self.eat(operator) {
+            Ok(())
+        } else {
+            Err(self.error(match operator {
+                ")" => "expected ')'",
+                _ => "expected ']'",
+            }))
+        }
+    }
+
+    fn word(&mut self) -> &str {
+        let start = self.at;
+        while self
+            .bytes
+            .get(self.at)
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+        {
+            self.at += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.at]).unwrap()
+    }
+
+    fn number(&mut self) -> Result<Expr, ExprError> {
+        let start = self.at;
+        let word = self.word().to_ascii_lowercase();
+        let value = match word.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => word.parse(),
+        };
+        value.map(Expr::Number).map_err(|_| ExprError {
+            offset: start,
+            message: "invalid number",
+        })
+    }
+
+    fn name(&mut self) -> Result<Expr, ExprError> {
+        let start = self.at;
+        let word = self.word().to_ascii_lowercase();
+        let operand = match word.as_str() {
+            "mem" => {
+                if !self.eat("[") {
+                    return Err(self.error("expected '['"));
+                }
+                let addr = self.binary(0)?;
+                self.expect("]")?;
+                return Ok(Expr::Memory(Box::new(addr)));
+            }
+            "i" => Operand::I,
+            "pc" => Operand::Pc,
+            "sp" => Operand::Sp,
+            "dt" => Operand::Dt,
+            "st" => Operand::St,
+            "frame" => Operand::Frame,
+            name => match name
+                .strip_prefix('v')
+                .map(|idx| usize::from_str_radix(idx, 16))
+            {
+                Some(Ok(idx)) if idx < 16 && name.len() == 2 => Operand::V(idx),
+                _ => {
+                    return Err(ExprError {
+                        offset: start,
+                        message: "unknown name",
+                    })
+                }
+            },
+        };
+        Ok(Expr::Operand(operand))
+    }
+}
+
+#[cfg(test)]
+mod expr_tests {
+    use super::Expr;
+    use crate::core::cpu::Cpu;
+
+    fn eval(text: &str, cpu: &Cpu) -> i64 {
+        Expr::parse(text).unwrap().eval(cpu)
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut cpu = Cpu::new();
+        cpu.registers.v[0xA] = 7;
+        cpu.registers.i = 0x300;
+        cpu.memory.write(0x3F0, 5);
+
+        assert_eq!(eval("mem[0x3F0] == 5", &cpu), 1);
+        assert_eq!(eval("mem[i + 0xF0]", &cpu), 5);
+        assert_eq!(eval("mem[0x10000]", &cpu), 0);
+        assert_eq!(eval("vA + 3 - 1", &cpu), 9);
+        assert_eq!(eval("va >= 7 && !(v0 != 0)", &cpu), 1);
+        assert_eq!(eval("v0 || va < 7", &cpu), 0);
+        assert_eq!(eval("1 + 2 == 3 & 7", &cpu), 1); // & binds tighter than ==
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text| Expr::parse(text).unwrap_err();
+        assert_eq!(error("v10").message, "unknown name");
+        assert_eq!(error("mem 3").message, "expected '['");
+        assert_eq!(error("mem[3").message, "expected ']'");
+        assert_eq!(error("(1").message, "expected ')'");
+        assert_eq!(error("1 +").message, "unexpected end");
+        assert_eq!(error("1 = 2").offset, 2);
+        assert_eq!(error("0xZ").message, "invalid number");
+    }
+}
@@ -0,0 +1,308 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+const MAX_CODES: usize = 4096;
+const NO_PREFIX: u16 = u16::MAX;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GifError {
+    NotAGif,
+    Truncated,
+    NoImage,
+    InvalidData,
+}
+
+impl fmt::Display for GifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GifError::NotAGif => write!(f, "Not a GIF image."),
+            GifError::Truncated => write!(f, "GIF image is truncated."),
+            GifError::NoImage => write!(f, "GIF file has no image."),
+            GifError::InvalidData => write!(f, "GIF image data is corrupted."),
+        }
+    }
+}
+
+// The first frame of a GIF, as color indices in row order.
+#[allow(dead_code)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub palette: Vec<[u8; 3]>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], GifError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(GifError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, GifError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, GifError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn palette(&mut self, packed: u8) -> Result<Vec<[u8; 3]>, GifError> {
+        if packed & 0x80 == 0 {
+            return Ok(Vec::new());
+        }
+        let size = 2 << (packed & 0x07);
+        Ok(self
+            .bytes(size * 3)?
+            .chunks(3)
+            .map(|color| [color[0], color[1], color[2]])
+            .collect())
+    }
+
+    // Joins data sub-blocks, up to the empty one ending them.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>, GifError> {
+        let mut data = Vec::new();
+        loop {
+            let len = self.u8()? as usize;
+            if len == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(self.bytes(len)?);
+        }
+    }
+}
+
+// Decodes the first frame, which is all cartridges need.
+pub fn decode_first_frame(data: &[u8]) -> Result<Frame, GifError> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        return Err(GifError::NotAGif);
+    }
+    let mut reader = Reader { data, pos: 6 };
+    reader.u16()?; // Canvas width
+    reader.u16()?; // and height
+    let packed = reader.u8()?;
+    reader.bytes(2)?; // Background color and aspect ratio
+    let global_palette = reader.palette(packed)?;
+
+    loop {
+        match reader.u8()? {
+            // Extension, e.g. animation timing
+            0x21 => {
+                reader.u8()?;
+                reader.sub_blocks()?;
+            }
+            0x2C => {
+                reader.bytes(4)?; // Position in the canvas
+                let width = reader.u16()? as usize;
+                let height = reader.u16()? as usize;
+                let packed = reader.u8()?;
+                let local_palette = reader.palette(packed)?;
+                let min_code_size = reader.u8()?;
+                let pixels = decompress(min_code_size, &reader.sub_blocks()?, width * height)?;
+                let pixels = if packed & 0x40 != 0 {
+                    deinterlace(&pixels, width, height)
+                } else {
+                    pixels
+                };
+                return Ok(Frame {
+                    width,
+                    height,
+                    pixels,
+                    palette: if local_palette.is_empty() {
+                        global_palette
+                    } else {
+                        local_palette
+                    },
+                });
+            }
+            _ => return Err(GifError::NoImage),
+        }
+    }
+}
+
+// Variable width LZW, codes packed least significant bit first.
+fn decompress(min_code_size: u8, data: &[u8], len: usize) -> Result<Vec<u8>, GifError> {
+    if !(2..=8).contains(&min_code_size) {
+        return Err(GifError::InvalidData);
+    }
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut prefix = [NO_PREFIX; MAX_CODES];
+    let mut suffix = [0u8; MAX_CODES];
+    for code in 0..clear {
+        suffix[code as usize] = code as u8;
+    }
+
+    let mut pixels = Vec::with_capacity(len);
+    let mut entry = Vec::new();
+    let mut code_size = min_code_size + 1;
+    let mut next = end + 1;
+    let mut previous: Option<u16> = None;
+    let mut bit = 0;
+    while bit + code_size as usize <= data.len() * 8 && pixels.len() < len {
+        let mut code = 0u16;
+        for idx in 0..code_size as usize {
+            let byte = data[(bit + idx) / 8];
+            code |= (((byte >> ((bit + idx) % 8)) & 1) as u16) << idx;
+        }
+        bit += code_size as usize;
+
+        if code == clear {
+            code_size = min_code_size + 1;
+            next = end + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+
+        // A code not in the table yet is the previous entry plus its
+        // own first pixel.
+        let known = code < next;
+        let first = match (known, previous) {
+            (true, _) => code,
+            (false, Some(previous)) if code == next => previous,
+            _ => return Err(GifError::InvalidData),
+        };
+        entry.clear();
+        let mut walk = first;
+        while walk != NO_PREFIX {
+            entry.push(suffix[walk as usize]);
+            walk = prefix[walk as usize];
+        }
+        entry.reverse();
+        if !known {
+            entry.push(entry[0]);
+        }
+        pixels.extend_from_slice(&entry);
+
+        if let Some(previous) = previous {
+            if (next as usize) < MAX_CODES {
+                prefix[next as usize] = previous;
+                suffix[next as usize] = entry[0];
+                next += 1;
+                if next == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+        }
+        previous = Some(code);
+    }
+
+    if pixels.len() < len {
+        return Err(GifError::Truncated);
+    }
+    pixels.truncate(len);
+    Ok(pixels)
+}
+
+// Interlaced images store every 8th row, then the 4th, 2nd and the rest.
+fn deinterlace(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rows = Vec::with_capacity(height);
+    for (start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)] {
+        rows.extend((start..height).step_by(step));
+    }
+    let mut result = vec![0; pixels.len()];
+    for (idx, row) in rows.into_iter().enumerate() {
+        result[row * width..(row + 1) * width]
+            .copy_from_slice(&pixels[idx * width..(idx + 1) * width]);
+    }
+    result
+}
+
+#[cfg(test)]
+pub mod gif_tests {
+    use super::{decode_first_frame, deinterlace, GifError};
+
+    // Writes pixels uncompressed, every code a literal, clearing the
+    // table before the codes grow past 9 bits.
+    pub fn encode(width: u16, height: u16, pixels: &[u8]) -> Vec<u8> {
+        let mut codes = Vec::new();
+        for chunk in pixels.chunks(254) {
+            codes.push(256);
+            codes.extend(chunk.iter().map(|&pixel| pixel as u16));
+        }
+        codes.push(257);
+
+        let mut data = vec![0u8; (codes.len() * 9).div_ceil(8)];
+        for (idx, code) in codes.iter().enumerate() {
+            for bit in 0..9 {
+                if code & (1 << bit) != 0 {
+                    let at = idx * 9 + bit;
+                    data[at / 8] |= 1 << (at % 8);
+                }
+            }
+        }
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.extend_from_slice(&[0xF7, 0, 0]); // 256 color palette
+        gif.extend((0..256).flat_map(|idx| [idx as u8; 3]));
+        gif.extend_from_slice(&[0x21, 0xF9, 4, 0, 0, 0, 0, 0]);
+        gif.push(0x2C);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.extend_from_slice(&[0, 8]);
+        for block in data.chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.extend_from_slice(&[0, 0x3B]);
+        gif
+    }
+
+    #[test]
+    fn test_decode() {
+        let pixels: Vec<u8> = (0..2000).map(|idx| (idx * 7) as u8).collect();
+        let frame = decode_first_frame(&encode(50, 40, &pixels)).unwrap();
+        assert_eq!((frame.width, frame.height), (50, 40));
+        assert_eq!(frame.pixels, pixels);
+        assert_eq!(frame.palette.len(), 256);
+    }
+
+    #[test]
+    fn test_repeated_codes() {
+        // Min code size 2: clear, 1, 6, 7, end. Codes 6 and 7 are each
+        // used right as they're added to the table.
+        let data = [0x8C, 0x5F];
+        let pixels = super::decompress(2, &data, 6).unwrap();
+        assert_eq!(pixels, vec![1, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(decode_first_frame(b"PNG").err(), Some(GifError::NotAGif));
+        let gif = encode(4, 4, &[0; 16]);
+        assert_eq!(
+            decode_first_frame(&gif[..gif.len() - 8]).err(),
+            Some(GifError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_deinterlace() {
+        // One pixel per row, holding its row number
+        let rows = [0, 8, 4, 2, 6, 1, 3, 5, 7, 9];
+        assert_eq!(deinterlace(&rows, 1, 10), (0..10).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,127 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+// Where a problem was found, both counted from 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HexError {
+    Empty,
+    InvalidCharacter(Position, char),
+    OddDigits(Position),
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HexError::Empty => write!(f, "No bytes found."),
+            HexError::InvalidCharacter(pos, c) => write!(
+                f,
+                "Line {}, column {}: '{}' is not a hex digit.",
+                pos.line, pos.column, c
+            ),
+            HexError::OddDigits(pos) => write!(
+                f,
+                "Line {}, column {}: odd number of hex digits.",
+                pos.line, pos.column
+            ),
+        }
+    }
+}
+
+// Parses a ROM written as hex text, e.g. from Octo's binary tools or a
+// forum post. Bytes may be grouped ("00E0 A22A") or prefixed with 0x or
+// $, separated by spaces, commas or semicolons. '#' starts a comment,
+// and hexdump addresses ("0200:") are skipped.
+pub fn parse(text: &str) -> Result<Vec<u8>, HexError> {
+    let mut bytes = Vec::new();
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut column = 0;
+        for token in line.split(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+            let start = column;
+            column += token.chars().count() + 1;
+            if token.is_empty() || token.ends_with(':') {
+                continue;
+            }
+            let pos = |offset: usize| Position {
+                line: line_idx + 1,
+                column: start + offset + 1,
+            };
+
+            let (offset, digits) = match token {
+                _ if token.starts_with("0x") || token.starts_with("0X") => (2, &token[2..]),
+                _ if token.starts_with('$') => (1, &token[1..]),
+                _ => (0, token),
+            };
+            if let Some((idx, c)) = digits
+                .chars()
+                .enumerate()
+                .find(|(_, c)| !c.is_ascii_hexdigit())
+            {
+                return Err(HexError::InvalidCharacter(pos(offset + idx), c));
+            }
+            if digits.is_empty() || digits.len() % 2 != 0 {
+                return Err(HexError::OddDigits(pos(0)));
+            }
+            for idx in (0..digits.len()).step_by(2) {
+                bytes.push(u8::from_str_radix(&digits[idx..idx + 2], 16).unwrap());
+            }
+        }
+    }
+
+    if bytes.is_empty() {
+        return Err(HexError::Empty);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod hex_rom_tests {
+    use super::{parse, HexError, Position};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("00E0 A22A").unwrap(), vec![0x00, 0xE0, 0xA2, 0x2A]);
+        assert_eq!(
+            parse("0x00, 0xe0,\n$12;$00").unwrap(),
+            vec![0x00, 0xE0, 0x12, 0x00]
+        );
+        assert_eq!(
+            parse("# Clear the screen\n0200: 00 E0 # CLS\n0202: 12 02").unwrap(),
+            vec![0x00, 0xE0, 0x12, 0x02]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(" \n# Nothing\n"), Err(HexError::Empty));
+        assert_eq!(
+            parse("00E0\nA2 2G"),
+            Err(HexError::InvalidCharacter(
+                Position { line: 2, column: 5 },
+                'G'
+            ))
+        );
+        assert_eq!(
+            parse("0xE 00"),
+            Err(HexError::OddDigits(Position { line: 1, column: 1 }))
+        );
+        assert_eq!(
+            parse("12 0x"),
+            Err(HexError::OddDigits(Position { line: 1, column: 4 }))
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::screen::Screen;
+
+type PixelFlipHook = Box<dyn FnMut(usize, usize, bool)>;
+
+// Callbacks an embedder (a bot, a visualizer...) registers on a Cpu to
+// react to what the program does instead of polling its state every
+// frame. Unset hooks cost a branch where their event could happen.
+#[derive(Default)]
+pub struct Hooks {
+    pixel_flip: Option<PixelFlipHook>,     // x, y and the new state
+    sound: Option<Box<dyn FnMut(bool)>>,   // The buzzer starting or stopping
+    key_wait: Option<Box<dyn FnMut(u16)>>, // PC of an Fx0A starting to wait
+
+    sounding: bool,
+    waiting: bool,
+}
+
+impl Hooks {
+    #[allow(dead_code)]
+    pub fn set_pixel_flip(&mut self, hook: impl FnMut(usize, usize, bool) + 'static) {
+        self.pixel_flip = Some(Box::new(hook));
+    }
+
+    #[allow(dead_code)]
+    pub fn set_sound(&mut self, hook: impl FnMut(bool) + 'static) {
+        self.sound = Some(Box::new(hook));
+    }
+
+    #[allow(dead_code)]
+    pub fn set_key_wait(&mut self, hook: impl FnMut(u16) + 'static) {
+        self.key_wait = Some(Box::new(hook));
+    }
+
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        *self = Hooks::default();
+    }
+
+    // Forgets the state events are tracked with, when the program restarts.
+    pub fn reset(&mut self) {
+        self.sounding = false;
+        self.waiting = false;
+    }
+
+    // Whether the screen needs copying before instructions that draw,
+    // to report their flips.
+    pub fn wants_pixel_flips(&self) -> bool {
+        self.pixel_flip.is_some()
+    }
+
+    pub fn pixel_flips(&mut self, screen: &Screen, before: &[u64]) {
+        if let Some(hook) = self.pixel_flip.as_mut() {
+            for (x, y, on) in screen.changes_since(before) {
+                hook(x, y, on);
+            }
+        }
+    }
+
+    // Called every frame, reporting only changes.
+    pub fn sound(&mut self, sounding: bool) {
+        if self.sounding != sounding {
+            self.sounding = sounding;
+            if let Some(hook) = self.sound.as_mut() {
+                hook(sounding);
+            }
+        }
+    }
+
+    // Called every time Fx0A runs without a key, reporting only the
+    // first of each wait.
+    pub fn key_wait(&mut self, pc: u16) {
+        if !self.waiting {
+            self.waiting = true;
+            if let Some(hook) = self.key_wait.as_mut() {
+                hook(pc);
+            }
+        }
+    }
+
+    pub fn key_wait_done(&mut self) {
+        self.waiting = false;
+    }
+}
@@ -9,9 +9,315 @@
 
 pub struct Instruction((u8, u8, u8, u8));
 
+// Instruction kind, without its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Cls,             // 00E0
+    Ret,             // 00EE
+    Sys,             // 0nnn
+    Jump,            // 1nnn
+    Call,            // 2nnn
+    SkipEqByte,      // 3xkk
+    SkipNeByte,      // 4xkk
+    SkipEqReg,       // 5xy0
+    LoadByte,        // 6xkk
+    AddByte,         // 7xkk
+    Move,            // 8xy0
+    Or,              // 8xy1
+    And,             // 8xy2
+    Xor,             // 8xy3
+    Add,             // 8xy4
+    Sub,             // 8xy5
+    ShiftRight,      // 8xy6
+    SubN,            // 8xy7
+    ShiftLeft,       // 8xyE
+    SkipNeReg,       // 9xy0
+    LoadI,           // Annn
+    JumpV0,          // Bnnn
+    Random,          // Cxkk
+    Draw,            // Dxyn
+    SkipKeyPressed,  // Ex9E
+    SkipKeyReleased, // ExA1
+    LoadDelay,       // Fx07
+    WaitKey,         // Fx0A
+    SetDelay,        // Fx15
+    SetSound,        // Fx18
+    AddI,            // Fx1E
+    LoadFont,        // Fx29
+    Bcd,             // Fx33
+    StoreRegisters,  // Fx55
+    LoadRegisters,   // Fx65
+    Unknown,
+}
+
+// Reference data of a supported opcode.
+pub struct OpcodeInfo {
+    pub opcode: Opcode,
+    pub encoding: &'static str,
+    pub mnemonic: &'static str,
+    pub description: &'static str,
+    // Behavior with the related quirk (see Quirk) enabled, and disabled
+    pub quirk: Option<(Quirk, &'static str, &'static str)>,
+}
+
+// Quirks changing what an opcode does, by their Cpu field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    ShiftsAgainstVy,
+    MemoryLoadSaveIncrementI,
+    SpriteClipping,
+    JumpToNnn,
+    SoundTimerThreshold,
+    VfResultWins,
+}
+
+const fn info(
+    opcode: Opcode,
+    encoding: &'static str,
+    mnemonic: &'static str,
+    description: &'static str,
+) -> OpcodeInfo {
+    OpcodeInfo {
+        opcode,
+        encoding,
+        mnemonic,
+        description,
+        quirk: None,
+    }
+}
+
+const fn quirky(
+    base: OpcodeInfo,
+    quirk: Quirk,
+    enabled: &'static str,
+    disabled: &'static str,
+) -> OpcodeInfo {
+    OpcodeInfo {
+        quirk: Some((quirk, enabled, disabled)),
+        ..base
+    }
+}
+
+// Every supported opcode, in encoding order.
+pub const OPCODES: [OpcodeInfo; 35] = [
+    info(Opcode::Cls, "00E0", "CLS", "Clears the screen."),
+    info(
+        Opcode::Ret,
+        "00EE",
+        "RET",
+        "Returns from a subroutine, popping PC from the stack.",
+    ),
+    info(
+        Opcode::Sys,
+        "0nnn",
+        "SYS nnn",
+        "Calls a machine code routine. Not supported: halts.",
+    ),
+    info(Opcode::Jump, "1nnn", "JP nnn", "Jumps to nnn."),
+    info(
+        Opcode::Call,
+        "2nnn",
+        "CALL nnn",
+        "Calls the subroutine at nnn, pushing PC to the stack.",
+    ),
+    info(
+        Opcode::SkipEqByte,
+        "3xkk",
+        "SE Vx, kk",
+        "Skips the next instruction if Vx == kk.",
+    ),
+    info(
+        Opcode::SkipNeByte,
+        "4xkk",
+        "SNE Vx, kk",
+        "Skips the next instruction if Vx != kk.",
+    ),
+    info(
+        Opcode::SkipEqReg,
+        "5xy0",
+        "SE Vx, Vy",
+        "Skips the next instruction if Vx == Vy.",
+    ),
+    info(Opcode::LoadByte, "6xkk", "LD Vx, kk", "Vx = kk."),
+    info(
+        Opcode::AddByte,
+        "7xkk",
+        "ADD Vx, kk",
+        "Vx = Vx + kk, without carry.",
+    ),
+    info(Opcode::Move, "8xy0", "LD Vx, Vy", "Vx = Vy."),
+    info(Opcode::Or, "8xy1", "OR Vx, Vy", "Vx = Vx | Vy, VF = 0."),
+    info(Opcode::And, "8xy2", "AND Vx, Vy", "Vx = Vx & Vy, VF = 0."),
+    info(Opcode::Xor, "8xy3", "XOR Vx, Vy", "Vx = Vx ^ Vy, VF = 0."),
+    quirky(
+        info(
+            Opcode::Add,
+            "8xy4",
+            "ADD Vx, Vy",
+            "Vx = Vx + Vy, VF = 1 on carry.",
+        ),
+        Quirk::VfResultWins,
+        "ADD VF, Vy leaves the result in VF",
+        "ADD VF, Vy leaves the flag in VF",
+    ),
+    quirky(
+        info(
+            Opcode::Sub,
+            "8xy5",
+            "SUB Vx, Vy",
+            "Vx = Vx - Vy, VF = 0 on borrow.",
+        ),
+        Quirk::VfResultWins,
+        "SUB VF, Vy leaves the result in VF",
+        "SUB VF, Vy leaves the flag in VF",
+    ),
+    quirky(
+        info(
+            Opcode::ShiftRight,
+            "8xy6",
+            "SHR Vx, Vy",
+            "Shifts right by one, VF = the bit shifted out.",
+        ),
+        Quirk::ShiftsAgainstVy,
+        "Vx = Vy >> 1",
+        "Vx = Vx >> 1",
+    ),
+    quirky(
+        info(
+            Opcode::SubN,
+            "8xy7",
+            "SUBN Vx, Vy",
+            "Vx = Vy - Vx, VF = 0 on borrow.",
+        ),
+        Quirk::VfResultWins,
+        "SUBN VF, Vy leaves the result in VF",
+        "SUBN VF, Vy leaves the flag in VF",
+    ),
+    quirky(
+        info(
+            Opcode::ShiftLeft,
+            "8xyE",
+            "SHL Vx, Vy",
+            "Shifts left by one, VF = the bit shifted out.",
+        ),
+        Quirk::ShiftsAgainstVy,
+        "Vx = Vy << 1",
+        "Vx = Vx << 1",
+    ),
+    info(
+        Opcode::SkipNeReg,
+        "9xy0",
+        "SNE Vx, Vy",
+        "Skips the next instruction if Vx != Vy.",
+    ),
+    info(Opcode::LoadI, "Annn", "LD I, nnn", "I = nnn."),
+    quirky(
+        info(
+            Opcode::JumpV0,
+            "Bnnn",
+            "JP V0, nnn",
+            "Jumps with an offset.",
+        ),
+        Quirk::JumpToNnn,
+        "PC = V0 + nnn",
+        "PC = Vx + kk",
+    ),
+    info(
+        Opcode::Random,
+        "Cxkk",
+        "RND Vx, kk",
+        "Vx = a random byte & kk.",
+    ),
+    quirky(
+        info(
+            Opcode::Draw,
+            "Dxyn",
+            "DRW Vx, Vy, n",
+            "XORs the n byte sprite at I into the screen at (Vx, Vy), VF = 1 on collision.",
+        ),
+        Quirk::SpriteClipping,
+        "Sprites past the edges get clipped",
+        "Sprites past the edges wrap around",
+    ),
+    info(
+        Opcode::SkipKeyPressed,
+        "Ex9E",
+        "SKP Vx",
+        "Skips the next instruction if the key in Vx is down.",
+    ),
+    info(
+        Opcode::SkipKeyReleased,
+        "ExA1",
+        "SKNP Vx",
+        "Skips the next instruction if the key in Vx is up.",
+    ),
+    info(
+        Opcode::LoadDelay,
+        "Fx07",
+        "LD Vx, DT",
+        "Vx = the delay timer.",
+    ),
+    info(
+        Opcode::WaitKey,
+        "Fx0A",
+        "LD Vx, K",
+        "Waits for a key to be released, and stores it in Vx.",
+    ),
+    info(Opcode::SetDelay, "Fx15", "LD DT, Vx", "Delay timer = Vx."),
+    quirky(
+        info(Opcode::SetSound, "Fx18", "LD ST, Vx", "Sound timer = Vx."),
+        Quirk::SoundTimerThreshold,
+        "The buzzer sounds while ST >= 2",
+        "The buzzer sounds while ST > 0",
+    ),
+    info(Opcode::AddI, "Fx1E", "ADD I, Vx", "I = I + Vx."),
+    info(
+        Opcode::LoadFont,
+        "Fx29",
+        "LD F, Vx",
+        "I = the address of the font sprite for the digit in Vx.",
+    ),
+    info(
+        Opcode::Bcd,
+        "Fx33",
+        "LD B, Vx",
+        "Stores the decimal digits of Vx at I, I+1 and I+2.",
+    ),
+    quirky(
+        info(
+            Opcode::StoreRegisters,
+            "Fx55",
+            "LD [I], Vx",
+            "Stores V0 to Vx in memory, starting at I.",
+        ),
+        Quirk::MemoryLoadSaveIncrementI,
+        "I = I + x + 1 afterwards",
+        "I is left unchanged",
+    ),
+    quirky(
+        info(
+            Opcode::LoadRegisters,
+            "Fx65",
+            "LD Vx, [I]",
+            "Loads V0 to Vx from memory, starting at I.",
+        ),
+        Quirk::MemoryLoadSaveIncrementI,
+        "I = I + x + 1 afterwards",
+        "I is left unchanged",
+    ),
+];
+
+impl Opcode {
+    // None for Unknown.
+    #[allow(dead_code)]
+    pub fn info(&self) -> Option<&'static OpcodeInfo> {
+        OPCODES.iter().find(|info| info.opcode == *self)
+    }
+}
+
 impl Instruction {
     pub fn new(code: (u8, u8, u8, u8)) -> Instruction {
-        Instruction { 0: code }
+        Instruction(code)
     }
 
     pub fn parts(&self) -> (u8, u8, u8, u8) {
@@ -34,11 +340,89 @@ impl Instruction {
     }
 
     pub fn kk(&self) -> u8 {
-        ((self.0 .2 as u8) << 4) | (self.0 .3 as u8)
+        (self.0 .2 << 4) | self.0 .3
     }
 
     pub fn nnn(&self) -> u16 {
         // 12 bits
         ((self.0 .1 as u16) << 8) | ((self.0 .2 as u16) << 4) | (self.0 .3 as u16)
     }
+
+    pub fn opcode(&self) -> Opcode {
+        match self.0 {
+            (0, 0, 0xE, 0) => Opcode::Cls,
+            (0, 0, 0xE, 0xE) => Opcode::Ret,
+            (0, _, _, _) => Opcode::Sys,
+            (1, _, _, _) => Opcode::Jump,
+            (2, _, _, _) => Opcode::Call,
+            (3, _, _, _) => Opcode::SkipEqByte,
+            (4, _, _, _) => Opcode::SkipNeByte,
+            (5, _, _, 0) => Opcode::SkipEqReg,
+            (6, _, _, _) => Opcode::LoadByte,
+            (7, _, _, _) => Opcode::AddByte,
+            (8, _, _, 0) => Opcode::Move,
+            (8, _, _, 1) => Opcode::Or,
+            (8, _, _, 2) => Opcode::And,
+            (8, _, _, 3) => Opcode::Xor,
+            (8, _, _, 4) => Opcode::Add,
+            (8, _, _, 5) => Opcode::Sub,
+            (8, _, _, 6) => Opcode::ShiftRight,
+            (8, _, _, 7) => Opcode::SubN,
+            (8, _, _, 0xE) => Opcode::ShiftLeft,
+            (9, _, _, 0) => Opcode::SkipNeReg,
+            (0xA, _, _, _) => Opcode::LoadI,
+            (0xB, _, _, _) => Opcode::JumpV0,
+            (0xC, _, _, _) => Opcode::Random,
+            (0xD, _, _, _) => Opcode::Draw,
+            (0xE, _, 9, 0xE) => Opcode::SkipKeyPressed,
+            (0xE, _, 0xA, 1) => Opcode::SkipKeyReleased,
+            (0xF, _, 0, 7) => Opcode::LoadDelay,
+            (0xF, _, 0, 0xA) => Opcode::WaitKey,
+            (0xF, _, 1, 5) => Opcode::SetDelay,
+            (0xF, _, 1, 8) => Opcode::SetSound,
+            (0xF, _, 1, 0xE) => Opcode::AddI,
+            (0xF, _, 2, 9) => Opcode::LoadFont,
+            (0xF, _, 3, 3) => Opcode::Bcd,
+            (0xF, _, 5, 5) => Opcode::StoreRegisters,
+            (0xF, _, 6, 5) => Opcode::LoadRegisters,
+            _ => Opcode::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod instruction_tests {
+    use super::{Instruction, Opcode, OPCODES};
+
+    #[test]
+    fn test_opcode() {
+        assert_eq!(Instruction::new((0, 0, 0xE, 0)).opcode(), Opcode::Cls);
+        assert_eq!(Instruction::new((0, 0, 0xE, 0xE)).opcode(), Opcode::Ret);
+        assert_eq!(Instruction::new((0, 1, 2, 3)).opcode(), Opcode::Sys);
+        assert_eq!(Instruction::new((8, 1, 2, 0xE)).opcode(), Opcode::ShiftLeft);
+        assert_eq!(Instruction::new((0xD, 1, 2, 5)).opcode(), Opcode::Draw);
+        assert_eq!(
+            Instruction::new((0xF, 3, 6, 5)).opcode(),
+            Opcode::LoadRegisters
+        );
+        assert_eq!(Instruction::new((5, 1, 2, 1)).opcode(), Opcode::Unknown);
+        assert_eq!(Instruction::new((8, 1, 2, 8)).opcode(), Opcode::Unknown);
+        assert_eq!(Instruction::new((0xF, 0, 9, 9)).opcode(), Opcode::Unknown);
+    }
+
+    #[test]
+    fn test_opcode_info_matches_decoding() {
+        for info in &OPCODES {
+            // The encoding with every operand set to 0 decodes to the opcode
+            let nibbles: Vec<u8> = info
+                .encoding
+                .chars()
+                .map(|c| c.to_digit(16).unwrap_or(0) as u8)
+                .collect();
+            let code = (nibbles[0], nibbles[1], nibbles[2], nibbles[3]);
+            assert_eq!(Instruction::new(code).opcode(), info.opcode);
+            assert_eq!(info.opcode.info().unwrap().encoding, info.encoding);
+        }
+        assert!(Opcode::Unknown.info().is_none());
+    }
 }
@@ -0,0 +1,357 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::Cpu,
+    json::{Json, JsonError},
+    registers::{DELAY_TIMER, SOUND_TIMER, STACK_CAPACITY},
+};
+
+// Machine state as JSON, meant to be exchanged with other tools and
+// emulators. Schema (version 1), every number in decimal:
+// {
+//   "format": "chip-8-state",
+//   "version": 1,
+//   "v": [16 bytes],          V0 - VF
+//   "i": 0-65535,
+//   "pc": 0-65535,
+//   "sp": 0-16,
+//   "stack": [16 addresses],
+//   "dt": 0-255,              Delay timer
+//   "st": 0-255,              Sound timer
+//   "quirks": {               Optional, missing ones keep their value
+//     "shifts_against_vy": bool,
+//     "memory_load_save_increment_i": bool,
+//     "sprite_clipping_x": bool,
+//     "sprite_clipping_y": bool,
+//     "jump_to_nnn": bool,
+//     "sound_timer_threshold": bool,
+//     "vf_result_wins": bool
+//   },
+//   "memory": "...",          Every byte (4096 on classic machines) as 2 hex digits
+//   "screen": {
+//     "width": 64,            A multiple of 64
+//     "height": 32,
+//     "rows": ["#..#...", ...] One string per row, # for lit pixels
+//   }
+// }
+//
+// Like binary save states, the keypad and RNG aren't stored.
+const FORMAT: &str = "chip-8-state";
+const VERSION: u64 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum JsonStateError {
+    Json(JsonError),
+    NotAState,
+    UnsupportedVersion(u64),
+    Invalid(&'static str), // The field that's missing or out of range
+}
+
+impl fmt::Display for JsonStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonStateError::Json(err) => write!(f, "{}", err),
+            JsonStateError::NotAState => write!(f, "Not a CHIP-8 state file."),
+            JsonStateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported state version ({}).", version)
+            }
+            JsonStateError::Invalid(field) => write!(f, "Missing or invalid '{}'.", field),
+        }
+    }
+}
+
+pub fn save(cpu: &Cpu) -> String {
+    let registers = &cpu.registers;
+    let number = |value: u64| Json::Number(value as f64);
+    let numbers = |values: Vec<u64>| Json::Array(values.into_iter().map(number).collect());
+
+    let quirks = [
+        ("shifts_against_vy", cpu.shifts_against_vy),
+        (
+            "memory_load_save_increment_i",
+            cpu.memory_load_save_increment_i,
+        ),
+        ("sprite_clipping_x", cpu.sprite_clipping_x),
+        ("sprite_clipping_y", cpu.sprite_clipping_y),
+        ("jump_to_nnn", cpu.jump_to_nnn),
+        ("sound_timer_threshold", cpu.sound_timer_threshold),
+        ("vf_result_wins", cpu.vf_result_wins),
+    ];
+    let memory: String = cpu
+        .memory
+        .dump()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let rows = (0..cpu.screen.height())
+        .map(|y| {
+            let row = (0..cpu.screen.width())
+                .map(|x| if cpu.screen.get(x, y) { '#' } else { '.' })
+                .collect();
+            Json::String(row)
+        })
+        .collect();
+
+    let state = Json::Object(vec![
+        ("format".to_string(), Json::String(FORMAT.to_string())),
+        ("version".to_string(), number(VERSION)),
+        (
+            "v".to_string(),
+            numbers(registers.v.iter().map(|&v| v as u64).collect()),
+        ),
+        ("i".to_string(), number(registers.i as u64)),
+        ("pc".to_string(), number(registers.pc as u64)),
+        ("sp".to_string(), number(registers.sp as u64)),
+        (
+            "stack".to_string(),
+            numbers(registers.stack.iter().map(|&addr| addr as u64).collect()),
+        ),
+        (
+            "dt".to_string(),
+            number(registers.timers[DELAY_TIMER].read() as u64),
+        ),
+        (
+            "st".to_string(),
+            number(registers.timers[SOUND_TIMER].read() as u64),
+        ),
+        (
+            "quirks".to_string(),
+            Json::Object(
+                quirks
+                    .iter()
+                    .map(|(name, on)| (name.to_string(), Json::Bool(*on)))
+                    .collect(),
+            ),
+        ),
+        ("memory".to_string(), Json::String(memory)),
+        (
+            "screen".to_string(),
+            Json::Object(vec![
+                ("width".to_string(), number(cpu.screen.width() as u64)),
+                ("height".to_string(), number(cpu.screen.height() as u64)),
+                ("rows".to_string(), Json::Array(rows)),
+            ]),
+        ),
+    ]);
+    state.to_string()
+}
+
+// Restores a JSON state into the cpu. As with binary states, the loaded
+// ROM is kept. Nothing changes if the state is invalid.
+pub fn load(cpu: &mut Cpu, text: &str) -> Result<(), JsonStateError> {
+    let state = Json::parse(text).map_err(JsonStateError::Json)?;
+    if state.get("format").and_then(Json::as_str) != Some(FORMAT) {
+        return Err(JsonStateError::NotAState);
+    }
+    match state.get("version").and_then(Json::as_u64) {
+        Some(VERSION) => {}
+        Some(version) => return Err(JsonStateError::UnsupportedVersion(version)),
+        None => return Err(JsonStateError::Invalid("version")),
+    }
+
+    let field = |name: &'static str, max: u64| {
+        state
+            .get(name)
+            .and_then(Json::as_u64)
+            .filter(|&value| value <= max)
+            .ok_or(JsonStateError::Invalid(name))
+    };
+    let array = |name: &'static str, max: u64| -> Result<[u64; 16], JsonStateError> {
+        let values: Option<Vec<u64>> = state
+            .get(name)
+            .and_then(Json::as_array)
+            .filter(|values| values.len() == 16)
+            .map(|values| values.iter().map(Json::as_u64).collect())
+            .and_then(|values: Option<Vec<u64>>| values)
+            .filter(|values| values.iter().all(|&value| value <= max));
+        values
+            .map(|values| values.try_into().unwrap())
+            .ok_or(JsonStateError::Invalid(name))
+    };
+
+    let v = array("v", 0xFF)?;
+    let stack = array("stack", 0xFFFF)?;
+    let i = field("i", 0xFFFF)?;
+    let pc = field("pc", 0xFFFF)?;
+    let sp = field("sp", STACK_CAPACITY as u64)?;
+    let dt = field("dt", 0xFF)?;
+    let st = field("st", 0xFF)?;
+
+    let memory = state
+        .get("memory")
+        .and_then(Json::as_str)
+        .filter(|hex| hex.len() == cpu.memory.size() * 2 && hex.is_ascii())
+        .and_then(|hex| {
+            (0..cpu.memory.size())
+                .map(|idx| u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16).ok())
+                .collect::<Option<Vec<u8>>>()
+        })
+        .ok_or(JsonStateError::Invalid("memory"))?;
+
+    let screen = state
+        .get("screen")
+        .ok_or(JsonStateError::Invalid("screen"))?;
+    let size = |name: &'static str| {
+        screen
+            .get(name)
+            .and_then(Json::as_u64)
+            .filter(|&size| size > 0 && size <= 1024)
+            .map(|size| size as usize)
+            .ok_or(JsonStateError::Invalid(name))
+    };
+    let (width, height) = (size("width")?, size("height")?);
+    if !width.is_multiple_of(64) {
+        return Err(JsonStateError::Invalid("width"));
+    }
+    let rows: Vec<&str> = screen
+        .get("rows")
+        .and_then(Json::as_array)
+        .and_then(|rows| rows.iter().map(Json::as_str).collect::<Option<Vec<&str>>>())
+        .filter(|rows| {
+            rows.len() == height
+                && rows
+                    .iter()
+                    .all(|row| row.len() == width && row.bytes().all(|c| c == b'#' || c == b'.'))
+        })
+        .ok_or(JsonStateError::Invalid("rows"))?;
+
+    let mut quirks = Vec::new();
+    if let Some(Json::Object(entries)) = state.get("quirks") {
+        for (name, value) in entries {
+            let on = value.as_bool().ok_or(JsonStateError::Invalid("quirks"))?;
+            quirks.push((name.as_str(), on));
+        }
+    }
+
+    let registers = &mut cpu.registers;
+    registers.v = v.map(|value| value as u8);
+    registers.stack = stack.map(|addr| addr as u16);
+    registers.i = i as u16;
+    registers.pc = pc as u16;
+    registers.sp = sp as u8;
+    registers.timers[DELAY_TIMER].write(dt as u8);
+    registers.timers[SOUND_TIMER].write(st as u8);
+
+    for (name, on) in quirks {
+        match name {
+            "shifts_against_vy" => cpu.shifts_against_vy = on,
+            "memory_load_save_increment_i" => cpu.memory_load_save_increment_i = on,
+            "sprite_clipping_x" => cpu.sprite_clipping_x = on,
+            "sprite_clipping_y" => cpu.sprite_clipping_y = on,
+            "jump_to_nnn" => cpu.jump_to_nnn = on,
+            "sound_timer_threshold" => cpu.sound_timer_threshold = on,
+            "vf_result_wins" => cpu.vf_result_wins = on,
+            _ => log::warn!("Ignoring unknown quirk '{}' in the state.", name),
+        }
+    }
+
+    cpu.memory
+        .load_slice(0, &memory)
+        .map_err(|_| JsonStateError::Invalid("memory"))?;
+    cpu.screen.set_resolution(width, height);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, pixel) in row.bytes().enumerate() {
+            cpu.screen.set(x, y, pixel == b'#');
+        }
+    }
+    cpu.mark_screens_dirty();
+
+    for key in 0..=0xF {
+        cpu.keypad.set_key(key, false);
+    }
+    cpu.keypad.last_key = None;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod json_state_tests {
+    use super::{load, save, JsonStateError};
+    use crate::core::{cpu::Cpu, profile::MachineProfile};
+
+    fn sample_cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        cpu.registers.v[3] = 0x42;
+        cpu.registers.i = 0x0321;
+        cpu.registers.pc = 0x0204;
+        cpu.registers.sp = 2;
+        cpu.registers.stack[1] = 0x0200;
+        cpu.memory.write(0x0300, 0xAB);
+        cpu.screen.set(5, 7, true);
+        cpu.screen.set(63, 31, true);
+        cpu.set_quirk_bits(0x15);
+        cpu
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let cpu = sample_cpu();
+        let text = save(&cpu);
+        assert!(text.starts_with(r#"{"format":"chip-8-state","version":1,"v":[0,0,0,66,"#));
+
+        let mut restored = Cpu::new();
+        restored.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        load(&mut restored, &text).unwrap();
+        assert_eq!(restored.state_hash(), cpu.state_hash());
+        assert_eq!(restored.quirk_bits(), 0x15);
+        assert!(restored.screen.get(5, 7));
+        assert!(!restored.screen.get(6, 7));
+    }
+
+    #[test]
+    fn test_quirks_are_optional() {
+        let text = save(&sample_cpu());
+        let start = text.find(r#""quirks""#).unwrap();
+        let end = text.find(r#""memory""#).unwrap();
+        let without_quirks = format!("{}{}", &text[..start], &text[end..]);
+
+        let mut cpu = Cpu::new();
+        let quirks = cpu.quirk_bits();
+        load(&mut cpu, &without_quirks).unwrap();
+        assert_eq!(cpu.quirk_bits(), quirks);
+        assert_eq!(cpu.registers.v[3], 0x42);
+    }
+
+    #[test]
+    fn test_invalid_states() {
+        let text = save(&sample_cpu());
+        let mut cpu = Cpu::new();
+
+        assert!(matches!(load(&mut cpu, "{"), Err(JsonStateError::Json(_))));
+        assert_eq!(load(&mut cpu, "{}"), Err(JsonStateError::NotAState));
+        assert_eq!(
+            load(&mut cpu, &text.replace(r#""version":1"#, r#""version":2"#)),
+            Err(JsonStateError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            load(&mut cpu, &text.replace(r#""pc":516"#, r#""pc":70000"#)),
+            Err(JsonStateError::Invalid("pc"))
+        );
+        cpu.set_profile(MachineProfile::XoChip);
+        assert_eq!(
+            load(&mut cpu, &text),
+            Err(JsonStateError::Invalid("memory"))
+        );
+        cpu.set_profile(MachineProfile::Modern);
+        assert_eq!(
+            load(&mut cpu, &text.replace(r#""width":64"#, r#""width":65"#)),
+            Err(JsonStateError::Invalid("width"))
+        );
+        assert_eq!(
+            load(&mut cpu, &text.replacen("#", "x", 1)),
+            Err(JsonStateError::Invalid("rows"))
+        );
+        // Nothing was changed by the failed loads
+        assert_eq!(cpu.registers.v[3], 0);
+    }
+}
@@ -0,0 +1,188 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::{cpu::Cpu, replay::InputEvent};
+
+// A short recorded key sequence (e.g. the combo starting a game),
+// played back on demand. Event frames count from the macro's start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMacro {
+    pub events: Vec<InputEvent>,
+}
+
+impl KeyMacro {
+    // Length in frames, up to the last event.
+    #[allow(dead_code)]
+    pub fn length(&self) -> u64 {
+        self.events.last().map_or(0, |event| event.frame + 1)
+    }
+
+    // Events as "frame+key" (pressed) or "frame-key" (released), with
+    // the key in hex, e.g. "0+5 4-5 4+6 9-6".
+    pub fn to_text(&self) -> String {
+        self.events
+            .iter()
+            .map(|event| {
+                let sign = if event.pressed { '+' } else { '-' };
+                format!("{}{}{:X}", event.frame, sign, event.key)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn parse(text: &str) -> Option<KeyMacro> {
+        let mut events = Vec::new();
+        for token in text.split_whitespace() {
+            let sign_at = token.find(['+', '-'])?;
+            let frame: u64 = token[..sign_at].parse().ok()?;
+            let key = u8::from_str_radix(&token[sign_at + 1..], 16).ok()?;
+            if key > 0xF
+                || events
+                    .last()
+                    .is_some_and(|last: &InputEvent| last.frame > frame)
+            {
+                return None;
+            }
+            events.push(InputEvent {
+                frame,
+                key,
+                pressed: token.as_bytes()[sign_at] == b'+',
+            });
+        }
+        Some(KeyMacro { events })
+    }
+}
+
+// Collects the keypad events of a macro being recorded.
+pub struct MacroRecorder {
+    start: u64,
+    events: Vec<InputEvent>,
+    held: u16, // Keys down at this point of the recording
+}
+
+impl MacroRecorder {
+    pub fn new(frame: u64) -> MacroRecorder {
+        MacroRecorder {
+            start: frame,
+            events: Vec::new(),
+            held: 0,
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, key: u8, pressed: bool) {
+        let bit = 1 << key;
+        if (self.held & bit != 0) == pressed {
+            return; // Key repeat, or released a key pressed before recording
+        }
+        self.held ^= bit;
+        self.events.push(InputEvent {
+            frame: frame.saturating_sub(self.start),
+            key,
+            pressed,
+        });
+    }
+
+    // Keys still down get released at the end, so playing the macro
+    // never leaves keys stuck.
+    pub fn finish(mut self, frame: u64) -> KeyMacro {
+        for key in 0..=0xF {
+            if self.held & (1 << key) != 0 {
+                self.record(frame, key, false);
+            }
+        }
+        KeyMacro {
+            events: self.events,
+        }
+    }
+}
+
+// Injects a macro's events into a cpu as its frames are reached.
+pub struct MacroPlayer {
+    key_macro: KeyMacro,
+    start: u64,
+    next_event: usize,
+}
+
+impl MacroPlayer {
+    pub fn new(key_macro: KeyMacro, frame: u64) -> MacroPlayer {
+        MacroPlayer {
+            key_macro,
+            start: frame,
+            next_event: 0,
+        }
+    }
+
+    // Applies the events due by the cpu's current frame, and returns
+    // them (with relative frames), e.g. to record them into a replay.
+    pub fn apply(&mut self, cpu: &mut Cpu) -> &[InputEvent] {
+        let first = self.next_event;
+        let elapsed = cpu.frame().saturating_sub(self.start);
+        while let Some(event) = self.key_macro.events.get(self.next_event) {
+            if event.frame > elapsed {
+                break;
+            }
+            cpu.keypad.set_key(event.key, event.pressed);
+            self.next_event += 1;
+        }
+        &self.key_macro.events[first..self.next_event]
+    }
+
+    #[allow(dead_code)]
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.key_macro.events.len()
+    }
+}
+
+#[cfg(test)]
+mod key_macro_tests {
+    use super::{KeyMacro, MacroPlayer, MacroRecorder};
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_record() {
+        let mut recorder = MacroRecorder::new(100);
+        recorder.record(100, 0x5, true);
+        recorder.record(101, 0x5, true); // Repeat
+        recorder.record(104, 0x5, false);
+        recorder.record(104, 0x3, false); // Never pressed
+        recorder.record(105, 0xA, true);
+        let key_macro = recorder.finish(110);
+        assert_eq!(key_macro.to_text(), "0+5 4-5 5+A 10-A");
+        assert_eq!(key_macro.length(), 11);
+    }
+
+    #[test]
+    fn test_parse() {
+        let key_macro = KeyMacro::parse(" 0+5  4-5 5+a 10-A ").unwrap();
+        assert_eq!(key_macro.to_text(), "0+5 4-5 5+A 10-A");
+        assert_eq!(KeyMacro::parse("").unwrap().events, vec![]);
+        assert_eq!(KeyMacro::parse("0*5"), None);
+        assert_eq!(KeyMacro::parse("0+10"), None);
+        assert_eq!(KeyMacro::parse("4+1 2-1"), None); // Out of order
+    }
+
+    #[test]
+    fn test_play() {
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 1;
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        cpu.tick();
+
+        let key_macro = KeyMacro::parse("0+5 2-5").unwrap();
+        let mut player = MacroPlayer::new(key_macro, cpu.frame());
+        assert_eq!(player.apply(&mut cpu).len(), 1);
+        assert!(cpu.keypad.get_key_state(0x5));
+        cpu.tick();
+        assert!(player.apply(&mut cpu).is_empty());
+        cpu.tick();
+        assert_eq!(player.apply(&mut cpu).len(), 1);
+        assert!(!cpu.keypad.get_key_state(0x5));
+        assert!(player.is_finished());
+    }
+}
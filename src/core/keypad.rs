@@ -7,7 +7,14 @@
 //
 // https://github.com/keelus/chip-8-emu
 
-use std::collections::HashMap;
+pub const KEY_COUNT: usize = 16;
+
+// What Ex9E/ExA1 see of keys changing in the middle of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Raw,     // Every change, as soon as it's reported
+    Latched, // The keys as they were when the frame began
+}
 
 // Keypad:
 // 1 2 3 C    1 2 3 4
@@ -15,32 +22,64 @@ use std::collections::HashMap;
 // 7 8 9 E -> A S D F
 // A 0 B F    Z X C V
 //
+#[derive(Clone)]
 pub struct Keypad {
-    key_map: HashMap<u8, bool>, // Down = true, Up = false
+    keys: [bool; KEY_COUNT], // Down = true, Up = false
+    pressed: u16,            // Keys that went down this frame, bit n for key n
+    released: u16,           // Keys that went up this frame
+    mode: InputMode,
+    latched: u16, // Keys seen by the instructions in latched mode
+    events: u64,  // Presses and releases so far
     pub last_key: Option<u8>,
 }
 
 impl Keypad {
     pub fn new() -> Keypad {
+        Keypad::with_input_mode(InputMode::Raw)
+    }
+
+    pub fn with_input_mode(mode: InputMode) -> Keypad {
         Keypad {
-            key_map: HashMap::from((0x0..=0xF).map(|i| (i, false)).collect::<HashMap<_, _>>()),
+            keys: [false; KEY_COUNT],
+            pressed: 0,
+            released: 0,
+            mode,
+            latched: 0,
+            events: 0,
             last_key: None,
         }
     }
 
+    pub fn input_mode(&self) -> InputMode {
+        self.mode
+    }
+
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.mode = mode;
+        self.latched = self.pressed_mask();
+    }
+
     pub fn set_key(&mut self, idx: u8, state: bool) {
-        let entry = self.key_map.get_mut(&idx).unwrap();
+        let entry = &mut self.keys[idx as usize];
 
         if *entry && !state {
             // Key released
             self.last_key = Some(idx);
+            self.released |= 1 << idx;
+            self.events += 1;
+        } else if !*entry && state {
+            self.pressed |= 1 << idx;
+            self.events += 1;
         }
 
         *entry = state;
     }
 
     pub fn get_key_state(&mut self, idx: u8) -> bool {
-        let state = *self.key_map.get(&idx).unwrap();
+        let state = match self.mode {
+            InputMode::Raw => self.keys[idx as usize],
+            InputMode::Latched => self.latched & (1 << idx) != 0,
+        };
         self.last_key = None;
         state
     }
@@ -50,4 +89,98 @@ impl Keypad {
         self.last_key = None;
         last_key
     }
+
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+
+    #[allow(dead_code)]
+    pub fn any_pressed(&self) -> bool {
+        self.keys.contains(&true)
+    }
+
+    // Keys currently down, bit n for key n.
+    pub fn pressed_mask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (idx, &down)| mask | ((down as u16) << idx))
+    }
+
+    pub fn pressed_this_frame(&self) -> u16 {
+        self.pressed
+    }
+
+    #[allow(dead_code)]
+    pub fn released_this_frame(&self) -> u16 {
+        self.released
+    }
+
+    // Latches the keys for the frame about to run. Keys tapped since
+    // the last frame count as down, even if already released, so short
+    // taps between two frames aren't lost. Fx0A isn't latched, as it
+    // waits for a release anyway.
+    pub fn begin_frame(&mut self) {
+        self.latched = self.pressed_mask() | self.pressed;
+    }
+
+    // Forgets this frame's presses and releases.
+    pub fn end_frame(&mut self) {
+        self.pressed = 0;
+        self.released = 0;
+    }
+}
+
+#[cfg(test)]
+mod keypad_tests {
+    use super::{InputMode, Keypad};
+
+    #[test]
+    fn test_masks() {
+        let mut keypad = Keypad::new();
+        assert!(!keypad.any_pressed());
+        assert_eq!(keypad.pressed_mask(), 0);
+
+        keypad.set_key(0x1, true);
+        keypad.set_key(0xF, true);
+        assert!(keypad.any_pressed());
+        assert_eq!(keypad.pressed_mask(), 0x8002);
+        assert_eq!(keypad.pressed_this_frame(), 0x8002);
+
+        keypad.end_frame();
+        keypad.set_key(0x1, false);
+        keypad.set_key(0xF, true); // Already down
+        assert_eq!(keypad.pressed_mask(), 0x8000);
+        assert_eq!(keypad.pressed_this_frame(), 0);
+        assert_eq!(keypad.released_this_frame(), 0x0002);
+        assert_eq!(keypad.get_released_key(), Some(0x1));
+    }
+
+    #[test]
+    fn test_latched_input() {
+        let mut keypad = Keypad::with_input_mode(InputMode::Latched);
+        keypad.begin_frame();
+        keypad.set_key(0x5, true);
+        assert!(!keypad.get_key_state(0x5)); // Pressed mid frame
+        keypad.end_frame();
+
+        keypad.begin_frame();
+        keypad.set_key(0x5, false);
+        assert!(keypad.get_key_state(0x5)); // Released mid frame
+        keypad.end_frame();
+
+        // Tapped between frames
+        keypad.set_key(0x3, true);
+        keypad.set_key(0x3, false);
+        keypad.begin_frame();
+        assert!(keypad.get_key_state(0x3));
+        assert!(!keypad.get_key_state(0x5));
+        keypad.end_frame();
+        keypad.begin_frame();
+        assert!(!keypad.get_key_state(0x3));
+
+        keypad.set_input_mode(InputMode::Raw);
+        keypad.set_key(0x5, true);
+        assert!(keypad.get_key_state(0x5));
+    }
 }
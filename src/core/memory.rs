@@ -7,9 +7,13 @@
 //
 // https://github.com/keelus/chip-8-emu
 
+use std::fmt;
+
 use super::instruction::Instruction;
 
-const MEMORY_SIZE: usize = 4096;
+// Classic 4K address space. Machine profiles can have larger ones
+// (see Memory::with_size).
+pub const MEMORY_SIZE: usize = 4096;
 
 const HEX_SPRITES: [[u8; 5]; 16] = [
     [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
@@ -33,52 +37,290 @@ const HEX_SPRITES: [[u8; 5]; 16] = [
 pub const HEX_SPRITES_HEIGHT: u8 = 5;
 pub const HEX_SPRITES_START_MEM: u16 = 0x0000;
 
+pub const INTERPRETER_START_MEM: u16 = HEX_SPRITES_START_MEM + 16 * HEX_SPRITES_HEIGHT as u16;
+pub const PROGRAM_START_MEM: u16 = 0x0200;
+// Where ETI 660 programs start
+pub const ETI_START_MEM: u16 = 0x0600;
+
 // Memory structure:
-// 0x200 - 0xFFF -> Program/ROM memory
-// 0x000 - 0x1FF -> Interpreter specific
+// 0x000 - 0x04F -> Font (hex sprites)
+// 0x050 - 0x1FF -> Interpreter specific
+// 0x200 - ...   -> Program/ROM memory
+// ...   - end   -> Work RAM (everything after the loaded program)
 //
-pub struct Memory([u8; MEMORY_SIZE]);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Font,
+    Interpreter,
+    Program,
+    WorkRam,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadError {
+    // The base address would overwrite the font.
+    InvalidBase(u16),
+    TooLarge { size: usize, available: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::InvalidBase(base) => {
+                write!(f, "ROM can't be loaded at 0x{:04X}.", base)
+            }
+            LoadError::TooLarge { size, available } => write!(
+                f,
+                "ROM is too large ({} bytes, only {} available).",
+                size, available
+            ),
+        }
+    }
+}
+
+// What accesses past the end of memory do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundsPolicy {
+    Wrap,  // Around to the start, like unconnected address lines
+    Clamp, // To the last byte
+    Error, // Reads give 0 and writes are dropped, raising a BoundsError
+}
+
+pub const BOUNDS_POLICIES: [BoundsPolicy; 3] =
+    [BoundsPolicy::Wrap, BoundsPolicy::Clamp, BoundsPolicy::Error];
+
+impl BoundsPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BoundsPolicy::Wrap => "Wrap around",
+            BoundsPolicy::Clamp => "Clamp to the last byte",
+            BoundsPolicy::Error => "Halt with an error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundsError {
+    OutOfBounds { addr: usize },
+}
+
+impl fmt::Display for BoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoundsError::OutOfBounds { addr } => {
+                write!(f, "Memory access out of bounds at 0x{:04X}", addr)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    data: Vec<u8>,
+    program_start: u16,
+    program_end: usize,
+
+    // If true, writes into 0x000 - 0x1FF are ignored and flagged.
+    protected: bool,
+    last_violation: Option<u16>,
+
+    bounds_policy: BoundsPolicy,
+    last_error: Option<BoundsError>,
+}
 
 impl Memory {
     pub fn new() -> Memory {
+        Memory::with_size(MEMORY_SIZE)
+    }
+
+    pub fn with_size(size: usize) -> Memory {
         let mut mem = Memory {
-            0: [0; MEMORY_SIZE],
+            data: vec![0; size],
+            program_start: PROGRAM_START_MEM,
+            program_end: PROGRAM_START_MEM as usize,
+            protected: false,
+            last_violation: None,
+            bounds_policy: BoundsPolicy::Wrap,
+            last_error: None,
         };
+        mem.reset();
+        mem
+    }
 
-        let mut addr = HEX_SPRITES_START_MEM;
-        for &hex_sprite in &HEX_SPRITES {
-            for row in hex_sprite {
-                mem.write(addr, row);
-                addr += 1;
-            }
+    // Clears everything but the font, which gets restored. The
+    // protection mode and bounds policy are kept.
+    pub fn reset(&mut self) {
+        self.data.fill(0);
+        let font = HEX_SPRITES.as_flattened();
+        self.load_slice(HEX_SPRITES_START_MEM, font)
+            .expect("every memory size fits the font");
+        self.program_start = PROGRAM_START_MEM;
+        self.program_end = PROGRAM_START_MEM as usize;
+        self.last_violation = None;
+        self.last_error = None;
+    }
+
+    // Loads a ROM at base, replacing any previously loaded one.
+    pub fn load_rom(&mut self, rom: &[u8], base: u16) -> Result<(), LoadError> {
+        if base < INTERPRETER_START_MEM {
+            return Err(LoadError::InvalidBase(base));
         }
 
-        mem
+        let available = self.size().saturating_sub(base as usize);
+        if rom.len() > available {
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                available,
+            });
+        }
+
+        self.reset();
+        self.load_slice(base, rom)?;
+        self.program_start = base;
+        self.program_end = base as usize + rom.len();
+        Ok(())
     }
 
-    pub fn load_rom(&mut self, program: Vec<u8>, program_begin: u16) {
-        for (index, &data) in program.iter().enumerate() {
-            let (addr, overflows) = program_begin.overflowing_add(index as u16);
-            if overflows {
-                panic!("Program read overflowed. Stopping.");
-            }
-            self.write(addr, data);
+    // Where the loaded program starts.
+    pub fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    // Current contents of the loaded program's region, including any
+    // change made to it since loading. Trimming drops trailing zeros.
+    pub fn export_program(&self, trim: bool) -> Vec<u8> {
+        let mut program = self.data[self.program_start as usize..self.program_end].to_vec();
+        if trim {
+            let len = program
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |idx| idx + 1);
+            program.truncate(len);
+        }
+        program
+    }
+
+    // Copies data starting at addr, bypassing the protection mode.
+    // Useful for tooling and restoring save states. Nothing is copied
+    // if the data runs past the end.
+    pub fn load_slice(&mut self, addr: u16, data: &[u8]) -> Result<(), LoadError> {
+        let start = addr as usize;
+        let available = self.size().saturating_sub(start);
+        let target = self
+            .data
+            .get_mut(start..start + data.len())
+            .ok_or(LoadError::TooLarge {
+                size: data.len(),
+                available,
+            })?;
+        target.copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn dump(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    // Grows or shrinks the address space, keeping the contents that
+    // still fit. A program loaded past the new end is left empty.
+    pub fn set_size(&mut self, size: usize) {
+        self.data.resize(size, 0);
+        self.program_end = self.program_end.min(size);
+        self.program_start = (self.program_start as usize).min(self.program_end) as u16;
+    }
+
+    pub fn region(&self, addr: u16) -> Region {
+        if addr < INTERPRETER_START_MEM {
+            Region::Font
+        } else if addr < PROGRAM_START_MEM {
+            Region::Interpreter
+        } else if (addr as usize) < self.program_end {
+            Region::Program
+        } else {
+            Region::WorkRam
+        }
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.protected
+    }
+
+    pub fn set_protected(&mut self, protected: bool) {
+        self.protected = protected;
+    }
+
+    // Returns (and clears) the last address a write was blocked at.
+    pub fn take_violation(&mut self) -> Option<u16> {
+        self.last_violation.take()
+    }
+
+    pub fn bounds_policy(&self) -> BoundsPolicy {
+        self.bounds_policy
+    }
+
+    pub fn set_bounds_policy(&mut self, policy: BoundsPolicy) {
+        self.bounds_policy = policy;
+    }
+
+    // Returns (and clears) the last out of bounds access made with
+    // the Error policy.
+    pub fn take_error(&mut self) -> Option<BoundsError> {
+        self.last_error.take()
+    }
+
+    // Where an access to addr lands, following the bounds policy.
+    pub fn resolve(&self, addr: usize) -> Result<usize, BoundsError> {
+        let size = self.size();
+        if addr < size {
+            return Ok(addr);
         }
+        match self.bounds_policy {
+            BoundsPolicy::Wrap => Ok(addr % size),
+            BoundsPolicy::Clamp => Ok(size - 1),
+            BoundsPolicy::Error => Err(BoundsError::OutOfBounds { addr }),
+        }
+    }
+
+    fn resolve_or_flag(&mut self, addr: usize) -> Option<usize> {
+        self.resolve(addr)
+            .map_err(|err| self.last_error = Some(err))
+            .ok()
     }
 
+    // Protection applies to the resolved address, so wrapping past the
+    // end can't reach the interpreter area either.
     pub fn write(&mut self, addr: u16, data: u8) {
-        self.0[addr as usize] = data;
+        let Some(resolved) = self.resolve_or_flag(addr as usize) else {
+            return;
+        };
+        if self.protected && resolved < PROGRAM_START_MEM as usize {
+            self.last_violation = Some(addr);
+            return;
+        }
+        self.data[resolved] = data;
     }
 
     pub fn read(&mut self, addr: u16) -> u8 {
-        self.0[addr as usize]
+        self.resolve_or_flag(addr as usize)
+            .map_or(0, |addr| self.data[addr])
+    }
+
+    // Like read, for tooling. Addresses past the end give None.
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        self.data.get(addr as usize).copied()
     }
 
+    // Doesn't flag errors, so tooling can use it freely. The CPU
+    // checks fetches with resolve.
     pub fn read_u16(&self, addr: u16) -> u16 {
-        let addr = addr as usize;
+        let byte = |addr: usize| self.resolve(addr).map_or(0, |addr| self.data[addr] as u16);
 
-        let msb = self.0[addr] as u16;
-        let lsb = self.0[addr + 1] as u16;
+        let msb = byte(addr as usize);
+        let lsb = byte(addr as usize + 1);
 
         msb << 8 | lsb
     }
@@ -97,16 +339,182 @@ impl Memory {
 
 #[cfg(test)]
 mod memory_tests {
-    use super::Memory;
+    use super::{BoundsError, BoundsPolicy, LoadError, Memory, Region, MEMORY_SIZE};
 
     #[test]
     fn test_read_instruction() {
         let mut mem = Memory::new();
-        mem.load_rom(vec![0x12, 0x34], 0x0200);
+        mem.load_rom(&[0x12, 0x34], 0x0200).unwrap();
         let instruction = mem.read_instruction(0x0200);
         assert_eq!(instruction.parts().0, 0x01);
         assert_eq!(instruction.parts().1, 0x02);
         assert_eq!(instruction.parts().2, 0x03);
         assert_eq!(instruction.parts().3, 0x04);
     }
+
+    #[test]
+    fn test_regions() {
+        let mut mem = Memory::new();
+        mem.load_rom(&[0x12, 0x34, 0x56], 0x0200).unwrap();
+        assert_eq!(mem.region(0x0000), Region::Font);
+        assert_eq!(mem.region(0x004F), Region::Font);
+        assert_eq!(mem.region(0x0050), Region::Interpreter);
+        assert_eq!(mem.region(0x01FF), Region::Interpreter);
+        assert_eq!(mem.region(0x0200), Region::Program);
+        assert_eq!(mem.region(0x0202), Region::Program);
+        assert_eq!(mem.region(0x0203), Region::WorkRam);
+        assert_eq!(mem.region(0x0FFF), Region::WorkRam);
+    }
+
+    #[test]
+    fn test_protection() {
+        let mut mem = Memory::new();
+        let font_byte = mem.read(0x0000);
+
+        mem.set_protected(true);
+        mem.write(0x0000, 0xAB);
+        assert_eq!(mem.read(0x0000), font_byte);
+        assert_eq!(mem.take_violation(), Some(0x0000));
+        assert_eq!(mem.take_violation(), None);
+
+        mem.write(0x0200, 0xAB);
+        assert_eq!(mem.read(0x0200), 0xAB);
+        assert_eq!(mem.take_violation(), None);
+
+        // Wrapping around the end lands in the protected area too
+        mem.write(0x1000, 0xAB);
+        assert_eq!(mem.read(0x0000), font_byte);
+        assert_eq!(mem.take_violation(), Some(0x1000));
+
+        mem.set_protected(false);
+        mem.write(0x0000, 0xAB);
+        assert_eq!(mem.read(0x0000), 0xAB);
+        assert_eq!(mem.take_violation(), None);
+    }
+
+    #[test]
+    fn test_load_slice_dump() {
+        let mut mem = Memory::new();
+        mem.set_protected(true);
+        mem.load_slice(0x01FE, &[0x01, 0x02, 0x03]).unwrap();
+        assert_eq!(mem.take_violation(), None);
+
+        let dump = mem.dump();
+        assert_eq!(dump.len(), MEMORY_SIZE);
+        assert_eq!(&dump[0x01FE..0x0201], &[0x01, 0x02, 0x03]);
+        assert_eq!(dump[0x0000], 0xF0);
+
+        // Past the end nothing is copied
+        assert_eq!(
+            mem.load_slice(0x0FFE, &[0xAA; 3]),
+            Err(LoadError::TooLarge {
+                size: 3,
+                available: 2
+            })
+        );
+        assert_eq!(mem.peek(0x0FFE), Some(0x00));
+    }
+
+    #[test]
+    fn test_load_rom_validation() {
+        let mut mem = Memory::new();
+        assert_eq!(
+            mem.load_rom(&[0x00], 0x0010),
+            Err(LoadError::InvalidBase(0x0010))
+        );
+        assert_eq!(
+            mem.load_rom(&[0x00; MEMORY_SIZE], 0x0200),
+            Err(LoadError::TooLarge {
+                size: MEMORY_SIZE,
+                available: MEMORY_SIZE - 0x0200
+            })
+        );
+        assert!(mem.load_rom(&[0xAA; MEMORY_SIZE - 0x0200], 0x0200).is_ok());
+        assert_eq!(mem.read(0x0FFF), 0xAA);
+    }
+
+    #[test]
+    fn test_reload_and_reset() {
+        let mut mem = Memory::new();
+        mem.load_rom(&[0x12, 0x34, 0x56, 0x78], 0x0200).unwrap();
+        mem.write(0x0300, 0xFF);
+
+        mem.load_rom(&[0xAB], 0x0200).unwrap();
+        assert_eq!(mem.read(0x0200), 0xAB);
+        assert_eq!(mem.read(0x0201), 0x00);
+        assert_eq!(mem.read(0x0300), 0x00);
+        assert_eq!(mem.region(0x0201), Region::WorkRam);
+
+        mem.set_protected(true);
+        mem.load_slice(0x0000, &[0x00; 5]).unwrap();
+        mem.reset();
+        assert!(mem.is_protected());
+        assert_eq!(mem.read(0x0200), 0x00);
+        assert_eq!(&mem.dump()[0..5], &[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    }
+
+    #[test]
+    fn test_export_program() {
+        let mut mem = Memory::new();
+        assert!(mem.export_program(false).is_empty());
+
+        mem.load_rom(&[0x12, 0x34, 0x00, 0x00], 0x0600).unwrap();
+        mem.write(0x0601, 0xFF);
+        mem.write(0x0604, 0xEE);
+        assert_eq!(mem.export_program(false), vec![0x12, 0xFF, 0x00, 0x00]);
+        assert_eq!(mem.export_program(true), vec![0x12, 0xFF]);
+    }
+
+    #[test]
+    fn test_bounds_policy() {
+        let mut mem = Memory::new();
+        mem.write(0x0FFF, 0xAB);
+        mem.write(0x0000, 0xCD);
+        assert_eq!(mem.read_u16(0x0FFF), 0xABCD); // Wraps by default
+
+        mem.set_bounds_policy(BoundsPolicy::Clamp);
+        assert_eq!(mem.read(0x1005), 0xAB);
+        assert_eq!(mem.read_u16(0x0FFF), 0xABAB);
+        mem.write(0x2000, 0x12);
+        assert_eq!(mem.read(0x0FFF), 0x12);
+        assert_eq!(mem.take_error(), None);
+
+        mem.set_bounds_policy(BoundsPolicy::Error);
+        assert_eq!(mem.read(0x0FFF), 0x12);
+        assert_eq!(mem.take_error(), None);
+        assert_eq!(mem.read(0x1000), 0x00);
+        assert_eq!(
+            mem.take_error(),
+            Some(BoundsError::OutOfBounds { addr: 0x1000 })
+        );
+        mem.write(0x1001, 0x34);
+        assert_eq!(
+            mem.take_error(),
+            Some(BoundsError::OutOfBounds { addr: 0x1001 })
+        );
+        assert_eq!(mem.read_u16(0x0FFF), 0x1200);
+        assert_eq!(mem.take_error(), None);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let mut mem = Memory::with_size(0x10000);
+        assert_eq!(mem.size(), 0x10000);
+        let rom = vec![0xAA; 0x10000 - 0x0200];
+        assert!(mem.load_rom(&rom, 0x0200).is_ok());
+        assert_eq!(mem.read(0xFFFF), 0xAA);
+        assert_eq!(mem.region(0xFFFF), Region::Program);
+        assert_eq!(mem.read_u16(0xFFFF), 0xAAF0); // Wraps to the font
+
+        mem.set_size(MEMORY_SIZE);
+        assert_eq!(mem.dump().len(), MEMORY_SIZE);
+        assert_eq!(mem.export_program(false).len(), MEMORY_SIZE - 0x0200);
+        assert_eq!(mem.read(0x1000), 0xF0);
+
+        // Shrinking below where the program was loaded
+        let mut mem = Memory::with_size(0x10000);
+        mem.load_rom(&[0x12, 0x34], 0x8000).unwrap();
+        mem.set_size(MEMORY_SIZE);
+        assert!(mem.export_program(false).is_empty());
+    }
 }
@@ -0,0 +1,86 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+// How many times instructions read and wrote each address (fetches
+// aren't counted). Filled by the CPU's memory hooks while enabled.
+pub struct MemoryAccesses {
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+impl MemoryAccesses {
+    pub fn new(size: usize) -> MemoryAccesses {
+        MemoryAccesses {
+            reads: vec![0; size],
+            writes: vec![0; size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.reads.len()
+    }
+
+    pub fn record_read(&mut self, addr: u16) {
+        if let Some(count) = self.reads.get_mut(addr as usize) {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    pub fn record_write(&mut self, addr: u16) {
+        if let Some(count) = self.writes.get_mut(addr as usize) {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    pub fn reads(&self, addr: u16) -> u32 {
+        self.reads.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    pub fn writes(&self, addr: u16) -> u32 {
+        self.writes.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    // Highest read and write counts, to scale the heatmap.
+    pub fn max(&self) -> (u32, u32) {
+        let max = |counts: &[u32]| counts.iter().copied().max().unwrap_or(0);
+        (max(&self.reads), max(&self.writes))
+    }
+
+    pub fn clear(&mut self) {
+        self.reads.fill(0);
+        self.writes.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod memory_access_tests {
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_counting() {
+        // LD I, 0x300 - LD [I], V1 - LD V1, [I] - JP 0x206
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0xA3, 0x00, 0xF1, 0x55, 0xF1, 0x65, 0x12, 0x06], 0x0200)
+            .unwrap();
+        cpu.set_memory_access_counting(true);
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+        let accesses = cpu.memory_accesses().unwrap();
+        assert_eq!(accesses.writes(0x300), 1);
+        assert_eq!(accesses.writes(0x301), 1);
+        assert_eq!(accesses.reads(0x300), 0);
+        assert_eq!(accesses.reads(0x302), 1); // I moved past the stored bytes
+        assert_eq!(accesses.reads(0x200), 0); // Fetches don't count
+        assert_eq!(accesses.max(), (1, 1));
+
+        cpu.set_memory_access_counting(false);
+        assert!(cpu.memory_accesses().is_none());
+    }
+}
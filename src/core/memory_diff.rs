@@ -0,0 +1,57 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+// Bytes that differ between two memory dumps, in address order. Only
+// the addresses both dumps have are compared.
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<Change> {
+    old.iter()
+        .zip(new)
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(addr, (&old, &new))| Change {
+            addr: addr as u16,
+            old,
+            new,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod memory_diff_tests {
+    use super::{diff, Change};
+
+    #[test]
+    fn test_diff() {
+        let old = [0x00, 0x11, 0x22, 0x33];
+        let new = [0x00, 0x12, 0x22, 0x30, 0x44];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change {
+                    addr: 1,
+                    old: 0x11,
+                    new: 0x12
+                },
+                Change {
+                    addr: 3,
+                    old: 0x33,
+                    new: 0x30
+                },
+            ]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+}
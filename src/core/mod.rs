@@ -7,10 +7,43 @@
 //
 // https://github.com/keelus/chip-8-emu
 
+pub mod analyzer;
+pub mod assembler;
 pub mod beep;
+pub mod cartridge;
+pub mod clock;
 pub mod cpu;
+pub mod debug_command;
+pub mod expr;
+pub mod gif;
+pub mod hash;
+pub mod hex_rom;
+pub mod hooks;
 pub mod instruction;
+pub mod json;
+pub mod json_state;
+pub mod key_macro;
 pub mod keypad;
 pub mod memory;
+pub mod memory_access;
+pub mod memory_diff;
+pub mod patch;
+pub mod profile;
+pub mod register_log;
 pub mod registers;
+pub mod replay;
+pub mod rewind;
+pub mod rng_stats;
+pub mod savestate;
 pub mod screen;
+pub mod source_array;
+pub mod sprite;
+pub mod stats;
+pub mod symbols;
+pub mod test_script;
+pub mod timeline;
+pub mod timing;
+pub mod toml;
+pub mod trace;
+pub mod triggers;
+pub mod watchdog;
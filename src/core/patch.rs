@@ -0,0 +1,373 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{hash::crc32, profile::MachineProfile};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+const BPS_FOOTER_SIZE: usize = 12;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    UnknownFormat,
+    Truncated,
+    InvalidAction,
+    SourceMismatch,
+    TargetMismatch,
+    PatchChecksumMismatch,
+    TooLarge,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "Not an IPS or BPS patch."),
+            PatchError::Truncated => write!(f, "Patch file is truncated."),
+            PatchError::InvalidAction => write!(f, "Patch contains an invalid action."),
+            PatchError::SourceMismatch => write!(f, "Patch was made for a different ROM."),
+            PatchError::TargetMismatch => write!(f, "Patched ROM checksum doesn't match."),
+            PatchError::PatchChecksumMismatch => write!(f, "Patch file is corrupted."),
+            PatchError::TooLarge => write!(f, "Patched ROM doesn't fit in memory."),
+        }
+    }
+}
+
+// Applies an IPS or BPS patch, detected by its header.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+// Reads patches sequentially, failing with Truncated past the end.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let end = self.pos.checked_add(len).ok_or(PatchError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(PatchError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // Big endian unsigned integer of len bytes (IPS).
+    fn uint_be(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |acc, &byte| acc << 8 | byte as usize))
+    }
+
+    // Variable length integer (BPS).
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.u8()?;
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|add| value.checked_add(add))
+                .ok_or(PatchError::InvalidAction)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::InvalidAction)?;
+            value = value.checked_add(shift).ok_or(PatchError::InvalidAction)?;
+        }
+    }
+}
+
+// IPS layout (big endian):
+// "PATCH", then records until "EOF":
+//   [3] offset, [2] size, [size] data
+//   [3] offset, [2] 0, [2] run length, [1] value  (RLE record)
+// Optionally followed by [3] truncated size.
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut output = rom.to_vec();
+    let mut reader = Reader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        let offset_bytes = reader.bytes(3)?;
+        if offset_bytes == IPS_EOF {
+            break;
+        }
+        let offset = offset_bytes
+            .iter()
+            .fold(0, |acc, &byte| acc << 8 | byte as usize);
+
+        let size = reader.uint_be(2)?;
+        let data = if size == 0 {
+            let run = reader.uint_be(2)?;
+            vec![reader.u8()?; run]
+        } else {
+            reader.bytes(size)?.to_vec()
+        };
+
+        let end = offset + data.len();
+        if output.len() < end {
+            output.resize(end, 0);
+        }
+        output[offset..end].copy_from_slice(&data);
+    }
+
+    if let Ok(size) = reader.uint_be(3) {
+        output.truncate(size);
+    }
+
+    Ok(output)
+}
+
+// BPS layout: "BPS1", varint source size, varint target size,
+// varint metadata size, metadata, actions, then source/target/patch
+// CRC32s (little endian).
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(PatchError::Truncated);
+    }
+
+    let footer_start = patch.len() - BPS_FOOTER_SIZE;
+    let crc_at = |pos: usize| u32::from_le_bytes(patch[pos..pos + 4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != crc_at(footer_start + 8) {
+        return Err(PatchError::PatchChecksumMismatch);
+    }
+
+    let mut reader = Reader::new(&patch[..footer_start], BPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+
+    if rom.len() != source_size || crc32(rom) != crc_at(footer_start) {
+        return Err(PatchError::SourceMismatch);
+    }
+
+    // No machine can load more than its whole memory
+    if target_size > MachineProfile::XoChip.memory_size() {
+        return Err(PatchError::TooLarge);
+    }
+
+    let mut output = Vec::with_capacity(target_size);
+    let mut source_pos: isize = 0;
+    let mut target_pos: isize = 0;
+
+    while reader.pos < footer_start {
+        let data = reader.varint()?;
+        let command = data & 0x3;
+        let length = (data >> 2) + 1;
+        if length > target_size - output.len() {
+            return Err(PatchError::InvalidAction);
+        }
+
+        match command {
+            // SourceRead
+            0 => {
+                let start = output.len();
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or(PatchError::InvalidAction)?;
+                output.extend_from_slice(bytes);
+            }
+            // TargetRead
+            1 => output.extend_from_slice(reader.bytes(length)?),
+            // SourceCopy / TargetCopy
+            _ => {
+                let offset = reader.varint()?;
+                let delta = isize::try_from(offset >> 1).map_err(|_| PatchError::InvalidAction)?;
+                let delta = if offset & 1 != 0 { -delta } else { delta };
+                // Fits, as length is at most the target size
+                let length_delta = length as isize;
+
+                if command == 2 {
+                    let start = source_pos
+                        .checked_add(delta)
+                        .and_then(|pos| usize::try_from(pos).ok())
+                        .ok_or(PatchError::InvalidAction)?;
+                    let bytes = start
+                        .checked_add(length)
+                        .and_then(|end| rom.get(start..end))
+                        .ok_or(PatchError::InvalidAction)?;
+                    output.extend_from_slice(bytes);
+                    source_pos = (start as isize) + length_delta;
+                } else {
+                    let start = target_pos
+                        .checked_add(delta)
+                        .and_then(|pos| usize::try_from(pos).ok())
+                        .ok_or(PatchError::InvalidAction)?;
+                    if start >= output.len() {
+                        return Err(PatchError::InvalidAction);
+                    }
+                    // Byte by byte, as the copy may overlap its own output
+                    for idx in start..start + length {
+                        output.push(output[idx]);
+                    }
+                    target_pos = (start as isize) + length_delta;
+                }
+            }
+        }
+    }
+
+    if output.len() != target_size || crc32(&output) != crc_at(footer_start + 4) {
+        return Err(PatchError::TargetMismatch);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod patch_tests {
+    use super::{apply, PatchError};
+    use crate::core::hash::crc32;
+
+    #[test]
+    fn test_ips() {
+        let rom = [0x00, 0xE0, 0x12, 0x00];
+        let mut patch = b"PATCH".to_vec();
+        // Replace byte 2 with 0x13
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x01, 0x13]);
+        // RLE: 3 bytes of 0xAA at offset 5, extending the ROM
+        patch.extend_from_slice(&[0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xAA]);
+        patch.extend_from_slice(b"EOF");
+
+        let patched = apply(&rom, &patch).unwrap();
+        assert_eq!(
+            patched,
+            vec![0x00, 0xE0, 0x13, 0x00, 0x00, 0xAA, 0xAA, 0xAA]
+        );
+
+        // Truncation extension
+        patch.extend_from_slice(&[0x00, 0x00, 0x02]);
+        assert_eq!(apply(&rom, &patch).unwrap(), vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn test_ips_truncated() {
+        assert_eq!(
+            apply(&[0x00], b"PATCH\x00\x00\x01\x00\x04\xAA"),
+            Err(PatchError::Truncated)
+        );
+        assert_eq!(apply(&[0x00], b"NOPE"), Err(PatchError::UnknownFormat));
+    }
+
+    fn bps_patch(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        patch.push(0x80 | source.len() as u8);
+        patch.push(0x80 | target.len() as u8);
+        patch.push(0x80); // No metadata
+        patch.extend_from_slice(actions);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = [0x60, 0x01, 0x12, 0x00];
+        let target = [0x60, 0x01, 0x61, 0x02, 0x61, 0x02, 0x60, 0x01];
+        let actions = [
+            0x80 | (1 << 2),     // SourceRead 2
+            0x80 | (1 << 2 | 1), // TargetRead 2
+            0x61,
+            0x02,
+            0x80 | (1 << 2 | 3), // TargetCopy 2 from target offset 2
+            0x80 | 2 << 1,
+            0x80 | (1 << 2 | 2), // SourceCopy 2 from source offset 0
+            0x80,
+        ];
+        let patch = bps_patch(&source, &target, &actions);
+        assert_eq!(apply(&source, &patch).unwrap(), target.to_vec());
+
+        assert_eq!(
+            apply(&[0x00, 0x00, 0x00, 0x00], &patch),
+            Err(PatchError::SourceMismatch)
+        );
+
+        let mut corrupted = patch.clone();
+        corrupted[5] ^= 0x01;
+        assert_eq!(
+            apply(&source, &corrupted),
+            Err(PatchError::PatchChecksumMismatch)
+        );
+    }
+
+    fn varint(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    fn bps_raw(source: &[u8], target_size: usize, actions: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(varint(source.len()));
+        patch.extend(varint(target_size));
+        patch.push(0x80); // No metadata
+        patch.extend_from_slice(actions);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&0u32.to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_bps_hostile() {
+        let source = [0x60, 0x01, 0x12, 0x00];
+
+        // Target bigger than any machine's memory
+        let patch = bps_raw(&source, usize::MAX >> 1, &[]);
+        assert_eq!(apply(&source, &patch), Err(PatchError::TooLarge));
+
+        // Action writing past the target size
+        let mut actions = varint(9 << 2 | 1); // TargetRead 10
+        actions.extend([0; 10]);
+        let patch = bps_raw(&source, 4, &actions);
+        assert_eq!(apply(&source, &patch), Err(PatchError::InvalidAction));
+
+        // SourceCopy offsets overflowing the position
+        let mut actions = varint(2); // SourceCopy 1 from 0
+        actions.extend(varint(0));
+        actions.extend(varint(2)); // SourceCopy 1 from 1 + isize::MAX
+        actions.extend(varint(usize::MAX - 1));
+        let patch = bps_raw(&source, 4, &actions);
+        assert_eq!(apply(&source, &patch), Err(PatchError::InvalidAction));
+
+        // TargetCopy before anything was written
+        let mut actions = varint(3); // TargetCopy 1
+        actions.extend(varint(0));
+        let patch = bps_raw(&source, 4, &actions);
+        assert_eq!(apply(&source, &patch), Err(PatchError::InvalidAction));
+    }
+}
@@ -0,0 +1,61 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::{memory::MEMORY_SIZE, registers::STACK_CAPACITY};
+
+// The hardware the emulated machine mimics, as opposed to the quirks,
+// which change how instructions behave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineProfile {
+    CosmacVip,
+    Modern,
+    XoChip,
+}
+
+pub const PROFILES: [MachineProfile; 3] = [
+    MachineProfile::CosmacVip,
+    MachineProfile::Modern,
+    MachineProfile::XoChip,
+];
+
+impl MachineProfile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MachineProfile::CosmacVip => "COSMAC VIP",
+            MachineProfile::Modern => "Modern",
+            MachineProfile::XoChip => "XO-CHIP",
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            MachineProfile::CosmacVip | MachineProfile::Modern => MEMORY_SIZE,
+            MachineProfile::XoChip => 0x10000,
+        }
+    }
+
+    // Bits kept when I is set by Annn, Fx1E, Fx55 and Fx65. I is 12
+    // bits wide on both the VIP and the interpreters modelled after it,
+    // while XO-CHIP addresses its 64K with all 16 (accesses past the
+    // end still follow the memory's bounds policy).
+    pub fn i_mask(&self) -> u16 {
+        match self {
+            MachineProfile::CosmacVip | MachineProfile::Modern => 0x0FFF,
+            MachineProfile::XoChip => 0xFFFF,
+        }
+    }
+
+    // Nested subroutine calls it can make.
+    pub fn stack_size(&self) -> usize {
+        match self {
+            MachineProfile::CosmacVip => 12,
+            MachineProfile::Modern | MachineProfile::XoChip => STACK_CAPACITY,
+        }
+    }
+}
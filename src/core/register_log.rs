@@ -0,0 +1,172 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::collections::VecDeque;
+
+use super::instruction::{Instruction, Opcode};
+
+// Register bits of a write mask: V0-VF are bits 0-15, then these
+pub const REGISTER_I: usize = 16;
+pub const REGISTER_SP: usize = 17;
+pub const REGISTER_DT: usize = 18;
+pub const REGISTER_ST: usize = 19;
+pub const REGISTER_COUNT: usize = 20;
+
+pub fn register_name(idx: usize) -> String {
+    match idx {
+        0..=15 => format!("V{:X}", idx),
+        REGISTER_I => "I".to_string(),
+        REGISTER_SP => "SP".to_string(),
+        REGISTER_DT => "DT".to_string(),
+        _ => "ST".to_string(),
+    }
+}
+
+// Registers the instruction writes, even if it writes the value
+// they already had. The PC is left out, as every instruction moves it.
+pub fn written_registers(instruction: &Instruction, increments_i: bool) -> u32 {
+    let x = 1 << instruction.x();
+    let vf = 1 << 0xF;
+    let i = 1 << REGISTER_I;
+    match instruction.opcode() {
+        Opcode::Call | Opcode::Ret => 1 << REGISTER_SP,
+        Opcode::LoadByte | Opcode::AddByte | Opcode::Move | Opcode::Random => x,
+        Opcode::LoadDelay | Opcode::WaitKey => x,
+        Opcode::Or | Opcode::And | Opcode::Xor | Opcode::Add | Opcode::Sub => x | vf,
+        Opcode::ShiftRight | Opcode::SubN | Opcode::ShiftLeft => x | vf,
+        Opcode::Draw => vf,
+        Opcode::LoadI | Opcode::AddI | Opcode::LoadFont => i,
+        Opcode::SetDelay => 1 << REGISTER_DT,
+        Opcode::SetSound => 1 << REGISTER_ST,
+        Opcode::StoreRegisters if increments_i => i,
+        Opcode::LoadRegisters => {
+            let v = (x << 1) - 1; // V0 to Vx
+            if increments_i {
+                v | i
+            } else {
+                v
+            }
+        }
+        _ => 0,
+    }
+}
+
+// The write masks of the last executed instructions, oldest first.
+pub struct RegisterLog {
+    masks: VecDeque<u32>,
+    capacity: usize,
+}
+
+impl RegisterLog {
+    pub fn new(capacity: usize) -> RegisterLog {
+        RegisterLog {
+            masks: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, mask: u32) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.masks.len() == self.capacity {
+            self.masks.pop_front();
+        }
+        self.masks.push_back(mask);
+    }
+
+    pub fn pop(&mut self) -> Option<u32> {
+        self.masks.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.masks.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    #[allow(dead_code)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, idx: usize) -> Option<u32> {
+        self.masks.get(idx).copied()
+    }
+
+    // How many of the instructions in the range wrote each register.
+    pub fn counts(&self, start: usize, end: usize) -> [u32; REGISTER_COUNT] {
+        let mut counts = [0; REGISTER_COUNT];
+        for mask in self.masks.range(start.min(self.len())..end.min(self.len())) {
+            for (idx, count) in counts.iter_mut().enumerate() {
+                *count += (mask >> idx) & 1;
+            }
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod register_log_tests {
+    use super::{written_registers, RegisterLog, REGISTER_I, REGISTER_SP};
+    use crate::core::{cpu::Cpu, instruction::Instruction};
+
+    #[test]
+    fn test_written_registers() {
+        let written = |code, increments_i| written_registers(&Instruction::new(code), increments_i);
+        assert_eq!(written((0x6, 3, 0, 7), true), 1 << 3);
+        assert_eq!(written((0x8, 2, 5, 4), true), (1 << 2) | (1 << 0xF));
+        assert_eq!(written((0xD, 0, 1, 5), true), 1 << 0xF);
+        assert_eq!(written((0x2, 3, 0, 0), true), 1 << REGISTER_SP);
+        assert_eq!(written((0xF, 2, 6, 5), true), 0b111 | (1 << REGISTER_I));
+        assert_eq!(written((0xF, 0xF, 6, 5), false), 0xFFFF);
+        assert_eq!(written((0xF, 2, 5, 5), false), 0);
+        assert_eq!(written((0x3, 0, 0, 0), true), 0);
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut log = RegisterLog::new(3);
+        for mask in [0b01, 0b11, 0b10, 0b10] {
+            log.push(mask);
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.get(0), Some(0b11));
+        let counts = log.counts(0, 10);
+        assert_eq!(&counts[..3], &[1, 3, 0]);
+        assert_eq!(log.counts(1, 2)[0], 0);
+    }
+
+    #[test]
+    fn test_cpu_log() {
+        // LD V3, 7 - LD I, 0x300 - JP 0x204
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x63, 0x07, 0xA3, 0x00, 0x12, 0x04], 0x0200)
+            .unwrap();
+        cpu.set_register_log_capacity(16);
+        cpu.set_history_capacity(16);
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        let log = cpu.register_log().unwrap();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.get(1), Some(1 << REGISTER_I));
+
+        cpu.step_back();
+        assert_eq!(cpu.register_log().unwrap().len(), 2);
+    }
+}
@@ -7,50 +7,42 @@
 //
 // https://github.com/keelus/chip-8-emu
 
-use std::time::Instant;
-
+// Counts down once per emulated frame, at 60Hz of emulated time.
 pub struct Timer {
-    last_write: Instant,
-    write_data: u8,
+    value: u8,
 }
 
-pub const TIMER_HZ: f64 = 60.0;
-
 impl Timer {
     pub fn new() -> Timer {
-        Timer {
-            last_write: Instant::now(),
-            write_data: 0,
-        }
+        Timer { value: 0 }
     }
 
     pub fn write(&mut self, data: u8) {
-        self.write_data = data;
-        self.last_write = Instant::now()
+        self.value = data;
     }
 
     pub fn read(&self) -> u8 {
-        let now = Instant::now();
-        let diff = now.duration_since(self.last_write);
-
-        let diff_s = diff.as_secs_f64();
-        let value = self.write_data as f64 - TIMER_HZ * diff_s;
-        let value = value.round() as u8;
+        self.value
+    }
 
-        value
+    pub fn tick_frame(&mut self) {
+        self.value = self.value.saturating_sub(1);
     }
 }
 
 pub const DELAY_TIMER: usize = 0;
 pub const SOUND_TIMER: usize = 1;
 
+// Stack entries of the largest supported machine
+pub const STACK_CAPACITY: usize = 16;
+
 pub struct Registers {
     pub v: [u8; 16], // General purpose
     pub i: u16,      // Memory address oriented [12bit]
     pub timers: [Timer; 2],
     pub pc: u16,
     pub sp: u8,
-    pub stack: [u16; 16],
+    pub stack: [u16; STACK_CAPACITY],
 }
 
 impl Registers {
@@ -61,7 +53,25 @@ impl Registers {
             timers: [Timer::new(), Timer::new()],
             pc: 0,
             sp: 0,
-            stack: [0; 16],
+            stack: [0; STACK_CAPACITY],
         }
     }
 }
+
+#[cfg(test)]
+mod registers_tests {
+    use super::Timer;
+
+    #[test]
+    fn test_timer() {
+        let mut timer = Timer::new();
+        timer.write(2);
+        assert_eq!(timer.read(), 2);
+
+        timer.tick_frame();
+        assert_eq!(timer.read(), 1);
+        timer.tick_frame();
+        timer.tick_frame();
+        assert_eq!(timer.read(), 0);
+    }
+}
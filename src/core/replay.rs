@@ -0,0 +1,678 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::{Cpu, Snapshot},
+    hash,
+    keypad::InputMode,
+    memory::{BoundsPolicy, LoadError, BOUNDS_POLICIES, PROGRAM_START_MEM},
+    profile::{MachineProfile, PROFILES},
+    savestate::{self, SaveStateError},
+    timing::{TimingModel, TIMING_MODELS},
+};
+
+// Replay file layout (little endian):
+// 0x00 [4] -> Magic "C8RP"
+// 0x04 [1] -> Format version
+// 0x05 [8] -> ROM hash (FNV-1a)
+// 0x0D [1] -> Quirk bits (see Cpu::quirk_bits)
+// 0x0E [8] -> RNG seed
+// 0x16 [4] -> Input event count
+// 0x1A     -> Input events, 10 bytes each: frame [8], key [1], pressed [1]
+// then [4]  -> Base save state size, n, 0 if recorded from power-on (since version 3)
+// then [n]  -> Base save state
+// then [4]  -> Ticks per frame (since version 4)
+// then [1]  -> Machine profile, index in PROFILES
+// then [1]  -> Bounds policy, index in BOUNDS_POLICIES
+// then [1]  -> Input mode, 0 = raw, 1 = latched
+// then [2]  -> Address the ROM was loaded at
+// then [1]  -> Timing model, index in TIMING_MODELS (since version 5)
+// then [8]  -> Frames recorded (since version 6)
+//
+// Files from before version 4 play with the defaults of a new Cpu,
+// loaded at 0x200. Before version 6 they end at their last input.
+const MAGIC: &[u8; 4] = b"C8RP";
+const VERSION: u8 = 6;
+const SETTINGS_SIZE: usize = 9;
+const HEADER_SIZE: usize = 0x1A;
+const EVENT_SIZE: usize = 10;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidSettings,
+    RomMismatch { expected: u64, found: u64 },
+    Load(LoadError),
+    State(SaveStateError),
+}
+
+impl From<LoadError> for ReplayError {
+    fn from(err: LoadError) -> ReplayError {
+        ReplayError::Load(err)
+    }
+}
+
+impl From<SaveStateError> for ReplayError {
+    fn from(err: SaveStateError) -> ReplayError {
+        ReplayError::State(err)
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InvalidMagic => write!(f, "Not a replay file."),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported replay version ({}).", version)
+            }
+            ReplayError::Truncated => write!(f, "Replay file is truncated."),
+            ReplayError::InvalidSettings => {
+                write!(f, "Replay was recorded with unknown machine settings.")
+            }
+            ReplayError::RomMismatch { expected, found } => write!(
+                f,
+                "Replay was recorded with a different ROM (expected hash {:016X}, found {:016X}).",
+                expected, found
+            ),
+            ReplayError::Load(err) => err.fmt(f),
+            ReplayError::State(err) => err.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub rom_hash: u64,
+    pub quirks: u8,
+    pub rng_seed: u64,
+    pub ticks_per_frame: u32,
+    pub profile: MachineProfile,
+    pub bounds_policy: BoundsPolicy,
+    pub input_mode: InputMode,
+    pub program_begin: u16,
+    pub timing_model: TimingModel,
+    pub events: Vec<InputEvent>,
+    pub frames: u64, // Frames recorded, at least up to the last event
+    // Save state the recording started from, instead of power-on
+    pub base_state: Option<Vec<u8>>,
+}
+
+impl Replay {
+    // Starts a new replay from the cpu's current configuration.
+    // The cpu should have just been reset and loaded with the rom.
+    pub fn new(rom: &[u8], cpu: &Cpu) -> Replay {
+        Replay {
+            rom_hash: hash::hash_bytes(rom),
+            quirks: cpu.quirk_bits(),
+            rng_seed: cpu.rng_seed(),
+            ticks_per_frame: cpu.ticks_per_frame,
+            profile: cpu.profile(),
+            bounds_policy: cpu.memory.bounds_policy(),
+            input_mode: cpu.keypad.input_mode(),
+            program_begin: cpu.memory.program_start(),
+            timing_model: cpu.timing_model,
+            events: Vec::new(),
+            frames: 0,
+            base_state: None,
+        }
+    }
+
+    // Starts a new replay from a save state, e.g. to re-record a
+    // section without playing up to it.
+    pub fn from_state(rom: &[u8], cpu: &Cpu, state: Vec<u8>) -> Replay {
+        Replay {
+            base_state: Some(state),
+            ..Replay::new(rom, cpu)
+        }
+    }
+
+    // Resets the cpu to where the recording started: power-on with the
+    // recorded configuration, then the base state if there's one.
+    pub fn restart(&self, rom: &[u8], cpu: &mut Cpu) -> Result<(), ReplayError> {
+        self.check_rom(rom)?;
+
+        cpu.clear();
+        cpu.set_quirk_bits(self.quirks);
+        cpu.ticks_per_frame = self.ticks_per_frame;
+        cpu.timing_model = self.timing_model;
+        cpu.set_profile(self.profile);
+        cpu.memory.set_bounds_policy(self.bounds_policy);
+        cpu.keypad.set_input_mode(self.input_mode);
+        cpu.seed_rng(self.rng_seed);
+        cpu.load_rom(rom, self.program_begin)?;
+        if let Some(state) = self.base_state.as_deref() {
+            if let Err(err) = savestate::load(cpu, state) {
+                cpu.clear();
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, frame: u64, key: u8, pressed: bool) {
+        self.events.push(InputEvent {
+            frame,
+            key,
+            pressed,
+        });
+        self.frames = self.frames.max(frame + 1);
+    }
+
+    // Marks the frame recording stopped at, so playback also covers
+    // what happened after the last input (e.g. a crash).
+    pub fn finish(&mut self, frame: u64) {
+        self.frames = self.frames.max(frame);
+    }
+
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), ReplayError> {
+        let found = hash::hash_bytes(rom);
+        if found != self.rom_hash {
+            return Err(ReplayError::RomMismatch {
+                expected: self.rom_hash,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.events.len() * EVENT_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.rom_hash.to_le_bytes());
+        bytes.push(self.quirks);
+        bytes.extend_from_slice(&self.rng_seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.events.len() as u32).to_le_bytes());
+
+        for event in &self.events {
+            bytes.extend_from_slice(&event.frame.to_le_bytes());
+            bytes.push(event.key);
+            bytes.push(event.pressed as u8);
+        }
+
+        let state = self.base_state.as_deref().unwrap_or_default();
+        bytes.extend_from_slice(&(state.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(state);
+
+        let index_of = |found: Option<usize>| found.unwrap_or_default() as u8;
+        bytes.extend_from_slice(&self.ticks_per_frame.to_le_bytes());
+        bytes.push(index_of(PROFILES.iter().position(|&p| p == self.profile)));
+        bytes.push(index_of(
+            BOUNDS_POLICIES
+                .iter()
+                .position(|&p| p == self.bounds_policy),
+        ));
+        bytes.push((self.input_mode == InputMode::Latched) as u8);
+        bytes.extend_from_slice(&self.program_begin.to_le_bytes());
+        bytes.push(index_of(
+            TIMING_MODELS.iter().position(|&m| m == self.timing_model),
+        ));
+        bytes.extend_from_slice(&self.frames.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        if bytes.len() < MAGIC.len() || &bytes[0..4] != MAGIC {
+            return Err(ReplayError::InvalidMagic);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(ReplayError::Truncated);
+        }
+        let version = bytes[0x04];
+        if version == 0 || version > VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+
+        let rom_hash = read_u64(0x05);
+        let mut quirks = bytes[0x0D];
+        if version == 1 {
+            // Version 1 had a single clipping bit for both axes
+            quirks |= (quirks & 0x4) << 2;
+        }
+        let rng_seed = read_u64(0x0E);
+        let count = u32::from_le_bytes(bytes[0x16..0x1A].try_into().unwrap()) as usize;
+
+        if bytes.len() < HEADER_SIZE + count * EVENT_SIZE {
+            return Err(ReplayError::Truncated);
+        }
+
+        let events: Vec<InputEvent> = (0..count)
+            .map(|idx| {
+                let at = HEADER_SIZE + idx * EVENT_SIZE;
+                InputEvent {
+                    frame: read_u64(at),
+                    key: bytes[at + 8] & 0xF,
+                    pressed: bytes[at + 9] != 0,
+                }
+            })
+            .collect();
+
+        let mut at = HEADER_SIZE + count * EVENT_SIZE;
+        let base_state = if version >= 3 {
+            let size = bytes
+                .get(at..at + 4)
+                .map(|size| u32::from_le_bytes(size.try_into().unwrap()) as usize)
+                .ok_or(ReplayError::Truncated)?;
+            let state = bytes
+                .get(at + 4..at + 4 + size)
+                .ok_or(ReplayError::Truncated)?;
+            at += 4 + size;
+            (size > 0).then(|| state.to_vec())
+        } else {
+            None
+        };
+
+        let defaults = Cpu::new();
+        let mut replay = Replay {
+            rom_hash,
+            quirks,
+            rng_seed,
+            ticks_per_frame: defaults.ticks_per_frame,
+            profile: defaults.profile(),
+            bounds_policy: defaults.memory.bounds_policy(),
+            input_mode: defaults.keypad.input_mode(),
+            program_begin: PROGRAM_START_MEM,
+            timing_model: defaults.timing_model,
+            frames: events.last().map_or(0, |event| event.frame + 1),
+            events,
+            base_state,
+        };
+        if version >= 4 {
+            let settings = bytes
+                .get(at..at + SETTINGS_SIZE)
+                .ok_or(ReplayError::Truncated)?;
+            replay.ticks_per_frame = u32::from_le_bytes(settings[0..4].try_into().unwrap());
+            replay.profile = *PROFILES
+                .get(settings[4] as usize)
+                .ok_or(ReplayError::InvalidSettings)?;
+            replay.bounds_policy = *BOUNDS_POLICIES
+                .get(settings[5] as usize)
+                .ok_or(ReplayError::InvalidSettings)?;
+            replay.input_mode = match settings[6] {
+                0 => InputMode::Raw,
+                1 => InputMode::Latched,
+                _ => return Err(ReplayError::InvalidSettings),
+            };
+            replay.program_begin = u16::from_le_bytes(settings[7..9].try_into().unwrap());
+            at += SETTINGS_SIZE;
+        }
+        if version >= 5 {
+            let model = *bytes.get(at).ok_or(ReplayError::Truncated)?;
+            replay.timing_model = *TIMING_MODELS
+                .get(model as usize)
+                .ok_or(ReplayError::InvalidSettings)?;
+            at += 1;
+        }
+        if version >= 6 {
+            let frames = bytes.get(at..at + 8).ok_or(ReplayError::Truncated)?;
+            replay.finish(u64::from_le_bytes(frames.try_into().unwrap()));
+        }
+        Ok(replay)
+    }
+}
+
+// Frames between the snapshots used for seeking.
+pub const SNAPSHOT_INTERVAL: u64 = 60;
+
+// Feeds a replay's inputs into a cpu as its frames are reached,
+// keeping periodic snapshots so playback can be seeked.
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_event: usize,
+    snapshots: Vec<Snapshot>,
+
+    pub paused: bool,
+    pub speed: u32, // Frames run per call to run_frame
+}
+
+impl ReplayPlayer {
+    // Verifies the rom, then resets and configures the cpu so
+    // it starts in the same state the recording did.
+    pub fn start(replay: Replay, rom: Vec<u8>, cpu: &mut Cpu) -> Result<ReplayPlayer, ReplayError> {
+        replay.restart(&rom, cpu)?;
+
+        Ok(ReplayPlayer {
+            replay,
+            next_event: 0,
+            snapshots: vec![cpu.snapshot()],
+            paused: false,
+            speed: 1,
+        })
+    }
+
+    // Applies every event due up to (and including) the cpu's current frame.
+    pub fn apply(&mut self, cpu: &mut Cpu) {
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.frame > cpu.frame() {
+                break;
+            }
+            cpu.keypad.set_key(event.key, event.pressed);
+            self.next_event += 1;
+        }
+    }
+
+    // Advances the cpu by `speed` frames, unless paused. Pauses
+    // automatically once the end of the replay is reached.
+    pub fn run_frame(&mut self, cpu: &mut Cpu) {
+        if self.paused {
+            return;
+        }
+
+        for _ in 0..self.speed {
+            if cpu.frame() >= self.length() {
+                self.paused = true;
+                break;
+            }
+            self.step(cpu);
+        }
+    }
+
+    fn step(&mut self, cpu: &mut Cpu) {
+        let frame = cpu.frame();
+        let last_snapshot = self.snapshots.last().map_or(0, |snapshot| snapshot.frame());
+        if frame.is_multiple_of(SNAPSHOT_INTERVAL) && frame > last_snapshot {
+            self.snapshots.push(cpu.snapshot());
+        }
+
+        self.apply(cpu);
+        cpu.tick();
+    }
+
+    // Moves playback to the given frame, restoring the closest
+    // earlier snapshot and running forward from there if needed.
+    pub fn seek(&mut self, cpu: &mut Cpu, frame: u64) {
+        let frame = frame.min(self.length());
+
+        let snapshot = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.frame() <= frame)
+            .unwrap();
+        if frame < cpu.frame() || snapshot.frame() > cpu.frame() {
+            cpu.restore(snapshot);
+            self.next_event = self
+                .replay
+                .events
+                .partition_point(|event| event.frame < snapshot.frame());
+        }
+
+        while cpu.frame() < frame && cpu.is_rom_loaded() && !cpu.is_halted() {
+            self.step(cpu);
+        }
+    }
+
+    // Starts a new recording which keeps this replay's inputs
+    // up to the cpu's current frame.
+    pub fn branch(&self, cpu: &Cpu) -> Replay {
+        let mut replay = self.replay.clone();
+        replay.events.retain(|event| event.frame < cpu.frame());
+        replay.frames = cpu.frame();
+        replay
+    }
+
+    // Amount of frames the replay spans.
+    pub fn length(&self) -> u64 {
+        self.replay.frames
+    }
+
+    #[allow(dead_code)]
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.replay.events.len()
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::{Replay, ReplayError, ReplayPlayer};
+    use crate::core::{
+        cpu::Cpu, keypad::InputMode, memory::BoundsPolicy, profile::MachineProfile, savestate,
+        timing::TimingModel,
+    };
+
+    fn sample_replay() -> Replay {
+        let mut cpu = Cpu::new();
+        cpu.seed_rng(42);
+        let mut replay = Replay::new(&[0x12, 0x00], &cpu);
+        replay.record(0, 0x5, true);
+        replay.record(3, 0x5, false);
+        replay
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let replay = sample_replay();
+        let bytes = replay.to_bytes();
+        assert_eq!(Replay::from_bytes(&bytes), Ok(replay));
+    }
+
+    #[test]
+    fn test_settings() {
+        // Recorded on a machine set up unlike a new one
+        let rom = [0x12, 0x00];
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 20;
+        cpu.set_profile(MachineProfile::XoChip);
+        cpu.memory.set_bounds_policy(BoundsPolicy::Clamp);
+        cpu.keypad.set_input_mode(InputMode::Latched);
+        cpu.timing_model = TimingModel::CosmacVip;
+        cpu.load_rom(&rom, 0x0600).unwrap();
+        let replay = Replay::new(&rom, &cpu);
+        let bytes = replay.to_bytes();
+        assert_eq!(Replay::from_bytes(&bytes).as_ref(), Ok(&replay));
+
+        let mut other = Cpu::new();
+        ReplayPlayer::start(replay, rom.to_vec(), &mut other).unwrap();
+        assert_eq!(other.ticks_per_frame, 20);
+        assert_eq!(other.profile(), MachineProfile::XoChip);
+        assert_eq!(other.memory.bounds_policy(), BoundsPolicy::Clamp);
+        assert_eq!(other.keypad.input_mode(), InputMode::Latched);
+        assert_eq!(other.registers.pc, 0x0600);
+        assert_eq!(other.timing_model, TimingModel::CosmacVip);
+
+        let mut bytes = bytes;
+        let len = bytes.len();
+        assert_eq!(
+            Replay::from_bytes(&bytes[..len - 1]),
+            Err(ReplayError::Truncated)
+        );
+        bytes[len - 9] = 2;
+        assert_eq!(
+            Replay::from_bytes(&bytes),
+            Err(ReplayError::InvalidSettings)
+        );
+    }
+
+    #[test]
+    fn test_base_state_roundtrip() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        let mut replay = Replay::from_state(&[0x12, 0x00], &cpu, savestate::save(&cpu));
+        replay.record(2, 0x1, true);
+        let bytes = replay.to_bytes();
+        assert_eq!(Replay::from_bytes(&bytes), Ok(replay));
+        assert_eq!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ReplayError::Truncated)
+        );
+
+        // Version 2 files end with the events, version 3 ones with the
+        // base state
+        let mut bytes = sample_replay().to_bytes();
+        bytes.truncate(bytes.len() - 9);
+        bytes[0x04] = 3;
+        assert_eq!(Replay::from_bytes(&bytes), Ok(sample_replay()));
+        bytes.truncate(bytes.len() - 4);
+        bytes[0x04] = 2;
+        assert_eq!(Replay::from_bytes(&bytes), Ok(sample_replay()));
+    }
+
+    #[test]
+    fn test_rerecord_from_state() {
+        // Loop adding 1 to V0 each frame
+        let rom = vec![0x70, 0x01, 0x12, 0x00];
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 1;
+        cpu.load_rom(&rom, 0x0200).unwrap();
+        for _ in 0..50 {
+            cpu.tick();
+        }
+        let state = savestate::save(&cpu);
+
+        let mut replay = Replay::from_state(&rom, &cpu, state);
+        replay.restart(&rom, &mut cpu).unwrap();
+        assert_eq!(cpu.frame(), 0);
+        assert_eq!(cpu.registers.v[0], 25);
+        replay.record(5, 0x2, true);
+
+        let mut other = Cpu::new();
+        other.ticks_per_frame = 1;
+        let mut player = ReplayPlayer::start(replay, rom, &mut other).unwrap();
+        player.speed = 10;
+        player.run_frame(&mut other);
+        assert_eq!(other.registers.v[0], 25 + 3);
+        assert!(other.keypad.get_key_state(0x2));
+
+        let mut broken = sample_replay();
+        broken.base_state = Some(b"C8SS".to_vec());
+        assert!(matches!(
+            broken.restart(&[0x12, 0x00], &mut other),
+            Err(ReplayError::State(_))
+        ));
+        assert!(!other.is_rom_loaded());
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let bytes = sample_replay().to_bytes();
+        assert_eq!(Replay::from_bytes(b"nope"), Err(ReplayError::InvalidMagic));
+        assert_eq!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ReplayError::Truncated)
+        );
+
+        let mut bytes = bytes;
+        bytes[0x04] = 0xFF;
+        assert_eq!(
+            Replay::from_bytes(&bytes),
+            Err(ReplayError::UnsupportedVersion(0xFF))
+        );
+    }
+
+    #[test]
+    fn test_rom_mismatch() {
+        let replay = sample_replay();
+        assert!(replay.check_rom(&[0x12, 0x00]).is_ok());
+        assert!(matches!(
+            replay.check_rom(&[0x12, 0x02]),
+            Err(ReplayError::RomMismatch { .. })
+        ));
+
+        let mut cpu = Cpu::new();
+        assert!(ReplayPlayer::start(replay, vec![0x00, 0xE0], &mut cpu).is_err());
+        assert!(!cpu.is_rom_loaded());
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 1;
+        let mut player = ReplayPlayer::start(sample_replay(), vec![0x12, 0x00], &mut cpu).unwrap();
+        assert_eq!(cpu.rng_seed(), 42);
+
+        player.apply(&mut cpu);
+        assert!(cpu.keypad.get_key_state(0x5));
+        for _ in 0..3 {
+            cpu.tick();
+            player.apply(&mut cpu);
+        }
+        assert!(!cpu.keypad.get_key_state(0x5));
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn test_seek_and_branch() {
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 1;
+        // Loop adding 1 to V0 each frame
+        let rom = vec![0x70, 0x01, 0x12, 0x00];
+        let mut replay = Replay::new(&rom, &cpu);
+        replay.record(10, 0x1, true);
+        replay.record(150, 0x1, false);
+
+        let mut player = ReplayPlayer::start(replay, rom, &mut cpu).unwrap();
+        player.speed = 200;
+        player.run_frame(&mut cpu);
+        assert!(player.paused);
+        assert_eq!(cpu.frame(), player.length());
+        let end_hash = cpu.state_hash();
+
+        player.seek(&mut cpu, 20);
+        assert_eq!(cpu.frame(), 20);
+        assert_eq!(cpu.registers.v[0], 10);
+        assert!(cpu.keypad.get_key_state(0x1));
+
+        player.seek(&mut cpu, player.length());
+        assert_eq!(cpu.state_hash(), end_hash);
+
+        player.seek(&mut cpu, 100);
+        let branch = player.branch(&cpu);
+        assert_eq!(branch.events.len(), 1);
+        assert_eq!(branch.rom_hash, player.replay().rom_hash);
+    }
+
+    #[test]
+    fn test_recorded_frames() {
+        // Nothing pressed after frame 3, but recording went on to 100
+        let mut replay = sample_replay();
+        replay.finish(100);
+        let bytes = replay.to_bytes();
+        assert_eq!(Replay::from_bytes(&bytes).as_ref(), Ok(&replay));
+
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 1;
+        let mut player = ReplayPlayer::start(replay, vec![0x12, 0x00], &mut cpu).unwrap();
+        assert_eq!(player.length(), 100);
+        player.speed = 200;
+        player.run_frame(&mut cpu);
+        assert_eq!(cpu.frame(), 100);
+
+        // Version 5 files end at their last input
+        let mut bytes = bytes;
+        bytes.truncate(bytes.len() - 8);
+        bytes[0x04] = 5;
+        assert_eq!(Replay::from_bytes(&bytes).unwrap().frames, 4);
+    }
+
+    #[test]
+    fn test_version_1_quirks() {
+        let mut bytes = sample_replay().to_bytes();
+        bytes[0x04] = 1;
+        bytes[0x0D] = 0b0100;
+        assert_eq!(Replay::from_bytes(&bytes).unwrap().quirks, 0b10100);
+    }
+}
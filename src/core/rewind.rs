@@ -0,0 +1,125 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::collections::VecDeque;
+
+use super::cpu::Snapshot;
+
+// Bounded history of snapshots. Once full, the oldest
+// snapshot is dropped to make room for the new one.
+pub struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> RewindBuffer {
+        RewindBuffer {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    pub fn oldest_frame(&self) -> Option<u64> {
+        self.snapshots.front().map(Snapshot::frame)
+    }
+
+    // Drops the snapshots from the given frame on, returning the first
+    // of them, taken as the frame started. None if the buffer doesn't
+    // reach back to the frame or hasn't got to it yet.
+    pub fn rewind_to_frame(&mut self, frame: u64) -> Option<Snapshot> {
+        if self.oldest_frame()? > frame {
+            return None;
+        }
+        let idx = self
+            .snapshots
+            .partition_point(|snapshot| snapshot.frame() < frame);
+        if idx == self.snapshots.len() {
+            return None;
+        }
+        self.snapshots.truncate(idx + 1);
+        self.snapshots.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod rewind_tests {
+    use super::RewindBuffer;
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_capacity() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        let mut buffer = RewindBuffer::new(2);
+        for _ in 0..3 {
+            buffer.push(cpu.snapshot());
+            cpu.tick();
+        }
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.pop().unwrap().frame(), 2);
+        assert_eq!(buffer.pop().unwrap().frame(), 1);
+        assert!(buffer.pop().is_none());
+    }
+
+    #[test]
+    fn test_rewind_to_frame() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x70, 0x01, 0x12, 0x00], 0x0200).unwrap();
+        cpu.ticks_per_frame = 2;
+        cpu.set_history_capacity(16);
+        for _ in 0..5 {
+            cpu.tick();
+        }
+        assert!(!cpu.can_rewind_to(5));
+        assert!(cpu.can_rewind_to(0));
+        assert!(cpu.rewind_to_frame(2));
+        assert_eq!(cpu.frame(), 2);
+        assert_eq!(cpu.registers.v[0], 2);
+        assert_eq!(cpu.history_len(), 4);
+
+        let mut buffer = RewindBuffer::new(2);
+        buffer.push(cpu.snapshot());
+        assert!(buffer.rewind_to_frame(1).is_none());
+        assert!(buffer.rewind_to_frame(3).is_none());
+        assert_eq!(buffer.rewind_to_frame(2).unwrap().frame(), 2);
+        assert!(buffer.is_empty());
+    }
+}
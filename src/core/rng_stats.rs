@@ -0,0 +1,111 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+// How often Cxkk produced each value, both the random byte itself and
+// the result after masking it with kk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RngStats {
+    random: [u64; 256],
+    results: [u64; 256],
+    total: u64,
+}
+
+impl Default for RngStats {
+    fn default() -> RngStats {
+        RngStats {
+            random: [0; 256],
+            results: [0; 256],
+            total: 0,
+        }
+    }
+}
+
+impl RngStats {
+    pub fn record(&mut self, random: u8, result: u8) {
+        self.random[random as usize] += 1;
+        self.results[result as usize] += 1;
+        self.total += 1;
+    }
+
+    pub fn clear(&mut self) {
+        *self = RngStats::default();
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn random(&self) -> &[u64; 256] {
+        &self.random
+    }
+
+    pub fn results(&self) -> &[u64; 256] {
+        &self.results
+    }
+
+    pub fn mean(counts: &[u64; 256]) -> Option<f64> {
+        let total: u64 = counts.iter().sum();
+        let sum: u64 = counts
+            .iter()
+            .enumerate()
+            .map(|(value, &count)| value as u64 * count)
+            .sum();
+        (total > 0).then(|| sum as f64 / total as f64)
+    }
+
+    // Pearson's chi-squared statistic of the random bytes against a
+    // uniform distribution. Uniform bytes give about 255, the degrees
+    // of freedom, while a biased generator gives much more.
+    pub fn chi_squared(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+        let expected = self.total as f64 / 256.0;
+        Some(
+            self.random
+                .iter()
+                .map(|&count| (count as f64 - expected).powi(2) / expected)
+                .sum(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod rng_stats_tests {
+    use super::RngStats;
+
+    #[test]
+    fn test_record() {
+        let mut stats = RngStats::default();
+        assert_eq!(
+            (stats.chi_squared(), RngStats::mean(stats.random())),
+            (None, None)
+        );
+        stats.record(0xFF, 0x0F);
+        stats.record(0x01, 0x01);
+        assert_eq!(stats.total(), 2);
+        assert_eq!((stats.random()[0xFF], stats.results()[0x0F]), (1, 1));
+        assert_eq!(RngStats::mean(stats.results()), Some(8.0));
+        stats.clear();
+        assert_eq!(stats.total(), 0);
+    }
+
+    #[test]
+    fn test_chi_squared() {
+        let mut uniform = RngStats::default();
+        let mut biased = RngStats::default();
+        for value in 0..=255 {
+            uniform.record(value, value);
+            biased.record(value & 0xF0, value & 0xF0);
+        }
+        assert_eq!(uniform.chi_squared(), Some(0.0));
+        // 16 values seen 16 times each, where 1 was expected
+        assert_eq!(biased.chi_squared(), Some(16.0 * 225.0 + 240.0));
+    }
+}
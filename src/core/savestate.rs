@@ -0,0 +1,296 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::Cpu,
+    memory::MEMORY_SIZE,
+    registers::{DELAY_TIMER, SOUND_TIMER},
+};
+
+// Save state file layout (little endian):
+// 0x00 [4]    -> Magic "C8SS"
+// 0x04 [1]    -> Format version
+// 0x05 [16]   -> V0 - VF
+// 0x15 [2]    -> I
+// 0x17 [2]    -> PC
+// 0x19 [1]    -> SP
+// 0x1A [32]   -> Stack
+// 0x3A [2]    -> Delay and sound timers
+// 0x3C [1]    -> Quirk bits (see Cpu::quirk_bits)
+// 0x3D [2]    -> Screen width
+// 0x3F [2]    -> Screen height
+// 0x41 [4]    -> Memory size, n (since version 2)
+// 0x45 [n]    -> Memory
+// 0x45 + n    -> Screen, 8 pixels per byte, row by row, MSB first
+//
+// Version 1 files have no memory size, with 4096 bytes of memory at 0x41.
+// The keypad and RNG aren't stored: keys start released and the
+// RNG keeps the cpu's current seed.
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u8 = 2;
+const HEADER_SIZE: usize = 0x45;
+const V1_HEADER_SIZE: usize = 0x41;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SaveStateError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidResolution { width: usize, height: usize },
+    // Saved on a machine profile with another memory size
+    MemorySize { saved: usize, current: usize },
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::InvalidMagic => write!(f, "Not a save state file."),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version ({}).", version)
+            }
+            SaveStateError::Truncated => write!(f, "Save state file is truncated."),
+            SaveStateError::InvalidResolution { width, height } => {
+                write!(f, "Invalid screen resolution ({}x{}).", width, height)
+            }
+            SaveStateError::MemorySize { saved, current } => write!(
+                f,
+                "Save state has {} bytes of memory, but the machine has {}.",
+                saved, current
+            ),
+        }
+    }
+}
+
+pub fn save(cpu: &Cpu) -> Vec<u8> {
+    let registers = &cpu.registers;
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + cpu.memory.size());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&registers.v);
+    bytes.extend_from_slice(&registers.i.to_le_bytes());
+    bytes.extend_from_slice(&registers.pc.to_le_bytes());
+    bytes.push(registers.sp);
+    for addr in registers.stack {
+        bytes.extend_from_slice(&addr.to_le_bytes());
+    }
+    bytes.push(registers.timers[DELAY_TIMER].read());
+    bytes.push(registers.timers[SOUND_TIMER].read());
+    bytes.push(cpu.quirk_bits());
+    bytes.extend_from_slice(&(cpu.screen.width() as u16).to_le_bytes());
+    bytes.extend_from_slice(&(cpu.screen.height() as u16).to_le_bytes());
+    bytes.extend_from_slice(&(cpu.memory.size() as u32).to_le_bytes());
+    bytes.extend_from_slice(&cpu.memory.dump());
+
+    for y in 0..cpu.screen.height() {
+        for x in (0..cpu.screen.width()).step_by(8) {
+            let byte = (0..8).fold(0, |byte, bit| {
+                byte | (cpu.screen.get(x + bit, y) as u8) << (7 - bit)
+            });
+            bytes.push(byte);
+        }
+    }
+
+    bytes
+}
+
+// Where the memory starts in a save state file, and its size.
+fn memory_layout(bytes: &[u8]) -> Result<(usize, usize), SaveStateError> {
+    if bytes.len() < MAGIC.len() || &bytes[0..4] != MAGIC {
+        return Err(SaveStateError::InvalidMagic);
+    }
+    if bytes.len() < V1_HEADER_SIZE {
+        return Err(SaveStateError::Truncated);
+    }
+    let version = bytes[0x04];
+    if version == 0 || version > VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+    if version == 1 {
+        Ok((V1_HEADER_SIZE, MEMORY_SIZE))
+    } else if bytes.len() < HEADER_SIZE {
+        Err(SaveStateError::Truncated)
+    } else {
+        let size = u32::from_le_bytes(bytes[0x41..0x45].try_into().unwrap());
+        Ok((HEADER_SIZE, size as usize))
+    }
+}
+
+// The memory stored in a save state, e.g. to compare states without
+// loading them.
+pub fn memory(bytes: &[u8]) -> Result<&[u8], SaveStateError> {
+    let (start, size) = memory_layout(bytes)?;
+    bytes
+        .get(start..start + size)
+        .ok_or(SaveStateError::Truncated)
+}
+
+// Restores a save state into the cpu. The loaded ROM is kept, so
+// states should be loaded on top of the ROM they were saved with.
+pub fn load(cpu: &mut Cpu, bytes: &[u8]) -> Result<(), SaveStateError> {
+    let (memory_start, memory_size) = memory_layout(bytes)?;
+    if memory_size != cpu.memory.size() {
+        return Err(SaveStateError::MemorySize {
+            saved: memory_size,
+            current: cpu.memory.size(),
+        });
+    }
+
+    let read_u16 = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+
+    let width = read_u16(0x3D) as usize;
+    let height = read_u16(0x3F) as usize;
+    if width == 0 || !width.is_multiple_of(64) || height == 0 {
+        return Err(SaveStateError::InvalidResolution { width, height });
+    }
+    let screen_start = memory_start + memory_size;
+    if bytes.len() < screen_start + width / 8 * height {
+        return Err(SaveStateError::Truncated);
+    }
+
+    let registers = &mut cpu.registers;
+    registers.v.copy_from_slice(&bytes[0x05..0x15]);
+    registers.i = read_u16(0x15);
+    registers.pc = read_u16(0x17);
+    registers.sp = bytes[0x19];
+    for (idx, addr) in registers.stack.iter_mut().enumerate() {
+        *addr = read_u16(0x1A + idx * 2);
+    }
+    registers.timers[DELAY_TIMER].write(bytes[0x3A]);
+    registers.timers[SOUND_TIMER].write(bytes[0x3B]);
+    cpu.set_quirk_bits(bytes[0x3C]);
+
+    cpu.memory
+        .load_slice(0, &bytes[memory_start..screen_start])
+        .map_err(|_| SaveStateError::MemorySize {
+            saved: memory_size,
+            current: cpu.memory.size(),
+        })?;
+
+    cpu.screen.set_resolution(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let byte = bytes[screen_start + (y * width + x) / 8];
+            cpu.screen.set(x, y, byte & (0x80 >> (x % 8)) != 0);
+        }
+    }
+
+    for key in 0..=0xF {
+        cpu.keypad.set_key(key, false);
+    }
+    cpu.keypad.last_key = None;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod savestate_tests {
+    use super::{load, memory, save, SaveStateError};
+    use crate::core::{cpu::Cpu, profile::MachineProfile};
+
+    fn sample_cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        cpu.registers.v[3] = 0x42;
+        cpu.registers.i = 0x0321;
+        cpu.registers.pc = 0x0204;
+        cpu.registers.sp = 2;
+        cpu.registers.stack[1] = 0x0200;
+        cpu.memory.write(0x0300, 0xAB);
+        cpu.screen.set(5, 7, true);
+        cpu.screen.set(63, 31, true);
+        cpu.set_quirk_bits(0x15);
+        cpu
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let cpu = sample_cpu();
+        let bytes = save(&cpu);
+
+        let mut restored = Cpu::new();
+        restored.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        load(&mut restored, &bytes).unwrap();
+        assert_eq!(restored.state_hash(), cpu.state_hash());
+        assert_eq!(restored.quirk_bits(), 0x15);
+        assert!(restored.screen.get(5, 7));
+        assert!(!restored.screen.get(6, 7));
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let bytes = save(&sample_cpu());
+        let mut cpu = Cpu::new();
+        assert_eq!(load(&mut cpu, b"nope"), Err(SaveStateError::InvalidMagic));
+        assert_eq!(
+            load(&mut cpu, &bytes[..bytes.len() - 1]),
+            Err(SaveStateError::Truncated)
+        );
+
+        let mut bytes = bytes;
+        bytes[0x04] = 0xFF;
+        assert_eq!(
+            load(&mut cpu, &bytes),
+            Err(SaveStateError::UnsupportedVersion(0xFF))
+        );
+
+        bytes[0x04] = 1;
+        bytes[0x3D] = 10;
+        assert_eq!(
+            load(&mut cpu, &bytes),
+            Err(SaveStateError::InvalidResolution {
+                width: 0x000A,
+                height: 32
+            })
+        );
+    }
+
+    #[test]
+    fn test_memory_sizes() {
+        let mut cpu = sample_cpu();
+        cpu.set_profile(MachineProfile::XoChip);
+        cpu.memory.write(0xFF00, 0x77);
+        let bytes = save(&cpu);
+
+        let mut restored = Cpu::new();
+        assert_eq!(
+            load(&mut restored, &bytes),
+            Err(SaveStateError::MemorySize {
+                saved: 0x10000,
+                current: 0x1000
+            })
+        );
+        restored.set_profile(MachineProfile::XoChip);
+        load(&mut restored, &bytes).unwrap();
+        assert_eq!(restored.memory.peek(0xFF00), Some(0x77));
+        assert_eq!(restored.state_hash(), cpu.state_hash());
+
+        // Version 1, without the memory size
+        let mut bytes = save(&sample_cpu());
+        bytes[0x04] = 1;
+        bytes.drain(0x41..0x45);
+        let mut restored = Cpu::new();
+        load(&mut restored, &bytes).unwrap();
+        assert_eq!(restored.state_hash(), sample_cpu().state_hash());
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut cpu = sample_cpu();
+        let saved = memory(&save(&cpu)).unwrap().to_vec();
+        assert_eq!(saved, cpu.memory.dump());
+
+        cpu.set_profile(MachineProfile::XoChip);
+        let bytes = save(&cpu);
+        assert_eq!(memory(&bytes).map(|memory| memory.len()), Ok(0x10000));
+        assert_eq!(memory(&bytes[..0x100]), Err(SaveStateError::Truncated));
+        assert_eq!(memory(b"nope"), Err(SaveStateError::InvalidMagic));
+    }
+}
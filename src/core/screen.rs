@@ -7,17 +7,629 @@
 //
 // https://github.com/keelus/chip-8-emu
 
+use super::hash;
+
+pub struct Palette {
+    pub enabled_px: [u8; 3],
+    pub disabled_px: [u8; 3],
+}
+
+// Area affected by a sprite draw. With wrapping enabled it
+// may extend past the right/bottom edges, continuing on the
+// opposite side of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRegion {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub collision: bool,
+}
+
+// Default (classic CHIP-8) resolution.
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
-pub struct Screen(pub [u64; HEIGHT]);
+// Horizontal scroll step, as used by SCHIP's 00FB/00FC.
+#[allow(dead_code)]
+pub const SCROLL_PIXELS: usize = 4;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// How far ghost pixels (see render_rgb_with_ghost) are blended from the
+// disabled towards the enabled pixel color.
+const GHOST_STRENGTH: f32 = 0.3;
+
+// Each row is stored as one or more u64 words, where the most
+// significant bit of the first word is the leftmost pixel (x = 0).
+// Widths must be a multiple of 64 (64x32, 64x48, 64x64, 128x64...).
+// Any change marks the screen as dirty, so frontends only need to
+// upload it again after take_dirty returns true.
+#[derive(Clone)]
+pub struct Screen {
+    width: usize,
+    height: usize,
+    words: Vec<u64>,
+    dirty: bool,
+}
 
 impl Screen {
     pub fn new() -> Screen {
-        Screen { 0: [0; HEIGHT] }
+        Screen::with_resolution(WIDTH, HEIGHT)
+    }
+
+    pub fn with_resolution(width: usize, height: usize) -> Screen {
+        assert!(
+            width > 0 && width.is_multiple_of(WORD_BITS),
+            "Screen width must be a multiple of 64."
+        );
+        Screen {
+            width,
+            height,
+            words: vec![0; width / WORD_BITS * height],
+            dirty: true,
+        }
+    }
+
+    // Switches to a new resolution, clearing the screen.
+    pub fn set_resolution(&mut self, width: usize, height: usize) {
+        *self = Screen::with_resolution(width, height);
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
     }
 
     pub fn clear(&mut self) {
-        self.0.fill(0);
+        self.words.fill(0);
+        self.dirty = true;
+    }
+
+    #[allow(dead_code)]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    // Forces the next take_dirty to return true (e.g. when the palette changes).
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    // Returns whether the screen changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn words_per_row(&self) -> usize {
+        self.width / WORD_BITS
+    }
+
+    // Index of the word holding pixel (x, y), and the mask of the pixel in it.
+    fn locate(&self, x: usize, y: usize) -> (usize, u64) {
+        let idx = y * self.words_per_row() + x / WORD_BITS;
+        let mask = 0x1 << (WORD_BITS - 1 - x % WORD_BITS);
+        (idx, mask)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let (idx, mask) = self.locate(x, y);
+        self.words[idx] & mask != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        let (idx, mask) = self.locate(x, y);
+        if on {
+            self.words[idx] |= mask;
+        } else {
+            self.words[idx] &= !mask;
+        }
+        self.dirty = true;
+    }
+
+    // Words of row y, leftmost first.
+    #[allow(dead_code)]
+    pub fn row(&self, y: usize) -> &[u64] {
+        let words_per_row = self.words_per_row();
+        &self.words[y * words_per_row..(y + 1) * words_per_row]
+    }
+
+    // Words of every row, top to bottom (see row).
+    pub fn rows(&self) -> impl Iterator<Item = &[u64]> {
+        self.words.chunks(self.words_per_row())
+    }
+
+    // Every row's words, top to bottom.
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    // Pixels that differ from an earlier copy of words(), with their
+    // state now.
+    pub fn changes_since<'a>(
+        &'a self,
+        before: &'a [u64],
+    ) -> impl Iterator<Item = (usize, usize, bool)> + 'a {
+        let words_per_row = self.words_per_row();
+        self.words
+            .iter()
+            .zip(before)
+            .enumerate()
+            .filter(|(_, (now, then))| now != then)
+            .flat_map(move |(idx, (&now, &then))| {
+                let y = idx / words_per_row;
+                let left = idx % words_per_row * WORD_BITS;
+                let changed = now ^ then;
+                (0..WORD_BITS)
+                    .map(move |bit| 0x1 << (WORD_BITS - 1 - bit))
+                    .enumerate()
+                    .filter(move |(_, mask)| changed & mask != 0)
+                    .map(move |(bit, mask)| (left + bit, y, now & mask != 0))
+            })
+    }
+
+    // Packed as sprites are, a bit per pixel with the leftmost pixel
+    // as the most significant bit, row after row.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+
+    // Identifies what's shown, e.g. for scripts comparing screens.
+    pub fn hash(&self) -> u64 {
+        let bytes: Vec<u8> = self
+            .words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        hash::hash_bytes(&bytes)
+    }
+
+    // XORs an 8 pixel wide sprite row into row y starting at x,
+    // returning whether any enabled pixel got disabled. Pixels past
+    // the right edge wrap around or get clipped.
+    pub fn draw_sprite_row(&mut self, x: usize, y: usize, sprite: u8, wrap: bool) -> bool {
+        self.xor_row(x, y, (sprite as u64) << (WORD_BITS - 8), wrap)
+    }
+
+    // Same as draw_sprite_row, for SCHIP's 16 pixel wide sprites.
+    #[allow(dead_code)]
+    pub fn draw_wide_sprite_row(&mut self, x: usize, y: usize, sprite: u16, wrap: bool) -> bool {
+        self.xor_row(x, y, (sprite as u64) << (WORD_BITS - 16), wrap)
+    }
+
+    // XORs a pattern (leftmost pixel in the most significant bit, up to
+    // 63 pixels wide) into row y at x. Patterns span two words at most,
+    // so this works on words instead of pixels: the part in x's word,
+    // and the part that spills into the next one. Past the last word,
+    // the spill wraps to the first one, or is masked out when clipping.
+    fn xor_row(&mut self, x: usize, y: usize, pattern: u64, wrap: bool) -> bool {
+        let words_per_row = self.words_per_row();
+        let x = x % self.width;
+        let word = x / WORD_BITS;
+        let shift = (x % WORD_BITS) as u32;
+
+        let last_word = word + 1 == words_per_row;
+        let next_word = if last_word { 0 } else { word + 1 };
+        let spill_mask = if last_word && !wrap { 0 } else { u64::MAX };
+        let head = pattern >> shift;
+        let spill = pattern.checked_shl(WORD_BITS as u32 - shift).unwrap_or(0) & spill_mask;
+
+        let row = &mut self.words[y * words_per_row..(y + 1) * words_per_row];
+        let collision = (row[word] & head) | (row[next_word] & spill) != 0;
+        row[word] ^= head;
+        row[next_word] ^= spill;
+        self.dirty |= pattern != 0;
+        collision
+    }
+
+    // Moves every row n pixels down, blanking the rows left on top.
+    #[allow(dead_code)]
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.height) * self.words_per_row();
+        self.words.rotate_right(n);
+        self.words[..n].fill(0);
+        self.dirty = true;
+    }
+
+    // Moves every row n pixels up, blanking the rows left at the bottom.
+    #[allow(dead_code)]
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.height) * self.words_per_row();
+        self.words.rotate_left(n);
+        let len = self.words.len();
+        self.words[len - n..].fill(0);
+        self.dirty = true;
+    }
+
+    // Moves the screen SCROLL_PIXELS to the left.
+    #[allow(dead_code)]
+    pub fn scroll_left(&mut self) {
+        let words_per_row = self.words_per_row();
+        for row in self.words.chunks_mut(words_per_row) {
+            for idx in 0..row.len() {
+                let carry = row
+                    .get(idx + 1)
+                    .map_or(0, |next| next >> (WORD_BITS - SCROLL_PIXELS));
+                row[idx] = row[idx] << SCROLL_PIXELS | carry;
+            }
+        }
+        self.dirty = true;
+    }
+
+    // Moves the screen SCROLL_PIXELS to the right.
+    #[allow(dead_code)]
+    pub fn scroll_right(&mut self) {
+        let words_per_row = self.words_per_row();
+        for row in self.words.chunks_mut(words_per_row) {
+            for idx in (0..row.len()).rev() {
+                let carry = if idx > 0 {
+                    row[idx - 1] << (WORD_BITS - SCROLL_PIXELS)
+                } else {
+                    0
+                };
+                row[idx] = row[idx] >> SCROLL_PIXELS | carry;
+            }
+        }
+        self.dirty = true;
+    }
+
+    // Iterates every pixel as (x, y, on), row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y, self.get(x, y))))
+    }
+
+    // Expands the screen into a row-major RGB buffer (3 bytes per pixel).
+    pub fn render_rgb(&self, palette: &Palette) -> Vec<u8> {
+        self.render(palette, None, None)
+    }
+
+    // Same as render_rgb, but with an opaque alpha channel (4 bytes per pixel).
+    #[allow(dead_code)]
+    pub fn render_rgba(&self, palette: &Palette) -> Vec<u8> {
+        self.render(palette, Some(0xFF), None)
+    }
+
+    // Same as render_rgb, but pixels that are off now and were on in the
+    // ghost screen get a dim color, to tell what changed since it.
+    // Ghosts with another resolution are ignored.
+    pub fn render_rgb_with_ghost(&self, palette: &Palette, ghost: &Screen) -> Vec<u8> {
+        let ghost = (ghost.width == self.width && ghost.height == self.height).then_some(ghost);
+        self.render(palette, None, ghost)
+    }
+
+    fn render(&self, palette: &Palette, alpha: Option<u8>, ghost: Option<&Screen>) -> Vec<u8> {
+        let channels = if alpha.is_some() { 4 } else { 3 };
+        let mut buffer = Vec::with_capacity(self.width * self.height * channels);
+
+        let mut ghost_px = palette.disabled_px;
+        for (channel, enabled) in ghost_px.iter_mut().zip(palette.enabled_px) {
+            *channel = (*channel as f32 + (enabled as f32 - *channel as f32) * GHOST_STRENGTH)
+                .round() as u8;
+        }
+
+        for (x, y, pixel_on) in self.iter() {
+            let color = if pixel_on {
+                palette.enabled_px
+            } else if ghost.is_some_and(|ghost| ghost.get(x, y)) {
+                ghost_px
+            } else {
+                palette.disabled_px
+            };
+
+            buffer.extend_from_slice(&color);
+            if let Some(alpha) = alpha {
+                buffer.push(alpha);
+            }
+        }
+
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod screen_tests {
+    use super::{Palette, Screen, HEIGHT, WIDTH};
+
+    #[test]
+    fn test_changes_since() {
+        let mut screen = Screen::new();
+        screen.set(3, 0, true);
+        screen.set(63, 5, true);
+        let before = screen.words().to_vec();
+        screen.set(3, 0, false);
+        screen.set(0, 1, true);
+        screen.set(63, 31, true);
+        assert_eq!(
+            screen.changes_since(&before).collect::<Vec<_>>(),
+            vec![(3, 0, false), (0, 1, true), (63, 31, true)]
+        );
+    }
+
+    #[test]
+    fn test_set_get() {
+        let mut screen = Screen::new();
+        screen.set(0, 0, true);
+        screen.set(63, 31, true);
+        assert!(screen.get(0, 0));
+        assert!(screen.get(63, 31));
+        assert!(!screen.get(1, 0));
+        assert_eq!(screen.row(0), &[0x1 << 63]);
+        assert_eq!(screen.row(31), &[0x1]);
+
+        screen.set(0, 0, false);
+        assert!(!screen.get(0, 0));
+        assert_eq!(screen.row(0), &[0]);
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let mut screen = Screen::with_resolution(128, 2);
+        screen.set(0, 0, true);
+        screen.set(127, 1, true);
+        let bytes = screen.to_bytes();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!((bytes[0], bytes[1], bytes[31]), (0x80, 0x00, 0x01));
+    }
+
+    #[test]
+    fn test_draw_sprite_row() {
+        let mut screen = Screen::new();
+        assert!(!screen.draw_sprite_row(0, 4, 0xF0, false));
+        assert!(screen.get(0, 4));
+        assert!(screen.get(3, 4));
+        assert!(!screen.get(4, 4));
+        assert!(screen.draw_sprite_row(0, 4, 0x80, false));
+        assert!(!screen.get(0, 4));
+        assert!(screen.get(1, 4));
+    }
+
+    #[test]
+    fn test_draw_sprite_row_edges() {
+        let mut screen = Screen::new();
+        screen.draw_sprite_row(62, 0, 0xE0, false);
+        assert_eq!(screen.row(0), &[0b11]);
+
+        screen.draw_sprite_row(62, 1, 0xE0, true);
+        assert_eq!(screen.row(1), &[0b11 | 0x1 << 63]);
+    }
+
+    // Pixel by pixel version of draw_sprite_row, to check against.
+    fn draw_by_pixel(
+        screen: &mut Screen,
+        x: usize,
+        y: usize,
+        sprite: u16,
+        bits: usize,
+        wrap: bool,
+    ) -> bool {
+        let mut collision = false;
+        for bit in 0..bits {
+            if sprite & (1 << (bits - 1 - bit)) == 0 {
+                continue;
+            }
+            let mut px = x + bit;
+            if px >= screen.width() {
+                if !wrap {
+                    break;
+                }
+                px %= screen.width();
+            }
+            collision |= screen.get(px, y);
+            screen.set(px, y, !screen.get(px, y));
+        }
+        collision
+    }
+
+    #[test]
+    fn test_draw_sprite_row_matches_pixels() {
+        for (width, height) in [(64, 32), (128, 64)] {
+            for wrap in [false, true] {
+                let mut by_words = Screen::with_resolution(width, height);
+                let mut by_pixels = Screen::with_resolution(width, height);
+                for x in 0..width {
+                    let y = x % (height - 1);
+                    let sprite = (x as u16).wrapping_mul(0x9E37) | 0x8001;
+                    assert_eq!(
+                        by_words.draw_sprite_row(x, y, sprite as u8, wrap),
+                        draw_by_pixel(&mut by_pixels, x, y, sprite & 0xFF, 8, wrap)
+                    );
+                    assert_eq!(
+                        by_words.draw_wide_sprite_row(x, y + 1, sprite, wrap),
+                        draw_by_pixel(&mut by_pixels, x, y + 1, sprite, 16, wrap)
+                    );
+                    // Again on top, for collisions
+                    assert_eq!(
+                        by_words.draw_wide_sprite_row(x, y, sprite, wrap),
+                        draw_by_pixel(&mut by_pixels, x, y, sprite, 16, wrap)
+                    );
+                }
+                assert_eq!(by_words.words(), by_pixels.words());
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_sprite_row() {
+        let mut screen = Screen::with_resolution(128, 64);
+        // Across the word boundary
+        assert!(!screen.draw_wide_sprite_row(56, 0, 0xFFFF, false));
+        assert_eq!(screen.row(0), &[0xFF, 0xFF << 56]);
+        assert!(screen.draw_wide_sprite_row(56, 0, 0x0080, false));
+        assert_eq!(screen.row(0), &[0xFF, 0x7F << 56]);
+
+        // Clipped and wrapped at the right edge
+        screen.draw_wide_sprite_row(120, 1, 0xFFFF, false);
+        assert_eq!(screen.row(1), &[0, 0xFF]);
+        screen.draw_wide_sprite_row(120, 2, 0xFFFF, true);
+        assert_eq!(screen.row(2), &[0xFF << 56, 0xFF]);
+    }
+
+    #[test]
+    fn test_resolutions() {
+        for (width, height) in [(64, 32), (64, 48), (64, 64), (128, 64)] {
+            let mut screen = Screen::with_resolution(width, height);
+            assert_eq!(screen.iter().count(), width * height);
+
+            screen.set(width - 1, height - 1, true);
+            assert!(screen.get(width - 1, height - 1));
+            assert_eq!(screen.words().len(), width / 64 * height);
+
+            screen.draw_sprite_row(60, 0, 0xFF, false);
+            let drawn = 8.min(width - 60);
+            assert_eq!(screen.iter().filter(|(_, _, on)| *on).count(), drawn + 1);
+
+            screen.clear();
+            assert!(screen.words().iter().all(|word| *word == 0));
+        }
+    }
+
+    #[test]
+    fn test_wide_rows() {
+        let mut screen = Screen::with_resolution(128, 64);
+        screen.draw_sprite_row(60, 3, 0xFF, false);
+        assert_eq!(screen.row(3), &[0xF, 0xF << 60]);
+
+        let rows: Vec<_> = screen.rows().collect();
+        assert_eq!(rows.len(), 64);
+        assert_eq!(rows[3], screen.row(3));
+
+        screen.set_resolution(64, 32);
+        assert_eq!(screen.rows().count(), 32);
+        assert_eq!(screen.width(), 64);
+        assert!(screen.words().iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    fn test_scroll_vertical() {
+        let mut screen = Screen::new();
+        screen.set(3, 0, true);
+        screen.set(3, 30, true);
+
+        screen.scroll_down(2);
+        assert!(screen.get(3, 2));
+        assert!(!screen.get(3, 0));
+        assert!(!screen.get(3, 30));
+        assert!(screen.words()[..2].iter().all(|word| *word == 0));
+
+        screen.scroll_up(2);
+        assert!(screen.get(3, 0));
+        assert_eq!(screen.iter().filter(|(_, _, on)| *on).count(), 1);
+
+        screen.scroll_down(100);
+        assert!(screen.words().iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    fn test_scroll_horizontal() {
+        let mut screen = Screen::new();
+        screen.set(0, 1, true);
+        screen.set(62, 1, true);
+
+        screen.scroll_right();
+        assert!(screen.get(4, 1));
+        assert!(!screen.get(0, 1));
+        assert_eq!(screen.iter().filter(|(_, _, on)| *on).count(), 1);
+
+        screen.scroll_left();
+        assert!(screen.get(0, 1));
+        screen.scroll_left();
+        assert!(screen.words().iter().all(|word| *word == 0));
+    }
+
+    #[test]
+    fn test_scroll_wide_rows() {
+        let mut screen = Screen::with_resolution(128, 64);
+        screen.set(62, 5, true);
+        screen.scroll_right();
+        assert!(screen.get(66, 5));
+        assert_eq!(screen.row(5), &[0, 0x1 << 61]);
+
+        screen.scroll_left();
+        assert!(screen.get(62, 5));
+        assert_eq!(screen.row(5), &[0b10, 0]);
+    }
+
+    #[test]
+    fn test_dirty() {
+        let mut screen = Screen::new();
+        assert!(screen.take_dirty());
+        assert!(!screen.take_dirty());
+
+        screen.draw_sprite_row(0, 0, 0x00, false);
+        assert!(!screen.is_dirty());
+        screen.draw_sprite_row(0, 0, 0x80, false);
+        assert!(screen.take_dirty());
+
+        screen.scroll_left();
+        assert!(screen.take_dirty());
+        screen.clear();
+        assert!(screen.take_dirty());
+        screen.mark_dirty();
+        assert!(screen.take_dirty());
+
+        screen.set_resolution(128, 64);
+        assert!(screen.take_dirty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut screen = Screen::new();
+        screen.set(5, 7, true);
+        assert_eq!(screen.iter().count(), WIDTH * HEIGHT);
+        let on: Vec<_> = screen.iter().filter(|(_, _, on)| *on).collect();
+        assert_eq!(on, vec![(5, 7, true)]);
+    }
+
+    #[test]
+    fn test_render_rgb() {
+        let palette = Palette {
+            enabled_px: [0xAA, 0xBB, 0xCC],
+            disabled_px: [0x11, 0x22, 0x33],
+        };
+        let mut screen = Screen::new();
+        screen.set(1, 0, true);
+
+        let rgb = screen.render_rgb(&palette);
+        assert_eq!(rgb.len(), WIDTH * HEIGHT * 3);
+        assert_eq!(&rgb[0..6], &[0x11, 0x22, 0x33, 0xAA, 0xBB, 0xCC]);
+
+        let rgba = screen.render_rgba(&palette);
+        assert_eq!(rgba.len(), WIDTH * HEIGHT * 4);
+        assert_eq!(
+            &rgba[0..8],
+            &[0x11, 0x22, 0x33, 0xFF, 0xAA, 0xBB, 0xCC, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_render_with_ghost() {
+        let palette = Palette {
+            enabled_px: [0xC8, 0xC8, 0xC8],
+            disabled_px: [0x00, 0x0A, 0xC8],
+        };
+        let mut ghost = Screen::new();
+        ghost.set(0, 0, true);
+        ghost.set(1, 0, true);
+        let mut screen = Screen::new();
+        screen.set(1, 0, true);
+        screen.set(2, 0, true);
+
+        let rgb = screen.render_rgb_with_ghost(&palette, &ghost);
+        assert_eq!(
+            &rgb[0..12],
+            &[0x3C, 0x43, 0xC8, 0xC8, 0xC8, 0xC8, 0xC8, 0xC8, 0xC8, 0x00, 0x0A, 0xC8]
+        );
+
+        let ghost = Screen::with_resolution(128, 64);
+        assert_eq!(
+            screen.render_rgb_with_ghost(&palette, &ghost),
+            screen.render_rgb(&palette)
+        );
     }
 }
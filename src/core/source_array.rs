@@ -0,0 +1,100 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+const BYTES_PER_LINE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    C,
+    Rust,
+}
+
+impl Language {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::C => "C",
+            Language::Rust => "Rust",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Language::C => "h",
+            Language::Rust => "rs",
+        }
+    }
+}
+
+// Turns a name into a valid identifier, upper case for Rust constants.
+fn identifier(name: &str, language: Language) -> String {
+    let mut ident: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    match language {
+        Language::C => ident,
+        Language::Rust => ident.to_ascii_uppercase(),
+    }
+}
+
+// Bytes as an array declaration to paste into another program, with a
+// comment line above it, e.g. for a 2 byte C array named "logo":
+//   // 8x2 sprite
+//   const unsigned char logo[2] = {
+//       0x3C, 0x42,
+//   };
+pub fn format(name: &str, comment: &str, bytes: &[u8], language: Language) -> String {
+    let ident = identifier(name, language);
+    let mut output = String::new();
+    if !comment.is_empty() {
+        output += &format!("// {}\n", comment);
+    }
+    output += &match language {
+        Language::C => format!("const unsigned char {}[{}] = {{\n", ident, bytes.len()),
+        Language::Rust => format!("pub const {}: [u8; {}] = [\n", ident, bytes.len()),
+    };
+    for line in bytes.chunks(BYTES_PER_LINE) {
+        let line: Vec<String> = line.iter().map(|byte| format!("0x{:02X},", byte)).collect();
+        output += &format!("    {}\n", line.join(" "));
+    }
+    output += match language {
+        Language::C => "};\n",
+        Language::Rust => "];\n",
+    };
+    output
+}
+
+#[cfg(test)]
+mod source_array_tests {
+    use super::{format, identifier, Language};
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(identifier("title screen", Language::C), "title_screen");
+        assert_eq!(identifier("title-screen", Language::Rust), "TITLE_SCREEN");
+        assert_eq!(identifier("8x8 font", Language::C), "_8x8_font");
+        assert_eq!(identifier("", Language::C), "_");
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format("logo", "8x2 sprite", &[0x3C, 0x42], Language::C),
+            "// 8x2 sprite\nconst unsigned char logo[2] = {\n    0x3C, 0x42,\n};\n"
+        );
+        let rust = format("font", "", &[0xF0; 20], Language::Rust);
+        assert!(rust.starts_with("pub const FONT: [u8; 20] = [\n"));
+        assert!(rust.ends_with("    0xF0, 0xF0, 0xF0, 0xF0,\n];\n"));
+        assert_eq!(rust.lines().count(), 4);
+    }
+}
@@ -0,0 +1,215 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+// Rows DXYN can draw from an 8 pixel wide sprite.
+pub const MAX_HEIGHT: usize = 15;
+// SCHIP's DXY0 draws 16x16 sprites, two bytes per row.
+pub const WIDE_SIZE: usize = 16;
+
+// A sprite as stored in memory, a byte (or two, when wide) per row.
+// The most significant bit of a row is its leftmost pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sprite {
+    wide: bool,
+    rows: Vec<u16>,
+}
+
+impl Sprite {
+    pub fn new(height: usize) -> Sprite {
+        Sprite {
+            wide: false,
+            rows: vec![0; height.clamp(1, MAX_HEIGHT)],
+        }
+    }
+
+    pub fn new_wide() -> Sprite {
+        Sprite {
+            wide: true,
+            rows: vec![0; WIDE_SIZE],
+        }
+    }
+
+    // Missing bytes are blank rows. Narrow sprites take as many rows
+    // as there are bytes, up to MAX_HEIGHT.
+    pub fn from_bytes(bytes: &[u8], wide: bool) -> Sprite {
+        if wide {
+            let mut sprite = Sprite::new_wide();
+            for (row, pair) in sprite.rows.iter_mut().zip(bytes.chunks(2)) {
+                *row = (pair[0] as u16) << 8 | pair.get(1).copied().unwrap_or(0) as u16;
+            }
+            sprite
+        } else {
+            let mut sprite = Sprite::new(bytes.len());
+            for (row, &byte) in sprite.rows.iter_mut().zip(bytes) {
+                *row = byte as u16;
+            }
+            sprite
+        }
+    }
+
+    pub fn is_wide(&self) -> bool {
+        self.wide
+    }
+
+    pub fn width(&self) -> usize {
+        if self.wide {
+            WIDE_SIZE
+        } else {
+            8
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    // Only narrow sprites can change height. Rows added are blank.
+    pub fn set_height(&mut self, height: usize) {
+        if !self.wide {
+            self.rows.resize(height.clamp(1, MAX_HEIGHT), 0);
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width() && self.rows.get(y).is_some_and(|row| row & self.bit(x) != 0)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let bit = self.bit(x);
+        if on {
+            self.rows[y] |= bit;
+        } else {
+            self.rows[y] &= !bit;
+        }
+    }
+
+    fn bit(&self, x: usize) -> u16 {
+        1 << (self.width() - 1 - x)
+    }
+
+    fn mask(&self) -> u16 {
+        if self.wide {
+            u16::MAX
+        } else {
+            0xFF
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.fill(0);
+    }
+
+    pub fn invert(&mut self) {
+        let mask = self.mask();
+        for row in self.rows.iter_mut() {
+            *row = !*row & mask;
+        }
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        let shift = 16 - self.width();
+        for row in self.rows.iter_mut() {
+            *row = row.reverse_bits() >> shift;
+        }
+    }
+
+    pub fn flip_vertical(&mut self) {
+        self.rows.reverse();
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.wide {
+            self.rows.iter().flat_map(|row| row.to_be_bytes()).collect()
+        } else {
+            self.rows.iter().map(|&row| row as u8).collect()
+        }
+    }
+
+    // Bytes as Octo writes them, e.g. "0x3C 0x42 0x3C". Wide sprites
+    // get a line per row.
+    pub fn to_hex(&self) -> String {
+        let bytes: Vec<String> = self
+            .to_bytes()
+            .iter()
+            .map(|byte| format!("0x{:02X}", byte))
+            .collect();
+        if self.wide {
+            bytes
+                .chunks(2)
+                .map(|row| row.join(" "))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            bytes.join(" ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod sprite_tests {
+    use super::{Sprite, MAX_HEIGHT};
+
+    #[test]
+    fn test_pixels() {
+        let mut sprite = Sprite::new(3);
+        sprite.set(0, 0, true);
+        sprite.set(7, 2, true);
+        sprite.set(8, 0, true); // Outside
+        assert!(sprite.get(0, 0) && sprite.get(7, 2) && !sprite.get(1, 0));
+        assert_eq!(sprite.to_bytes(), vec![0x80, 0x00, 0x01]);
+        sprite.set(0, 0, false);
+        assert_eq!(sprite.to_bytes(), vec![0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn test_transforms() {
+        let mut sprite = Sprite::from_bytes(&[0xF0, 0x01], false);
+        sprite.flip_horizontal();
+        assert_eq!(sprite.to_bytes(), vec![0x0F, 0x80]);
+        sprite.flip_vertical();
+        assert_eq!(sprite.to_bytes(), vec![0x80, 0x0F]);
+        sprite.invert();
+        assert_eq!(sprite.to_bytes(), vec![0x7F, 0xF0]);
+
+        let mut wide = Sprite::from_bytes(&[0x80, 0x01], true);
+        wide.flip_horizontal();
+        assert_eq!(&wide.to_bytes()[..2], &[0x80, 0x01]);
+        wide.invert();
+        assert_eq!(&wide.to_bytes()[..4], &[0x7F, 0xFE, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_size() {
+        let mut sprite = Sprite::from_bytes(&[0xFF; 20], false);
+        assert_eq!(sprite.height(), MAX_HEIGHT);
+        sprite.set_height(2);
+        assert_eq!(sprite.to_bytes(), vec![0xFF, 0xFF]);
+        sprite.set_height(0);
+        assert_eq!(sprite.height(), 1);
+
+        let mut wide = Sprite::from_bytes(&[0xAB, 0xCD, 0xEF], true);
+        wide.set_height(2);
+        assert_eq!((wide.width(), wide.height()), (16, 16));
+        assert_eq!(&wide.to_bytes()[..4], &[0xAB, 0xCD, 0xEF, 0x00]);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(
+            Sprite::from_bytes(&[0x3C, 0x42, 0x3C], false).to_hex(),
+            "0x3C 0x42 0x3C"
+        );
+        let hex = Sprite::from_bytes(&[0x12, 0x34], true).to_hex();
+        assert!(hex.starts_with("0x12 0x34\n0x00 0x00\n"));
+        assert_eq!(hex.lines().count(), 16);
+    }
+}
@@ -0,0 +1,26 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::beep::FRAME_RATE;
+
+// What a machine did since its ROM was loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub cycles: u64,       // Instructions run, or waited on
+    pub frames: u64,       // Frames ended
+    pub draws: u64,        // DXYN instructions
+    pub key_events: u64,   // Key presses and releases
+    pub sound_frames: u64, // Frames with the buzzer on
+}
+
+impl Stats {
+    pub fn sound_seconds(&self) -> f64 {
+        self.sound_frames as f64 / FRAME_RATE
+    }
+}
@@ -0,0 +1,180 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+// Symbol files list one label per line, in any of these forms:
+//   0x0202 draw_player
+//   0202 draw_player
+//   draw_player = 0x0202
+//   : draw_player 0x0202
+// Empty lines and lines starting with '#' or ';' are ignored.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SymbolError {
+    pub line: usize,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid symbol definition at line {}.", self.line)
+    }
+}
+
+#[derive(Default)]
+pub struct SymbolTable {
+    by_addr: BTreeMap<u16, String>,
+    by_name: HashMap<String, u16>,
+}
+
+// Labels further than this from an address aren't used to describe it.
+const MAX_LABEL_OFFSET: u16 = 0x100;
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn parse(source: &str) -> Result<SymbolTable, SymbolError> {
+        let mut table = SymbolTable::new();
+
+        for (idx, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            let error = SymbolError { line: idx + 1 };
+            let tokens: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == '=')
+                .filter(|token| !token.is_empty() && *token != ":")
+                .collect();
+            if tokens.len() != 2 {
+                return Err(error);
+            }
+
+            let (name, addr) = if has_hex_prefix(tokens[1]) {
+                (tokens[0], tokens[1])
+            } else {
+                (tokens[1], tokens[0])
+            };
+            let addr = parse_address(addr).ok_or(error)?;
+            table.insert(name, addr);
+        }
+
+        Ok(table)
+    }
+
+    pub fn insert(&mut self, name: &str, addr: u16) {
+        self.by_addr.insert(addr, name.to_string());
+        self.by_name.insert(name.to_string(), addr);
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.by_addr.get(&addr).map(String::as_str)
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    // Accepts either a label or a hex address.
+    pub fn resolve(&self, input: &str) -> Option<u16> {
+        let input = input.trim();
+        self.address(input).or_else(|| parse_address(input))
+    }
+
+    // Formats addr as its label, the closest previous label plus
+    // an offset, or as plain hex when no label is near.
+    pub fn describe(&self, addr: u16) -> String {
+        match self.by_addr.range(..=addr).next_back() {
+            Some((&label_addr, name)) if label_addr == addr => name.clone(),
+            Some((&label_addr, name)) if addr - label_addr <= MAX_LABEL_OFFSET => {
+                format!("{}+{}", name, addr - label_addr)
+            }
+            _ => format!("{:#06X}", addr),
+        }
+    }
+}
+
+fn has_hex_prefix(token: &str) -> bool {
+    token.starts_with("0x") || token.starts_with("0X")
+}
+
+fn parse_address(token: &str) -> Option<u16> {
+    let digits = if has_hex_prefix(token) {
+        &token[2..]
+    } else {
+        token
+    };
+    u16::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod symbols_tests {
+    use super::{SymbolError, SymbolTable};
+
+    #[test]
+    fn test_parse() {
+        let source = "
+            # Generated symbols
+            0x0200 main
+            0202 loop
+            draw_player = 0x0210
+            : sprite_data 0x0300
+            ; trailing comment
+        ";
+        let table = SymbolTable::parse(source).unwrap();
+        assert_eq!(table.len(), 4);
+        assert_eq!(table.address("main"), Some(0x0200));
+        assert_eq!(table.address("loop"), Some(0x0202));
+        assert_eq!(table.address("draw_player"), Some(0x0210));
+        assert_eq!(table.label(0x0300), Some("sprite_data"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            SymbolTable::parse("0x200 main\nbroken").err(),
+            Some(SymbolError { line: 2 })
+        );
+        assert_eq!(
+            SymbolTable::parse("main = nowhere").err(),
+            Some(SymbolError { line: 1 })
+        );
+    }
+
+    #[test]
+    fn test_describe_and_resolve() {
+        let mut table = SymbolTable::new();
+        table.insert("draw_player", 0x0210);
+
+        assert_eq!(table.describe(0x0210), "draw_player");
+        assert_eq!(table.describe(0x0214), "draw_player+4");
+        assert_eq!(table.describe(0x0200), "0x0200");
+        assert_eq!(table.describe(0x0400), "0x0400");
+
+        assert_eq!(table.resolve("draw_player"), Some(0x0210));
+        assert_eq!(table.resolve("0x2A0"), Some(0x02A0));
+        assert_eq!(table.resolve("nowhere"), None);
+    }
+}
@@ -0,0 +1,325 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::Cpu,
+    expr::{Expr, ExprError},
+    json::Json,
+    key_macro::{KeyMacro, MacroPlayer},
+    memory::LoadError,
+    toml::{self, TomlError},
+};
+
+// ROM tests, a tests.toml shipped next to the ROM:
+//   frames = 600                 # Default for every test
+//
+//   [[test]]
+//   name = "score starts at 0"
+//   frames = 120                 # Frames to run
+//   seed = 1                     # Random number generator seed (default: 0)
+//   input = "0+5 4-5"            # Key presses, as in key macros
+//   expect = ["mem[0x3F0] == 0", "v3 >= 10"]  # Must hold at the end
+//   screen_hash = "0123456789abcdef"          # As in --json-result
+//   screen = ["..##..", ".#..#."]  # Top left rows, # for lit pixels
+// Every test starts from a freshly loaded ROM.
+
+const DEFAULT_FRAMES: u64 = 600;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScriptError {
+    Toml(TomlError),
+    NoTests,
+    InvalidValue { test: String, key: &'static str },
+    Expr { test: String, err: ExprError },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Toml(err) => write!(f, "{}", err),
+            ScriptError::NoTests => write!(f, "No [[test]] found."),
+            ScriptError::InvalidValue { test, key } => {
+                write!(f, "Invalid '{}' in test '{}'.", key, test)
+            }
+            ScriptError::Expr { test, err } => write!(f, "In test '{}': {}", test, err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub frames: u64,
+    pub seed: u64,
+    pub input: KeyMacro,
+    pub expect: Vec<(String, Expr)>,
+    pub screen_hash: Option<u64>,
+    pub screen: Vec<String>,
+}
+
+pub fn parse(text: &str) -> Result<Vec<TestCase>, ScriptError> {
+    let toml = toml::parse(text).map_err(ScriptError::Toml)?;
+    let default_frames = match toml.get("frames") {
+        Some(frames) => frames.as_u64().ok_or(ScriptError::InvalidValue {
+            test: String::new(),
+            key: "frames",
+        })?,
+        None => DEFAULT_FRAMES,
+    };
+    let tests = toml
+        .get("test")
+        .and_then(Json::as_array)
+        .filter(|tests| !tests.is_empty())
+        .ok_or(ScriptError::NoTests)?;
+    tests
+        .iter()
+        .enumerate()
+        .map(|(idx, test)| parse_test(test, idx, default_frames))
+        .collect()
+}
+
+fn parse_test(test: &Json, idx: usize, default_frames: u64) -> Result<TestCase, ScriptError> {
+    let name = match test.get("name") {
+        Some(name) => name.as_str().map(str::to_string),
+        None => Some(format!("#{}", idx + 1)),
+    }
+    .ok_or(ScriptError::InvalidValue {
+        test: format!("#{}", idx + 1),
+        key: "name",
+    })?;
+    let invalid = |key| ScriptError::InvalidValue {
+        test: name.clone(),
+        key,
+    };
+    let number = |key, default| match test.get(key) {
+        Some(value) => value.as_u64().ok_or(invalid(key)),
+        None => Ok(default),
+    };
+    let strings = |key| -> Result<Vec<String>, ScriptError> {
+        match test.get(key) {
+            Some(values) => values
+                .as_array()
+                .ok_or(invalid(key))?
+                .iter()
+                .map(|value| value.as_str().map(str::to_string).ok_or(invalid(key)))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    };
+
+    let frames = number("frames", default_frames)?;
+    let seed = number("seed", 0)?;
+    let input = match test.get("input") {
+        Some(input) => input
+            .as_str()
+            .and_then(KeyMacro::parse)
+            .ok_or(invalid("input"))?,
+        None => KeyMacro { events: Vec::new() },
+    };
+    let expect = strings("expect")?
+        .into_iter()
+        .map(|text| match Expr::parse(&text) {
+            Ok(expr) => Ok((text, expr)),
+            Err(err) => Err(ScriptError::Expr {
+                test: name.clone(),
+                err,
+            }),
+        })
+        .collect::<Result<_, _>>()?;
+    let screen_hash = match test.get("screen_hash") {
+        Some(hash) => Some(
+            hash.as_str()
+                .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+                .ok_or(invalid("screen_hash"))?,
+        ),
+        None => None,
+    };
+    let screen = strings("screen")?;
+    if screen
+        .iter()
+        .any(|row| row.chars().any(|c| c != '#' && c != '.'))
+    {
+        return Err(invalid("screen"));
+    }
+
+    Ok(TestCase {
+        name,
+        frames,
+        seed,
+        input,
+        expect,
+        screen_hash,
+        screen,
+    })
+}
+
+// Runs every test from a freshly loaded ROM, returning the failures
+// of each.
+pub fn run_all(
+    tests: &[TestCase],
+    rom: &[u8],
+    program_begin: u16,
+) -> Result<Vec<Vec<String>>, LoadError> {
+    tests
+        .iter()
+        .map(|test| {
+            let mut cpu = Cpu::new();
+            cpu.disable_beep();
+            cpu.load_rom(rom, program_begin)?;
+            Ok(test.run(&mut cpu))
+        })
+        .collect()
+}
+
+impl TestCase {
+    // Runs the test on a cpu with the ROM just loaded, returning why
+    // it failed, if it did.
+    pub fn run(&self, cpu: &mut Cpu) -> Vec<String> {
+        cpu.seed_rng(self.seed);
+        let mut player = MacroPlayer::new(self.input.clone(), cpu.frame());
+        for _ in 0..self.frames {
+            player.apply(cpu);
+            cpu.tick();
+            if let Some(fault) = cpu.last_fault() {
+                return vec![format!(
+                    "{} at {:#05X}, frame {}",
+                    fault,
+                    cpu.registers.pc,
+                    cpu.frame()
+                )];
+            }
+        }
+
+        let mut failures = Vec::new();
+        for (text, expr) in &self.expect {
+            if !expr.is_true(cpu) {
+                failures.push(format!("expected {}", text));
+            }
+        }
+        if let Some(expected) = self.screen_hash {
+            let hash = cpu.screen.hash();
+            if hash != expected {
+                failures.push(format!(
+                    "screen hash is {:016x}, expected {:016x}",
+                    hash, expected
+                ));
+            }
+        }
+        for (y, expected) in self.screen.iter().enumerate() {
+            let row: String = (0..expected.len().min(cpu.screen.width()))
+                .map(|x| {
+                    if y < cpu.screen.height() && cpu.screen.get(x, y) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            if row != *expected {
+                failures.push(format!(
+                    "screen row {} is \"{}\", expected \"{}\"",
+                    y, row, expected
+                ));
+            }
+        }
+        failures
+    }
+}
+
+#[cfg(test)]
+mod test_script_tests {
+    use super::{parse, run_all, ScriptError};
+    use crate::core::cpu::Cpu;
+
+    // Waits for key 5, then stores it at 0x300 and draws the "0" glyph:
+    // LD V0, K - LD I, 0x300 - LD [I], V0 - LD F, V1 - DRW V2, V2, 5 -
+    // JP 0x20A
+    const ROM: [u8; 12] = [
+        0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55, 0xF1, 0x29, 0xD2, 0x25, 0x12, 0x0A,
+    ];
+
+    fn run(script: &str) -> Vec<Vec<String>> {
+        run_all(&parse(script).unwrap(), &ROM, 0x0200).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let tests = parse(
+            r#"
+            frames = 30
+            [[test]]
+            name = "press"
+            input = "0+5 2-5"
+            expect = ["mem[0x300] == 5"]
+            [[test]]
+            frames = 5
+            seed = 7
+            screen_hash = "00000000000000ff"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(tests.len(), 2);
+        assert_eq!((tests[0].name.as_str(), tests[0].frames), ("press", 30));
+        assert_eq!(tests[0].input.events.len(), 2);
+        assert_eq!(tests[0].expect[0].0, "mem[0x300] == 5");
+        assert_eq!((tests[1].name.as_str(), tests[1].frames), ("#2", 5));
+        assert_eq!((tests[1].seed, tests[1].screen_hash), (7, Some(0xFF)));
+
+        assert_eq!(parse("frames = 1"), Err(ScriptError::NoTests));
+        assert_eq!(
+            parse("[[test]]\ninput = \"5\""),
+            Err(ScriptError::InvalidValue {
+                test: "#1".to_string(),
+                key: "input"
+            })
+        );
+        assert!(matches!(
+            parse("[[test]]\nexpect = [\"v0 ==\"]"),
+            Err(ScriptError::Expr { .. })
+        ));
+        assert!(matches!(parse("[[test"), Err(ScriptError::Toml(_))));
+    }
+
+    #[test]
+    fn test_run() {
+        let results = run(r#"
+            [[test]]
+            frames = 10
+            input = "0+5 2-5"
+            expect = ["mem[0x300] == 5", "v0 == 5"]
+            screen = ['####', '#..#']
+
+            [[test]]
+            frames = 10
+            expect = ["mem[0x300] == 5"]
+            screen = ['####']
+            "#);
+        assert_eq!(results[0], Vec::<String>::new());
+        assert_eq!(
+            results[1],
+            vec![
+                "expected mem[0x300] == 5",
+                "screen row 0 is \"....\", expected \"####\""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fault() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x00, 0xEE], 0x0200).unwrap(); // RET
+        let test = &parse("[[test]]\nframes = 2").unwrap()[0];
+        assert_eq!(
+            test.run(&mut cpu),
+            vec!["Stack underflow at 0x200, frame 1"]
+        );
+    }
+}
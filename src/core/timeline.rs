@@ -0,0 +1,181 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    KeyPress(u8),
+    SoundStart,
+    Clear,
+    SaveState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineEvent {
+    pub frame: u64,
+    pub kind: EventKind,
+}
+
+// Notable events by emulated frame, oldest first. Frames run again
+// after stepping back or seeking don't add their events twice.
+pub struct Timeline {
+    events: VecDeque<TimelineEvent>,
+    capacity: usize,
+    keys: u16,       // Keys down during the last frame
+    buzzer_on: bool, // Buzzer state at the end of the last frame
+}
+
+impl Timeline {
+    pub fn new(capacity: usize) -> Timeline {
+        Timeline {
+            events: VecDeque::new(),
+            capacity,
+            keys: 0,
+            buzzer_on: false,
+        }
+    }
+
+    pub fn push(&mut self, frame: u64, kind: EventKind) {
+        if self.capacity == 0 {
+            return;
+        }
+        let event = TimelineEvent { frame, kind };
+        let at = self.events.partition_point(|other| other.frame <= frame);
+        let same_frame = self.events.range(..at).rev();
+        if same_frame
+            .take_while(|other| other.frame == frame)
+            .any(|other| other.kind == kind)
+        {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            if at == 0 {
+                return;
+            }
+            self.events.pop_front();
+            self.events.insert(at - 1, event);
+        } else {
+            self.events.insert(at, event);
+        }
+    }
+
+    // Records the keys that went down since the last frame.
+    pub fn update_keys(&mut self, frame: u64, keys: u16) {
+        let pressed = keys & !self.keys;
+        for key in 0..16 {
+            if pressed & (1 << key) != 0 {
+                self.push(frame, EventKind::KeyPress(key));
+            }
+        }
+        self.keys = keys;
+    }
+
+    pub fn update_buzzer(&mut self, frame: u64, buzzer_on: bool) {
+        if buzzer_on && !self.buzzer_on {
+            self.push(frame, EventKind::SoundStart);
+        }
+        self.buzzer_on = buzzer_on;
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.keys = 0;
+        self.buzzer_on = false;
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    // Events from the start frame up to (not including) the end one.
+    pub fn range(&self, start: u64, end: u64) -> impl Iterator<Item = &TimelineEvent> {
+        let first = self.events.partition_point(|event| event.frame < start);
+        self.events
+            .range(first..)
+            .take_while(move |event| event.frame < end)
+    }
+
+    #[allow(dead_code)]
+    pub fn last_frame(&self) -> Option<u64> {
+        self.events.back().map(|event| event.frame)
+    }
+}
+
+#[cfg(test)]
+mod timeline_tests {
+    use super::{EventKind, Timeline};
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_push() {
+        let mut timeline = Timeline::new(3);
+        timeline.push(5, EventKind::Clear);
+        timeline.push(2, EventKind::Clear);
+        timeline.push(5, EventKind::Clear); // Already there
+        timeline.push(5, EventKind::SaveState);
+        let frames: Vec<u64> = timeline.range(0, 10).map(|event| event.frame).collect();
+        assert_eq!(frames, vec![2, 5, 5]);
+
+        // Full, the oldest goes
+        timeline.push(7, EventKind::SoundStart);
+        assert_eq!(timeline.range(0, 10).next().unwrap().frame, 5);
+        assert_eq!(timeline.range(6, 7).count(), 0);
+        assert_eq!(timeline.last_frame(), Some(7));
+    }
+
+    #[test]
+    fn test_keys_and_buzzer() {
+        let mut timeline = Timeline::new(16);
+        timeline.update_keys(1, 0b0011);
+        timeline.update_keys(2, 0b0011); // Still held
+        timeline.update_keys(3, 0b0100);
+        timeline.update_buzzer(3, true);
+        timeline.update_buzzer(4, true);
+        let events: Vec<_> = timeline.range(0, 10).map(|event| event.kind).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventKind::KeyPress(0),
+                EventKind::KeyPress(1),
+                EventKind::KeyPress(2),
+                EventKind::SoundStart
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cpu_events() {
+        // CLS - LD V0, 2 - LD ST, V0 - JP 0x206
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x00, 0xE0, 0x60, 0x02, 0xF0, 0x18, 0x12, 0x06], 0x0200)
+            .unwrap();
+        cpu.set_timeline_capacity(64);
+        cpu.keypad.set_key(0xA, true);
+        cpu.tick();
+        cpu.mark_save_state();
+        let timeline = cpu.timeline().unwrap();
+        let events: Vec<_> = timeline.range(0, 2).map(|event| event.kind).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventKind::KeyPress(0xA),
+                EventKind::Clear,
+                EventKind::SoundStart,
+                EventKind::SaveState
+            ]
+        );
+    }
+}
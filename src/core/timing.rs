@@ -0,0 +1,99 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::instruction::Opcode;
+
+// How long each instruction takes to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingModel {
+    // Every instruction costs one cycle (the classic "N instructions per frame").
+    Uniform,
+    // Approximate COSMAC VIP interpreter timings, in microseconds.
+    CosmacVip,
+}
+
+pub const TIMING_MODELS: [TimingModel; 2] = [TimingModel::Uniform, TimingModel::CosmacVip];
+
+impl TimingModel {
+    // Cycles available in a single 60Hz frame.
+    pub fn cycles_per_frame(&self, ticks_per_frame: u32) -> u32 {
+        match self {
+            TimingModel::Uniform => ticks_per_frame,
+            TimingModel::CosmacVip => 1_000_000 / 60,
+        }
+    }
+}
+
+impl Opcode {
+    pub fn cycles(&self, model: &TimingModel) -> u32 {
+        match model {
+            TimingModel::Uniform => 1,
+            TimingModel::CosmacVip => self.vip_cycles(),
+        }
+    }
+
+    fn vip_cycles(&self) -> u32 {
+        match self {
+            Opcode::Cls => 109,
+            Opcode::Ret | Opcode::Sys | Opcode::Jump | Opcode::Call | Opcode::JumpV0 => 105,
+            Opcode::SkipEqByte | Opcode::SkipNeByte | Opcode::LoadI => 55,
+            Opcode::SkipEqReg | Opcode::SkipNeReg => 73,
+            Opcode::SkipKeyPressed | Opcode::SkipKeyReleased => 73,
+            Opcode::LoadByte => 27,
+            Opcode::AddByte => 45,
+            Opcode::Move
+            | Opcode::Or
+            | Opcode::And
+            | Opcode::Xor
+            | Opcode::Add
+            | Opcode::Sub
+            | Opcode::ShiftRight
+            | Opcode::SubN
+            | Opcode::ShiftLeft => 200,
+            Opcode::Random => 164,
+            Opcode::Draw => 22734,
+            Opcode::LoadDelay | Opcode::SetDelay | Opcode::SetSound => 45,
+            // Cost of a single check, the wait itself depends on the user
+            Opcode::WaitKey => 45,
+            Opcode::AddI => 86,
+            Opcode::LoadFont => 91,
+            Opcode::Bcd => 927,
+            Opcode::StoreRegisters | Opcode::LoadRegisters => 605,
+            Opcode::Unknown => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod timing_tests {
+    use super::TimingModel;
+    use crate::core::instruction::{Instruction, Opcode};
+
+    #[test]
+    fn test_uniform() {
+        for opcode in [Opcode::Cls, Opcode::Draw, Opcode::Bcd] {
+            assert_eq!(opcode.cycles(&TimingModel::Uniform), 1);
+        }
+        assert_eq!(TimingModel::Uniform.cycles_per_frame(10), 10);
+    }
+
+    #[test]
+    fn test_cosmac_vip() {
+        let model = TimingModel::CosmacVip;
+        let draw = Instruction::new((0xD, 0, 1, 5)).opcode();
+        let load = Instruction::new((6, 0, 1, 2)).opcode();
+        assert_eq!(draw.cycles(&model), 22734);
+        assert_eq!(load.cycles(&model), 27);
+
+        // A frame fits hundreds of register loads, but not a single draw
+        let frame = model.cycles_per_frame(10);
+        assert!(frame / load.cycles(&model) > 100);
+        assert!(frame < draw.cycles(&model));
+    }
+}
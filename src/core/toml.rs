@@ -0,0 +1,367 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::json::Json;
+
+// Just enough TOML for files written by hand next to ROMs: key/value
+// pairs, [tables] and [[arrays of tables]] one level deep, strings,
+// integers (decimal or 0x hex), booleans, arrays and inline tables.
+// Documents are read into Json values, tables as objects.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TomlError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid TOML at line {}: {}.", self.line, self.message)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Json, TomlError> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        at: 0,
+        line: 1,
+    };
+    let mut root: Vec<(String, Json)> = Vec::new();
+    // Key of the table taking the pairs, none for the root
+    let mut table: Option<String> = None;
+
+    loop {
+        parser.skip_blank(true);
+        if parser.at == parser.bytes.len() {
+            return Ok(Json::Object(root));
+        }
+
+        if parser.eat("[[") {
+            let key = parser.key()?;
+            parser.expect("]]")?;
+            match root.iter_mut().find(|(name, _)| *name == key) {
+                Some((_, Json::Array(tables))) => tables.push(Json::Object(Vec::new())),
+                Some(_) => return Err(parser.error("key defined twice")),
+                None => root.push((key.clone(), Json::Array(vec![Json::Object(Vec::new())]))),
+            }
+            table = Some(key);
+        } else if parser.eat("[") {
+            let key = parser.key()?;
+            parser.expect("]")?;
+            if root.iter().any(|(name, _)| *name == key) {
+                return Err(parser.error("key defined twice"));
+            }
+            root.push((key.clone(), Json::Object(Vec::new())));
+            table = Some(key);
+        } else {
+            let key = parser.key()?;
+            parser.expect("=")?;
+            let value = parser.value()?;
+            let entries = match &table {
+                None => &mut root,
+                Some(table) => table_entries(&mut root, table),
+            };
+            if entries.iter().any(|(name, _)| *name == key) {
+                return Err(parser.error("key defined twice"));
+            }
+            entries.push((key, value));
+        }
+
+        // Anything else on the line must be a comment
+        parser.skip_blank(false);
+        match parser.bytes.get(parser.at) {
+            None | Some(b'\n') => {}
+            Some(_) => return Err(parser.error("expected the end of the line")),
+        }
+    }
+}
+
+// The pairs of a table, or of the last one in an array of tables.
+fn table_entries<'a>(root: &'a mut [(String, Json)], key: &str) -> &'a mut Vec<(String, Json)> {
+    let value = root
+        .iter_mut()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+        .unwrap();
+    let value = match value {
+        Json::Array(tables) => tables.last_mut().unwrap(),
+        value => value,
+    };
+    match value {
+        Json::Object(entries) => entries,
+        _ => unreachable!(),
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> TomlError {
+        TomlError {
+            line: self.line,
+            message,
+        }
+    }
+
+    // Skips spaces and comments, and line breaks if asked to.
+    fn skip_blank(&mut self, newlines: bool) {
+        while let Some(&byte) = self.bytes.get(self.at) {
+            match byte {
+                b' ' | b'\t' | b'\r' => self.at += 1,
+                b'\n' if newlines => {
+                    self.at += 1;
+                    self.line += 1;
+                }
+                b'#' => {
+                    while self.bytes.get(self.at).is_some_and(|&byte| byte != b'\n') {
+                        self.at += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        self.skip_blank(false);
+        if self.bytes[self.at..].starts_with(text.as_bytes()) {
+            self.at += text.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), TomlError> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(self.error(match text {
+                "=" => "expected '='",
+                "]" | "]]" => "expected ']'",
+                _ => "unexpected character",
+            }))
+        }
+    }
+
+    fn key(&mut self) -> Result<String, TomlError> {
+        self.skip_blank(false);
+        if self.bytes.get(self.at) == Some(&b'"') {
+            return self.string();
+        }
+        let start = self.at;
+        while self
+            .bytes
+            .get(self.at)
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-'))
+        {
+            self.at += 1;
+        }
+        if start == self.at {
+            return Err(self.error("expected a key"));
+        }
+        Ok(std::str::from_utf8(&self.bytes[start..self.at])
+            .unwrap()
+            .to_string())
+    }
+
+    fn value(&mut self) -> Result<Json, TomlError> {
+        self.skip_blank(false);
+        match self.bytes.get(self.at) {
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'\'') => self.literal_string().map(Json::String),
+            Some(b'[') => self.array(),
+            Some(b'{') => self.inline_table(),
+            Some(b't') if self.eat("true") => Ok(Json::Bool(true)),
+            Some(b'f') if self.eat("false") => Ok(Json::Bool(false)),
+            Some(b'0'..=b'9' | b'+' | b'-') => self.integer(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, TomlError> {
+        self.at += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.bytes.get(self.at) {
+                None | Some(b'\n') => return Err(self.error("unterminated string")),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    self.at += 1;
+                    bytes.push(match self.bytes.get(self.at) {
+                        Some(b'n') => b'\n',
+                        Some(b't') => b'\t',
+                        Some(b'"') => b'"',
+                        Some(b'\\') => b'\\',
+                        _ => return Err(self.error("invalid escape")),
+                    });
+                }
+                Some(&byte) => bytes.push(byte),
+            }
+            self.at += 1;
+        }
+        self.at += 1;
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    // 'Literal' strings have no escapes.
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        self.at += 1;
+        let start = self.at;
+        while self.bytes.get(self.at) != Some(&b'\'') {
+            match self.bytes.get(self.at) {
+                None | Some(b'\n') => return Err(self.error("unterminated string")),
+                _ => self.at += 1,
+            }
+        }
+        self.at += 1;
+        std::str::from_utf8(&self.bytes[start..self.at - 1])
+            .map(str::to_string)
+            .map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    // Integers may have underscores between digits, e.g. 1_000.
+    fn integer(&mut self) -> Result<Json, TomlError> {
+        let start = self.at;
+        while self.bytes.get(self.at).is_some_and(|byte| {
+            byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'+' | b'-' | b'.')
+        }) {
+            self.at += 1;
+        }
+        let text: String = std::str::from_utf8(&self.bytes[start..self.at])
+            .unwrap()
+            .chars()
+            .filter(|&c| c != '_')
+            .collect();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+        let value = match digits.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => digits.parse(),
+        }
+        .map_err(|_| self.error("invalid number"))?;
+        Ok(Json::Number(if negative { -value } else { value } as f64))
+    }
+
+    // Arrays can span lines, and end with a comma.
+    fn array(&mut self) -> Result<Json, TomlError> {
+        self.at += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank(true);
+            if self.eat("]") {
+                return Ok(Json::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank(true);
+            if !self.eat(",") {
+                self.skip_blank(true);
+                self.expect("]")?;
+                return Ok(Json::Array(values));
+            }
+        }
+    }
+
+    // Inline tables stay on a single line.
+    fn inline_table(&mut self) -> Result<Json, TomlError> {
+        self.at += 1;
+        let mut entries: Vec<(String, Json)> = Vec::new();
+        if self.eat("}") {
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            let key = self.key()?;
+            self.expect("=")?;
+            if entries.iter().any(|(name, _)| *name == key) {
+                return Err(self.error("key defined twice"));
+            }
+            let value = self.value()?;
+            entries.push((key, value));
+            if self.eat("}") {
+                return Ok(Json::Object(entries));
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected '}'"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod toml_tests {
+    use super::{parse, TomlError};
+    use crate::core::json::Json;
+
+    #[test]
+    fn test_parse() {
+        let toml = parse(
+            r#"
+            # ROM tests
+            frames = 1_200
+            title = "Pong \"2\""
+
+            [options]
+            quirks = 0x1F
+            wrap = false
+
+            [[test]]
+            name = 'start'
+            keys = { "5" = true, a = -1 }
+
+            [[test]]
+            expect = [
+                "v0 == 1", # First
+                "mem[0x300] == 2",
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(toml.get("frames").unwrap().as_u64(), Some(1200));
+        assert_eq!(toml.get("title").unwrap().as_str(), Some("Pong \"2\""));
+        let options = toml.get("options").unwrap();
+        assert_eq!(options.get("quirks").unwrap().as_u64(), Some(0x1F));
+        assert_eq!(options.get("wrap").unwrap().as_bool(), Some(false));
+
+        let tests = toml.get("test").unwrap().as_array().unwrap();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[0].get("name").unwrap().as_str(), Some("start"));
+        let keys = tests[0].get("keys").unwrap();
+        assert_eq!(keys.get("5"), Some(&Json::Bool(true)));
+        assert_eq!(keys.get("a"), Some(&Json::Number(-1.0)));
+        assert_eq!(
+            tests[1].get("expect").unwrap().as_array().unwrap(),
+            &[
+                Json::String("v0 == 1".to_string()),
+                Json::String("mem[0x300] == 2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |line, message| Err(TomlError { line, message });
+        assert_eq!(parse("a = 1\na = 2"), error(2, "key defined twice"));
+        assert_eq!(
+            parse("a = 1 b = 2"),
+            error(1, "expected the end of the line")
+        );
+        assert_eq!(parse("\n\nname = \"open"), error(3, "unterminated string"));
+        assert_eq!(parse("a 1"), error(1, "expected '='"));
+        assert_eq!(parse("a = 1.5"), error(1, "invalid number"));
+        assert_eq!(parse("[test]\n[test]"), error(2, "key defined twice"));
+    }
+}
@@ -0,0 +1,140 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::io::{self, Write};
+
+use super::{
+    cpu::Cpu,
+    registers::{DELAY_TIMER, SOUND_TIMER},
+};
+
+// Registers as they were before an instruction ran.
+#[derive(Clone, PartialEq, Eq)]
+struct State {
+    pc: u16,
+    opcode: u16,
+    v: [u8; 16],
+    i: u16,
+    sp: u8,
+    dt: u8,
+    st: u8,
+}
+
+impl State {
+    fn of(cpu: &Cpu) -> State {
+        State {
+            pc: cpu.registers.pc,
+            opcode: cpu.memory.read_u16(cpu.registers.pc),
+            v: cpu.registers.v,
+            i: cpu.registers.i,
+            sp: cpu.registers.sp,
+            dt: cpu.registers.timers[DELAY_TIMER].read(),
+            st: cpu.registers.timers[SOUND_TIMER].read(),
+        }
+    }
+}
+
+// Writes every executed instruction as a JSON line, e.g.
+// {"cycle":3,"pc":516,"opcode":28673,"diff":{"v0":1}}
+// with the registers it changed (besides the PC) in diff.
+pub struct Tracer<W: Write> {
+    output: W,
+    cycle: u64,
+    last: State,
+}
+
+impl<W: Write> Tracer<W> {
+    // Starts from the CPU's current state.
+    pub fn new(output: W, cpu: &Cpu) -> Tracer<W> {
+        Tracer {
+            output,
+            cycle: 0,
+            last: State::of(cpu),
+        }
+    }
+
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    // Call after every executed instruction.
+    pub fn record(&mut self, cpu: &Cpu) -> io::Result<()> {
+        let state = State::of(cpu);
+        writeln!(self.output, "{}", line(self.cycle, &self.last, &state))?;
+        self.cycle += 1;
+        self.last = state;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+fn line(cycle: u64, before: &State, after: &State) -> String {
+    let mut diff = Vec::new();
+    for (idx, (old, new)) in before.v.iter().zip(after.v.iter()).enumerate() {
+        if old != new {
+            diff.push(format!("\"v{:x}\":{}", idx, new));
+        }
+    }
+    let others = [
+        ("i", before.i, after.i),
+        ("sp", before.sp as u16, after.sp as u16),
+        ("dt", before.dt as u16, after.dt as u16),
+        ("st", before.st as u16, after.st as u16),
+    ];
+    for (name, old, new) in others {
+        if old != new {
+            diff.push(format!("\"{}\":{}", name, new));
+        }
+    }
+
+    format!(
+        "{{\"cycle\":{},\"pc\":{},\"opcode\":{},\"diff\":{{{}}}}}",
+        cycle,
+        before.pc,
+        before.opcode,
+        diff.join(",")
+    )
+}
+
+#[cfg(test)]
+mod trace_tests {
+    use super::Tracer;
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_trace() {
+        let mut cpu = Cpu::new();
+        cpu.ticks_per_frame = 3;
+        // LD V0, 5 - LD I, 0x300 - JP 0x204
+        cpu.load_rom(&[0x60, 0x05, 0xA3, 0x00, 0x12, 0x04], 0x0200)
+            .unwrap();
+
+        let mut output = Vec::new();
+        let mut tracer = Tracer::new(&mut output, &cpu);
+        cpu.tick_with(|cpu| tracer.record(cpu).is_ok());
+        assert_eq!(tracer.cycle(), 3);
+
+        let lines: Vec<String> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"cycle":0,"pc":512,"opcode":24581,"diff":{"v0":5}}"#,
+                r#"{"cycle":1,"pc":514,"opcode":41728,"diff":{"i":768}}"#,
+                r#"{"cycle":2,"pc":516,"opcode":4612,"diff":{}}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::fmt;
+
+use super::{
+    cpu::Cpu,
+    expr::{Expr, ExprError},
+};
+
+// Achievement-like triggers, read from a "rom.triggers" file next to
+// the ROM. One per line, as "condition => message", e.g.
+//   # Brix
+//   mem[0x3F0] >= 100 => Reached a score of 100!
+// Each trigger fires once per run, the first frame its condition holds.
+pub struct Trigger {
+    pub condition: Expr,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TriggerError {
+    MissingMessage { line: usize },
+    Condition { line: usize, err: ExprError },
+}
+
+impl fmt::Display for TriggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriggerError::MissingMessage { line } => {
+                write!(f, "Line {}: expected \"condition => message\".", line)
+            }
+            TriggerError::Condition { line, err } => write!(f, "Line {}: {}", line, err),
+        }
+    }
+}
+
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    fired: Vec<bool>,
+    last_frame: u64,
+}
+
+impl Triggers {
+    pub fn parse(text: &str) -> Result<Triggers, TriggerError> {
+        let mut triggers = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((condition, message)) = line.split_once("=>") else {
+                return Err(TriggerError::MissingMessage { line: idx + 1 });
+            };
+            let condition = Expr::parse(condition)
+                .map_err(|err| TriggerError::Condition { line: idx + 1, err })?;
+            triggers.push(Trigger {
+                condition,
+                message: message.trim().to_string(),
+            });
+        }
+
+        Ok(Triggers {
+            fired: vec![false; triggers.len()],
+            triggers,
+            last_frame: 0,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.triggers.len()
+    }
+
+    // Call after every frame. Returns the messages of the triggers
+    // that fired. A machine reset (its frame count going back) rearms
+    // every trigger.
+    pub fn check(&mut self, cpu: &Cpu) -> Vec<&str> {
+        if cpu.frame() < self.last_frame {
+            self.fired.fill(false);
+        }
+        self.last_frame = cpu.frame();
+
+        let mut messages = Vec::new();
+        for (trigger, fired) in self.triggers.iter().zip(self.fired.iter_mut()) {
+            if !*fired && trigger.condition.is_true(cpu) {
+                *fired = true;
+                messages.push(trigger.message.as_str());
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod triggers_tests {
+    use super::{TriggerError, Triggers};
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_check() {
+        let mut triggers =
+            Triggers::parse("# Score\nmem[0x3F0] >= 100 => Score 100!\n\nv0 == 1 => One").unwrap();
+        assert_eq!(triggers.len(), 2);
+
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        cpu.tick();
+        assert!(triggers.check(&cpu).is_empty());
+
+        cpu.memory.write(0x3F0, 120);
+        cpu.registers.v[0] = 1;
+        cpu.tick();
+        assert_eq!(triggers.check(&cpu), vec!["Score 100!", "One"]);
+        cpu.tick();
+        assert!(triggers.check(&cpu).is_empty()); // Only once
+
+        cpu.clear();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        cpu.registers.v[0] = 1;
+        assert_eq!(triggers.check(&cpu), vec!["One"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Triggers::parse("v0 == 1 => ok\nv1 == 2").err(),
+            Some(TriggerError::MissingMessage { line: 2 })
+        );
+        assert!(matches!(
+            Triggers::parse("v0 == => Nope").err(),
+            Some(TriggerError::Condition { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::time::{Duration, Instant};
+
+// Limits on how long a program may run, in wall clock time and in
+// executed instructions, so one stuck in a loop can't hang a script or
+// an attract mode rotation. Without limits it never expires.
+pub struct Watchdog {
+    time_limit: Option<Duration>,
+    cycle_limit: Option<u64>,
+    started: Instant,
+}
+
+impl Watchdog {
+    pub fn new(time_limit: Option<Duration>, cycle_limit: Option<u64>) -> Watchdog {
+        Watchdog {
+            time_limit,
+            cycle_limit,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.time_limit.is_some() || self.cycle_limit.is_some()
+    }
+
+    // Starts counting the time again, e.g. after switching programs.
+    pub fn restart(&mut self, now: Instant) {
+        self.started = now;
+    }
+
+    pub fn time_exceeded(&self, now: Instant) -> bool {
+        self.time_limit
+            .is_some_and(|limit| now.saturating_duration_since(self.started) >= limit)
+    }
+
+    // Cycles are counted by the caller, from the program's start.
+    pub fn cycles_exceeded(&self, cycles: u64) -> bool {
+        self.cycle_limit.is_some_and(|limit| cycles >= limit)
+    }
+
+    pub fn expired(&self, now: Instant, cycles: u64) -> bool {
+        self.time_exceeded(now) || self.cycles_exceeded(cycles)
+    }
+}
+
+#[cfg(test)]
+mod watchdog_tests {
+    use std::time::{Duration, Instant};
+
+    use super::Watchdog;
+
+    #[test]
+    fn test_expired() {
+        let now = Instant::now();
+        let never = Watchdog::new(None, None);
+        assert!(!never.is_enabled());
+        assert!(!never.expired(now + Duration::from_secs(3600), u64::MAX));
+
+        let mut watchdog = Watchdog::new(Some(Duration::from_secs(5)), Some(1000));
+        watchdog.restart(now);
+        assert!(!watchdog.expired(now + Duration::from_secs(4), 999));
+        assert!(watchdog.expired(now + Duration::from_secs(5), 0));
+        assert!(watchdog.expired(now, 1000));
+
+        watchdog.restart(now + Duration::from_secs(10));
+        assert!(!watchdog.time_exceeded(now + Duration::from_secs(12)));
+    }
+}
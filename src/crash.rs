@@ -0,0 +1,205 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    backtrace::Backtrace,
+    fmt::Write,
+    fs,
+    panic::{self, PanicHookInfo},
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+};
+
+use crate::{
+    core::{cpu::Cpu, savestate},
+    locale::tr,
+};
+
+// Machine state at the start of the current frame, kept up to date
+// by the main loop so a crash can be reported from the panic hook.
+pub struct MachineContext {
+    pub rom_path: Option<PathBuf>,
+    pub rom_hash: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub frame: u64,
+    pub save_state: Vec<u8>,
+}
+
+impl MachineContext {
+    pub fn capture(cpu: &Cpu, rom_path: Option<PathBuf>) -> MachineContext {
+        MachineContext {
+            rom_path,
+            rom_hash: cpu.rom_hash().unwrap_or_default(),
+            pc: cpu.registers.pc,
+            opcode: cpu.memory.read_u16(cpu.registers.pc),
+            frame: cpu.frame(),
+            save_state: savestate::save(cpu),
+        }
+    }
+}
+
+static CONTEXT: Mutex<Option<MachineContext>> = Mutex::new(None);
+
+pub fn set_context(context: Option<MachineContext>) {
+    if let Ok(mut current) = CONTEXT.lock() {
+        *current = context;
+    }
+}
+
+// Keeps the context up to date without saving a state every host
+// frame: it's only captured again once the machine, its ROM, its frame
+// or its PC changed.
+pub struct ContextTracker {
+    captured: Option<(usize, Option<u64>, u64, u16)>,
+}
+
+impl ContextTracker {
+    pub fn new() -> ContextTracker {
+        ContextTracker { captured: None }
+    }
+
+    pub fn update(&mut self, machine_id: usize, cpu: &Cpu, rom_path: Option<&PathBuf>) {
+        let key = cpu
+            .is_rom_loaded()
+            .then(|| (machine_id, cpu.rom_hash(), cpu.frame(), cpu.registers.pc));
+        if key == self.captured {
+            return;
+        }
+        self.captured = key;
+        set_context(key.map(|_| MachineContext::capture(cpu, rom_path.cloned())));
+    }
+}
+
+// Shows a dialog offering to save a report (and a save state, if a ROM
+// was running) whenever the calling thread panics, before it closes.
+// Panics in worker threads don't close the emulator, so they only get
+// the default report.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    let main_thread = thread::current().id();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if thread::current().id() != main_thread {
+            return;
+        }
+
+        let backtrace = Backtrace::force_capture().to_string();
+        let context = CONTEXT
+            .try_lock()
+            .ok()
+            .and_then(|mut context| context.take());
+        let report = report(&panic_message(info), &backtrace, context.as_ref());
+        log::error!("{}", report);
+
+        let save = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Error)
+            .set_title(tr("The emulator crashed"))
+            .set_description(tr(
+                "Something went wrong and the emulator has to close. Save a crash report to include in a bug report?",
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if save != rfd::MessageDialogResult::Yes {
+            return;
+        }
+
+        save_file("crash-report.txt", "txt", report.as_bytes());
+        if let Some(context) = context {
+            save_file("crash.c8s", "c8s", &context.save_state);
+        }
+    }));
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Unknown panic".to_string());
+
+    match info.location() {
+        Some(location) => format!("{} ({})", message, location),
+        None => message,
+    }
+}
+
+pub fn report(message: &str, backtrace: &str, context: Option<&MachineContext>) -> String {
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "chip-8-emu {} crash report",
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(report, "Panic: {}", message);
+    let _ = writeln!(report);
+
+    match context {
+        Some(context) => {
+            let rom = context
+                .rom_path
+                .as_ref()
+                .map_or("-".to_string(), |path| path.display().to_string());
+            let _ = writeln!(report, "ROM: {}", rom);
+            let _ = writeln!(report, "ROM hash: {:016X}", context.rom_hash);
+            let _ = writeln!(report, "Frame: {}", context.frame);
+            let _ = writeln!(report, "PC: {:#05X}", context.pc);
+            let _ = writeln!(report, "Opcode: {:04X}", context.opcode);
+        }
+        None => {
+            let _ = writeln!(report, "No ROM loaded.");
+        }
+    }
+
+    let _ = writeln!(report);
+    let _ = writeln!(report, "Backtrace:");
+    let _ = write!(report, "{}", backtrace);
+    report
+}
+
+fn save_file(name: &str, extension: &str, contents: &[u8]) {
+    let path = std::env::current_dir().unwrap_or_default();
+    let res = rfd::FileDialog::new()
+        .add_filter(extension, &[extension])
+        .set_directory(&path)
+        .set_file_name(name)
+        .save_file();
+
+    if let Some(file_path) = res {
+        if let Err(err) = fs::write(file_path, contents) {
+            log::error!("Could not save {}: {}", name, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod crash_tests {
+    use std::path::PathBuf;
+
+    use super::{report, MachineContext};
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_report_contents() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        let context = MachineContext::capture(&cpu, Some(PathBuf::from("game.ch8")));
+
+        let text = report("boom (src/main.rs:1:1)", "0: main", Some(&context));
+        assert!(text.contains("Panic: boom (src/main.rs:1:1)"));
+        assert!(text.contains("ROM: game.ch8"));
+        assert!(text.contains("PC: 0x200"));
+        assert!(text.contains("Opcode: 1200"));
+        assert!(text.ends_with("Backtrace:\n0: main"));
+
+        assert!(report("boom", "", None).contains("No ROM loaded."));
+    }
+}
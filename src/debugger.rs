@@ -0,0 +1,492 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        debug_command::Command,
+        expr::Expr,
+        register_log::{register_name, REGISTER_COUNT},
+        registers::{DELAY_TIMER, SOUND_TIMER},
+        rng_stats::RngStats,
+        stats::Stats,
+        symbols::SymbolTable,
+    },
+    locale::tr,
+};
+
+// Instructions that can be stepped back while the debugger is open
+pub const HISTORY_CAPACITY: usize = 1024;
+// Instructions shown by the register heatmap, at most
+pub const REGISTER_LOG_CAPACITY: usize = 8192;
+const HEATMAP_ROWS: usize = 64;
+
+pub struct DebuggerState {
+    pub symbols: SymbolTable,
+    breakpoint_input: String,
+    command_input: String,
+    watches: Vec<(String, Expr)>,
+    watch_input: String,
+    watch_error: Option<String>,
+    instructions_per_row: i32,
+    rng_results: bool, // Histogram of CXKK results instead of random bytes
+    forced_random: i32,
+}
+
+impl Default for DebuggerState {
+    fn default() -> DebuggerState {
+        DebuggerState {
+            symbols: SymbolTable::default(),
+            breakpoint_input: String::new(),
+            command_input: String::new(),
+            watches: Vec::new(),
+            watch_input: String::new(),
+            watch_error: None,
+            instructions_per_row: 8,
+            rng_results: false,
+            forced_random: 0,
+        }
+    }
+}
+
+impl DebuggerState {
+    pub fn watches(&self) -> Vec<String> {
+        self.watches.iter().map(|(text, _)| text.clone()).collect()
+    }
+
+    // Replaces the watches, skipping invalid expressions.
+    pub fn set_watches(&mut self, watches: &[String]) {
+        self.watches = watches
+            .iter()
+            .filter_map(|text| Some((text.clone(), Expr::parse(text).ok()?)))
+            .collect();
+    }
+}
+
+pub fn draw(ui: &Ui, cpu: &mut Cpu, state: &mut DebuggerState, opened: &mut bool) {
+    ui.window(format!("{}###debugger", tr("Debugger")))
+        .opened(opened)
+        .position([10.0, 50.0], Condition::FirstUseEver)
+        .always_auto_resize(true)
+        .build(|| {
+            let text = if cpu.is_halted() {
+                tr("Resume")
+            } else {
+                tr("Halt")
+            };
+            if ui.button_with_size(text, [ui.current_font_size() * 4.25, 0.0]) {
+                cpu.toggle_halt();
+            }
+
+            let halted_scope = ui.begin_disabled(!cpu.is_halted() || !cpu.is_rom_loaded());
+            {
+                ui.same_line();
+                if ui.button(tr("Step")) {
+                    // Faults get logged, and halt as when running
+                    let _ = cpu.step();
+                }
+
+                ui.same_line();
+                // Plays the frame's audio too, to hear when beeps happen
+                if ui.button(tr("Step frame")) {
+                    cpu.step_frame();
+                }
+
+                ui.same_line();
+                let no_history_scope = ui.begin_disabled(cpu.history_len() == 0);
+                if ui.button(tr("Step back")) {
+                    cpu.step_back();
+                }
+                no_history_scope.end();
+            }
+            halted_scope.end();
+            ui.same_line();
+            ui.text_disabled(format!("({} in history)", cpu.history_len()));
+
+            ui.checkbox(tr("Break after draw (DXYN)"), &mut cpu.break_on_draw);
+            ui.checkbox(tr("Break after clear (CLS)"), &mut cpu.break_on_clear);
+            ui.checkbox(
+                tr("Break after writing executed code"),
+                &mut cpu.break_on_self_modify,
+            );
+            ui.same_line();
+            ui.checkbox(tr("Log it"), &mut cpu.log_self_modify);
+            if let Some(modification) = cpu.last_self_modification() {
+                ui.text_disabled(format!(
+                    "{} {:#05X} -> {:#05X}",
+                    tr("Last self-modifying write:"),
+                    modification.pc,
+                    modification.addr
+                ));
+            }
+
+            ui.separator();
+            let registers = &cpu.registers;
+            let opcode = cpu.memory.read_u16(registers.pc);
+            ui.text(format!(
+                "PC: {:#05X}  [{:04X}]  {}",
+                registers.pc,
+                opcode,
+                state.symbols.describe(registers.pc)
+            ));
+            ui.text(format!("I:  {:#05X}", registers.i));
+            // Yellow near the limit, red once full
+            let depth = registers.sp as usize;
+            let color = if depth >= cpu.stack_size() {
+                [1.0, 0.3, 0.3, 1.0]
+            } else if depth + 2 >= cpu.stack_size() {
+                [1.0, 0.8, 0.2, 1.0]
+            } else {
+                ui.style_color(imgui::StyleColor::Text)
+            };
+            ui.text_colored(
+                color,
+                format!(
+                    "SP: {:#03X}  ({}/{})",
+                    registers.sp,
+                    depth,
+                    cpu.stack_size()
+                ),
+            );
+            ui.text(format!(
+                "DT: {:#04X}  ST: {:#04X}",
+                registers.timers[DELAY_TIMER].read(),
+                registers.timers[SOUND_TIMER].read()
+            ));
+
+            ui.separator();
+            for (idx, value) in registers.v.iter().enumerate() {
+                if idx % 4 != 0 {
+                    ui.same_line();
+                }
+                ui.text(format!("V{:X}: {:02X}", idx, value));
+            }
+
+            ui.separator();
+            draw_breakpoints(ui, cpu, state);
+
+            ui.separator();
+            draw_watches(ui, cpu, state);
+
+            ui.separator();
+            draw_command_input(ui, cpu, state);
+
+            if ui.collapsing_header(tr("Register writes"), imgui::TreeNodeFlags::empty()) {
+                draw_register_heatmap(ui, cpu, state);
+            }
+            if ui.collapsing_header(tr("Random numbers"), imgui::TreeNodeFlags::empty()) {
+                draw_rng(ui, cpu, state);
+            }
+        });
+}
+
+// Rows of the last executed instructions (newest at the bottom) by
+// register, brighter the more of the row's instructions wrote it.
+fn draw_register_heatmap(ui: &Ui, cpu: &Cpu, state: &mut DebuggerState) {
+    let Some(log) = cpu.register_log() else {
+        return;
+    };
+    ui.set_next_item_width(ui.current_font_size() * 10.0);
+    ui.slider(
+        tr("Instructions per row"),
+        1,
+        (REGISTER_LOG_CAPACITY / HEATMAP_ROWS) as i32,
+        &mut state.instructions_per_row,
+    );
+    let per_row = state.instructions_per_row.max(1) as usize;
+
+    let cell = [ui.current_font_size() * 1.25, 4.0];
+    let origin = ui.cursor_screen_pos();
+    let draw_list = ui.get_window_draw_list();
+    for idx in 0..REGISTER_COUNT {
+        let name = register_name(idx);
+        let x = origin[0] + idx as f32 * cell[0];
+        draw_list.add_text(
+            [x, origin[1]],
+            [0.6, 0.6, 0.6, 1.0],
+            &name[name.len() - 1..],
+        );
+    }
+    let top = origin[1] + ui.text_line_height();
+    let size = [
+        cell[0] * REGISTER_COUNT as f32,
+        ui.text_line_height() + cell[1] * HEATMAP_ROWS as f32,
+    ];
+    ui.dummy(size);
+
+    // The last row ends at the newest instruction
+    let row_start = |row: usize| (log.len() + row * per_row).checked_sub(HEATMAP_ROWS * per_row);
+    draw_list
+        .add_rect(
+            [origin[0], top],
+            [origin[0] + size[0], origin[1] + size[1]],
+            [0.1, 0.1, 0.1, 1.0],
+        )
+        .filled(true)
+        .build();
+    for row in 0..HEATMAP_ROWS {
+        let start = row_start(row).unwrap_or(0);
+        let end = row_start(row + 1).unwrap_or(0);
+        if start == end {
+            continue;
+        }
+        let counts = log.counts(start, end);
+        let y = top + row as f32 * cell[1];
+        for (idx, count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+            let heat = *count as f32 / (end - start) as f32;
+            let x = origin[0] + idx as f32 * cell[0];
+            draw_list
+                .add_rect(
+                    [x, y],
+                    [x + cell[0] - 1.0, y + cell[1]],
+                    [heat, 0.4 * heat, 0.1, 1.0],
+                )
+                .filled(true)
+                .build();
+        }
+    }
+
+    if ui.is_item_hovered() {
+        let [mouse_x, mouse_y] = ui.io().mouse_pos;
+        let idx = ((mouse_x - origin[0]) / cell[0]) as usize;
+        let row = ((mouse_y - top) / cell[1]).floor();
+        if idx < REGISTER_COUNT && row >= 0.0 {
+            let row = row as usize;
+            let start = row_start(row).unwrap_or(0);
+            let end = row_start(row + 1).unwrap_or(0);
+            if start != end {
+                ui.tooltip_text(format!(
+                    "{}: {}/{}\n{} {}-{}",
+                    register_name(idx),
+                    log.counts(start, end)[idx],
+                    end - start,
+                    tr("Instructions ago:"),
+                    log.len() - end + 1,
+                    log.len() - start
+                ));
+            }
+        }
+    }
+}
+
+// A gdb style command line, its output going to the log console.
+fn draw_command_input(ui: &Ui, cpu: &mut Cpu, state: &mut DebuggerState) {
+    ui.set_next_item_width(ui.current_font_size() * 16.0);
+    let submitted = ui
+        .input_text("##command", &mut state.command_input)
+        .hint(tr("Command (b, x/16, p, c, si, help)"))
+        .enter_returns_true(true)
+        .build();
+    if !submitted || state.command_input.trim().is_empty() {
+        return;
+    }
+    log::info!(target: "debugger", "> {}", state.command_input.trim());
+    match Command::parse(&state.command_input, &state.symbols, cpu) {
+        Ok(command) => {
+            for line in command.execute(cpu, &state.symbols) {
+                log::info!(target: "debugger", "{}", line);
+            }
+        }
+        Err(err) => log::warn!(target: "debugger", "{}", err),
+    }
+    state.command_input.clear();
+    // Keeps typing commands after Enter
+    ui.set_keyboard_focus_here_with_offset(imgui::FocusedWidget::Previous);
+}
+
+fn draw_breakpoints(ui: &Ui, cpu: &mut Cpu, state: &mut DebuggerState) {
+    ui.text(tr("Breakpoints"));
+    let submitted = ui
+        .input_text("##breakpoint", &mut state.breakpoint_input)
+        .hint(tr("Label or address"))
+        .enter_returns_true(true)
+        .build();
+    ui.same_line();
+    if ui.button(tr("Add")) || submitted {
+        if let Some(addr) = state.symbols.resolve(&state.breakpoint_input) {
+            cpu.breakpoints.insert(addr);
+            state.breakpoint_input.clear();
+        }
+    }
+
+    let mut removed = None;
+    for &addr in &cpu.breakpoints {
+        let _id = ui.push_id_int(addr as i32);
+        if ui.small_button("x") {
+            removed = Some(addr);
+        }
+        ui.same_line();
+        ui.text(format!("{:#05X}  {}", addr, state.symbols.describe(addr)));
+    }
+    if let Some(addr) = removed {
+        cpu.breakpoints.remove(&addr);
+    }
+}
+
+// Expressions over the machine state, evaluated every frame.
+fn draw_watches(ui: &Ui, cpu: &Cpu, state: &mut DebuggerState) {
+    ui.text(tr("Watches"));
+    let _id = ui.push_id("watches");
+    let submitted = ui
+        .input_text("##watch", &mut state.watch_input)
+        .hint(tr("Expression, e.g. mem[0x3F0]"))
+        .enter_returns_true(true)
+        .build();
+    ui.same_line();
+    if (ui.button(tr("Add")) || submitted) && !state.watch_input.trim().is_empty() {
+        let text = state.watch_input.trim().to_string();
+        match Expr::parse(&text) {
+            Ok(expr) => {
+                state.watches.push((text, expr));
+                state.watch_input.clear();
+                state.watch_error = None;
+            }
+            Err(err) => state.watch_error = Some(err.to_string()),
+        }
+    }
+    if let Some(error) = &state.watch_error {
+        ui.text_colored([1.0, 0.4, 0.4, 1.0], error);
+    }
+
+    let mut removed = None;
+    for (idx, (text, expr)) in state.watches.iter().enumerate() {
+        let _id = ui.push_id_usize(idx);
+        if ui.small_button("x") {
+            removed = Some(idx);
+        }
+        ui.same_line();
+        let value = expr.eval(cpu);
+        ui.text(format!("{} = {} ({:#X})", text, value, value));
+    }
+    if let Some(idx) = removed {
+        state.watches.remove(idx);
+    }
+}
+
+// Values CXKK produced since the ROM started, and a value to force
+// so random branches can be tested one at a time.
+fn draw_rng(ui: &Ui, cpu: &mut Cpu, state: &mut DebuggerState) {
+    let stats = cpu.rng_stats();
+    let counts = if state.rng_results {
+        stats.results()
+    } else {
+        stats.random()
+    };
+    let values: Vec<f32> = counts.iter().map(|&count| count as f32).collect();
+    ui.plot_histogram("##rng_histogram", &values)
+        .graph_size([256.0 * 1.5, 80.0])
+        .scale_min(0.0)
+        .build();
+    ui.text(format!("{} {}", stats.total(), tr("values")));
+    if let Some(mean) = RngStats::mean(counts) {
+        ui.same_line();
+        ui.text(format!("  {} {:.1}", tr("Mean:"), mean));
+    }
+    if let Some(chi_squared) = stats.chi_squared() {
+        ui.same_line();
+        ui.text(format!("  {} {:.0}", tr("Chi-squared:"), chi_squared));
+        if ui.is_item_hovered() {
+            ui.tooltip_text(tr(
+                "Of the random bytes against uniform ones, about 255 when unbiased.",
+            ));
+        }
+    }
+    ui.checkbox(tr("After masking with kk"), &mut state.rng_results);
+    ui.same_line();
+    if ui.button(tr("Clear")) {
+        cpu.clear_rng_stats();
+    }
+
+    let mut forced = cpu.forced_random.is_some();
+    if ui.checkbox(tr("Force random byte"), &mut forced) {
+        cpu.forced_random = forced.then_some(state.forced_random as u8);
+    }
+    ui.same_line();
+    ui.set_next_item_width(ui.current_font_size() * 8.0);
+    if ui
+        .input_int("##forced_random", &mut state.forced_random)
+        .chars_hexadecimal(true)
+        .display_format("%02X")
+        .build()
+    {
+        state.forced_random = state.forced_random.clamp(0, 0xFF);
+        if forced {
+            cpu.forced_random = Some(state.forced_random as u8);
+        }
+    }
+}
+
+// Outlines the area affected by the last DXYN, red if it
+// collided with enabled pixels and green otherwise.
+pub fn draw_region_overlay(ui: &Ui, cpu: &Cpu) {
+    let region = match cpu.last_draw_region() {
+        Some(region) if cpu.is_rom_loaded() => region,
+        _ => return,
+    };
+
+    let display_size = ui.io().display_size;
+    let top = crate::menu_bar_height(display_size[1]);
+    let (width, height) = (cpu.screen.width(), cpu.screen.height());
+    let scale_x = display_size[0] / width as f32;
+    let scale_y = (display_size[1] - top) / height as f32;
+
+    let color = if region.collision {
+        [1.0, 0.2, 0.2, 1.0]
+    } else {
+        [0.2, 1.0, 0.2, 1.0]
+    };
+
+    // Split the region in up to 4 parts if it wraps around the edges
+    let split = |start: usize, size: usize, max: usize| {
+        let end = start + size;
+        if end > max {
+            vec![(start, max), (0, end - max)]
+        } else {
+            vec![(start, end)]
+        }
+    };
+
+    let draw_list = ui.get_background_draw_list();
+    for (x_start, x_end) in split(region.x, region.width, width) {
+        for &(y_start, y_end) in &split(region.y, region.height, height) {
+            draw_list
+                .add_rect(
+                    [x_start as f32 * scale_x, top + y_start as f32 * scale_y],
+                    [x_end as f32 * scale_x, top + y_end as f32 * scale_y],
+                    color,
+                )
+                .thickness(2.0)
+                .build();
+        }
+    }
+}
+
+// Counts of what the program did, in a corner over the screen.
+pub fn draw_stats_overlay(ui: &Ui, stats: &Stats, top: f32) {
+    ui.window("##stats")
+        .position([8.0, top + 8.0], Condition::Always)
+        .flags(imgui::WindowFlags::NO_DOCKING | imgui::WindowFlags::NO_SAVED_SETTINGS)
+        .bg_alpha(0.6)
+        .no_decoration()
+        .always_auto_resize(true)
+        .movable(false)
+        .build(|| {
+            ui.text(format!("{:<12} {}", tr("Cycles"), stats.cycles));
+            ui.text(format!("{:<12} {}", tr("Frames"), stats.frames));
+            ui.text(format!("{:<12} {}", tr("Draws"), stats.draws));
+            ui.text(format!("{:<12} {}", tr("Key events"), stats.key_events));
+            ui.text(format!(
+                "{:<12} {:.1} s",
+                tr("Sound time"),
+                stats.sound_seconds()
+            ));
+        });
+}
@@ -0,0 +1,170 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::time::{Duration, Instant};
+
+use imgui::{Condition, Ui};
+
+use crate::{locale::tr, screen::Screen};
+
+// Part of the screen flipping between two shown frames that makes a flash
+const FLASH_AREA: f32 = 0.25;
+// At most 3 flashes a second, the usual photosensitivity limit
+const MIN_FLASH_INTERVAL: Duration = Duration::from_millis(334);
+// How long the notice stays up after the last held back flash
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
+
+// Photosensitivity guard: frames flipping most of the screen too soon
+// after the previous flash aren't shown, the screen keeps the last
+// shown frame until enough time passed.
+pub struct FlashGuard {
+    pub enabled: bool,
+    shown: Vec<u64>, // Words of the frame last shown
+    last_flash: Option<Instant>,
+    last_held: Option<Instant>,
+}
+
+impl FlashGuard {
+    pub fn new(enabled: bool) -> FlashGuard {
+        FlashGuard {
+            enabled,
+            shown: Vec::new(),
+            last_flash: None,
+            last_held: None,
+        }
+    }
+
+    // Whether the frame can be shown now. When it can't, the caller
+    // should try again later with the latest frame.
+    pub fn allow(&mut self, screen: &Screen, now: Instant) -> bool {
+        let words = screen.words();
+        if self.enabled && is_flash(&self.shown, words) {
+            let too_soon = self
+                .last_flash
+                .is_some_and(|last_flash| now.duration_since(last_flash) < MIN_FLASH_INTERVAL);
+            if too_soon {
+                self.last_held = Some(now);
+                return false;
+            }
+            self.last_flash = Some(now);
+        }
+        self.shown.clear();
+        self.shown.extend_from_slice(words);
+        true
+    }
+
+    // Whether flashes were held back lately, to tell the user.
+    pub fn is_limiting(&self, now: Instant) -> bool {
+        self.last_held
+            .is_some_and(|last_held| now.duration_since(last_held) < NOTICE_DURATION)
+    }
+
+    // Notice in the bottom left corner while flashes are held back.
+    pub fn draw_notice(&self, ui: &Ui) {
+        if !self.is_limiting(Instant::now()) {
+            return;
+        }
+        let height = ui.io().display_size[1];
+        ui.window("##flash_guard")
+            .position([8.0, height - 8.0], Condition::Always)
+            .position_pivot([0.0, 1.0])
+            .flags(imgui::WindowFlags::NO_DOCKING | imgui::WindowFlags::NO_SAVED_SETTINGS)
+            .bg_alpha(0.8)
+            .no_decoration()
+            .no_inputs()
+            .always_auto_resize(true)
+            .build(|| {
+                ui.text(tr("Flashing reduced"));
+            });
+    }
+}
+
+// Frames of different resolutions aren't compared.
+fn is_flash(before: &[u64], now: &[u64]) -> bool {
+    if before.len() != now.len() || now.is_empty() {
+        return false;
+    }
+    let flipped: u32 = before
+        .iter()
+        .zip(now)
+        .map(|(then, now)| (then ^ now).count_ones())
+        .sum();
+    flipped as f32 >= FLASH_AREA * (now.len() * u64::BITS as usize) as f32
+}
+
+#[cfg(test)]
+mod flash_guard_tests {
+    use std::time::{Duration, Instant};
+
+    use super::{FlashGuard, MIN_FLASH_INTERVAL};
+    use crate::screen::Screen;
+
+    fn filled(on: bool) -> Screen {
+        let mut screen = Screen::new();
+        for (x, y, _) in Screen::new().iter() {
+            screen.set(x, y, on);
+        }
+        screen
+    }
+
+    #[test]
+    fn test_strobe_is_capped() {
+        let (black, white) = (filled(false), filled(true));
+        let mut guard = FlashGuard::new(true);
+        let start = Instant::now();
+        let frame = Duration::from_millis(16);
+
+        // Inverting the screen every frame for a second, counting the
+        // times the shown frame changed
+        let mut shown = None;
+        let mut flashes = 0;
+        for idx in 0..60 {
+            let on = idx % 2 == 0;
+            let screen = if on { &white } else { &black };
+            if guard.allow(screen, start + frame * idx) && shown.replace(on) == Some(!on) {
+                flashes += 1;
+            }
+        }
+        assert_eq!(flashes, 3);
+        assert!(guard.is_limiting(start + frame * 60));
+        assert!(!guard.is_limiting(start + Duration::from_secs(5)));
+
+        // Flashes spaced out enough all show
+        let later = start + Duration::from_secs(10);
+        assert!(guard.allow(&white, later));
+        assert!(guard.allow(&black, later + MIN_FLASH_INTERVAL));
+    }
+
+    #[test]
+    fn test_small_changes_always_show() {
+        let mut guard = FlashGuard::new(true);
+        let now = Instant::now();
+        let mut screen = Screen::new();
+        assert!(guard.allow(&screen, now));
+        for x in 0..32 {
+            screen.set(x, 0, true);
+            assert!(guard.allow(&screen, now));
+        }
+        assert!(!guard.is_limiting(now));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut guard = FlashGuard::new(false);
+        let now = Instant::now();
+        assert!(guard.allow(&filled(true), now));
+        assert!(guard.allow(&filled(false), now));
+        assert!(guard.allow(&filled(true), now));
+
+        // Enabling it later compares against what was last shown
+        guard.enabled = true;
+        assert!(guard.allow(&filled(false), now));
+        assert!(!guard.allow(&filled(true), now));
+    }
+}
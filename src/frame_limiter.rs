@@ -0,0 +1,216 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    hint, thread,
+    time::{Duration, Instant},
+};
+
+use sdl2::{event::Event, video::SwapInterval, EventPump, VideoSubsystem};
+
+use crate::core::beep::FRAME_RATE;
+
+// Emulated frames run at once to catch up after a slow host frame
+const MAX_CATCH_UP: u32 = 4;
+
+// Sleeps overshoot by up to a scheduler tick, so the end of each wait
+// is spun instead.
+const SPIN_DURATION: Duration = Duration::from_millis(2);
+
+// Host frames while nothing runs, unless input arrives first
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+// Full rate kept after input, for the UI to react to it
+const IDLE_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VSync {
+    Off, // Frames paced by the frame limiter
+    On,
+    Adaptive, // Late frames are shown right away instead of waiting
+}
+
+// Adaptive vsync isn't supported everywhere, regular vsync is used then.
+pub fn set_swap_interval(video: &VideoSubsystem, vsync: VSync) {
+    let result = match vsync {
+        VSync::Off => video.gl_set_swap_interval(SwapInterval::Immediate),
+        VSync::On => video.gl_set_swap_interval(SwapInterval::VSync),
+        VSync::Adaptive => video
+            .gl_set_swap_interval(SwapInterval::LateSwapTearing)
+            .or_else(|_| video.gl_set_swap_interval(SwapInterval::VSync)),
+    };
+    if let Err(err) = result {
+        log::warn!("Could not set the swap interval: {}", err);
+    }
+}
+
+// Caps the framerate when vsync is off, keeping a steady cadence of
+// deadlines rather than waiting a fixed time after each frame.
+pub struct FrameLimiter {
+    deadline: Option<Instant>, // When the last frame was due
+}
+
+impl FrameLimiter {
+    pub fn new() -> FrameLimiter {
+        FrameLimiter { deadline: None }
+    }
+
+    // Waits until the next frame is due.
+    pub fn wait(&mut self, max_fps: u32) {
+        let frame = Duration::from_secs_f64(1.0 / max_fps.max(1) as f64);
+        let deadline = next_deadline(self.deadline, Instant::now(), frame);
+        sleep_until(deadline);
+        self.deadline = Some(deadline);
+    }
+
+    // Starts a new cadence, e.g. after vsync paced the frames for a while.
+    pub fn reset(&mut self) {
+        self.deadline = None;
+    }
+}
+
+// Emulated frames due each host frame, so the machine runs at 60
+// frames per second whatever the display's refresh rate or frame cap.
+pub struct FramePacer {
+    last: Option<Instant>,
+    due: f64, // Emulated frames owed, negative when ahead
+}
+
+impl FramePacer {
+    pub fn new() -> FramePacer {
+        FramePacer {
+            last: None,
+            due: 0.0,
+        }
+    }
+
+    pub fn frames_due(&mut self, now: Instant) -> u32 {
+        let elapsed = self.last.map_or(1.0 / FRAME_RATE, |last| {
+            now.saturating_duration_since(last).as_secs_f64()
+        });
+        self.last = Some(now);
+        // Rounding keeps 60Hz displays at one frame each, even with jitter
+        self.due = (self.due + elapsed * FRAME_RATE).min(MAX_CATCH_UP as f64);
+        let frames = self.due.round().max(0.0) as u32;
+        self.due -= frames as f64;
+        frames
+    }
+}
+
+// Slows the loop down while the screen is static (halted, or no ROM),
+// so a still image doesn't keep a core and the GPU busy.
+pub struct IdleWait {
+    last_input: Option<Instant>,
+}
+
+impl IdleWait {
+    pub fn new() -> IdleWait {
+        IdleWait { last_input: None }
+    }
+
+    pub fn input(&mut self, now: Instant) {
+        self.last_input = Some(now);
+    }
+
+    // Whether the last input was long enough ago to slow down.
+    pub fn is_idle(&self, now: Instant) -> bool {
+        self.last_input
+            .is_none_or(|last_input| now.saturating_duration_since(last_input) >= IDLE_GRACE)
+    }
+
+    // Waits for the next host frame, returning early with the input
+    // that woke it up. That event has to be handled with the next ones.
+    pub fn wait(&mut self, events: &mut EventPump) -> Option<Event> {
+        let event = events.wait_event_timeout(IDLE_INTERVAL.as_millis() as u32);
+        if event.is_some() {
+            self.input(Instant::now());
+        }
+        event
+    }
+}
+
+// A frame after the last deadline, unless running over a frame late,
+// where the lost time is dropped instead of rushing frames to catch up.
+fn next_deadline(last: Option<Instant>, now: Instant, frame: Duration) -> Instant {
+    match last {
+        Some(last) if last + frame * 2 >= now => last + frame,
+        _ => now,
+    }
+}
+
+fn sleep_until(deadline: Instant) {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining > SPIN_DURATION {
+        thread::sleep(remaining - SPIN_DURATION);
+    }
+    while Instant::now() < deadline {
+        hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod frame_limiter_tests {
+    use std::time::{Duration, Instant};
+
+    use super::{next_deadline, FrameLimiter, FramePacer, IdleWait, IDLE_GRACE};
+
+    #[test]
+    fn test_next_deadline() {
+        let now = Instant::now();
+        let frame = Duration::from_millis(10);
+        assert_eq!(next_deadline(None, now, frame), now);
+        // Early, or a bit late, keeps the cadence
+        assert_eq!(next_deadline(Some(now), now, frame), now + frame);
+        let late = now + Duration::from_millis(15);
+        assert_eq!(next_deadline(Some(now), late, frame), now + frame);
+        // Over a frame late starts again from now
+        let very_late = now + Duration::from_millis(25);
+        assert_eq!(next_deadline(Some(now), very_late, frame), very_late);
+    }
+
+    #[test]
+    fn test_frames_due() {
+        let start = Instant::now();
+        let count = |pacer: &mut FramePacer, fps: f64, frames: u32| -> u32 {
+            pacer.frames_due(start);
+            (1..=frames)
+                .map(|frame| pacer.frames_due(start + Duration::from_secs_f64(frame as f64 / fps)))
+                .sum()
+        };
+        assert_eq!(count(&mut FramePacer::new(), 60.0, 60), 60);
+        assert_eq!(count(&mut FramePacer::new(), 144.0, 144), 60);
+        assert_eq!(count(&mut FramePacer::new(), 30.0, 30), 60);
+
+        // A long stall only catches up a few frames
+        let mut pacer = FramePacer::new();
+        pacer.frames_due(start);
+        assert_eq!(pacer.frames_due(start + Duration::from_secs(2)), 4);
+    }
+
+    #[test]
+    fn test_idle_after_grace() {
+        let now = Instant::now();
+        let mut idle_wait = IdleWait::new();
+        assert!(idle_wait.is_idle(now));
+        idle_wait.input(now);
+        assert!(!idle_wait.is_idle(now));
+        assert!(!idle_wait.is_idle(now + IDLE_GRACE / 2));
+        assert!(idle_wait.is_idle(now + IDLE_GRACE));
+    }
+
+    #[test]
+    fn test_wait() {
+        let mut limiter = FrameLimiter::new();
+        limiter.wait(200);
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.wait(200);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(15));
+    }
+}
@@ -0,0 +1,102 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::time::{Duration, Instant};
+
+use crate::core::beep::FRAME_RATE;
+
+// Emulated frames are counted over this long before judging the speed
+const WINDOW: Duration = Duration::from_secs(1);
+
+// Slower than this gets reported, small hiccups don't
+const SLOW_SPEED: f64 = 0.95;
+
+// Notices when the host can't keep up with the emulated clock, e.g.
+// with lots of instructions per frame and the debugger recording each
+// of them, so the slowdown is shown instead of silently drifting.
+pub struct Governor {
+    window: Option<(Instant, u64)>, // Start time and emulated frame
+    speed: Option<f64>,             // Over the last window, 1.0 is full speed
+}
+
+impl Governor {
+    pub fn new() -> Governor {
+        Governor {
+            window: None,
+            speed: None,
+        }
+    }
+
+    // Called once per host frame. Halted or stepped machines aren't
+    // expected to keep up, so they reset the measurement.
+    pub fn update(&mut self, now: Instant, frame: u64, running: bool) {
+        if !running {
+            self.window = None;
+            self.speed = None;
+            return;
+        }
+        match self.window {
+            // Rewinding or restarting goes back, starting over
+            Some((start, start_frame)) if frame >= start_frame => {
+                let elapsed = now.saturating_duration_since(start);
+                if elapsed >= WINDOW {
+                    let expected = elapsed.as_secs_f64() * FRAME_RATE;
+                    self.speed = Some((frame - start_frame) as f64 / expected);
+                    self.window = Some((now, frame));
+                }
+            }
+            _ => self.window = Some((now, frame)),
+        }
+    }
+
+    // The speed as a percentage, when it's too slow.
+    pub fn slow_percent(&self) -> Option<u32> {
+        self.speed
+            .filter(|&speed| speed < SLOW_SPEED)
+            .map(|speed| (speed * 100.0).round() as u32)
+    }
+}
+
+#[cfg(test)]
+mod governor_tests {
+    use std::time::{Duration, Instant};
+
+    use super::Governor;
+
+    #[test]
+    fn test_slow_percent() {
+        let start = Instant::now();
+        let at = |secs: f64| start + Duration::from_secs_f64(secs);
+        let mut governor = Governor::new();
+        governor.update(at(0.0), 0, true);
+        governor.update(at(0.5), 20, true);
+        assert_eq!(governor.slow_percent(), None);
+        governor.update(at(1.0), 43, true);
+        assert_eq!(governor.slow_percent(), Some(72));
+
+        // Full speed again
+        governor.update(at(2.0), 103, true);
+        assert_eq!(governor.slow_percent(), None);
+
+        governor.update(at(3.0), 120, true);
+        governor.update(at(3.5), 125, false);
+        assert_eq!(governor.slow_percent(), None);
+    }
+
+    #[test]
+    fn test_going_back() {
+        let start = Instant::now();
+        let mut governor = Governor::new();
+        governor.update(start, 500, true);
+        // A rewind to frame 10 starts a new window instead of underflowing
+        governor.update(start + Duration::from_millis(500), 10, true);
+        governor.update(start + Duration::from_millis(1500), 40, true);
+        assert_eq!(governor.slow_percent(), Some(50));
+    }
+}
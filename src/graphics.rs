@@ -10,38 +10,122 @@
 use glow::HasContext;
 use imgui_glow_renderer::{glow, AutoRenderer};
 
-use crate::{screen, ColorPalette};
+use crate::{
+    screen::{self, Screen},
+    ColorPalette,
+};
 
-const GL_VERTEX_TOP_MARGIN: f32 =
-    (super::WINDOW_HEIGHT - super::MENU_BAR_HEIGHT) as f32 / super::WINDOW_HEIGHT as f32;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+pub const ROTATIONS: [Rotation; 4] = [
+    Rotation::None,
+    Rotation::Cw90,
+    Rotation::Cw180,
+    Rotation::Cw270,
+];
+
+impl Rotation {
+    pub fn degrees(&self) -> u16 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Cw90 => 90,
+            Rotation::Cw180 => 180,
+            Rotation::Cw270 => 270,
+        }
+    }
+
+    pub fn from_degrees(degrees: u16) -> Option<Rotation> {
+        ROTATIONS
+            .into_iter()
+            .find(|rotation| rotation.degrees() == degrees)
+    }
+
+    // Whether the screen's width runs vertically on the display
+    pub fn swaps_axes(&self) -> bool {
+        matches!(self, Rotation::Cw90 | Rotation::Cw270)
+    }
+}
+
+// What fills the window around the screen, set per palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Border {
+    Color([f32; 3]),
+    OffPixels, // Same color as the disabled pixels, for a seamless look
+}
+
+pub const DEFAULT_BORDER: Border = Border::Color([0.1, 0.1, 0.1]);
+
+impl Border {
+    pub fn color(&self, off_pixels: [f32; 3]) -> [f32; 3] {
+        match *self {
+            Border::Color(color) => color,
+            Border::OffPixels => off_pixels,
+        }
+    }
+}
+
+// Display tweaks for palettes looking off on some monitors, applied to
+// the screen by the fragment shader.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjustment {
+    pub gamma: f32,      // Above 1 brightens the midtones
+    pub brightness: f32, // Added to every channel
+    pub contrast: f32,   // Scales the distance from middle gray
+}
+
+pub const GAMMA_RANGE: (f32, f32) = (0.5, 2.5);
+pub const BRIGHTNESS_RANGE: (f32, f32) = (-0.5, 0.5);
+pub const CONTRAST_RANGE: (f32, f32) = (0.5, 2.0);
+
+impl Default for ColorAdjustment {
+    fn default() -> ColorAdjustment {
+        ColorAdjustment {
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+        }
+    }
+}
+
+impl ColorAdjustment {
+    // Same as the shader, for colors drawn outside of it.
+    pub fn apply(&self, color: [f32; 3]) -> [f32; 3] {
+        color.map(|channel| {
+            let channel = ((channel - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0);
+            channel.powf(1.0 / self.gamma)
+        })
+    }
+}
+
+// Vertex and texture buffers of the screen quad, kept to change its
+// orientation later on.
+pub struct ScreenQuad {
+    pub texture: glow::Texture,
+    vbo: glow::Buffer,
+    grid_opacity: Option<glow::UniformLocation>,
+    gamma: Option<glow::UniformLocation>,
+    brightness: Option<glow::UniformLocation>,
+    contrast: Option<glow::UniformLocation>,
+}
 
 pub unsafe fn update_render(
     renderer: &mut AutoRenderer,
-    buffer: &mut [u8; screen::WIDTH * screen::HEIGHT * 3],
     texture: &glow::Texture,
-    screen_data: &[u64; screen::HEIGHT],
+    screen: &Screen,
+    ghost: Option<&Screen>,
     active_palette: &ColorPalette,
 ) {
-    // Update the buffer data
-    let mut buff_idx = 0;
-    for row in screen_data {
-        for col in 0x0..screen::WIDTH {
-            let mask: u64 = 0x1 << (63 - col);
-            let pixel_on = (row & mask) != 0;
-
-            let color = if pixel_on {
-                active_palette.enabled_px
-            } else {
-                active_palette.disabled_px
-            };
-
-            buffer[buff_idx] = ((color.x) * 0xFF as f32) as u8;
-            buffer[buff_idx + 1] = ((color.y) * 0xFF as f32) as u8;
-            buffer[buff_idx + 2] = ((color.z) * 0xFF as f32) as u8;
-
-            buff_idx += 3;
-        }
-    }
+    let palette = active_palette.screen_palette();
+    let buffer = match ghost {
+        Some(ghost) => screen.render_rgb_with_ghost(&palette, ghost),
+        None => screen.render_rgb(&palette),
+    };
 
     // Render the buffer into the texture
     renderer
@@ -51,25 +135,139 @@ pub unsafe fn update_render(
         glow::TEXTURE_2D,
         0,
         glow::RGB as i32,
-        screen::WIDTH as i32,
-        screen::HEIGHT as i32,
+        screen.width() as i32,
+        screen.height() as i32,
         0,
         glow::RGB,
         glow::UNSIGNED_BYTE,
-        Some(buffer),
+        Some(&buffer),
+    );
+}
+
+// Texture coordinate shown at the given corner of the quad (0 to 1,
+// from the top-left), for rotated (clockwise) and mirrored output.
+fn texcoord(x: f32, y: f32, rotation: Rotation, mirrored: bool) -> [f32; 2] {
+    let x = if mirrored { 1.0 - x } else { x };
+    match rotation {
+        Rotation::None => [x, y],
+        Rotation::Cw90 => [y, 1.0 - x],
+        Rotation::Cw180 => [1.0 - x, 1.0 - y],
+        Rotation::Cw270 => [1.0 - y, x],
+    }
+}
+
+// Position and texture coordinate of every corner of the quad, which
+// fills the whole viewport.
+fn quad_vertices(rotation: Rotation, mirrored: bool) -> [f32; 16] {
+    // Top-right, top-left, bottom-right, bottom-left
+    const CORNERS: [(f32, f32); 4] = [(1.0, 0.0), (0.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+    let mut vertices = [0.0; 16];
+    for (idx, (x, y)) in CORNERS.into_iter().enumerate() {
+        let [u, v] = texcoord(x, y, rotation, mirrored);
+        vertices[idx * 4..idx * 4 + 4].copy_from_slice(&[x * 2.0 - 1.0, 1.0 - y * 2.0, u, v]);
+    }
+    vertices
+}
+
+// Area of the window the screen gets drawn into: everything below the
+// top margin (the menu bar), minus the padding on every side. Returns
+// x, y, width and height, as taken by glViewport (from the bottom-left).
+pub fn screen_viewport(drawable: (u32, u32), top_margin: u32, padding: u32) -> [i32; 4] {
+    let (width, height) = (drawable.0 as i32, drawable.1 as i32);
+    let padding = padding as i32;
+    [
+        padding,
+        padding,
+        (width - padding * 2).max(1),
+        (height - top_margin as i32 - padding * 2).max(1),
+    ]
+}
+
+// Reads back the window's current (not yet swapped) frame, bottom row
+// first, as BGR. Areas outside the window are left black, in case it
+// shrank since the recording started.
+pub unsafe fn read_window(
+    gl: &glow::Context,
+    drawable: (u32, u32),
+    size: (usize, usize),
+) -> Vec<u8> {
+    let (width, height) = size;
+    let mut frame = vec![0; width * height * 3];
+    let read_width = width.min(drawable.0 as usize);
+    let read_height = height.min(drawable.1 as usize);
+    if read_width == 0 || read_height == 0 {
+        return frame;
+    }
+
+    let mut pixels = vec![0; read_width * read_height * 3];
+    gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+    gl.read_pixels(
+        0,
+        0,
+        read_width as i32,
+        read_height as i32,
+        glow::BGR,
+        glow::UNSIGNED_BYTE,
+        glow::PixelPackData::Slice(&mut pixels),
+    );
+    for (row, pixels_row) in frame
+        .chunks_exact_mut(width * 3)
+        .zip(pixels.chunks_exact(read_width * 3))
+    {
+        row[..read_width * 3].copy_from_slice(pixels_row);
+    }
+    frame
+}
+
+// Draws the screen texture over the given viewport.
+pub unsafe fn draw_screen(gl: &glow::Context, viewport: [i32; 4]) {
+    let [x, y, width, height] = viewport;
+    gl.viewport(x, y, width, height);
+    gl.draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_INT, 0);
+}
+
+// Opacity of the lines between pixels, 0 to hide them.
+pub unsafe fn set_grid_opacity(renderer: &mut AutoRenderer, quad: &ScreenQuad, opacity: f32) {
+    renderer
+        .gl_context()
+        .uniform_1_f32(quad.grid_opacity.as_ref(), opacity);
+}
+
+pub unsafe fn set_color_adjustment(
+    renderer: &mut AutoRenderer,
+    quad: &ScreenQuad,
+    adjustment: ColorAdjustment,
+) {
+    let gl = renderer.gl_context();
+    gl.uniform_1_f32(quad.gamma.as_ref(), adjustment.gamma);
+    gl.uniform_1_f32(quad.brightness.as_ref(), adjustment.brightness);
+    gl.uniform_1_f32(quad.contrast.as_ref(), adjustment.contrast);
+}
+
+pub unsafe fn set_orientation(
+    renderer: &mut AutoRenderer,
+    quad: &ScreenQuad,
+    rotation: Rotation,
+    mirrored: bool,
+) {
+    let vertices = quad_vertices(rotation, mirrored);
+    renderer
+        .gl_context()
+        .bind_buffer(glow::ARRAY_BUFFER, Some(quad.vbo));
+    renderer.gl_context().buffer_data_u8_slice(
+        glow::ARRAY_BUFFER,
+        vertices.align_to::<u8>().1,
+        glow::STATIC_DRAW,
     );
 }
 
 pub unsafe fn setup_opengl(
     renderer: &mut AutoRenderer,
-) -> ([u8; screen::WIDTH * screen::HEIGHT * 3], glow::Texture) {
-    #[rustfmt::skip]
-    let vertices: [f32; 16] = [
-        1.0, GL_VERTEX_TOP_MARGIN, 1.0, 0.0, // Top-right
-        -1.0, GL_VERTEX_TOP_MARGIN, 0.0, 0.0, // Top-left
-        1.0, -1.0, 1.0, 1.0, // Bottom-right
-        -1.0, -1.0, 0.0, 1.0, // Bottom-left
-    ];
+    rotation: Rotation,
+    mirrored: bool,
+) -> ScreenQuad {
+    let vertices = quad_vertices(rotation, mirrored);
     let elements: [u32; 6] = [
         0, 1, 2, // Top-left triangle
         1, 2, 3, // Bottom-right triangle
@@ -95,10 +293,27 @@ pub unsafe fn setup_opengl(
         in vec2 Texcoord;
         out vec4 outColor;
         uniform sampler2D tex;
+        uniform float gridOpacity;
+        uniform float gamma;
+        uniform float brightness;
+        uniform float contrast;
 
         void main()
         {
             outColor = texture(tex, Texcoord);
+
+            // Dark lines between emulated pixels, one display pixel wide.
+            // They fade out when pixels get too small to tell apart.
+            vec2 cells = Texcoord * vec2(textureSize(tex, 0));
+            vec2 lineWidth = fwidth(cells);
+            vec2 cell = fract(cells);
+            float onLine = any(lessThan(cell, lineWidth)) ? 1.0 : 0.0;
+            float fade = 1.0 - smoothstep(0.2, 0.5, max(lineWidth.x, lineWidth.y));
+            outColor.rgb *= 1.0 - onLine * fade * gridOpacity;
+
+            // Same as ColorAdjustment::apply
+            outColor.rgb = clamp((outColor.rgb - 0.5) * contrast + 0.5 + brightness, 0.0, 1.0);
+            outColor.rgb = pow(outColor.rgb, vec3(1.0 / gamma));
         }
     ";
 
@@ -147,7 +362,7 @@ pub unsafe fn setup_opengl(
         .bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
     renderer.gl_context().buffer_data_u8_slice(
         glow::ARRAY_BUFFER,
-        &vertices.align_to::<u8>().1,
+        vertices.align_to::<u8>().1,
         glow::STATIC_DRAW,
     );
 
@@ -158,7 +373,7 @@ pub unsafe fn setup_opengl(
         .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
     renderer.gl_context().buffer_data_u8_slice(
         glow::ELEMENT_ARRAY_BUFFER,
-        &elements.align_to::<u8>().1,
+        elements.align_to::<u8>().1,
         glow::STATIC_DRAW,
     );
 
@@ -166,7 +381,7 @@ pub unsafe fn setup_opengl(
     let pos_attrib = renderer
         .gl_context()
         .get_attrib_location(shader_program, "position")
-        .unwrap() as u32;
+        .unwrap();
     renderer.gl_context().enable_vertex_attrib_array(pos_attrib);
     renderer.gl_context().vertex_attrib_pointer_f32(
         pos_attrib,
@@ -180,7 +395,7 @@ pub unsafe fn setup_opengl(
     let tcoord_attrib = renderer
         .gl_context()
         .get_attrib_location(shader_program, "texcoord")
-        .unwrap() as u32;
+        .unwrap();
     renderer
         .gl_context()
         .enable_vertex_attrib_array(tcoord_attrib);
@@ -212,7 +427,7 @@ pub unsafe fn setup_opengl(
         glow::NEAREST as i32,
     );
 
-    let buffer = [0 as u8; (screen::WIDTH * screen::HEIGHT * 3) as usize];
+    let buffer = [0_u8; (screen::WIDTH * screen::HEIGHT * 3)];
     renderer.gl_context().tex_image_2d(
         glow::TEXTURE_2D,
         0,
@@ -225,5 +440,95 @@ pub unsafe fn setup_opengl(
         Some(&buffer),
     );
 
-    (buffer, tex)
+    let grid_opacity = renderer
+        .gl_context()
+        .get_uniform_location(shader_program, "gridOpacity");
+    renderer
+        .gl_context()
+        .uniform_1_f32(grid_opacity.as_ref(), 0.0);
+    let uniform = |name| {
+        renderer
+            .gl_context()
+            .get_uniform_location(shader_program, name)
+    };
+    let quad = ScreenQuad {
+        texture: tex,
+        vbo,
+        grid_opacity,
+        gamma: uniform("gamma"),
+        brightness: uniform("brightness"),
+        contrast: uniform("contrast"),
+    };
+    set_color_adjustment(renderer, &quad, ColorAdjustment::default());
+    quad
+}
+
+#[cfg(test)]
+mod graphics_tests {
+    use super::{
+        quad_vertices, screen_viewport, texcoord, Border, ColorAdjustment, Rotation, ROTATIONS,
+    };
+
+    #[test]
+    fn test_default_quad() {
+        #[rustfmt::skip]
+        let expected: [f32; 16] = [
+            1.0, 1.0, 1.0, 0.0,
+            -1.0, 1.0, 0.0, 0.0,
+            1.0, -1.0, 1.0, 1.0,
+            -1.0, -1.0, 0.0, 1.0,
+        ];
+        assert_eq!(quad_vertices(Rotation::None, false), expected);
+    }
+
+    #[test]
+    fn test_screen_viewport() {
+        assert_eq!(screen_viewport((1280, 679), 39, 0), [0, 0, 1280, 640]);
+        assert_eq!(screen_viewport((1280, 679), 39, 20), [20, 20, 1240, 600]);
+        // Never collapses, however large the padding
+        assert_eq!(screen_viewport((100, 100), 0, 80), [80, 80, 1, 1]);
+    }
+
+    #[test]
+    fn test_border_color() {
+        let off_pixels = [0.2, 0.3, 0.4];
+        assert_eq!(Border::OffPixels.color(off_pixels), off_pixels);
+        assert_eq!(Border::Color([1.0; 3]).color(off_pixels), [1.0; 3]);
+    }
+
+    #[test]
+    fn test_color_adjustment() {
+        let color = [0.0, 0.25, 1.0];
+        assert_eq!(ColorAdjustment::default().apply(color), color);
+
+        let adjustment = |gamma, brightness, contrast| ColorAdjustment {
+            gamma,
+            brightness,
+            contrast,
+        };
+        assert_eq!(adjustment(1.0, 0.25, 1.0).apply(color), [0.25, 0.5, 1.0]);
+        assert_eq!(adjustment(1.0, 0.0, 2.0).apply(color), [0.0, 0.0, 1.0]);
+        assert_eq!(adjustment(2.0, 0.0, 1.0).apply(color), [0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_texcoords() {
+        // The screen's top-left corner ends up at the top-right
+        assert_eq!(texcoord(1.0, 0.0, Rotation::Cw90, false), [0.0, 0.0]);
+        assert_eq!(texcoord(1.0, 1.0, Rotation::Cw180, false), [0.0, 0.0]);
+        assert_eq!(texcoord(0.0, 1.0, Rotation::Cw270, false), [0.0, 0.0]);
+        // Mirroring swaps left and right
+        assert_eq!(texcoord(1.0, 0.0, Rotation::None, true), [0.0, 0.0]);
+        assert_eq!(texcoord(0.0, 0.0, Rotation::Cw90, true), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_degrees() {
+        for rotation in ROTATIONS {
+            assert_eq!(Rotation::from_degrees(rotation.degrees()), Some(rotation));
+        }
+        assert_eq!(Rotation::from_degrees(45), None);
+        assert!(Rotation::Cw270.swaps_axes());
+        assert!(!Rotation::Cw180.swaps_axes());
+    }
 }
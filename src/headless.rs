@@ -0,0 +1,802 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builtin_roms,
+    cli::{Args, BatchArgs, StopConditions, TestArgs, TraceGenArgs},
+    core::{
+        beep::FRAME_RATE,
+        cartridge,
+        cpu::{Cpu, Fault},
+        hash,
+        json::Json,
+        json_state,
+        registers::{DELAY_TIMER, SOUND_TIMER},
+        test_script,
+        trace::Tracer,
+        watchdog::Watchdog,
+    },
+    PROGRAM_BEGIN,
+};
+
+const TRACE_FORMAT: &str = "chip-8-trace";
+const TRACE_VERSION: u64 = 1;
+
+// Why a headless run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Frames, // Ran every requested frame
+    Cycles,
+    Pc,
+    ScreenStable,
+    Memory,
+    Breakpoint, // Halt conditions
+    Write,
+    Fault(Fault),
+    Watchdog,
+}
+
+impl Stop {
+    fn name(&self) -> &'static str {
+        match self {
+            Stop::Frames => "frames",
+            Stop::Cycles => "cycles",
+            Stop::Pc => "pc",
+            Stop::ScreenStable => "screen_stable",
+            Stop::Memory => "memory",
+            Stop::Breakpoint => "breakpoint",
+            Stop::Write => "write",
+            Stop::Fault(fault) => fault.name(),
+            Stop::Watchdog => "watchdog",
+        }
+    }
+}
+
+// How a headless run went, as told to scripts by the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Timeout, // The stop conditions weren't met in time, or the watchdog expired
+    Halt,    // A halt condition was hit
+    BadOpcode,
+    StackFault,
+    BadAddress,
+}
+
+impl Outcome {
+    fn new(stop: Stop, conditions: &StopConditions) -> Outcome {
+        match stop {
+            Stop::Frames if !conditions.is_empty() => Outcome::Timeout,
+            Stop::Watchdog => Outcome::Timeout,
+            Stop::Breakpoint | Stop::Write => Outcome::Halt,
+            Stop::Fault(Fault::BadOpcode) => Outcome::BadOpcode,
+            Stop::Fault(Fault::StackOverflow | Fault::StackUnderflow) => Outcome::StackFault,
+            Stop::Fault(Fault::BadAddress) => Outcome::BadAddress,
+            _ => Outcome::Success,
+        }
+    }
+
+    // 1 and 2 are taken by errors running it and invalid arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::Timeout => 3,
+            Outcome::Halt => 4,
+            Outcome::BadOpcode => 5,
+            Outcome::StackFault => 6,
+            Outcome::BadAddress => 7,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Timeout => "timeout",
+            Outcome::Halt => "halt",
+            Outcome::BadOpcode => "bad_opcode",
+            Outcome::StackFault => "stack_fault",
+            Outcome::BadAddress => "bad_address",
+        }
+    }
+}
+
+// How many runs of a batch ended some way, and the seed of the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tally {
+    count: u64,
+    first_seed: u64,
+}
+
+// How the runs of a batch ended.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BatchSummary {
+    stops: BTreeMap<&'static str, Tally>,
+    screens: BTreeMap<u64, Tally>, // By screen hash
+}
+
+// Counts a run under key.
+fn count<K: Ord>(tallies: &mut BTreeMap<K, Tally>, key: K, seed: u64) {
+    tallies
+        .entry(key)
+        .and_modify(|tally| tally.count += 1)
+        .or_insert(Tally {
+            count: 1,
+            first_seed: seed,
+        });
+}
+
+// Most common first.
+fn by_count<K: Copy>(tallies: &BTreeMap<K, Tally>) -> Vec<(K, Tally)> {
+    let mut sorted: Vec<(K, Tally)> = tallies.iter().map(|(&key, &tally)| (key, tally)).collect();
+    sorted.sort_by_key(|(_, tally)| std::cmp::Reverse(tally.count));
+    sorted
+}
+
+// What execute() ran into.
+#[derive(Debug, PartialEq, Eq)]
+struct Execution {
+    stop: Stop,
+    cycles: u64,
+}
+
+// Runs the ROM without a window, for scripts. Frames run in real time,
+// as they would in the window. With stop conditions, a summary
+// of the final state gets printed as JSON.
+pub fn run(args: &Args) -> Result<Outcome, String> {
+    let rom = read_rom(&args.roms[0])?;
+    let mut cpu = Cpu::new();
+    cpu.disable_beep();
+    cpu.load_rom(&rom, PROGRAM_BEGIN)
+        .map_err(|err| err.to_string())?;
+    if let Some(path) = args.load_state.as_deref() {
+        let state =
+            fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        json_state::load(&mut cpu, &state).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+
+    let mut tracer = match args.trace.as_deref() {
+        Some(path) => Some(Tracer::new(open_output(path)?, &cpu)),
+        None => None,
+    };
+
+    let watchdog = args.watchdog();
+    let execution = execute(
+        &mut cpu,
+        args.frames,
+        &args.stop,
+        &watchdog,
+        tracer.as_mut(),
+        true,
+    )
+    .map_err(|err| format!("Could not write the trace: {}", err))?;
+    let outcome = Outcome::new(execution.stop, &args.stop);
+
+    if let Some(tracer) = tracer.as_mut() {
+        tracer
+            .flush()
+            .map_err(|err| format!("Could not write the trace: {}", err))?;
+    }
+    if !args.stop.is_empty() {
+        println!("{}", state_json(&cpu, execution.stop, execution.cycles));
+    }
+    if let Some(path) = args.dump_state.as_deref() {
+        let mut output = open_output(path)?;
+        writeln!(output, "{}", json_state::save(&cpu))
+            .and_then(|_| output.flush())
+            .map_err(|err| format!("Could not write the state: {}", err))?;
+    }
+    if let Some(path) = args.json_result.as_deref() {
+        let mut output = open_output(path)?;
+        writeln!(output, "{}", result_json(&cpu, &execution, outcome))
+            .and_then(|_| output.flush())
+            .map_err(|err| format!("Could not write the result: {}", err))?;
+    }
+    Ok(outcome)
+}
+
+// Writes the canonical trace of a ROM: a header line identifying the
+// run, then the trace lines. Runs are reproducible, as the seed is
+// fixed, timers count frames and there's no input.
+pub fn trace_gen(args: &TraceGenArgs) -> Result<(), String> {
+    let rom = read_rom(&args.rom)?;
+    let output = open_output(args.output.as_deref().unwrap_or(Path::new("-")))?;
+    generate_trace(&rom, args.cycles, args.seed, output)
+}
+
+// Runs the ROM's tests, printing how each went. Returns whether they
+// all passed.
+pub fn test(args: &TestArgs) -> Result<bool, String> {
+    let rom = read_rom(&args.rom)?;
+    let path = &args.tests;
+    let script = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let tests =
+        test_script::parse(&script).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    let results =
+        test_script::run_all(&tests, &rom, PROGRAM_BEGIN).map_err(|err| err.to_string())?;
+    let mut failed = 0;
+    for (test, failures) in tests.iter().zip(results) {
+        if failures.is_empty() {
+            println!("test {} ... ok", test.name);
+        } else {
+            failed += 1;
+            println!("test {} ... FAILED", test.name);
+            for failure in failures {
+                println!("    {}", failure);
+            }
+        }
+    }
+    println!("\n{} passed, {} failed", tests.len() - failed, failed);
+    Ok(failed == 0)
+}
+
+// Runs the ROM once per seed and prints how the runs ended.
+pub fn batch(args: &BatchArgs) -> Result<(), String> {
+    let rom = read_rom(&args.rom)?;
+    let summary = run_batch(&rom, args.first_seed, args.runs, args.frames)?;
+
+    println!(
+        "{} runs, seeds {} to {}, {} frames each\n",
+        args.runs,
+        args.first_seed,
+        args.first_seed + args.runs - 1,
+        args.frames
+    );
+    println!("Stops:");
+    for (name, tally) in by_count(&summary.stops) {
+        println!(
+            "  {:<16}{:>8}  (first seed {})",
+            name, tally.count, tally.first_seed
+        );
+    }
+    println!("\nScreens ({} different):", summary.screens.len());
+    for (hash, tally) in by_count(&summary.screens) {
+        println!(
+            "  {:016x}{:>8}  (first seed {})",
+            hash, tally.count, tally.first_seed
+        );
+    }
+
+    if let Some(path) = args.json_result.as_deref() {
+        let mut output = open_output(path)?;
+        writeln!(output, "{}", batch_json(&summary, args))
+            .and_then(|_| output.flush())
+            .map_err(|err| format!("Could not write the result: {}", err))?;
+    }
+    Ok(())
+}
+
+// Runs as fast as possible and without input, so each run only
+// depends on its seed.
+fn run_batch(rom: &[u8], first_seed: u64, runs: u64, frames: u64) -> Result<BatchSummary, String> {
+    let mut summary = BatchSummary::default();
+    let watchdog = Watchdog::new(None, None);
+    for seed in first_seed..first_seed + runs {
+        let mut cpu = Cpu::new();
+        cpu.disable_beep();
+        cpu.seed_rng(seed);
+        cpu.load_rom(rom, PROGRAM_BEGIN)
+            .map_err(|err| err.to_string())?;
+        let execution = execute(
+            &mut cpu,
+            frames,
+            &StopConditions::default(),
+            &watchdog,
+            None::<&mut Tracer<io::Sink>>,
+            false,
+        )
+        .map_err(|err| err.to_string())?;
+        count(&mut summary.stops, execution.stop.name(), seed);
+        count(&mut summary.screens, cpu.screen.hash(), seed);
+    }
+    Ok(summary)
+}
+
+// The batch --json-result summary.
+fn batch_json(summary: &BatchSummary, args: &BatchArgs) -> Json {
+    let number = |value: u64| Json::Number(value as f64);
+    let tally = |key: (String, Json), tally: Tally| {
+        Json::Object(vec![
+            key,
+            ("count".to_string(), number(tally.count)),
+            ("first_seed".to_string(), number(tally.first_seed)),
+        ])
+    };
+    let stops = by_count(&summary.stops)
+        .into_iter()
+        .map(|(name, count)| tally(("stop".to_string(), Json::String(name.to_string())), count))
+        .collect();
+    let screens = by_count(&summary.screens)
+        .into_iter()
+        .map(|(hash, count)| {
+            tally(
+                (
+                    "screen_hash".to_string(),
+                    Json::String(format!("{:016x}", hash)),
+                ),
+                count,
+            )
+        })
+        .collect();
+    Json::Object(vec![
+        ("runs".to_string(), number(args.runs)),
+        ("first_seed".to_string(), number(args.first_seed)),
+        ("frames".to_string(), number(args.frames)),
+        ("stops".to_string(), Json::Array(stops)),
+        ("screens".to_string(), Json::Array(screens)),
+    ])
+}
+
+fn generate_trace<W: Write>(
+    rom: &[u8],
+    cycles: u64,
+    seed: u64,
+    mut output: W,
+) -> Result<(), String> {
+    let mut cpu = Cpu::new();
+    cpu.disable_beep();
+    cpu.seed_rng(seed);
+    cpu.load_rom(rom, PROGRAM_BEGIN)
+        .map_err(|err| err.to_string())?;
+
+    let header = Json::Object(vec![
+        ("format".to_string(), Json::String(TRACE_FORMAT.to_string())),
+        ("version".to_string(), Json::Number(TRACE_VERSION as f64)),
+        (
+            "rom".to_string(),
+            Json::String(format!("{:016x}", hash::hash_bytes(rom))),
+        ),
+        ("seed".to_string(), Json::String(seed.to_string())),
+        ("cycles".to_string(), Json::Number(cycles as f64)),
+        (
+            "ticks_per_frame".to_string(),
+            Json::Number(cpu.ticks_per_frame as f64),
+        ),
+        ("quirks".to_string(), Json::Number(cpu.quirk_bits() as f64)),
+    ]);
+    let write_error = |err: io::Error| format!("Could not write the trace: {}", err);
+    writeln!(output, "{}", header).map_err(write_error)?;
+
+    let mut tracer = Tracer::new(output, &cpu);
+    let mut result = Ok(());
+    while tracer.cycle() < cycles && result.is_ok() {
+        cpu.tick_with(|cpu| {
+            result = tracer.record(cpu);
+            result.is_ok() && tracer.cycle() < cycles
+        });
+    }
+    result.and_then(|_| tracer.flush()).map_err(write_error)
+}
+
+// Runs up to the given amount of frames, or until a stop condition is
+// met, or the program faults, or the watchdog expires.
+fn execute<W: Write>(
+    cpu: &mut Cpu,
+    frames: u64,
+    conditions: &StopConditions,
+    watchdog: &Watchdog,
+    mut tracer: Option<&mut Tracer<W>>,
+    real_time: bool,
+) -> io::Result<Execution> {
+    let mut cycles = 0;
+    let execution = |stop, cycles| Execution { stop, cycles };
+    // Writes are told apart by the memory access counts
+    if !conditions.break_on_write.is_empty() {
+        cpu.set_memory_access_counting(true);
+        cpu.clear_memory_accesses();
+    }
+    if let Some(stop) = step_stop(cpu, conditions, cycles) {
+        return Ok(execution(stop, cycles));
+    }
+
+    let frame_duration = Duration::from_secs_f64(1.0 / FRAME_RATE);
+    let mut next_frame = Instant::now();
+    let mut last_screen = cpu.screen.words().to_vec();
+    let mut stable_frames = 0;
+
+    for _ in 0..frames {
+        let mut result = Ok(());
+        let mut stop = None;
+        cpu.tick_with(|cpu| {
+            cycles += 1;
+            if let Some(tracer) = tracer.as_mut() {
+                result = tracer.record(cpu);
+            }
+            stop = step_stop(cpu, conditions, cycles)
+                .or(watchdog.cycles_exceeded(cycles).then_some(Stop::Watchdog));
+            result.is_ok() && stop.is_none()
+        });
+        result?;
+        if let Some(stop) = stop {
+            return Ok(execution(stop, cycles));
+        }
+
+        if cpu.screen.words() == last_screen {
+            stable_frames += 1;
+        } else {
+            stable_frames = 0;
+            last_screen = cpu.screen.words().to_vec();
+        }
+        if conditions
+            .stable_frames
+            .is_some_and(|frames| stable_frames >= frames)
+        {
+            return Ok(execution(Stop::ScreenStable, cycles));
+        }
+        if watchdog.time_exceeded(Instant::now()) {
+            return Ok(execution(Stop::Watchdog, cycles));
+        }
+
+        if real_time {
+            next_frame += frame_duration;
+            thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+        }
+    }
+    Ok(execution(Stop::Frames, cycles))
+}
+
+// The conditions checked between instructions.
+fn step_stop(cpu: &Cpu, conditions: &StopConditions, cycles: u64) -> Option<Stop> {
+    if let Some(fault) = cpu.last_fault() {
+        return Some(Stop::Fault(fault));
+    }
+    if conditions.cycles.is_some_and(|max| cycles >= max) {
+        return Some(Stop::Cycles);
+    }
+    if conditions.pc == Some(cpu.registers.pc) {
+        return Some(Stop::Pc);
+    }
+    if let Some((addr, data)) = conditions.memory {
+        if cpu.memory.peek(addr) == Some(data) {
+            return Some(Stop::Memory);
+        }
+    }
+    if conditions.break_at.contains(&cpu.registers.pc) {
+        return Some(Stop::Breakpoint);
+    }
+    if let Some(accesses) = cpu.memory_accesses() {
+        if conditions
+            .break_on_write
+            .iter()
+            .any(|&addr| accesses.writes(addr) > 0)
+        {
+            return Some(Stop::Write);
+        }
+    }
+    None
+}
+
+// Registers and screen (one string per row, # for lit pixels).
+fn state_json(cpu: &Cpu, stop: Stop, cycles: u64) -> String {
+    let registers = &cpu.registers;
+    let join = |values: Vec<String>| values.join(",");
+    let rows: Vec<String> = (0..cpu.screen.height())
+        .map(|y| {
+            let row: String = (0..cpu.screen.width())
+                .map(|x| if cpu.screen.get(x, y) { '#' } else { '.' })
+                .collect();
+            format!("\"{}\"", row)
+        })
+        .collect();
+
+    format!(
+        "{{\"stop\":\"{}\",\"cycles\":{},\"frame\":{},\"pc\":{},\"i\":{},\"sp\":{},\"dt\":{},\"st\":{},\"v\":[{}],\"stack\":[{}],\"screen\":[{}]}}",
+        stop.name(),
+        cycles,
+        cpu.frame(),
+        registers.pc,
+        registers.i,
+        registers.sp,
+        registers.timers[DELAY_TIMER].read(),
+        registers.timers[SOUND_TIMER].read(),
+        join(registers.v.iter().map(u8::to_string).collect()),
+        join(registers.stack.iter().map(u16::to_string).collect()),
+        join(rows)
+    )
+}
+
+// The --json-result summary.
+fn result_json(cpu: &Cpu, execution: &Execution, outcome: Outcome) -> Json {
+    let number = |value: u64| Json::Number(value as f64);
+    let stats = cpu.stats();
+    Json::Object(vec![
+        (
+            "result".to_string(),
+            Json::String(outcome.name().to_string()),
+        ),
+        ("exit_code".to_string(), number(outcome.exit_code() as u64)),
+        (
+            "stop".to_string(),
+            Json::String(execution.stop.name().to_string()),
+        ),
+        ("cycles".to_string(), number(execution.cycles)),
+        ("frames".to_string(), number(stats.frames)),
+        ("draws".to_string(), number(stats.draws)),
+        ("key_events".to_string(), number(stats.key_events)),
+        ("pc".to_string(), number(cpu.registers.pc as u64)),
+        (
+            "screen_hash".to_string(),
+            Json::String(format!("{:016x}", cpu.screen.hash())),
+        ),
+        ("sound_frames".to_string(), number(stats.sound_frames)),
+        (
+            "sound_seconds".to_string(),
+            Json::Number(stats.sound_seconds()),
+        ),
+    ])
+}
+
+fn read_rom(rom_path: &Path) -> Result<Vec<u8>, String> {
+    match builtin_roms::from_path(rom_path) {
+        Some(builtin) => Ok(builtin.data.to_vec()),
+        None => {
+            let rom =
+                fs::read(rom_path).map_err(|err| format!("{}: {}", rom_path.display(), err))?;
+            if cartridge::is_cartridge(rom_path) {
+                cartridge::read_rom(&rom).map_err(|err| format!("{}: {}", rom_path.display(), err))
+            } else {
+                Ok(rom)
+            }
+        }
+    }
+}
+
+fn open_output(path: &Path) -> Result<Box<dyn Write>, String> {
+    if path == Path::new("-") {
+        return Ok(Box::new(BufWriter::new(io::stdout().lock())));
+    }
+    let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+#[cfg(test)]
+mod headless_tests {
+    use std::{io::Sink, time::Duration};
+
+    use std::path::PathBuf;
+
+    use super::{
+        batch_json, by_count, execute, generate_trace, result_json, run_batch, state_json, Outcome,
+        Stop,
+    };
+    use crate::{
+        cli::{BatchArgs, StopConditions},
+        core::{cpu::Cpu, cpu::Fault, json::Json, trace::Tracer, watchdog::Watchdog},
+    };
+
+    // LD V0, 1 - LD I, 0x300 - LD [I], V0 - ADD V1, 1 - JP 0x206
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            &[0x60, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x71, 0x01, 0x12, 0x06],
+            0x0200,
+        )
+        .unwrap();
+        cpu
+    }
+
+    fn run_watched(conditions: StopConditions, watchdog: &Watchdog) -> (Stop, u64) {
+        let execution = execute(
+            &mut cpu(),
+            3,
+            &conditions,
+            watchdog,
+            None::<&mut Tracer<Sink>>,
+            false,
+        )
+        .unwrap();
+        (execution.stop, execution.cycles)
+    }
+
+    fn run(conditions: StopConditions) -> (Stop, u64) {
+        run_watched(conditions, &Watchdog::new(None, None))
+    }
+
+    #[test]
+    fn test_stop_conditions() {
+        assert_eq!(run(StopConditions::default()), (Stop::Frames, 30));
+
+        let cycles = StopConditions {
+            cycles: Some(15),
+            ..StopConditions::default()
+        };
+        assert_eq!(run(cycles), (Stop::Cycles, 15));
+
+        let pc = StopConditions {
+            pc: Some(0x208),
+            ..StopConditions::default()
+        };
+        assert_eq!(run(pc), (Stop::Pc, 4));
+
+        let memory = StopConditions {
+            memory: Some((0x300, 1)),
+            ..StopConditions::default()
+        };
+        assert_eq!(run(memory), (Stop::Memory, 3));
+
+        // Nothing is ever drawn
+        let stable = StopConditions {
+            stable_frames: Some(2),
+            ..StopConditions::default()
+        };
+        assert_eq!(run(stable), (Stop::ScreenStable, 20));
+    }
+
+    #[test]
+    fn test_halt_conditions() {
+        let breakpoint = StopConditions {
+            break_at: vec![0x300, 0x206],
+            ..StopConditions::default()
+        };
+        assert_eq!(run(breakpoint), (Stop::Breakpoint, 3));
+
+        // Stops right after LD [I], V0
+        let write = StopConditions {
+            break_on_write: vec![0x300],
+            ..StopConditions::default()
+        };
+        assert_eq!(run(write), (Stop::Write, 3));
+
+        let never_written = StopConditions {
+            break_on_write: vec![0x301],
+            ..StopConditions::default()
+        };
+        assert_eq!(run(never_written), (Stop::Frames, 30));
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let cycles = Watchdog::new(None, Some(12));
+        assert_eq!(
+            run_watched(StopConditions::default(), &cycles),
+            (Stop::Watchdog, 12)
+        );
+        // Checked after each frame
+        let time = Watchdog::new(Some(Duration::ZERO), None);
+        assert_eq!(
+            run_watched(StopConditions::default(), &time),
+            (Stop::Watchdog, 10)
+        );
+        assert_eq!(
+            Outcome::new(Stop::Watchdog, &StopConditions::default()).exit_code(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_outcome() {
+        let none = StopConditions::default();
+        let some = StopConditions {
+            cycles: Some(10),
+            ..StopConditions::default()
+        };
+        let outcome = |stop, conditions| Outcome::new(stop, conditions).exit_code();
+        assert_eq!(outcome(Stop::Frames, &none), 0);
+        assert_eq!(outcome(Stop::Frames, &some), 3);
+        assert_eq!(outcome(Stop::Cycles, &some), 0);
+        assert_eq!(outcome(Stop::Write, &none), 4);
+        assert_eq!(outcome(Stop::Fault(Fault::BadOpcode), &none), 5);
+        assert_eq!(outcome(Stop::Fault(Fault::StackUnderflow), &some), 6);
+        assert_eq!(outcome(Stop::Fault(Fault::BadAddress), &none), 7);
+    }
+
+    #[test]
+    fn test_result_json() {
+        // LD V0, 30 - LD ST, V0 - CALL 0x204 (until the stack overflows)
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x60, 0x1E, 0xF0, 0x18, 0x22, 0x04], 0x0200)
+            .unwrap();
+        cpu.ticks_per_frame = 4;
+        let conditions = StopConditions::default();
+        let execution = execute(
+            &mut cpu,
+            60,
+            &conditions,
+            &Watchdog::new(None, None),
+            None::<&mut Tracer<Sink>>,
+            false,
+        )
+        .unwrap();
+        assert_eq!(execution.stop, Stop::Fault(Fault::StackOverflow));
+        // 2 instructions, 16 calls, then the one that doesn't fit
+        assert_eq!(execution.cycles, 19);
+
+        let json = result_json(&cpu, &execution, Outcome::new(execution.stop, &conditions));
+        let field = |name| json.get(name).cloned().unwrap();
+        assert_eq!(field("result"), Json::String("stack_fault".to_string()));
+        assert_eq!(field("exit_code").as_u64(), Some(6));
+        assert_eq!(field("stop"), Json::String("stack_overflow".to_string()));
+        assert_eq!(field("pc").as_u64(), Some(0x204));
+        assert_eq!(field("frames").as_u64(), Some(5));
+        assert_eq!(field("draws").as_u64(), Some(0));
+        assert_eq!(field("sound_frames").as_u64(), Some(5));
+        assert_eq!(field("screen_hash").as_str().map(str::len), Some(16));
+    }
+
+    #[test]
+    fn test_generate_trace() {
+        // RND V0, 0xFF - LD DT, V0 - LD V1, DT - JP 0x200
+        let rom = [0xC0, 0xFF, 0xF0, 0x15, 0xF1, 0x07, 0x12, 0x00];
+        let trace = |seed| {
+            let mut output = Vec::new();
+            generate_trace(&rom, 25, seed, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let first = trace(7);
+        assert_eq!(first, trace(7));
+        assert_ne!(first, trace(8));
+
+        let lines: Vec<&str> = first.lines().collect();
+        assert_eq!(lines.len(), 26);
+        assert!(lines[0].starts_with(r#"{"format":"chip-8-trace","version":1,"rom":""#));
+        assert!(lines[0].contains(r#""seed":"7","cycles":25,"#));
+        assert!(lines[25].starts_with(r#"{"cycle":24,"#));
+    }
+
+    #[test]
+    fn test_batch() {
+        // RND V0, 0x01 - SE V0, 0 - RET (underflows) - JP 0x206
+        let rom = [0xC0, 0x01, 0x30, 0x00, 0x00, 0xEE, 0x12, 0x06];
+        let summary = run_batch(&rom, 5, 40, 2).unwrap();
+        let counts: Vec<u64> = summary.stops.values().map(|tally| tally.count).collect();
+        assert_eq!(counts.iter().sum::<u64>(), 40);
+        // Both branches get taken over that many seeds
+        assert_eq!(
+            summary.stops.keys().copied().collect::<Vec<_>>(),
+            vec!["frames", "stack_underflow"]
+        );
+        assert!(summary.stops.values().all(|tally| tally.first_seed >= 5));
+        assert_eq!(summary.screens.len(), 1);
+        assert_eq!(summary, run_batch(&rom, 5, 40, 2).unwrap());
+
+        let args = BatchArgs {
+            rom: PathBuf::from("game.ch8"),
+            runs: 40,
+            first_seed: 5,
+            frames: 2,
+            json_result: None,
+        };
+        let json = batch_json(&summary, &args);
+        let stops = json
+            .get("stops")
+            .and_then(|stops| stops.as_array())
+            .unwrap();
+        let most_common = by_count(&summary.stops)[0];
+        assert_eq!(
+            stops[0].get("stop").and_then(|stop| stop.as_str()),
+            Some(most_common.0)
+        );
+        assert_eq!(
+            stops[0].get("count").and_then(|count| count.as_u64()),
+            Some(most_common.1.count)
+        );
+        assert_eq!(json.get("runs").and_then(|runs| runs.as_u64()), Some(40));
+    }
+
+    #[test]
+    fn test_state_json() {
+        let mut cpu = cpu();
+        cpu.screen.set(1, 0, true);
+        let json = state_json(&cpu, Stop::Cycles, 7);
+        assert!(json.starts_with(r#"{"stop":"cycles","cycles":7,"frame":0,"pc":512,"i":0,"#));
+        assert!(json.contains(r#""v":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]"#));
+        assert!(json.contains(r#""screen":[".#.."#));
+        assert!(json.ends_with(r#"...."]}"#));
+    }
+}
@@ -0,0 +1,115 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    builtin_roms::{BuiltinRom, BUILTIN_ROMS},
+    locale::tr,
+};
+
+// COSMAC VIP keypad, as laid out on the keyboard (see keypad_key in main)
+pub const KEYPAD_LAYOUT: [[(u8, &str); 4]; 4] = [
+    [(0x1, "1"), (0x2, "2"), (0x3, "3"), (0xC, "4")],
+    [(0x4, "Q"), (0x5, "W"), (0x6, "E"), (0xD, "R")],
+    [(0x7, "A"), (0x8, "S"), (0x9, "D"), (0xE, "F")],
+    [(0xA, "Z"), (0x0, "X"), (0xB, "C"), (0xF, "V")],
+];
+
+const HOTKEYS: [(&str, &str); 8] = [
+    ("F1", "Show this help"),
+    ("F2", "Next ROM in the playlist"),
+    ("F3", "Next color palette, remembered for the ROM"),
+    ("F4", "Assemble & run, in the code editor"),
+    ("F5-F8", "Play the key macro in that slot"),
+    (
+        "Shift+F5-F8",
+        "Record a key macro into that slot, or stop recording",
+    ),
+    ("F9", "Speedrun split (or start)"),
+    ("F10", "Reset the speedrun timer"),
+];
+
+pub fn draw(ui: &Ui, opened: &mut bool) {
+    ui.window(format!("{}###help", tr("Help")))
+        .opened(opened)
+        .always_auto_resize(true)
+        .build(|| {
+            ui.text(tr("Keypad"));
+            ui.text_disabled(tr("CHIP-8 key / keyboard key"));
+            let size = ui.current_font_size() * 3.5;
+            for row in KEYPAD_LAYOUT {
+                for (idx, (key, keyboard)) in row.iter().enumerate() {
+                    if idx > 0 {
+                        ui.same_line();
+                    }
+                    ui.button_with_size(format!("{:X} / {}", key, keyboard), [size, size]);
+                }
+            }
+
+            ui.separator();
+            ui.text(tr("Hotkeys"));
+            for (key, action) in HOTKEYS {
+                ui.text(key);
+                ui.same_line_with_pos(ui.current_font_size() * 7.0);
+                ui.text(tr(action));
+            }
+        });
+}
+
+// Shown on the first run, pointing to the built-in ROMs. Returns the
+// ROM picked, if any. Closing it in any way clears opened.
+pub fn draw_welcome(ui: &Ui, opened: &mut bool) -> Option<&'static BuiltinRom> {
+    let mut picked = None;
+    let display_size = ui.io().display_size;
+    ui.window(format!("{}###welcome", tr("Welcome")))
+        .opened(opened)
+        .position(
+            [display_size[0] / 2.0, display_size[1] / 2.0],
+            Condition::Appearing,
+        )
+        .position_pivot([0.5, 0.5])
+        .always_auto_resize(true)
+        .collapsible(false)
+        .build(|| {
+            ui.text(tr("No ROM yet? Try one of the built-in ones:"));
+            for rom in &BUILTIN_ROMS {
+                if ui.button(rom.name) {
+                    picked = Some(rom);
+                }
+                ui.same_line();
+                ui.text_disabled(tr(rom.description));
+            }
+            ui.separator();
+            ui.text_wrapped(tr(
+                "They are always available in File > Open built-in. Press F1 for the keypad layout.",
+            ));
+        });
+
+    if picked.is_some() {
+        *opened = false;
+    }
+    picked
+}
+
+#[cfg(test)]
+mod help_tests {
+    use super::KEYPAD_LAYOUT;
+
+    #[test]
+    fn test_keypad_layout_covers_all_keys() {
+        let mut keys: Vec<u8> = KEYPAD_LAYOUT
+            .iter()
+            .flatten()
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, (0..16).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,134 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use imgui::{Condition, Ui};
+use lazy_static::lazy_static;
+
+use crate::{
+    core::{hash::hash_bytes, hex_rom},
+    locale::tr,
+    rom_check::PendingRom,
+};
+
+// Imported ROMs are remembered as "hex:<hash>.ch8" paths, so restarting
+// works the same as with ROMs on disk, until the emulator is closed.
+const PATH_PREFIX: &str = "hex:";
+
+lazy_static! {
+    static ref IMPORTED: Mutex<HashMap<PathBuf, Vec<u8>>> = Mutex::new(HashMap::new());
+}
+
+pub fn import(rom: Vec<u8>) -> PendingRom {
+    let rom_path = PathBuf::from(format!("{}{:016x}.ch8", PATH_PREFIX, hash_bytes(&rom)));
+    IMPORTED
+        .lock()
+        .unwrap()
+        .insert(rom_path.clone(), rom.clone());
+    PendingRom {
+        rom_path,
+        patch_path: None,
+        rom,
+    }
+}
+
+// The ROM an imported path refers to.
+pub fn from_path(path: &Path) -> Option<Vec<u8>> {
+    IMPORTED.lock().unwrap().get(path).cloned()
+}
+
+// Files with these extensions are read as hex text instead of bytes.
+pub fn is_hex_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ["hex", "txt"].contains(&extension.to_lowercase().as_str()))
+}
+
+#[derive(Default)]
+pub struct HexImportState {
+    pub opened: bool,
+    text: String,
+}
+
+// Text box for pasting a ROM as hex, validated as it's typed. Returns
+// the ROM once Load is clicked.
+pub fn draw(ui: &Ui, state: &mut HexImportState) -> Option<PendingRom> {
+    let mut imported = None;
+    let mut opened = state.opened;
+    ui.window(format!("{}###hex_import", tr("Import hex")))
+        .opened(&mut opened)
+        .size([460.0, 320.0], Condition::FirstUseEver)
+        .build(|| {
+            ui.text_wrapped(tr(
+                "Paste a ROM written in hex, e.g. from Octo or a forum post.",
+            ));
+            if ui.button(tr("Open file...")) {
+                let file = rfd::FileDialog::new()
+                    .add_filter("hex", &["hex", "txt"])
+                    .pick_file()
+                    .and_then(|path| fs::read_to_string(path).ok());
+                if let Some(text) = file {
+                    state.text = text;
+                }
+            }
+            ui.same_line();
+            if ui.button(tr("Clear")) {
+                state.text.clear();
+            }
+
+            let height = ui.content_region_avail()[1] - ui.frame_height_with_spacing() * 2.0;
+            ui.input_text_multiline("##hex", &mut state.text, [-1.0, height.max(40.0)])
+                .build();
+
+            let parsed = hex_rom::parse(&state.text);
+            match &parsed {
+                Ok(rom) => ui.text(format!("{} {}", rom.len(), tr("bytes"))),
+                Err(err) => ui.text_colored([1.0, 0.4, 0.4, 1.0], err.to_string()),
+            }
+
+            let disabled = ui.begin_disabled(parsed.is_err());
+            if ui.button(tr("Load")) {
+                if let Ok(rom) = parsed {
+                    imported = Some(import(rom));
+                    state.opened = false;
+                }
+            }
+            disabled.end();
+        });
+    state.opened &= opened;
+    imported
+}
+
+#[cfg(test)]
+mod hex_import_tests {
+    use std::path::Path;
+
+    use super::{from_path, import, is_hex_file};
+
+    #[test]
+    fn test_import() {
+        let pending = import(vec![0x00, 0xE0]);
+        assert!(pending.rom_path.to_str().unwrap().starts_with("hex:"));
+        assert_eq!(from_path(&pending.rom_path), Some(vec![0x00, 0xE0]));
+        assert_eq!(from_path(Path::new("hex:0.ch8")), None);
+    }
+
+    #[test]
+    fn test_hex_files() {
+        assert!(is_hex_file(Path::new("roms/pong.HEX")));
+        assert!(is_hex_file(Path::new("post.txt")));
+        assert!(!is_hex_file(Path::new("roms/pong.ch8")));
+    }
+}
@@ -0,0 +1,65 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::{boot, run_frames, screen_rows};
+
+// Shows 156 as a score would be: its digits stored with BCD, read
+// back into V0 - V2, then each drawn with its font glyph.
+// LD V0, 156 - LD I, 0x300 - LD B, V0 - LD V2, [I] - LD V3, 0 - LD V4, 0
+// LD F, V0 - DRW V3, V4, 5 - ADD V3, 5
+// LD F, V1 - DRW V3, V4, 5 - ADD V3, 5
+// LD F, V2 - DRW V3, V4, 5 - JP 0x21C
+const SCORE: [u8; 30] = [
+    0x60, 0x9C, 0xA3, 0x00, 0xF0, 0x33, 0xF2, 0x65, 0x63, 0x00, 0x64, 0x00, //
+    0xF0, 0x29, 0xD3, 0x45, 0x73, 0x05, //
+    0xF1, 0x29, 0xD3, 0x45, 0x73, 0x05, //
+    0xF2, 0x29, 0xD3, 0x45, 0x12, 0x1C,
+];
+
+#[test]
+fn test_score_display() {
+    let mut machine = boot(&SCORE);
+    run_frames(&mut machine, 3);
+    let cpu = &machine.cpu;
+    assert_eq!(&cpu.registers.v[..3], &[1, 5, 6]);
+    assert_eq!(
+        (0x300..0x303)
+            .map(|addr| cpu.memory.peek(addr))
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(5), Some(6)]
+    );
+    assert_eq!(cpu.registers.v[0xF], 0);
+    assert_eq!(
+        screen_rows(&machine, 15, 6),
+        vec![
+            "..#..####.####.",
+            ".##..#....#....",
+            "..#..####.####.",
+            "..#.....#.#..#.",
+            ".###.####.####.",
+            "...............",
+        ]
+    );
+}
+
+#[test]
+fn test_redraw_erases() {
+    // The score, then the last digit drawn again:
+    // DRW V3, V4, 5 - JP 0x21E
+    let mut rom = SCORE.to_vec();
+    rom.truncate(28);
+    rom.extend_from_slice(&[0xD3, 0x45, 0x12, 0x1E]);
+    let mut machine = boot(&rom);
+    run_frames(&mut machine, 3);
+    assert_eq!(machine.cpu.registers.v[0xF], 1);
+    assert_eq!(
+        screen_rows(&machine, 15, 2),
+        vec!["..#..####......", ".##..#........."]
+    );
+}
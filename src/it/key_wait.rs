@@ -0,0 +1,42 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::{boot, run_frames};
+
+// Waits for a key, then counts in V2 the instructions run while key 5
+// is held:
+// LD V0, K - LD V1, 5 - SKNP V1 - ADD V2, 1 - JP 0x204
+const WAIT_THEN_COUNT: [u8; 10] = [0xF0, 0x0A, 0x61, 0x05, 0xE1, 0xA1, 0x72, 0x01, 0x12, 0x04];
+
+#[test]
+fn test_key_wait_loop() {
+    let mut machine = boot(&WAIT_THEN_COUNT);
+    run_frames(&mut machine, 5);
+    assert_eq!(machine.cpu.registers.pc, 0x200);
+
+    // Fx0A only continues once the key is released
+    machine.cpu.keypad.set_key(0xA, true);
+    run_frames(&mut machine, 2);
+    assert_eq!(machine.cpu.registers.pc, 0x200);
+    machine.cpu.keypad.set_key(0xA, false);
+    run_frames(&mut machine, 2);
+    assert_eq!(machine.cpu.registers.v[0], 0xA);
+    assert_eq!(machine.cpu.registers.v[2], 0);
+
+    machine.cpu.keypad.set_key(5, true);
+    run_frames(&mut machine, 2);
+    let counted = machine.cpu.registers.v[2];
+    assert!(counted > 0);
+
+    machine.cpu.keypad.set_key(5, false);
+    run_frames(&mut machine, 1);
+    let counted = machine.cpu.registers.v[2];
+    run_frames(&mut machine, 2);
+    assert_eq!(machine.cpu.registers.v[2], counted);
+}
@@ -0,0 +1,44 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+// Integration tests: small ROMs run on a whole Machine, frame by frame
+// as the window runs them, checked through what the cpu exposes.
+
+mod bcd_font;
+mod key_wait;
+mod subroutines;
+
+use crate::{machine::Machine, PROGRAM_BEGIN};
+
+// A machine running the ROM, silently.
+fn boot(rom: &[u8]) -> Machine {
+    let mut machine = Machine::new();
+    let cpu = &mut machine.cpu;
+    cpu.disable_beep();
+    cpu.load_rom(rom, PROGRAM_BEGIN).unwrap();
+    machine
+}
+
+fn run_frames(machine: &mut Machine, frames: usize) {
+    for _ in 0..frames {
+        machine.run_frame();
+    }
+}
+
+// The top left of the screen, # for lit pixels.
+fn screen_rows(machine: &Machine, width: usize, height: usize) -> Vec<String> {
+    let screen = &machine.cpu.screen;
+    (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| if screen.get(x, y) { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
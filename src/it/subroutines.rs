@@ -0,0 +1,66 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use super::{boot, run_frames};
+use crate::core::cpu::Fault;
+
+// CALL 0x208 - LD I, 0x300 - LD [I], V0 - JP 0x206
+// 0x208: ADD V0, 1 - CALL 0x20E - RET
+// 0x20E: ADD V0, 1 - CALL 0x214 - RET
+// 0x214: ADD V0, 1 - RET
+const NESTED: [u8; 24] = [
+    0x22, 0x08, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06, //
+    0x70, 0x01, 0x22, 0x0E, 0x00, 0xEE, //
+    0x70, 0x01, 0x22, 0x14, 0x00, 0xEE, //
+    0x70, 0x01, 0x00, 0xEE,
+];
+
+// Calls itself V0 times, counting the calls in V1:
+// LD V0, depth - CALL 0x208 - JP 0x204 - (padding)
+// 0x208: SE V0, 0 - JP 0x20E - RET
+// 0x20E: ADD V0, 0xFF - ADD V1, 1 - CALL 0x208 - RET
+fn recursive(depth: u8) -> [u8; 22] {
+    [
+        0x60, depth, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00, //
+        0x30, 0x00, 0x12, 0x0E, 0x00, 0xEE, //
+        0x70, 0xFF, 0x71, 0x01, 0x22, 0x08, 0x00, 0xEE,
+    ]
+}
+
+#[test]
+fn test_nested_calls() {
+    let mut machine = boot(&NESTED);
+    run_frames(&mut machine, 2);
+    let cpu = &machine.cpu;
+    assert_eq!(cpu.registers.v[0], 3);
+    assert_eq!(cpu.memory.peek(0x300), Some(3));
+    assert_eq!((cpu.registers.pc, cpu.registers.sp), (0x206, 0));
+    assert_eq!(cpu.last_fault(), None);
+}
+
+#[test]
+fn test_recursion_fills_the_stack() {
+    let depth = boot(&[]).cpu.stack_size() as u8 - 1;
+
+    // The first call and depth more use the whole stack
+    let mut machine = boot(&recursive(depth));
+    run_frames(&mut machine, 10);
+    let cpu = &machine.cpu;
+    assert_eq!(cpu.last_fault(), None);
+    assert_eq!(cpu.registers.v[1], depth);
+    assert_eq!((cpu.registers.pc, cpu.registers.sp), (0x204, 0));
+
+    // One more doesn't fit
+    let mut machine = boot(&recursive(depth + 1));
+    run_frames(&mut machine, 10);
+    let cpu = &machine.cpu;
+    assert_eq!(cpu.last_fault(), Some(Fault::StackOverflow));
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.registers.v[1], depth + 1);
+}
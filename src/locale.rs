@@ -0,0 +1,616 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// UI strings are written in English and used as the lookup key in
+// every other language's table. Missing translations fall back to
+// the English text, so tables can be completed gradually.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+}
+
+pub const LANGUAGES: [Language; 2] = [Language::English, Language::Spanish];
+
+static CURRENT_LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+impl Language {
+    // Name shown in the language picker, in the language itself.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        LANGUAGES
+            .into_iter()
+            .find(|language| language.code() == code)
+    }
+
+    fn table(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::Spanish => SPANISH,
+        }
+    }
+}
+
+pub fn language() -> Language {
+    LANGUAGES[CURRENT_LANGUAGE.load(Ordering::Relaxed) as usize]
+}
+
+pub fn set_language(language: Language) {
+    let idx = LANGUAGES.iter().position(|&l| l == language).unwrap();
+    CURRENT_LANGUAGE.store(idx as u8, Ordering::Relaxed);
+}
+
+// Translates text into the current language.
+pub fn tr(text: &'static str) -> &'static str {
+    language()
+        .table()
+        .iter()
+        .find(|(key, _)| *key == text)
+        .map_or(text, |(_, translation)| translation)
+}
+
+const SPANISH: &[(&str, &str)] = &[
+    // File menu
+    ("File", "Archivo"),
+    ("Load ROM", "Cargar ROM"),
+    ("Load ROM with patch...", "Cargar ROM con parche..."),
+    ("Open built-in", "Abrir incluida"),
+    ("Generating preview...", "Generando vista previa..."),
+    ("Draws the CHIP-8 logo", "Dibuja el logo de CHIP-8"),
+    (
+        "Checks a few instructions, E marks a failure",
+        "Comprueba algunas instrucciones, E marca un fallo",
+    ),
+    ("Catch the ball with Q and E", "Atrapa la pelota con Q y E"),
+    ("Restart ROM", "Reiniciar ROM"),
+    ("Export ROM", "Exportar ROM"),
+    ("As loaded...", "Tal como se cargó..."),
+    ("Trimmed of trailing zeros...", "Sin ceros finales..."),
+    ("Save state...", "Guardar estado..."),
+    ("Load state...", "Cargar estado..."),
+    ("Close ROM", "Cerrar ROM"),
+    ("Playlist", "Lista de reproducción"),
+    ("Add ROMs...", "Añadir ROMs..."),
+    ("Next ROM", "Siguiente ROM"),
+    ("Auto-advance (seconds)", "Avance automático (segundos)"),
+    ("Clear", "Vaciar"),
+    ("Replay", "Repetición"),
+    ("Start recording", "Empezar a grabar"),
+    ("Stop recording & save", "Detener grabación y guardar"),
+    ("Play replay", "Reproducir repetición"),
+    ("Stop playback", "Detener reproducción"),
+    ("Exit", "Salir"),
+    ("Video", "Vídeo"),
+    ("Record screen...", "Grabar pantalla..."),
+    ("Record window with panels...", "Grabar ventana con paneles..."),
+    ("Stop recording", "Detener grabación"),
+    // Machines menu and tabs
+    ("Machines", "Máquinas"),
+    ("New machine", "Nueva máquina"),
+    ("Close machine", "Cerrar máquina"),
+    ("Empty", "Vacía"),
+    // Options menu
+    ("Options", "Opciones"),
+    ("Main options", "Opciones principales"),
+    ("Timings & display", "Tiempos y pantalla"),
+    (
+        "Emulation and draw timings",
+        "Tiempos de emulación y dibujado",
+    ),
+    ("Draws per second", "Dibujados por segundo"),
+    ("Reduce flashing", "Reducir destellos"),
+    (
+        "Limits frames flipping most of the screen to 3 a second.",
+        "Limita a 3 por segundo los fotogramas que invierten casi toda la pantalla.",
+    ),
+    ("Flashing reduced", "Destellos reducidos"),
+    ("COSMAC VIP timing", "Tiempos del COSMAC VIP"),
+    (
+        "Instructions take as long as on the original interpreter, instead of a fixed number per frame.",
+        "Las instrucciones tardan lo mismo que en el intérprete original, en vez de un número fijo por fotograma.",
+    ),
+    ("Ticks/cycles per frame", "Ciclos por fotograma"),
+    ("Display/window framerates", "Fotogramas de la ventana"),
+    ("Max FPS", "FPS máximos"),
+    ("Adaptive VSync", "VSync adaptativa"),
+    (
+        "Frames running late are shown right away, tearing instead of stuttering.",
+        "Los fotogramas que llegan tarde se muestran al momento, con cortes en lugar de tirones.",
+    ),
+    ("Running at {}% speed", "Funcionando al {}% de velocidad"),
+    (
+        "The computer can't keep up with the emulated clock. Try fewer instructions per frame, or closing the debugger.",
+        "El ordenador no puede seguir el reloj emulado. Prueba con menos instrucciones por fotograma, o cerrando el depurador.",
+    ),
+    ("Border matches disabled pixels", "Borde del color de los píxeles apagados"),
+    ("Border", "Borde"),
+    ("Padding", "Margen"),
+    ("Pixel grid", "Cuadrícula de píxeles"),
+    ("Gamma", "Gamma"),
+    ("Brightness", "Brillo"),
+    ("Contrast", "Contraste"),
+    ("Reset adjustments", "Restablecer ajustes"),
+    ("Orientation", "Orientación"),
+    ("No rotation", "Sin rotación"),
+    ("Mirror horizontally", "Reflejar horizontalmente"),
+    ("Projector window", "Ventana de proyector"),
+    ("Projector", "Proyector"),
+    ("Speedrun timer", "Cronómetro de speedrun"),
+    ("Show timer", "Mostrar cronómetro"),
+    ("Start on first input", "Empezar con la primera tecla"),
+    ("Split", "Parcial"),
+    ("Reset timer", "Reiniciar cronómetro"),
+    ("Auto-splitter...", "Parciales automáticos..."),
+    ("Auto-splitter", "Parciales automáticos"),
+    ("Export splits...", "Exportar parciales..."),
+    ("Could not export the splits", "No se pudieron exportar los parciales"),
+    (
+        "One condition per line. Split n happens when condition n becomes true.",
+        "Una condición por línea. El parcial n ocurre cuando la condición n se cumple.",
+    ),
+    ("Line", "Línea"),
+    ("Conditions", "Condiciones"),
+    ("Waiting for the first input", "Esperando la primera tecla"),
+    ("Press F9 to start", "Pulsa F9 para empezar"),
+    ("frames", "fotogramas"),
+    ("Sound enabled", "Sonido activado"),
+    ("Audio", "Audio"),
+    ("Sample rate", "Frecuencia de muestreo"),
+    ("Buffer size", "Tamaño del búfer"),
+    ("SDL default", "Predeterminado de SDL"),
+    ("samples", "muestras"),
+    ("Buffer latency", "Latencia del búfer"),
+    ("Measured latency", "Latencia medida"),
+    ("Color palette", "Paleta de colores"),
+    ("Active", "Activa"),
+    ("Default", "Predeterminada"),
+    ("Inverted", "Invertida"),
+    ("Brown", "Marrón"),
+    ("Red", "Roja"),
+    ("Deuteranopia", "Deuteranopía"),
+    ("Protanopia", "Protanopía"),
+    ("Tritanopia", "Tritanopía"),
+    ("High contrast", "Alto contraste"),
+    ("Color-blind friendly", "Para daltonismo"),
+    ("Custom", "Personalizada"),
+    ("Enabled pixels", "Píxeles encendidos"),
+    ("Disabled pixels", "Píxeles apagados"),
+    ("Theme", "Tema"),
+    ("Dark", "Oscuro"),
+    ("Light", "Claro"),
+    ("Classic", "Clásico"),
+    ("Accent", "Acento"),
+    ("Reset accent", "Restablecer acento"),
+    ("Language", "Idioma"),
+    ("Advanced", "Avanzado"),
+    ("Show state hash", "Mostrar hash del estado"),
+    ("Quirks", "Peculiaridades"),
+    (
+        "Shift operations against Vy instead of Vx register.",
+        "Desplazamientos sobre el registro Vy en vez de Vx.",
+    ),
+    (
+        "Memory load/save operations (fx55, fx65) increment I register.",
+        "Las operaciones de carga/guardado (fx55, fx65) incrementan I.",
+    ),
+    (
+        "Horizontal sprite clipping instead of wrapping.",
+        "Recortar sprites horizontalmente en vez de envolverlos.",
+    ),
+    (
+        "Vertical sprite clipping instead of wrapping.",
+        "Recortar sprites verticalmente en vez de envolverlos.",
+    ),
+    (
+        "Jump instructions to V0+NNN instead of VX+NN.",
+        "Saltos a V0+NNN en vez de VX+NN.",
+    ),
+    (
+        "Buzzer only sounds while ST >= 2 (COSMAC VIP).",
+        "El zumbador solo suena con ST >= 2 (COSMAC VIP).",
+    ),
+    (
+        "Arithmetic and shifts into VF keep the result instead of the flag.",
+        "Aritmética y desplazamientos sobre VF guardan el resultado en vez del indicador.",
+    ),
+    (
+        "Protect font/interpreter memory area",
+        "Proteger la memoria de fuente/intérprete",
+    ),
+    (
+        "Latch keypad input per frame",
+        "Fijar la entrada del teclado por fotograma",
+    ),
+    (
+        "Key checks see the keys as they were when the frame began.",
+        "Las comprobaciones de teclas ven las teclas como estaban al empezar el fotograma.",
+    ),
+    // Debug menu and debugger
+    ("Debug", "Depuración"),
+    ("Debugger", "Depurador"),
+    (
+        "Outline last sprite draw",
+        "Resaltar el último sprite dibujado",
+    ),
+    ("Load symbols...", "Cargar símbolos..."),
+    ("Console", "Consola"),
+    ("Teaching mode", "Modo docente"),
+    ("Statistics", "Estadísticas"),
+    ("Cycles", "Ciclos"),
+    ("Frames", "Fotogramas"),
+    ("Draws", "Dibujados"),
+    ("Key events", "Pulsaciones"),
+    ("Sound time", "Tiempo de sonido"),
+    // Instruction reference
+    ("Instruction reference", "Referencia de instrucciones"),
+    ("Search opcodes", "Buscar instrucciones"),
+    ("Break on this opcode", "Detener en esta instrucción"),
+    ("Current quirks:", "Con las peculiaridades actuales:"),
+    ("Otherwise:", "Si no:"),
+    ("Clears the screen.", "Borra la pantalla."),
+    (
+        "Returns from a subroutine, popping PC from the stack.",
+        "Vuelve de una subrutina, sacando PC de la pila.",
+    ),
+    (
+        "Calls a machine code routine. Not supported: halts.",
+        "Llama a una rutina en código máquina. No soportada: detiene la ejecución.",
+    ),
+    ("Jumps to nnn.", "Salta a nnn."),
+    (
+        "Calls the subroutine at nnn, pushing PC to the stack.",
+        "Llama a la subrutina en nnn, guardando PC en la pila.",
+    ),
+    ("Skips the next instruction if Vx == kk.", "Salta la siguiente instrucción si Vx == kk."),
+    ("Skips the next instruction if Vx != kk.", "Salta la siguiente instrucción si Vx != kk."),
+    ("Skips the next instruction if Vx == Vy.", "Salta la siguiente instrucción si Vx == Vy."),
+    ("Vx = Vx + kk, without carry.", "Vx = Vx + kk, sin acarreo."),
+    ("Vx = Vx + Vy, VF = 1 on carry.", "Vx = Vx + Vy, VF = 1 si hay acarreo."),
+    ("Vx = Vx - Vy, VF = 0 on borrow.", "Vx = Vx - Vy, VF = 0 si hay préstamo."),
+    (
+        "Shifts right by one, VF = the bit shifted out.",
+        "Desplaza un bit a la derecha, VF = el bit que sale.",
+    ),
+    ("Vx = Vy - Vx, VF = 0 on borrow.", "Vx = Vy - Vx, VF = 0 si hay préstamo."),
+    (
+        "Shifts left by one, VF = the bit shifted out.",
+        "Desplaza un bit a la izquierda, VF = el bit que sale.",
+    ),
+    ("Skips the next instruction if Vx != Vy.", "Salta la siguiente instrucción si Vx != Vy."),
+    ("Jumps with an offset.", "Salta con un desplazamiento."),
+    ("Vx = a random byte & kk.", "Vx = un byte aleatorio & kk."),
+    (
+        "XORs the n byte sprite at I into the screen at (Vx, Vy), VF = 1 on collision.",
+        "Dibuja con XOR el sprite de n bytes en I en la pantalla en (Vx, Vy), VF = 1 si hay colisión.",
+    ),
+    (
+        "Skips the next instruction if the key in Vx is down.",
+        "Salta la siguiente instrucción si la tecla en Vx está pulsada.",
+    ),
+    (
+        "Skips the next instruction if the key in Vx is up.",
+        "Salta la siguiente instrucción si la tecla en Vx no está pulsada.",
+    ),
+    ("Vx = the delay timer.", "Vx = el temporizador de retardo."),
+    (
+        "Waits for a key to be released, and stores it in Vx.",
+        "Espera a que se suelte una tecla, y la guarda en Vx.",
+    ),
+    ("Delay timer = Vx.", "Temporizador de retardo = Vx."),
+    ("Sound timer = Vx.", "Temporizador de sonido = Vx."),
+    (
+        "I = the address of the font sprite for the digit in Vx.",
+        "I = la dirección del sprite de la fuente para el dígito en Vx.",
+    ),
+    (
+        "Stores the decimal digits of Vx at I, I+1 and I+2.",
+        "Guarda los dígitos decimales de Vx en I, I+1 e I+2.",
+    ),
+    ("Stores V0 to Vx in memory, starting at I.", "Guarda de V0 a Vx en memoria, empezando en I."),
+    (
+        "Loads V0 to Vx from memory, starting at I.",
+        "Carga de V0 a Vx desde memoria, empezando en I.",
+    ),
+    ("Sprites past the edges get clipped", "Los sprites se recortan en los bordes"),
+    ("Sprites past the edges wrap around", "Los sprites dan la vuelta en los bordes"),
+    ("The buzzer sounds while ST >= 2", "El zumbador suena con ST >= 2"),
+    ("The buzzer sounds while ST > 0", "El zumbador suena con ST > 0"),
+    ("I = I + x + 1 afterwards", "I = I + x + 1 al terminar"),
+    ("I is left unchanged", "I no cambia"),
+    ("ADD VF, Vy leaves the result in VF", "ADD VF, Vy deja el resultado en VF"),
+    ("ADD VF, Vy leaves the flag in VF", "ADD VF, Vy deja el indicador en VF"),
+    ("SUB VF, Vy leaves the result in VF", "SUB VF, Vy deja el resultado en VF"),
+    ("SUB VF, Vy leaves the flag in VF", "SUB VF, Vy deja el indicador en VF"),
+    ("SUBN VF, Vy leaves the result in VF", "SUBN VF, Vy deja el resultado en VF"),
+    ("SUBN VF, Vy leaves the flag in VF", "SUBN VF, Vy deja el indicador en VF"),
+    ("Instructions per second", "Instrucciones por segundo"),
+    ("1. Fetch", "1. Búsqueda"),
+    ("2. Decode", "2. Decodificación"),
+    ("3. Execute", "3. Ejecución"),
+    ("Ghost previous frame", "Fantasma del fotograma anterior"),
+    ("Level", "Nivel"),
+    ("Auto-scroll", "Desplazamiento automático"),
+    ("Export...", "Exportar..."),
+    ("Halt", "Pausar"),
+    ("Resume", "Continuar"),
+    ("Step", "Paso"),
+    ("Step back", "Paso atrás"),
+    ("Step frame", "Avanzar fotograma"),
+    ("Break after draw (DXYN)", "Pausar tras dibujar (DXYN)"),
+    ("Break after clear (CLS)", "Pausar tras limpiar (CLS)"),
+    ("Breakpoints", "Puntos de ruptura"),
+    ("Register writes", "Escrituras de registros"),
+    ("Random numbers", "Números aleatorios"),
+    ("values", "valores"),
+    ("Mean:", "Media:"),
+    ("Chi-squared:", "Chi cuadrado:"),
+    (
+        "Of the random bytes against uniform ones, about 255 when unbiased.",
+        "De los bytes aleatorios frente a unos uniformes, cerca de 255 sin sesgo.",
+    ),
+    ("After masking with kk", "Tras aplicar la máscara kk"),
+    ("Force random byte", "Forzar byte aleatorio"),
+    ("Instructions per row", "Instrucciones por fila"),
+    ("Instructions ago:", "Instrucciones atrás:"),
+    ("Memory heatmap", "Mapa de calor de memoria"),
+    ("Read", "Leído"),
+    ("Written", "Escrito"),
+    ("Reads:", "Lecturas:"),
+    ("Writes:", "Escrituras:"),
+    // Sprite editor
+    ("Tools", "Herramientas"),
+    ("Sprite editor", "Editor de sprites"),
+    ("16x16 (SCHIP)", "16x16 (SCHIP)"),
+    ("Rows", "Filas"),
+    ("Invert", "Invertir"),
+    ("Flip horizontally", "Voltear horizontalmente"),
+    ("Flip vertically", "Voltear verticalmente"),
+    ("Copy hex", "Copiar hex"),
+    ("Paste hex", "Pegar hex"),
+    ("Address", "Dirección"),
+    ("Use I", "Usar I"),
+    ("Read from memory", "Leer de la memoria"),
+    ("Write to memory", "Escribir en la memoria"),
+    // Code editor
+    ("Code editor", "Editor de código"),
+    ("Save", "Guardar"),
+    ("Save as...", "Guardar como..."),
+    ("Saved", "Guardado"),
+    ("Assemble & Run (F4)", "Ensamblar y ejecutar (F4)"),
+    ("Assembled {} bytes", "{} bytes ensamblados"),
+    ("Octo assembly, without macros.", "Ensamblador de Octo, sin macros."),
+    ("Highlighted source", "Código resaltado"),
+    // Watches
+    ("Watches", "Vigilancia"),
+    ("Expression, e.g. mem[0x3F0]", "Expresión, p. ej. mem[0x3F0]"),
+    // Array export
+    ("Export as array", "Exportar como array"),
+    ("Screen", "Pantalla"),
+    ("Memory", "Memoria"),
+    ("Bytes", "Bytes"),
+    ("Name", "Nombre"),
+    ("Invalid memory region.", "Región de memoria no válida."),
+    ("Copy", "Copiar"),
+    ("Copied to the clipboard.", "Copiado al portapapeles."),
+    // Memory diff
+    ("Memory diff", "Diferencias de memoria"),
+    ("Live memory", "Memoria actual"),
+    ("Before", "Antes"),
+    ("After", "Después"),
+    ("Snapshot", "Instantánea"),
+    ("Snapshot at frame {}", "Instantánea en el fotograma {}"),
+    ("Pick what to compare.", "Elige qué comparar."),
+    (
+        "Memory sizes differ, comparing the first bytes.",
+        "Los tamaños de memoria difieren, se comparan los primeros bytes.",
+    ),
+    ("{} bytes changed", "{} bytes cambiados"),
+    // Project
+    ("Open project folder...", "Abrir carpeta de proyecto..."),
+    ("Project", "Proyecto"),
+    ("Source", "Código fuente"),
+    ("ROM", "ROM"),
+    ("Symbols", "Símbolos"),
+    ("Tests", "Pruebas"),
+    ("Change...", "Cambiar..."),
+    ("(none)", "(ninguno)"),
+    ("Build & Run", "Compilar y ejecutar"),
+    ("Run tests", "Ejecutar pruebas"),
+    ("Save project", "Guardar proyecto"),
+    ("Close project", "Cerrar proyecto"),
+    (
+        "The project needs a test file and a ROM.",
+        "El proyecto necesita un archivo de pruebas y una ROM.",
+    ),
+    ("Machine", "Máquina"),
+    ("Modern", "Moderna"),
+    ("Stack size:", "Tamaño de la pila:"),
+    ("Memory:", "Memoria:"),
+    ("Break after writing executed code", "Parar tras escribir código ejecutado"),
+    ("Log it", "Registrarlo"),
+    ("Writes into the font", "Escrituras en la fuente"),
+    ("Ignore", "Ignorar"),
+    ("Warn", "Avisar"),
+    ("The ROM wrote into the font", "La ROM escribió en la fuente"),
+    (
+        "The ROM asked for a font digit past F",
+        "La ROM pidió un dígito de la fuente mayor que F",
+    ),
+    (
+        "The ROM moved I past the end of memory",
+        "La ROM movió I más allá del final de la memoria",
+    ),
+    ("Load at", "Cargar en"),
+    ("Import hex...", "Importar hex..."),
+    ("Import hex", "Importar hex"),
+    (
+        "Paste a ROM written in hex, e.g. from Octo or a forum post.",
+        "Pega una ROM escrita en hexadecimal, p. ej. de Octo o de un foro.",
+    ),
+    ("Open file...", "Abrir archivo..."),
+    ("Load", "Cargar"),
+    ("Start recording from here", "Empezar a grabar desde aquí"),
+    ("Could not start recording", "No se pudo empezar a grabar"),
+    ("Recorded from a save state", "Grabado desde un estado guardado"),
+    ("Timeline", "Línea de tiempo"),
+    ("Frames shown", "Fotogramas mostrados"),
+    ("Keys", "Teclas"),
+    ("Sound", "Sonido"),
+    ("Clears", "Borrados"),
+    ("Save states", "Estados guardados"),
+    ("Key", "Tecla"),
+    ("Screen cleared", "Pantalla borrada"),
+    ("State saved", "Estado guardado"),
+    (
+        "Seeking needs a replay, or the debugger open while the frame ran.",
+        "Para saltar hace falta una repetición, o el depurador abierto mientras se ejecutaba el fotograma.",
+    ),
+    ("Last self-modifying write:", "Última escritura automodificante:"),
+    ("Out of bounds memory accesses", "Accesos fuera de la memoria"),
+    ("Wrap around", "Dar la vuelta"),
+    ("Clamp to the last byte", "Limitar al último byte"),
+    ("Halt with an error", "Detener con un error"),
+    ("Label or address", "Etiqueta o dirección"),
+    ("Add", "Añadir"),
+    (
+        "Command (b, x/16, p, c, si, help)",
+        "Comando (b, x/16, p, c, si, help)",
+    ),
+    // Help menu, help window and first-run popup
+    ("Help", "Ayuda"),
+    ("Keypad & hotkeys", "Teclado y atajos"),
+    ("Welcome", "Bienvenida"),
+    ("Keypad", "Teclado"),
+    (
+        "CHIP-8 key / keyboard key",
+        "Tecla CHIP-8 / tecla del teclado",
+    ),
+    ("Hotkeys", "Atajos"),
+    ("Show this help", "Mostrar esta ayuda"),
+    ("Next ROM in the playlist", "Siguiente ROM de la lista"),
+    (
+        "Next color palette, remembered for the ROM",
+        "Siguiente paleta de colores, recordada para la ROM",
+    ),
+    (
+        "Play the key macro in that slot",
+        "Reproducir la macro de teclas de esa ranura",
+    ),
+    (
+        "Record a key macro into that slot, or stop recording",
+        "Grabar una macro de teclas en esa ranura, o detener la grabación",
+    ),
+    ("Recording macro", "Grabando macro"),
+    ("Speedrun split (or start)", "Parcial del speedrun (o empezar)"),
+    (
+        "Assemble & run, in the code editor",
+        "Ensamblar y ejecutar, en el editor de código",
+    ),
+    ("Reset the speedrun timer", "Reiniciar el cronómetro del speedrun"),
+    (
+        "No ROM yet? Try one of the built-in ones:",
+        "¿Aún sin ROM? Prueba una de las incluidas:",
+    ),
+    (
+        "They are always available in File > Open built-in. Press F1 for the keypad layout.",
+        "Siempre están disponibles en Archivo > Abrir incluida. Pulsa F1 para ver el teclado.",
+    ),
+    // Replay viewer
+    ("Play", "Reproducir"),
+    ("Pause", "Pausa"),
+    ("Take control", "Tomar el control"),
+    ("Frame", "Fotograma"),
+    // Main screen and dialogs
+    ("ROM check", "Comprobación de la ROM"),
+    ("bytes", "bytes"),
+    ("reachable instructions", "instrucciones alcanzables"),
+    ("Requires:", "Requiere:"),
+    (
+        "Only plain CHIP-8 is emulated, so the ROM may not run correctly.",
+        "Solo se emula CHIP-8 básico, así que la ROM puede no funcionar bien.",
+    ),
+    ("Load anyway", "Cargar de todos modos"),
+    ("This ROM can't be loaded.", "Esta ROM no se puede cargar."),
+    ("Cancel", "Cancelar"),
+    ("Could not open the projector", "No se pudo abrir el proyector"),
+    (
+        "Could not start recording (is ffmpeg installed?)",
+        "No se pudo empezar a grabar (¿está instalado ffmpeg?)",
+    ),
+    ("Could not save video", "No se pudo guardar el vídeo"),
+    ("No ROM loaded!", "¡No hay ninguna ROM cargada!"),
+    ("Could not load ROM", "No se pudo cargar la ROM"),
+    ("Could not read ROM", "No se pudo leer la ROM"),
+    ("Could not apply patch", "No se pudo aplicar el parche"),
+    ("Could not export ROM", "No se pudo exportar la ROM"),
+    ("Could not open replay", "No se pudo abrir la repetición"),
+    (
+        "Could not play replay",
+        "No se pudo reproducir la repetición",
+    ),
+    ("Could not save replay", "No se pudo guardar la repetición"),
+    ("Could not save state", "No se pudo guardar el estado"),
+    ("Could not load state", "No se pudo cargar el estado"),
+    ("The emulator crashed", "El emulador ha fallado"),
+    (
+        "Something went wrong and the emulator has to close. Save a crash report to include in a bug report?",
+        "Algo ha ido mal y el emulador tiene que cerrarse. ¿Guardar un informe del fallo para adjuntarlo a un reporte de error?",
+    ),
+    (
+        "Could not load symbols",
+        "No se pudieron cargar los símbolos",
+    ),
+];
+
+#[cfg(test)]
+mod locale_tests {
+    use std::collections::HashSet;
+
+    use super::{set_language, tr, Language, LANGUAGES, SPANISH};
+
+    #[test]
+    fn test_tables_have_unique_keys() {
+        let keys: HashSet<_> = SPANISH.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys.len(), SPANISH.len());
+    }
+
+    #[test]
+    fn test_translate() {
+        set_language(Language::Spanish);
+        assert_eq!(tr("File"), "Archivo");
+        assert_eq!(tr("Not translated"), "Not translated");
+
+        set_language(Language::English);
+        assert_eq!(tr("File"), "File");
+    }
+
+    #[test]
+    fn test_codes() {
+        for language in LANGUAGES {
+            assert_eq!(Language::from_code(language.code()), Some(language));
+        }
+        assert_eq!(Language::from_code("xx"), None);
+    }
+}
@@ -0,0 +1,301 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        replay::{InputEvent, Replay, ReplayPlayer},
+        stats::Stats,
+        triggers::Triggers,
+    },
+    playlist::Playlist,
+    speedrun::SpeedrunTimer,
+};
+
+// Changes to the open machines, requested from the UI and applied at
+// the start of the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineAction {
+    New,
+    Select(usize),
+    Close(usize),
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// One emulated machine with its own ROM, quirks, speed and replay.
+// Several can be open at once, each in its own tab.
+pub struct Machine {
+    pub id: usize, // Stays the same while tabs get closed, unlike the index
+    pub cpu: Cpu,
+    pub loaded_rom_path: Option<PathBuf>,
+    pub loaded_patch_path: Option<PathBuf>,
+    pub recording: Option<Replay>,
+    pub replay_player: Option<ReplayPlayer>,
+    pub speedrun: SpeedrunTimer,
+    triggers: Option<Triggers>,
+    triggers_rom_path: Option<PathBuf>, // ROM the triggers were looked up for
+    pub achievements: Vec<String>,      // Messages of fired triggers, not shown yet
+    queued_keys: VecDeque<InputEvent>,  // By frame, see queue_key
+    queued_since: u64,                  // Frame last seen by the queue
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            cpu: Cpu::new(),
+            loaded_rom_path: None,
+            loaded_patch_path: None,
+            recording: None,
+            replay_player: None,
+            speedrun: SpeedrunTimer::new(),
+            triggers: None,
+            triggers_rom_path: None,
+            achievements: Vec::new(),
+            queued_keys: VecDeque::new(),
+            queued_since: 0,
+        }
+    }
+
+    // A new machine with the same quirks and speed.
+    pub fn with_settings_of(other: &Cpu) -> Machine {
+        let mut machine = Machine::new();
+        let cpu = &mut machine.cpu;
+        cpu.set_quirk_bits(other.quirk_bits());
+        cpu.ticks_per_frame = other.ticks_per_frame;
+        cpu.timing_model = other.timing_model;
+        if !other.is_beep_enabled() {
+            cpu.disable_beep();
+        }
+        cpu.memory.set_protected(other.memory.is_protected());
+        cpu.memory.set_bounds_policy(other.memory.bounds_policy());
+        cpu.keypad.set_input_mode(other.keypad.input_mode());
+        cpu.set_profile(other.profile());
+        cpu.font_write_action = other.font_write_action;
+        machine
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.loaded_rom_path
+            .as_ref()
+            .filter(|_| self.cpu.is_rom_loaded())
+            .map(|path| Playlist::title(path))
+    }
+
+    // What the program did since it was loaded, for the overlay and the
+    // benchmark.
+    pub fn stats(&self) -> Stats {
+        self.cpu.stats()
+    }
+
+    // Presses or releases a key right before the given emulated frame
+    // runs, however fast frames are being run, so scripted input plays
+    // back the same every time. Keys queued for frames already run are
+    // set before the next one. Queued keys are recorded as they're set.
+    pub fn queue_key(&mut self, frame: u64, key: u8, pressed: bool) {
+        self.drop_stale_keys();
+        let idx = self
+            .queued_keys
+            .partition_point(|event| event.frame <= frame);
+        self.queued_keys.insert(
+            idx,
+            InputEvent {
+                frame,
+                key,
+                pressed,
+            },
+        );
+    }
+
+    // Keys queued before the machine was reset (its frame count went
+    // back, or the ROM was closed) were meant for the previous run.
+    fn drop_stale_keys(&mut self) {
+        let frame = self.cpu.frame();
+        if frame < self.queued_since || !self.cpu.is_rom_loaded() {
+            self.queued_keys.clear();
+        }
+        self.queued_since = frame;
+    }
+
+    fn apply_queued_keys(&mut self) {
+        self.drop_stale_keys();
+        let frame = self.cpu.frame();
+        while let Some(event) = self
+            .queued_keys
+            .front()
+            .filter(|event| event.frame <= frame)
+        {
+            self.cpu.keypad.set_key(event.key, event.pressed);
+            if let Some(replay) = self.recording.as_mut() {
+                replay.record(frame, event.key, event.pressed);
+            }
+            self.queued_keys.pop_front();
+        }
+    }
+
+    // Runs one frame, fed by the replay if one is playing.
+    pub fn run_frame(&mut self) {
+        // Replays bring their own input
+        if let Some(player) = self.replay_player.as_mut() {
+            self.queued_keys.clear();
+            player.run_frame(&mut self.cpu);
+        } else {
+            self.apply_queued_keys();
+            self.cpu.tick();
+        }
+        self.speedrun.check_auto_split(&self.cpu);
+
+        self.sync_triggers();
+        if let Some(triggers) = self.triggers.as_mut() {
+            self.achievements
+                .extend(triggers.check(&self.cpu).into_iter().map(str::to_string));
+        }
+    }
+
+    // Reads the "rom.triggers" file of the ROM, once per loaded ROM.
+    fn sync_triggers(&mut self) {
+        if self.loaded_rom_path == self.triggers_rom_path {
+            return;
+        }
+        self.triggers_rom_path = self.loaded_rom_path.clone();
+        self.triggers = None;
+
+        let Some(path) = self
+            .loaded_rom_path
+            .as_ref()
+            .map(|rom_path| rom_path.with_extension("triggers"))
+            .filter(|path| path.is_file())
+        else {
+            return;
+        };
+        let result = fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Triggers::parse(&text).map_err(|err| err.to_string()));
+        match result {
+            Ok(triggers) => {
+                log::info!("Loaded {} triggers from {}", triggers.len(), path.display());
+                self.triggers = Some(triggers);
+            }
+            Err(err) => log::warn!("Could not load {}: {}", path.display(), err),
+        }
+    }
+
+    // Lets go of every key, e.g. when input moves to another machine.
+    pub fn release_keys(&mut self) {
+        for key in 0..=0xF {
+            self.cpu.keypad.set_key(key, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod machine_tests {
+    use std::path::PathBuf;
+
+    use super::Machine;
+
+    #[test]
+    fn test_with_settings_of() {
+        let mut machine = Machine::new();
+        machine.cpu.set_quirk_bits(0x2A);
+        machine.cpu.ticks_per_frame = 42;
+        machine.cpu.disable_beep();
+
+        let copy = Machine::with_settings_of(&machine.cpu);
+        assert_eq!(copy.cpu.quirk_bits(), 0x2A);
+        assert_eq!(copy.cpu.ticks_per_frame, 42);
+        assert!(!copy.cpu.is_beep_enabled());
+        assert!(!copy.cpu.is_rom_loaded());
+        assert_ne!(copy.id, machine.id);
+    }
+
+    #[test]
+    fn test_queue_key() {
+        // SKNP V0 - ADD V1, 1 - JP 0x200: counts instructions with key 0 down
+        let mut machine = Machine::new();
+        machine.cpu.ticks_per_frame = 3;
+        machine
+            .cpu
+            .load_rom(&[0xE0, 0xA1, 0x71, 0x01, 0x12, 0x00], 0x0200)
+            .unwrap();
+        machine.queue_key(3, 0, false);
+        machine.queue_key(2, 0, true);
+        assert_eq!(machine.queued_keys.len(), 2);
+
+        let mut counts = Vec::new();
+        for _ in 0..5 {
+            machine.run_frame();
+            counts.push(machine.cpu.registers.v[1]);
+        }
+        // Held during frame 2 only
+        assert_eq!(counts, vec![0, 0, 1, 1, 1]);
+        assert!(machine.queued_keys.is_empty());
+
+        // Late keys are set before the next frame
+        machine.queue_key(0, 0, true);
+        machine.run_frame();
+        assert!(machine.cpu.keypad.get_key_state(0));
+
+        // Restarting drops keys queued for the previous run
+        machine.queue_key(8, 1, true);
+        machine.cpu.clear();
+        machine
+            .cpu
+            .load_rom(&[0xE0, 0xA1, 0x71, 0x01, 0x12, 0x00], 0x0200)
+            .unwrap();
+        machine.run_frame();
+        assert!(machine.queued_keys.is_empty());
+        for _ in 0..10 {
+            machine.run_frame();
+        }
+        assert!(!machine.cpu.keypad.get_key_state(1));
+    }
+
+    #[test]
+    fn test_stats() {
+        // LD ST, V0 - DRW V0, V0, 0 - JP 0x202, with V0 = 0x20
+        let mut machine = Machine::new();
+        machine.cpu.ticks_per_frame = 5;
+        machine.cpu.disable_beep();
+        machine
+            .cpu
+            .load_rom(&[0xF0, 0x18, 0xD0, 0x00, 0x12, 0x02], 0x0200)
+            .unwrap();
+        machine.cpu.registers.v[0] = 0x20;
+        machine.queue_key(1, 4, true);
+        machine.queue_key(2, 4, false);
+        for _ in 0..3 {
+            machine.run_frame();
+        }
+
+        let stats = machine.stats();
+        assert_eq!((stats.cycles, stats.frames), (15, 3));
+        assert_eq!(stats.draws, 7);
+        assert_eq!(stats.key_events, 2);
+        assert_eq!(stats.sound_frames, 3);
+    }
+
+    #[test]
+    fn test_title() {
+        let mut machine = Machine::new();
+        assert_eq!(machine.title(), None);
+
+        machine.cpu.load_rom(&[0x12, 0x00], 0x0200).unwrap();
+        machine.loaded_rom_path = Some(PathBuf::from("roms/pong.ch8"));
+        assert_eq!(machine.title(), Some("pong".to_string()));
+    }
+}
@@ -8,158 +8,393 @@
 // https://github.com/keelus/chip-8-emu
 
 use lazy_static::lazy_static;
-use std::{borrow::Cow, fs, path::PathBuf, time::Instant};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use glow::HasContext;
 use imgui::{Condition, Context};
 use imgui_glow_renderer::{glow, AutoRenderer};
 use imgui_sdl2_support::SdlPlatform;
 use mint::{Vector2, Vector3};
-use rfd;
 
 use sdl2::{
-    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
-    event::Event,
-    keyboard::Keycode,
-    video::SwapInterval,
-    AudioSubsystem,
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
 };
 
+mod array_export;
+mod audio;
+#[cfg(feature = "bench")]
+mod bench;
+mod builtin_roms;
+mod cli;
+mod code_editor;
+mod config;
+mod console;
 mod core;
+mod crash;
+mod debugger;
+mod flash_guard;
+mod frame_limiter;
+mod governor;
 mod graphics;
-use core::{beep, cpu::Cpu, screen};
+mod headless;
+mod help;
+mod hex_import;
+#[cfg(test)]
+mod it;
+mod locale;
+mod machine;
+mod memory_diff;
+mod memory_heatmap;
+mod playlist;
+mod png;
+mod project;
+mod projector;
+mod reference;
+mod replay_viewer;
+mod rom_check;
+mod speedrun;
+mod sprite_editor;
+mod teaching;
+mod theme;
+mod thumbnails;
+mod timeline;
+mod toasts;
+mod video;
+use array_export::ArrayExportState;
+use audio::AudioStats;
+use builtin_roms::BUILTIN_ROMS;
+use cli::{Args, BatchArgs, TestArgs, TraceGenArgs};
+use code_editor::CodeEditorState;
+use config::Config;
+use console::ConsoleState;
+use core::{
+    analyzer, cartridge,
+    cpu::{Cpu, FONT_WRITE_ACTIONS},
+    hex_rom, json_state,
+    key_macro::{KeyMacro, MacroRecorder},
+    keypad::InputMode,
+    memory::BOUNDS_POLICIES,
+    patch,
+    profile::PROFILES,
+    replay::{Replay, ReplayPlayer},
+    savestate, screen,
+    symbols::SymbolTable,
+    timing::TimingModel,
+};
+use debugger::DebuggerState;
+use flash_guard::FlashGuard;
+use frame_limiter::{FrameLimiter, FramePacer, IdleWait, VSync};
+use governor::Governor;
+use hex_import::HexImportState;
+use locale::tr;
+use machine::{Machine, MachineAction};
+use memory_diff::MemoryDiffState;
+use playlist::Playlist;
+use project::{Project, ProjectAction, ProjectState};
+use projector::Projector;
+use reference::ReferenceState;
+use rom_check::{Decision, PendingRom, RomChecker};
+use sprite_editor::SpriteEditorState;
+use teaching::TeachingState;
+use thumbnails::Thumbnails;
+use timeline::TimelineState;
+use toasts::Toasts;
+use video::VideoRecorder;
 
-// Sample SquareWave struct code from SDL2's example
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
+const PROGRAM_BEGIN: u16 = 0x0200;
 
-// Sample AudioCallback impl code from SDL2's example
-impl AudioCallback for SquareWave {
-    type Channel = f32;
+const SCALE: usize = 20;
 
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
-    }
-}
+const MENU_BAR_HEIGHT: usize = 39;
 
-struct BeepHandler {
-    device: Option<AudioDevice<SquareWave>>,
-    desired_spec: AudioSpecDesired,
-    audio_subsystem: AudioSubsystem,
-}
+const WINDOW_WIDTH: usize = screen::WIDTH * SCALE;
+const WINDOW_HEIGHT: usize = screen::HEIGHT * SCALE + MENU_BAR_HEIGHT;
+
+// DPI at which the UI is drawn unscaled
+const BASE_DPI: f32 = 96.0;
+const BASE_FONT_SIZE: f32 = 13.0;
+
+const IMGUI_INI_FILE: &str = "chip-8-emu.ini";
 
-impl beep::BeepHandler for BeepHandler {
-    fn start(&mut self) {
-        if self.device.is_none() {
-            let new_device = self
-                .audio_subsystem
-                .open_playback(None, &self.desired_spec, |spec| SquareWave {
-                    phase_inc: 250.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.12,
-                })
-                .unwrap();
+const HELP_KEY: Keycode = Keycode::F1;
+const NEXT_ROM_KEY: Keycode = Keycode::F2;
+const NEXT_PALETTE_KEY: Keycode = Keycode::F3;
+const ASSEMBLE_KEY: Keycode = Keycode::F4;
+// Play the macro in their slot, or record it with shift held
+const MACRO_KEYS: [Keycode; 4] = [Keycode::F5, Keycode::F6, Keycode::F7, Keycode::F8];
+const SPLIT_KEY: Keycode = Keycode::F9;
+const RESET_TIMER_KEY: Keycode = Keycode::F10;
 
-            new_device.resume();
-            self.device = Some(new_device);
+fn main() {
+    console::init();
+
+    let mut raw_args = std::env::args().skip(1).peekable();
+    #[cfg(feature = "bench")]
+    if raw_args.peek().map(String::as_str) == Some("bench") {
+        raw_args.next();
+        bench::run(raw_args.next().as_deref());
+        return;
+    }
+    if raw_args.peek().map(String::as_str) == Some("trace-gen") {
+        raw_args.next();
+        let trace_args = match TraceGenArgs::parse(raw_args) {
+            Ok(trace_args) => trace_args,
+            Err(err) => {
+                eprintln!("{}\n\n{}", err, cli::TRACE_GEN_USAGE);
+                std::process::exit(2);
+            }
+        };
+        if let Err(err) = headless::trace_gen(&trace_args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
+        return;
     }
 
-    fn stop(&mut self) {
-        if self.device.is_some() {
-            let cur_device = self.device.take().unwrap();
-            cur_device.close_and_get_callback();
-            self.device = None;
+    if raw_args.peek().map(String::as_str) == Some("test") {
+        raw_args.next();
+        let test_args = match TestArgs::parse(raw_args) {
+            Ok(test_args) => test_args,
+            Err(err) => {
+                eprintln!("{}\n\n{}", err, cli::TEST_USAGE);
+                std::process::exit(2);
+            }
+        };
+        match headless::test(&test_args) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
+        return;
     }
-}
 
-const PROGRAM_BEGIN: u16 = 0x0200;
+    if raw_args.peek().map(String::as_str) == Some("batch") {
+        raw_args.next();
+        let batch_args = match BatchArgs::parse(raw_args) {
+            Ok(batch_args) => batch_args,
+            Err(err) => {
+                eprintln!("{}\n\n{}", err, cli::BATCH_USAGE);
+                std::process::exit(2);
+            }
+        };
+        if let Err(err) = headless::batch(&batch_args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-const SCALE: usize = 20;
+    let args = match Args::parse(raw_args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-const MENU_BAR_HEIGHT: usize = 39;
+    if args.is_headless() {
+        match headless::run(&args) {
+            Ok(outcome) if outcome.exit_code() != 0 => std::process::exit(outcome.exit_code()),
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-const WINDOW_WIDTH: usize = screen::WIDTH * SCALE;
-const WINDOW_HEIGHT: usize = screen::HEIGHT * SCALE + MENU_BAR_HEIGHT;
+    // Only the window reports crashes, with a dialog
+    crash::install_hook();
 
-fn main() {
     // Initialize SDL2 window
     let sdl = sdl2::init().unwrap();
     let video_subsystem = sdl.video().unwrap();
     let audio_subsystem = sdl.audio().unwrap();
-    let mut timer_subsystem = sdl.timer().unwrap();
     let mut event_loop = sdl.event_pump().unwrap();
 
-    let window = video_subsystem
-        .window("Hello triangle!", WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32)
-        .opengl()
-        .build()
-        .unwrap();
+    let ui_scale = dpi_scale(&video_subsystem);
+    let mut window_builder = video_subsystem.window(
+        "Hello triangle!",
+        (WINDOW_WIDTH as f32 * ui_scale) as u32,
+        (WINDOW_HEIGHT as f32 * ui_scale) as u32,
+    );
+    window_builder.opengl();
+    if args.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    let window = window_builder.build().unwrap();
 
     // Get GL context and setup screen
     let gl_attr = video_subsystem.gl_attr();
     gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
     gl_attr.set_context_version(3, 0);
 
-    let _gl_context = window.gl_create_context().unwrap();
+    let gl_context = window.gl_create_context().unwrap();
     let gl = unsafe {
         glow::Context::from_loader_function(|s| video_subsystem.gl_get_proc_address(s) as *const _)
     };
-    let _ = video_subsystem.gl_set_swap_interval(SwapInterval::VSync);
+    frame_limiter::set_swap_interval(&video_subsystem, VSync::On);
 
     // Initialize Imgui
     let mut imgui = Context::create();
-    imgui.set_ini_filename(None);
+    // Persist the docked panels' layout between sessions
+    imgui.set_ini_filename(Some(PathBuf::from(IMGUI_INI_FILE)));
     imgui.set_log_filename(None);
+    imgui.io_mut().config_flags |= imgui::ConfigFlags::DOCKING_ENABLE;
+
+    let mut config = Config::load();
+    theme::apply(imgui.style_mut(), config.theme, config.accent_color);
+    let mut theme_changed = false;
+    let mut config_changed = false;
+    locale::set_language(config.language);
 
+    imgui.style_mut().scale_all_sizes(ui_scale);
     imgui
         .fonts()
-        .add_font(&[imgui::FontSource::DefaultFontData { config: None }]);
+        .add_font(&[imgui::FontSource::DefaultFontData {
+            config: Some(imgui::FontConfig {
+                size_pixels: (BASE_FONT_SIZE * ui_scale).round(),
+                ..Default::default()
+            }),
+        }]);
 
     let mut platform = SdlPlatform::init(&mut imgui);
     let mut renderer = AutoRenderer::initialize(gl, &mut imgui).unwrap();
 
-    // Get texture and buffer where the emulator will render
-    let (mut buffer, tex) = unsafe {
-        renderer.gl_context().clear_color(0.1, 0.1, 0.1, 1.0);
-        graphics::setup_opengl(&mut renderer)
+    // Get texture where the emulator will render
+    let screen_quad = unsafe {
+        let quad = graphics::setup_opengl(
+            &mut renderer,
+            config.display_rotation,
+            config.display_mirrored,
+        );
+        graphics::set_grid_opacity(&mut renderer, &quad, config.pixel_grid);
+        graphics::set_color_adjustment(&mut renderer, &quad, config.color_adjustment);
+        quad
     };
+    let mut display_changed = false;
 
     // Setup Chip-8 and sound
-    let mut cpu = Cpu::new();
-    let mut loaded_rom_path: Option<PathBuf> = None;
-
-    let desired_spec = AudioSpecDesired {
-        freq: Some(22_100),
-        channels: Some(1), // mono
-        samples: None,     // default sample size
-    };
-
-    cpu.add_beep_handler(Box::new(BeepHandler {
-        device: None,
-        audio_subsystem,
-        desired_spec,
-    }));
+    // Each machine runs its own ROM, in its own tab
+    let mut machines = vec![Machine::new()];
+    let mut active = 0;
+    let mut machine_action: Option<MachineAction> = None;
+    let mut selected_tab_id = machines[active].id; // As last reported by the tab bar
+
+    let audio_stats = Arc::new(AudioStats::default());
+    machines[active]
+        .cpu
+        .set_audio_sink(Box::new(audio::QueueSink::new(
+            &audio_subsystem,
+            config.audio_sample_rate,
+            config.audio_buffer_size,
+            audio_stats.clone(),
+        )));
+    let mut audio_changed = false;
 
     let mut last = Instant::now();
 
     let mut active_palette_id = 0;
     let mut active_palette: ColorPalette = get_color_palette(active_palette_id).unwrap();
+    let mut palette_rom_hash = None; // ROM the palette was last picked for
+
+    let mut show_state_hash = false;
+    let mut show_debugger = false;
+    let mut show_draw_region = false;
+    let mut show_ghost = false;
+    let mut draws_per_second: u32 = 60;
+    let mut last_upload: Option<Instant> = None;
+    let mut frame_pending = false;
+    let mut showing_live = false; // The texture has the screen instead of the front one
+    let mut flash_guard = FlashGuard::new(config.reduce_flashing);
+    let mut video_recorder: Option<VideoRecorder> = None;
+    let mut debugger_state = DebuggerState::default();
+    let mut show_console = false;
+    let mut show_stats = false;
+    let mut console_state = ConsoleState::default();
+    let mut show_help = false;
+    let mut show_speedrun = false;
+    let mut show_auto_splitter = false;
+    let mut toasts = Toasts::new();
+    // Slowed down execution, showing every step
+    let mut teaching = TeachingState::default();
+    let mut show_reference = false;
+    let mut show_memory_heatmap = false;
+    let mut show_memory_diff = false;
+    let mut memory_diff_state = MemoryDiffState::default();
+    let mut hex_import_state = HexImportState::default();
+    let mut show_sprite_editor = false;
+    let mut sprite_editor_state = SpriteEditorState::default();
+    let mut show_code_editor = false;
+    let mut code_editor_state = CodeEditorState::default();
+    let mut show_array_export = false;
+    let mut array_export_state = ArrayExportState::default();
+    let mut show_timeline = false;
+    let mut timeline_state = TimelineState::default();
+    // An open folder keeps its breakpoints, watches and panels
+    let mut project: Option<Project> = None;
+    let mut show_project = false;
+    let mut project_state = ProjectState::default();
+    let mut project_to_open: Option<PathBuf> = None;
+    let mut reference_state = ReferenceState::default();
+    // Borderless window mirroring just the screen, e.g. for a projector
+    let mut projector: Option<Projector> = None;
+    // New users get pointed to the built-in ROMs once
+    let mut show_welcome = !config.welcome_shown && !args.kiosk && args.roms.is_empty();
+
+    // Several ROMs on the command line play in rotation
+    let mut playlist = Playlist::new(args.advance_secs);
+    let mut thumbnails = Thumbnails::new();
+    let rom_checker = RomChecker::new();
+    let mut rom_check = None;
+    for rom_path in &args.roms {
+        playlist.add(rom_path.clone());
+    }
+    let mut next_rom_requested = false;
+    if let Some(rom_path) = playlist.current() {
+        let machine = &mut machines[active];
+        if switch_rom(&mut machine.cpu, rom_path) {
+            machine.loaded_rom_path = Some(rom_path.to_path_buf());
+            if let Some(state_path) = args.load_state.as_deref() {
+                let result = fs::read_to_string(state_path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|state| {
+                        json_state::load(&mut machine.cpu, &state).map_err(|err| err.to_string())
+                    });
+                if let Err(err) = result {
+                    show_error(tr("Could not load state"), &err);
+                }
+            }
+        }
+    }
+
+    // Key macros, typed into the active machine
+    let mut macro_recorder: Option<(usize, MacroRecorder)> = None; // With its slot
+    let mut macro_to_play: Option<KeyMacro> = None;
+
+    // Kiosk mode restarts the ROM when nobody played for a while
+    let mut last_input = Instant::now();
+    let mut watchdog = args.watchdog();
 
-    let mut vsync_enabled = true;
+    let mut vsync = VSync::On;
     let mut max_fps: u32 = 200;
+    let mut frame_limiter = FrameLimiter::new();
+    let mut idle_wait = IdleWait::new();
+    let mut wake_event = None; // Input that ended an idle wait
+    let mut frame_pacer = FramePacer::new();
+    let mut governor = Governor::new();
+    let mut crash_context = crash::ContextTracker::new();
 
     let mut running = true;
     'running_loop: while running {
@@ -171,70 +406,327 @@ fn main() {
         }
         last = now;
 
-        for event in event_loop.poll_iter() {
-            platform.handle_event(&mut imgui, &event);
-            match event {
-                sdl2::event::Event::Quit { .. } => {
-                    break 'running_loop;
+        if let Some(action) = machine_action.take() {
+            match action {
+                MachineAction::New => {
+                    let machine = Machine::with_settings_of(&machines[active].cpu);
+                    machines.push(machine);
+                    let last = machines.len() - 1;
+                    active = switch_machine(&mut machines, active, last);
+                }
+                MachineAction::Select(idx) => active = switch_machine(&mut machines, active, idx),
+                MachineAction::Close(idx) if machines.len() > 1 => {
+                    if idx == active {
+                        // The next machine (or previous, if it was the last) takes its place
+                        let next = if idx + 1 < machines.len() {
+                            idx + 1
+                        } else {
+                            idx - 1
+                        };
+                        active = switch_machine(&mut machines, active, next);
+                    }
+                    machines.remove(idx);
+                    if idx < active {
+                        active -= 1;
+                    }
                 }
-                Event::KeyUp { keycode, .. } => {
-                    if let Some(key) = keycode {
-                        match key {
-                            Keycode::Num1 => cpu.keypad.set_key(1, false),
-                            Keycode::Num2 => cpu.keypad.set_key(2, false),
-                            Keycode::Num3 => cpu.keypad.set_key(3, false),
-                            Keycode::Num4 => cpu.keypad.set_key(0xC, false),
+                MachineAction::Close(_) => {}
+            }
+        }
+
+        // Machines in other tabs keep running in the background
+        for (idx, machine) in machines.iter_mut().enumerate() {
+            if idx != active {
+                machine.run_frame();
+            }
+        }
 
-                            Keycode::Q => cpu.keypad.set_key(4, false),
-                            Keycode::W => cpu.keypad.set_key(5, false),
-                            Keycode::E => cpu.keypad.set_key(6, false),
-                            Keycode::R => cpu.keypad.set_key(0xD, false),
+        let machine_tabs: Vec<(usize, String)> = machines
+            .iter()
+            .map(|machine| {
+                let title = machine.title().unwrap_or_else(|| tr("Empty").to_string());
+                (machine.id, title)
+            })
+            .collect();
+        let stats = machines[active].stats();
+        let Machine {
+            id: machine_id,
+            cpu,
+            loaded_rom_path,
+            loaded_patch_path,
+            recording,
+            replay_player,
+            speedrun,
+            achievements,
+            ..
+        } = &mut machines[active];
+        thumbnails.poll();
+
+        // ROMs with nothing to report load right away
+        let mut rom_to_load = None;
+        if let Some(checked) = rom_checker.poll() {
+            if checked.analysis.is_clean() {
+                rom_to_load = Some((checked.pending, checked.analysis.base));
+            } else {
+                rom_check = Some(checked);
+            }
+        }
 
-                            Keycode::A => cpu.keypad.set_key(7, false),
-                            Keycode::S => cpu.keypad.set_key(8, false),
-                            Keycode::D => cpu.keypad.set_key(9, false),
-                            Keycode::F => cpu.keypad.set_key(0xE, false),
+        for event in wake_event.take().into_iter().chain(event_loop.poll_iter()) {
+            idle_wait.input(Instant::now());
+            // Besides its own events, the projector only takes keypad input
+            if let Some(screen_window) = projector.as_mut() {
+                if screen_window.is_event_for(&event) {
+                    if !screen_window.handle_event(&event) {
+                        projector = None;
+                    }
+                    if !matches!(event, Event::KeyDown { .. } | Event::KeyUp { .. }) {
+                        continue;
+                    }
+                }
+            }
 
-                            Keycode::Z => cpu.keypad.set_key(0xA, false),
-                            Keycode::X => cpu.keypad.set_key(0, false),
-                            Keycode::C => cpu.keypad.set_key(0xB, false),
-                            Keycode::V => cpu.keypad.set_key(0xF, false),
-                            _ => {}
+            platform.handle_event(&mut imgui, &event);
+            let key_state = match event {
+                // Kiosk mode can only be left through the quit combo
+                sdl2::event::Event::Quit { .. } if !args.kiosk => {
+                    break 'running_loop;
+                }
+                // With the projector open, closing the main window doesn't quit on its own
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Close,
+                    ..
+                } if window_id == window.id() && !args.kiosk => {
+                    break 'running_loop;
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    ..
+                } if args.kiosk && args.quit_combo.matches(keycode, keymod) => {
+                    break 'running_loop;
+                }
+                Event::KeyDown {
+                    keycode: Some(HELP_KEY),
+                    repeat: false,
+                    ..
+                } if !args.kiosk => {
+                    show_help = !show_help;
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(NEXT_ROM_KEY),
+                    repeat: false,
+                    ..
+                } => {
+                    next_rom_requested = true;
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(NEXT_PALETTE_KEY),
+                    repeat: false,
+                    ..
+                } => {
+                    active_palette_id = next_color_palette(active_palette_id);
+                    active_palette = get_color_palette(active_palette_id).unwrap();
+                    cpu.mark_screens_dirty();
+                    config_changed |= remember_palette(&mut config, cpu, &active_palette);
+                    toasts.push(format!(
+                        "{}: {}",
+                        tr("Color palette"),
+                        tr(active_palette.name)
+                    ));
+                    None
+                }
+                Event::DropFile { filename, .. } if !args.kiosk => {
+                    let rom_path = PathBuf::from(filename);
+                    if let Some(rom) = read_rom(&rom_path, None) {
+                        rom_checker.check(PendingRom {
+                            rom_path,
+                            patch_path: None,
+                            rom,
+                        });
+                    }
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(SPLIT_KEY),
+                    repeat: false,
+                    ..
+                } if show_speedrun => {
+                    speedrun.split(cpu.frame());
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(ASSEMBLE_KEY),
+                    repeat: false,
+                    ..
+                } if show_code_editor => {
+                    code_editor_state.assemble_requested = true;
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(RESET_TIMER_KEY),
+                    repeat: false,
+                    ..
+                } if show_speedrun => {
+                    speedrun.reset();
+                    None
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if !args.kiosk && MACRO_KEYS.contains(&keycode) => {
+                    let slot = MACRO_KEYS.iter().position(|&key| key == keycode).unwrap() + 1;
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        // Stops the ongoing recording, whatever its slot
+                        match macro_recorder.take() {
+                            Some((slot, recorder)) => {
+                                config.key_macros.insert(slot, recorder.finish(cpu.frame()));
+                                config_changed = true;
+                            }
+                            None => macro_recorder = Some((slot, MacroRecorder::new(cpu.frame()))),
                         }
+                    } else if let (Some(key_macro), None) =
+                        (config.key_macros.get(&slot), &macro_recorder)
+                    {
+                        macro_to_play = Some(key_macro.clone());
                     }
+                    None
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => keypad_key(keycode).map(|key| (key, false)),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => keypad_key(keycode).map(|key| (key, true)),
+                _ => None,
+            };
+
+            // While a replay is playing, it drives the keypad instead
+            if let (Some((key, pressed)), None) = (key_state, &replay_player) {
+                last_input = Instant::now();
+                cpu.keypad.set_key(key, pressed);
+                if let Some(replay) = recording.as_mut() {
+                    replay.record(cpu.frame(), key, pressed);
+                }
+                if let Some((_, recorder)) = macro_recorder.as_mut() {
+                    recorder.record(cpu.frame(), key, pressed);
+                }
+                if pressed {
+                    speedrun.on_input(cpu.frame());
                 }
-                Event::KeyDown { keycode, .. } => {
-                    if let Some(key) = keycode {
-                        match key {
-                            Keycode::Num1 => cpu.keypad.set_key(1, true),
-                            Keycode::Num2 => cpu.keypad.set_key(2, true),
-                            Keycode::Num3 => cpu.keypad.set_key(3, true),
-                            Keycode::Num4 => cpu.keypad.set_key(0xC, true),
+            }
+        }
 
-                            Keycode::Q => cpu.keypad.set_key(4, true),
-                            Keycode::W => cpu.keypad.set_key(5, true),
-                            Keycode::E => cpu.keypad.set_key(6, true),
-                            Keycode::R => cpu.keypad.set_key(0xD, true),
+        let idle_reset = Duration::from_secs(args.idle_reset_secs);
+        let mut restart_requested =
+            args.kiosk && args.idle_reset_secs > 0 && last_input.elapsed() >= idle_reset;
+        if restart_requested {
+            last_input = Instant::now();
+        }
 
-                            Keycode::A => cpu.keypad.set_key(7, true),
-                            Keycode::S => cpu.keypad.set_key(8, true),
-                            Keycode::D => cpu.keypad.set_key(9, true),
-                            Keycode::F => cpu.keypad.set_key(0xE, true),
+        // A ROM stuck in a loop, or faulted, gets restarted, or skipped
+        // in a rotation
+        let cycles = cpu.clock().cycles();
+        if args.kiosk
+            && watchdog.is_enabled()
+            && (cpu.last_fault().is_some() || watchdog.expired(now, cycles))
+        {
+            log::warn!("Watchdog expired, restarting.");
+            if playlist.len() > 1 {
+                next_rom_requested = true;
+            } else {
+                restart_requested = true;
+            }
+        }
 
-                            Keycode::Z => cpu.keypad.set_key(0xA, true),
-                            Keycode::X => cpu.keypad.set_key(0, true),
-                            Keycode::C => cpu.keypad.set_key(0xB, true),
-                            Keycode::V => cpu.keypad.set_key(0xF, true),
-                            _ => {}
-                        }
-                    }
+        if restart_requested {
+            watchdog.restart(now);
+            if let Some(rom) = loaded_rom_path
+                .as_ref()
+                .and_then(|path| read_rom(path, None))
+            {
+                let base = cpu.memory.program_start();
+                cpu.clear();
+                if let Err(err) = cpu.load_rom(&rom, base) {
+                    log::error!("Could not restart the ROM: {}", err);
                 }
+            }
+        }
 
-                _ => {}
+        if (next_rom_requested && playlist.len() > 1) || playlist.should_advance(now) {
+            watchdog.restart(now);
+            if let Some(rom_path) = playlist.advance() {
+                if switch_rom(cpu, rom_path) {
+                    *loaded_rom_path = Some(rom_path.to_path_buf());
+                    *loaded_patch_path = None;
+                    *recording = None;
+                    *replay_player = None;
+                }
+            }
+        }
+        next_rom_requested = false;
+
+        // Each ROM comes back with the palette it was last played with
+        if cpu.rom_hash() != palette_rom_hash {
+            palette_rom_hash = cpu.rom_hash();
+            let remembered = palette_rom_hash
+                .and_then(|rom_hash| config.rom_palette(rom_hash))
+                .and_then(color_palette_id);
+            if let Some(palette_id) = remembered.filter(|&id| id != active_palette_id) {
+                active_palette_id = palette_id;
+                active_palette = get_color_palette(palette_id).unwrap();
+                cpu.mark_screens_dirty();
             }
         }
 
         platform.prepare_frame(&mut imgui, &window, &event_loop);
+        if theme_changed {
+            theme::apply(imgui.style_mut(), config.theme, config.accent_color);
+        }
+        if display_changed {
+            unsafe {
+                graphics::set_orientation(
+                    &mut renderer,
+                    &screen_quad,
+                    config.display_rotation,
+                    config.display_mirrored,
+                );
+                graphics::set_grid_opacity(&mut renderer, &screen_quad, config.pixel_grid);
+                graphics::set_color_adjustment(
+                    &mut renderer,
+                    &screen_quad,
+                    config.color_adjustment,
+                );
+            }
+            display_changed = false;
+            config_changed = true;
+        }
+        if audio_changed {
+            // Reopen the device with the new settings
+            cpu.remove_audio_sink();
+            cpu.set_audio_sink(Box::new(audio::QueueSink::new(
+                &audio_subsystem,
+                config.audio_sample_rate,
+                config.audio_buffer_size,
+                audio_stats.clone(),
+            )));
+            audio_changed = false;
+            config_changed = true;
+        }
+        if theme_changed || config_changed {
+            theme_changed = false;
+            config_changed = false;
+            if let Err(err) = config.save() {
+                log::warn!("Could not save the config: {}", err);
+            }
+        }
         imgui.style_mut().window_rounding = 0.0;
         imgui.style_mut().window_border_size = 0.0;
 
@@ -242,181 +734,1028 @@ fn main() {
         let io = ui.io();
         let w = ui
             .window("A window!")
+            .flags(imgui::WindowFlags::NO_DOCKING)
+            .bring_to_front_on_focus(false)
             .position([0.0, 0.0], Condition::Appearing)
             .size(
                 [io.display_size[0], io.display_size[1]],
                 Condition::Appearing,
             )
-            .menu_bar(true)
+            .menu_bar(!args.kiosk)
             .draw_background(false)
             .movable(false)
             .no_decoration();
 
         w.build(|| {
-            let main_menu = ui.begin_menu_bar().unwrap();
-            {
-                if let Some(menu) = ui.begin_menu("File") {
+            if let Some(main_menu) = ui.begin_menu_bar() {
+                if let Some(menu) = ui.begin_menu(tr("File")) {
                     let btn = ui
-                        .menu_item_config("Load ROM")
+                        .menu_item_config(tr("Load ROM"))
                         .enabled(!cpu.is_rom_loaded())
                         .shortcut("Ctrl + O")
                         .build();
                     if btn {
-                        loaded_rom_path = rom_select_window(&mut cpu);
+                        if let Some(pending) = rom_select_window(false) {
+                            rom_checker.check(pending);
+                        }
+                    }
+                    if ui
+                        .menu_item_config(tr("Load ROM with patch..."))
+                        .enabled(!cpu.is_rom_loaded())
+                        .build()
+                    {
+                        if let Some(pending) = rom_select_window(true) {
+                            rom_checker.check(pending);
+                        }
+                    }
+                    if ui
+                        .menu_item_config(tr("Import hex..."))
+                        .enabled(!cpu.is_rom_loaded())
+                        .build()
+                    {
+                        hex_import_state.opened = true;
+                    }
+                    if ui.menu_item(tr("Open project folder...")) {
+                        project_to_open = rfd::FileDialog::new().pick_folder();
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Open built-in")) {
+                        for rom in &BUILTIN_ROMS {
+                            if ui.menu_item(rom.name) {
+                                let rom_path = rom.path();
+                                if switch_rom(cpu, &rom_path) {
+                                    *loaded_rom_path = Some(rom_path);
+                                    *loaded_patch_path = None;
+                                    *recording = None;
+                                    *replay_player = None;
+                                }
+                            }
+                            if ui.is_item_hovered() {
+                                let thumbnail = thumbnails.get(rom.data);
+                                ui.tooltip(|| {
+                                    ui.text(tr(rom.description));
+                                    thumbnail_or_placeholder(ui, thumbnail, &active_palette);
+                                });
+                            }
+                        }
                     }
                     if ui
-                        .menu_item_config("Restart ROM")
+                        .menu_item_config(tr("Restart ROM"))
                         .shortcut("Ctrl + R")
                         .enabled(cpu.is_rom_loaded())
                         .build()
                     {
-                        let rom = fs::read(loaded_rom_path.as_ref().unwrap()).unwrap();
+                        let base = cpu.memory.program_start();
                         cpu.clear();
-                        cpu.load_rom(rom, PROGRAM_BEGIN);
+                        if let Some(rom) = read_rom(
+                            loaded_rom_path.as_ref().unwrap(),
+                            loaded_patch_path.as_deref(),
+                        ) {
+                            if let Err(err) = cpu.load_rom(&rom, base) {
+                                show_error(tr("Could not load ROM"), &err.to_string());
+                            }
+                        }
+                        *recording = None;
+                        *replay_player = None;
                     };
+                    if let Some(_menu) =
+                        ui.begin_menu_with_enabled(tr("Export ROM"), cpu.is_rom_loaded())
+                    {
+                        if ui.menu_item(tr("As loaded...")) {
+                            rom_export_window(&cpu.memory.export_program(false));
+                        }
+                        if ui.menu_item(tr("Trimmed of trailing zeros...")) {
+                            rom_export_window(&cpu.memory.export_program(true));
+                        }
+                    }
+                    if ui
+                        .menu_item_config(tr("Save state..."))
+                        .enabled(cpu.is_rom_loaded())
+                        .build()
+                        && state_save_window(&savestate::save(cpu))
+                    {
+                        cpu.mark_save_state();
+                    }
+                    if ui
+                        .menu_item_config(tr("Load state..."))
+                        .enabled(cpu.is_rom_loaded())
+                        .build()
+                    {
+                        if let Some(bytes) = state_open_window() {
+                            if let Err(err) = savestate::load(cpu, &bytes) {
+                                show_error(tr("Could not load state"), &err.to_string());
+                            }
+                        }
+                    }
                     if ui
-                        .menu_item_config("Close ROM")
+                        .menu_item_config(tr("Close ROM"))
                         .shortcut("Ctrl + W")
                         .enabled(cpu.is_rom_loaded())
                         .build()
                     {
                         cpu.clear();
-                        unsafe {
-                            graphics::update_render(
-                                &mut renderer,
-                                &mut buffer,
-                                &tex,
-                                &cpu.screen.0,
-                                &active_palette,
-                            );
-                        }
+                        *recording = None;
+                        *replay_player = None;
                     };
-                    ui.separator();
-                    if ui.menu_item("Exit") {
-                        running = false;
-                    }
-                    menu.end();
-                }
-
-                if let Some(menu) = ui.begin_menu("Options") {
-                    ui.menu_item_config("Main options").enabled(false).build();
-                    if let Some(_) = ui.begin_menu("Timings & display") {
-                        ui.text("Emulation and draw timings");
-                        ui.slider("Draws per second", 30, 400, &mut cpu.draws_per_second);
-                        ui.slider("Ticks/cycles per frame", 1, 500, &mut cpu.ticks_per_frame);
+                    if let Some(_menu) = ui.begin_menu(tr("Playlist")) {
+                        if ui.menu_item(tr("Add ROMs...")) {
+                            for rom_path in roms_select_window() {
+                                playlist.add(rom_path);
+                            }
+                        }
+                        if ui
+                            .menu_item_config(tr("Next ROM"))
+                            .shortcut("F2")
+                            .enabled(playlist.len() > 1)
+                            .build()
+                        {
+                            next_rom_requested = true;
+                        }
+                        let mut advance_secs = playlist.advance_secs as i32;
+                        if ui
+                            .input_int(tr("Auto-advance (seconds)"), &mut advance_secs)
+                            .step(10)
+                            .build()
+                        {
+                            playlist.advance_secs = advance_secs.max(0) as u64;
+                        }
+                        if ui
+                            .menu_item_config(tr("Clear"))
+                            .enabled(!playlist.is_empty())
+                            .build()
+                        {
+                            playlist.clear();
+                        }
 
-                        let cur_cursor = ui.cursor_pos();
-                        ui.set_cursor_pos(Vector2 {
-                            x: cur_cursor[0],
-                            y: cur_cursor[1] + 20.0,
-                        });
                         ui.separator();
-                        ui.text("Display/window framerates");
-                        if ui.checkbox(&"VSync", &mut vsync_enabled) {
-                            if vsync_enabled {
-                                let _ = video_subsystem.gl_set_swap_interval(SwapInterval::VSync);
-                            } else {
-                                let _ =
-                                    video_subsystem.gl_set_swap_interval(SwapInterval::Immediate);
+                        let mut selected = None;
+                        for (idx, rom_path) in playlist.entries().iter().enumerate() {
+                            let _id = ui.push_id_usize(idx);
+                            if ui
+                                .menu_item_config(Playlist::title(rom_path))
+                                .selected(idx == playlist.current_index())
+                                .build()
+                            {
+                                selected = Some(idx);
+                            }
+                            if ui.is_item_hovered() {
+                                let thumbnail = thumbnails.get_for_path(rom_path);
+                                ui.tooltip(|| {
+                                    thumbnail_or_placeholder(ui, thumbnail, &active_palette)
+                                });
                             }
                         }
-                        let disabled_region = ui.begin_disabled(vsync_enabled);
+                        if let Some(rom_path) = selected.and_then(|idx| playlist.select(idx)) {
+                            if switch_rom(cpu, rom_path) {
+                                *loaded_rom_path = Some(rom_path.to_path_buf());
+                                *loaded_patch_path = None;
+                                *recording = None;
+                                *replay_player = None;
+                            }
+                        }
+                    }
+                    ui.separator();
+                    if let Some(_menu) = ui.begin_menu(tr("Replay")) {
+                        let idle = recording.is_none() && replay_player.is_none();
+                        if ui
+                            .menu_item_config(tr("Start recording"))
+                            .enabled(cpu.is_rom_loaded() && idle)
+                            .build()
                         {
-                            if ui.input_scalar("Max FPS", &mut max_fps).step(10).build() {
-                                if max_fps < 10 {
-                                    max_fps = 10
+                            // Recordings always start from a freshly reset machine,
+                            // with the ROM where it was loaded
+                            if let Some(rom) = read_rom(
+                                loaded_rom_path.as_ref().unwrap(),
+                                loaded_patch_path.as_deref(),
+                            ) {
+                                let base = cpu.memory.program_start();
+                                cpu.clear();
+                                cpu.seed_rng(rand::random());
+                                match cpu.load_rom(&rom, base) {
+                                    Ok(()) => *recording = Some(Replay::new(&rom, cpu)),
+                                    Err(err) => {
+                                        show_error(tr("Could not load ROM"), &err.to_string())
+                                    }
                                 }
                             }
                         }
-                        disabled_region.end();
-                    }
-                    if ui
-                        .menu_item_config("Sound enabled")
-                        .selected(cpu.is_beep_enabled())
-                        .build()
-                    {
-                        cpu.toggle_beep_enabled();
-                    }
-                    if let Some(_) = ui.begin_menu("Color palette") {
-                        if ui.combo(
-                            "Active",
-                            &mut active_palette_id,
-                            COLOR_PALETTES.as_ref(),
-                            |e| Cow::from(e.name),
-                        ) {
-                            if let Some(palette) = get_color_palette(active_palette_id) {
-                                active_palette = palette;
-                            } else {
-                                active_palette.name = "Custom";
+                        if ui
+                            .menu_item_config(tr("Start recording from here"))
+                            .enabled(cpu.is_rom_loaded() && idle)
+                            .build()
+                        {
+                            // The current state becomes the replay's starting point,
+                            // e.g. to re-record from a loaded save state
+                            if let Some(rom) = read_rom(
+                                loaded_rom_path.as_ref().unwrap(),
+                                loaded_patch_path.as_deref(),
+                            ) {
+                                let state = savestate::save(cpu);
+                                cpu.seed_rng(rand::random());
+                                let replay = Replay::from_state(&rom, cpu, state);
+                                match replay.restart(&rom, cpu) {
+                                    Ok(()) => *recording = Some(replay),
+                                    Err(err) => show_error(
+                                        tr("Could not start recording"),
+                                        &err.to_string(),
+                                    ),
+                                }
                             }
                         }
-
-                        if active_palette.name == "Custom" {
-                            ui.color_picker3("Enabled pixels", &mut active_palette.enabled_px);
-                            ui.color_picker3("Disabled pixels", &mut active_palette.disabled_px);
+                        if ui
+                            .menu_item_config(tr("Stop recording & save"))
+                            .enabled(recording.is_some())
+                            .build()
+                        {
+                            let mut replay = recording.take().unwrap();
+                            replay.finish(cpu.frame());
+                            replay_save_window(&replay);
                         }
-                    }
-                    ui.separator();
-                    ui.menu_item_config("Advanced").enabled(false).build();
-
-                    if let Some(_) = ui.begin_menu("Quirks") {
+                        ui.separator();
                         if ui
-                            .menu_item_config("Shift operations against Vy instead of Vx register.")
-                            .selected(cpu.shifts_against_vy)
+                            .menu_item_config(tr("Play replay"))
+                            .enabled(cpu.is_rom_loaded() && idle)
                             .build()
                         {
-                            cpu.shifts_against_vy = !cpu.shifts_against_vy
+                            let rom = read_rom(
+                                loaded_rom_path.as_ref().unwrap(),
+                                loaded_patch_path.as_deref(),
+                            );
+                            if let (Some(rom), Some(replay)) = (rom, replay_open_window()) {
+                                match ReplayPlayer::start(replay, rom, cpu) {
+                                    Ok(player) => *replay_player = Some(player),
+                                    Err(err) => {
+                                        show_error(tr("Could not play replay"), &err.to_string())
+                                    }
+                                }
+                            }
                         }
-
                         if ui
-                            .menu_item_config(
-                                "Memory load/save operations (fx55, fx65) increment I register.",
-                            )
-                            .selected(cpu.memory_load_save_increment_i)
+                            .menu_item_config(tr("Stop playback"))
+                            .enabled(replay_player.is_some())
                             .build()
                         {
-                            cpu.memory_load_save_increment_i = !cpu.memory_load_save_increment_i
+                            *replay_player = None;
                         }
-
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Video")) {
+                        let idle = video_recorder.is_none();
+                        let mut source = None;
                         if ui
-                            .menu_item_config("Sprite clipping instead of wrapping.")
-                            .selected(cpu.sprite_clipping)
+                            .menu_item_config(tr("Record screen..."))
+                            .enabled(idle)
                             .build()
                         {
-                            cpu.sprite_clipping = !cpu.sprite_clipping
+                            source = Some(video::Source::Screen);
                         }
-
                         if ui
-                            .menu_item_config("Jump instructions to V0+NNN instead of VX+NN.")
-                            .selected(cpu.jump_to_nnn)
+                            .menu_item_config(tr("Record window with panels..."))
+                            .enabled(idle)
                             .build()
                         {
-                            cpu.jump_to_nnn = !cpu.jump_to_nnn
+                            source = Some(video::Source::Window);
+                        }
+                        if let Some(source) = source {
+                            let size = match source {
+                                video::Source::Screen => (
+                                    cpu.screen.width() * video::SCREEN_SCALE,
+                                    cpu.screen.height() * video::SCREEN_SCALE,
+                                ),
+                                video::Source::Window => {
+                                    let (width, height) = window.drawable_size();
+                                    (width as usize, height as usize)
+                                }
+                            };
+                            if let Some(path) = video_save_window() {
+                                match VideoRecorder::start(&path, source, size.0, size.1) {
+                                    Ok(recorder) => video_recorder = Some(recorder),
+                                    Err(err) => show_error(
+                                        tr("Could not start recording (is ffmpeg installed?)"),
+                                        &err.to_string(),
+                                    ),
+                                }
+                            }
+                        }
+                        if ui
+                            .menu_item_config(tr("Stop recording"))
+                            .enabled(!idle)
+                            .build()
+                        {
+                            if let Err(err) = video_recorder.take().unwrap().finish() {
+                                show_error(tr("Could not save video"), &err);
+                            }
                         }
                     }
-
+                    ui.separator();
+                    if ui.menu_item(tr("Exit")) {
+                        running = false;
+                    }
                     menu.end();
                 }
 
-                let halt_width = 55.0;
-                let fps_width = 90.0;
-                let margin =
-                    ui.cursor_pos()[0] + ui.content_region_avail()[0] - halt_width - fps_width;
-                let disabled_scope = ui.begin_disabled(false);
-                {
-                    ui.set_cursor_pos([margin, ui.cursor_pos()[1]]);
-                    let text = if cpu.is_halted() { "Resume" } else { "Halt" };
-                    if ui.button_with_size(text, [halt_width, 0.0]) {
-                        cpu.toggle_halt();
+                if let Some(_menu) = ui.begin_menu(tr("Machines")) {
+                    if ui.menu_item(tr("New machine")) {
+                        machine_action = Some(MachineAction::New);
+                    }
+                    if ui
+                        .menu_item_config(tr("Close machine"))
+                        .enabled(machine_tabs.len() > 1)
+                        .build()
+                    {
+                        machine_action = Some(MachineAction::Close(active));
+                    }
+                    ui.separator();
+                    for (idx, (id, title)) in machine_tabs.iter().enumerate() {
+                        let _id = ui.push_id_usize(*id);
+                        if ui.menu_item_config(title).selected(idx == active).build() {
+                            machine_action = Some(MachineAction::Select(idx));
+                        }
                     }
                 }
 
-                disabled_scope.end();
-                let margin = ui.cursor_pos()[0] + ui.content_region_avail()[0] - fps_width;
-                let disabled_scope = ui.begin_disabled(true);
-                {
-                    ui.set_cursor_pos([margin, ui.cursor_pos()[1]]);
-                    let align_scope =
+                if let Some(menu) = ui.begin_menu(tr("Options")) {
+                    ui.menu_item_config(tr("Main options"))
+                        .enabled(false)
+                        .build();
+                    if let Some(_menu) = ui.begin_menu(tr("Timings & display")) {
+                        ui.text(tr("Emulation and draw timings"));
+                        ui.slider(tr("Draws per second"), 30, 400, &mut draws_per_second);
+                        if ui.checkbox(tr("Reduce flashing"), &mut config.reduce_flashing) {
+                            flash_guard.enabled = config.reduce_flashing;
+                            config_changed = true;
+                        }
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text(tr(
+                                "Limits frames flipping most of the screen to 3 a second.",
+                            ));
+                        }
+                        let mut vip_timing = cpu.timing_model == TimingModel::CosmacVip;
+                        if ui.checkbox(tr("COSMAC VIP timing"), &mut vip_timing) {
+                            cpu.timing_model = if vip_timing {
+                                TimingModel::CosmacVip
+                            } else {
+                                TimingModel::Uniform
+                            };
+                        }
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text(tr(
+                                "Instructions take as long as on the original interpreter, instead of a fixed number per frame.",
+                            ));
+                        }
+                        let disabled_region = ui.begin_disabled(vip_timing);
+                        ui.slider(
+                            tr("Ticks/cycles per frame"),
+                            1,
+                            500,
+                            &mut cpu.ticks_per_frame,
+                        );
+                        disabled_region.end();
+
+                        let cur_cursor = ui.cursor_pos();
+                        ui.set_cursor_pos(Vector2 {
+                            x: cur_cursor[0],
+                            y: cur_cursor[1] + 20.0,
+                        });
+                        ui.separator();
+                        ui.text(tr("Display/window framerates"));
+                        let mut vsync_enabled = vsync != VSync::Off;
+                        if ui.checkbox("VSync", &mut vsync_enabled) {
+                            vsync = if vsync_enabled { VSync::On } else { VSync::Off };
+                            frame_limiter::set_swap_interval(&video_subsystem, vsync);
+                            frame_limiter.reset();
+                        }
+                        let disabled_region = ui.begin_disabled(!vsync_enabled);
+                        {
+                            let mut adaptive = vsync == VSync::Adaptive;
+                            if ui.checkbox(tr("Adaptive VSync"), &mut adaptive) {
+                                vsync = if adaptive { VSync::Adaptive } else { VSync::On };
+                                frame_limiter::set_swap_interval(&video_subsystem, vsync);
+                            }
+                            if ui.is_item_hovered() {
+                                ui.tooltip_text(tr(
+                                    "Frames running late are shown right away, tearing instead of stuttering.",
+                                ));
+                            }
+                        }
+                        disabled_region.end();
+                        let disabled_region = ui.begin_disabled(vsync_enabled);
+                        {
+                            if ui
+                                .input_scalar(tr("Max FPS"), &mut max_fps)
+                                .step(10)
+                                .build()
+                                && max_fps < 10
+                            {
+                                max_fps = 10
+                            }
+                        }
+                        disabled_region.end();
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Orientation")) {
+                        for rotation in graphics::ROTATIONS {
+                            let name = match rotation {
+                                graphics::Rotation::None => tr("No rotation").to_string(),
+                                rotation => format!("{}°", rotation.degrees()),
+                            };
+                            if ui
+                                .menu_item_config(name)
+                                .selected(config.display_rotation == rotation)
+                                .build()
+                            {
+                                config.display_rotation = rotation;
+                                display_changed = true;
+                            }
+                        }
+                        ui.separator();
+                        if ui
+                            .menu_item_config(tr("Mirror horizontally"))
+                            .selected(config.display_mirrored)
+                            .build()
+                        {
+                            config.display_mirrored = !config.display_mirrored;
+                            display_changed = true;
+                        }
+                    }
+                    if ui
+                        .menu_item_config(tr("Projector window"))
+                        .selected(projector.is_some())
+                        .build()
+                    {
+                        if projector.is_some() {
+                            projector = None;
+                        } else {
+                            match Projector::open(&video_subsystem, ui_scale) {
+                                Ok(screen_window) => projector = Some(screen_window),
+                                Err(err) => show_error(tr("Could not open the projector"), &err),
+                            }
+                        }
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Speedrun timer")) {
+                        if ui
+                            .menu_item_config(tr("Show timer"))
+                            .selected(show_speedrun)
+                            .build()
+                        {
+                            show_speedrun = !show_speedrun;
+                        }
+                        ui.menu_item_config(tr("Start on first input"))
+                            .build_with_ref(&mut speedrun.auto_start);
+                        if ui
+                            .menu_item_config(tr("Split"))
+                            .shortcut("F9")
+                            .enabled(show_speedrun)
+                            .build()
+                        {
+                            speedrun.split(cpu.frame());
+                        }
+                        if ui
+                            .menu_item_config(tr("Reset timer"))
+                            .shortcut("F10")
+                            .enabled(show_speedrun)
+                            .build()
+                        {
+                            speedrun.reset();
+                        }
+                        ui.separator();
+                        ui.menu_item_config(tr("Auto-splitter..."))
+                            .build_with_ref(&mut show_auto_splitter);
+                        if ui
+                            .menu_item_config(tr("Export splits..."))
+                            .enabled(!speedrun.splits().is_empty())
+                            .build()
+                        {
+                            export_splits_window(&speedrun.export_splits());
+                        }
+                    }
+                    if ui
+                        .menu_item_config(tr("Sound enabled"))
+                        .selected(cpu.is_beep_enabled())
+                        .build()
+                    {
+                        cpu.toggle_beep_enabled();
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Audio")) {
+                        ui.set_next_item_width(ui.current_font_size() * 6.0);
+                        if let Some(_combo) = ui.begin_combo(
+                            tr("Sample rate"),
+                            format!("{} Hz", config.audio_sample_rate),
+                        ) {
+                            for sample_rate in audio::SAMPLE_RATES {
+                                let selected = config.audio_sample_rate == sample_rate;
+                                if ui
+                                    .selectable_config(format!("{} Hz", sample_rate))
+                                    .selected(selected)
+                                    .build()
+                                {
+                                    config.audio_sample_rate = sample_rate;
+                                    audio_changed = true;
+                                }
+                            }
+                        }
+                        let buffer_size_name = |buffer_size: u16| match buffer_size {
+                            0 => tr("SDL default").to_string(),
+                            samples => format!("{} {}", samples, tr("samples")),
+                        };
+                        ui.set_next_item_width(ui.current_font_size() * 6.0);
+                        if let Some(_combo) = ui.begin_combo(
+                            tr("Buffer size"),
+                            buffer_size_name(config.audio_buffer_size),
+                        ) {
+                            for buffer_size in audio::BUFFER_SIZES {
+                                let selected = config.audio_buffer_size == buffer_size;
+                                if ui
+                                    .selectable_config(buffer_size_name(buffer_size))
+                                    .selected(selected)
+                                    .build()
+                                {
+                                    config.audio_buffer_size = buffer_size;
+                                    audio_changed = true;
+                                }
+                            }
+                        }
+                        ui.separator();
+                        let latency_text = |latency: Option<Duration>| {
+                            latency.map_or("-".to_string(), |latency| {
+                                format!("{:.1} ms", latency.as_secs_f64() * 1000.0)
+                            })
+                        };
+                        ui.text_disabled(format!(
+                            "{}: {}",
+                            tr("Buffer latency"),
+                            latency_text(audio_stats.buffer_latency())
+                        ));
+                        ui.text_disabled(format!(
+                            "{}: {}",
+                            tr("Measured latency"),
+                            latency_text(audio_stats.measured_latency())
+                        ));
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Color palette")) {
+                        let mut palette_picked = ui.combo(
+                            tr("Active"),
+                            &mut active_palette_id,
+                            COLOR_PALETTES.as_ref(),
+                            |e| Cow::from(tr(e.name)),
+                        );
+                        if let Some(_menu) = ui.begin_menu(tr("Color-blind friendly")) {
+                            for (idx, palette) in COLOR_PALETTES
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, palette)| palette.color_blind_safe)
+                            {
+                                if ui
+                                    .menu_item_config(tr(palette.name))
+                                    .selected(idx == active_palette_id)
+                                    .build()
+                                {
+                                    active_palette_id = idx;
+                                    palette_picked = true;
+                                }
+                            }
+                        }
+                        if palette_picked {
+                            if let Some(palette) = get_color_palette(active_palette_id) {
+                                active_palette = palette;
+                            } else {
+                                active_palette.name = "Custom";
+                            }
+                            cpu.mark_screens_dirty();
+                            config_changed |= remember_palette(&mut config, cpu, &active_palette);
+                        }
+
+                        if active_palette.name == "Custom"
+                            && (ui.color_picker3(
+                                tr("Enabled pixels"),
+                                &mut active_palette.enabled_px,
+                            ) | ui.color_picker3(
+                                tr("Disabled pixels"),
+                                &mut active_palette.disabled_px,
+                            ))
+                        {
+                            cpu.mark_screens_dirty();
+                        }
+
+                        ui.separator();
+                        let mut border = config.palette_border(active_palette.name);
+                        let mut off_pixels = border == graphics::Border::OffPixels;
+                        if ui.checkbox(tr("Border matches disabled pixels"), &mut off_pixels) {
+                            border = if off_pixels {
+                                graphics::Border::OffPixels
+                            } else {
+                                graphics::DEFAULT_BORDER
+                            };
+                            config
+                                .palette_borders
+                                .insert(active_palette.name.to_string(), border);
+                            config_changed = true;
+                        }
+                        if let graphics::Border::Color(mut color) = border {
+                            if ui.color_edit3(tr("Border"), &mut color) {
+                                config.palette_borders.insert(
+                                    active_palette.name.to_string(),
+                                    graphics::Border::Color(color),
+                                );
+                                config_changed = true;
+                            }
+                        }
+                        if ui.slider(
+                            tr("Padding"),
+                            0,
+                            config::MAX_SCREEN_PADDING,
+                            &mut config.screen_padding,
+                        ) {
+                            config_changed = true;
+                        }
+                        if ui
+                            .slider_config(tr("Pixel grid"), 0.0, 1.0)
+                            .display_format("%.2f")
+                            .build(&mut config.pixel_grid)
+                        {
+                            display_changed = true;
+                        }
+
+                        ui.separator();
+                        let adjustment = &mut config.color_adjustment;
+                        for (label, (min, max), value) in [
+                            (tr("Gamma"), graphics::GAMMA_RANGE, &mut adjustment.gamma),
+                            (
+                                tr("Brightness"),
+                                graphics::BRIGHTNESS_RANGE,
+                                &mut adjustment.brightness,
+                            ),
+                            (tr("Contrast"), graphics::CONTRAST_RANGE, &mut adjustment.contrast),
+                        ] {
+                            if ui
+                                .slider_config(label, min, max)
+                                .display_format("%.2f")
+                                .build(value)
+                            {
+                                display_changed = true;
+                            }
+                        }
+                        if *adjustment != graphics::ColorAdjustment::default()
+                            && ui.menu_item(tr("Reset adjustments"))
+                        {
+                            *adjustment = graphics::ColorAdjustment::default();
+                            display_changed = true;
+                        }
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Theme")) {
+                        for theme in theme::THEMES {
+                            if ui
+                                .menu_item_config(tr(theme.name()))
+                                .selected(config.theme == theme)
+                                .build()
+                            {
+                                config.theme = theme;
+                                theme_changed = true;
+                            }
+                        }
+                        ui.separator();
+                        if ui.color_edit3(tr("Accent"), &mut config.accent_color) {
+                            theme_changed = true;
+                        }
+                        if ui.menu_item(tr("Reset accent")) {
+                            config.accent_color = theme::DEFAULT_ACCENT;
+                            theme_changed = true;
+                        }
+                    }
+                    if let Some(_menu) = ui.begin_menu(tr("Language")) {
+                        for language in locale::LANGUAGES {
+                            if ui
+                                .menu_item_config(language.name())
+                                .selected(locale::language() == language)
+                                .build()
+                            {
+                                locale::set_language(language);
+                                config.language = language;
+                                config_changed = true;
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.menu_item_config(tr("Advanced")).enabled(false).build();
+
+                    if ui
+                        .menu_item_config(tr("Show state hash"))
+                        .selected(show_state_hash)
+                        .build()
+                    {
+                        show_state_hash = !show_state_hash;
+                    }
+
+                    if let Some(_menu) = ui.begin_menu(tr("Machine")) {
+                        for profile in PROFILES {
+                            if ui
+                                .menu_item_config(tr(profile.name()))
+                                .selected(cpu.profile() == profile)
+                                .build()
+                            {
+                                cpu.set_profile(profile);
+                            }
+                            if ui.is_item_hovered() {
+                                ui.tooltip_text(format!(
+                                    "{} {}\n{} {} KB",
+                                    tr("Stack size:"),
+                                    profile.stack_size(),
+                                    tr("Memory:"),
+                                    profile.memory_size() / 1024
+                                ));
+                            }
+                        }
+                    }
+
+                    if let Some(_menu) = ui.begin_menu(tr("Out of bounds memory accesses")) {
+                        for policy in BOUNDS_POLICIES {
+                            if ui
+                                .menu_item_config(tr(policy.name()))
+                                .selected(cpu.memory.bounds_policy() == policy)
+                                .build()
+                            {
+                                cpu.memory.set_bounds_policy(policy);
+                            }
+                        }
+                    }
+
+                    if let Some(_menu) = ui.begin_menu(tr("Writes into the font")) {
+                        for action in FONT_WRITE_ACTIONS {
+                            if ui
+                                .menu_item_config(tr(action.name()))
+                                .selected(cpu.font_write_action == action)
+                                .build()
+                            {
+                                cpu.font_write_action = action;
+                            }
+                        }
+                    }
+
+                    if let Some(_menu) = ui.begin_menu(tr("Quirks")) {
+                        if ui
+                            .menu_item_config(tr(
+                                "Shift operations against Vy instead of Vx register.",
+                            ))
+                            .selected(cpu.shifts_against_vy)
+                            .build()
+                        {
+                            cpu.shifts_against_vy = !cpu.shifts_against_vy
+                        }
+
+                        if ui
+                            .menu_item_config(tr(
+                                "Memory load/save operations (fx55, fx65) increment I register.",
+                            ))
+                            .selected(cpu.memory_load_save_increment_i)
+                            .build()
+                        {
+                            cpu.memory_load_save_increment_i = !cpu.memory_load_save_increment_i
+                        }
+
+                        if ui
+                            .menu_item_config(tr("Horizontal sprite clipping instead of wrapping."))
+                            .selected(cpu.sprite_clipping_x)
+                            .build()
+                        {
+                            cpu.sprite_clipping_x = !cpu.sprite_clipping_x
+                        }
+
+                        if ui
+                            .menu_item_config(tr("Vertical sprite clipping instead of wrapping."))
+                            .selected(cpu.sprite_clipping_y)
+                            .build()
+                        {
+                            cpu.sprite_clipping_y = !cpu.sprite_clipping_y
+                        }
+
+                        if ui
+                            .menu_item_config(tr("Jump instructions to V0+NNN instead of VX+NN."))
+                            .selected(cpu.jump_to_nnn)
+                            .build()
+                        {
+                            cpu.jump_to_nnn = !cpu.jump_to_nnn
+                        }
+
+                        if ui
+                            .menu_item_config(tr("Buzzer only sounds while ST >= 2 (COSMAC VIP)."))
+                            .selected(cpu.sound_timer_threshold)
+                            .build()
+                        {
+                            cpu.sound_timer_threshold = !cpu.sound_timer_threshold
+                        }
+
+                        if ui
+                            .menu_item_config(tr(
+                                "Arithmetic and shifts into VF keep the result instead of the flag.",
+                            ))
+                            .selected(cpu.vf_result_wins)
+                            .build()
+                        {
+                            cpu.vf_result_wins = !cpu.vf_result_wins
+                        }
+                    }
+
+                    if ui
+                        .menu_item_config(tr("Protect font/interpreter memory area"))
+                        .selected(cpu.memory.is_protected())
+                        .build()
+                    {
+                        let protected = cpu.memory.is_protected();
+                        cpu.memory.set_protected(!protected);
+                    }
+
+                    let latched = cpu.keypad.input_mode() == InputMode::Latched;
+                    if ui
+                        .menu_item_config(tr("Latch keypad input per frame"))
+                        .selected(latched)
+                        .build()
+                    {
+                        cpu.keypad.set_input_mode(if latched {
+                            InputMode::Raw
+                        } else {
+                            InputMode::Latched
+                        });
+                    }
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(tr(
+                            "Key checks see the keys as they were when the frame began.",
+                        ));
+                    }
+
+                    menu.end();
+                }
+
+                if show_state_hash {
+                    ui.text_disabled(format!("State: {:016X}", cpu.state_hash()));
+                }
+                if let Some((slot, _)) = macro_recorder.as_ref() {
+                    ui.text_colored(
+                        [1.0, 0.3, 0.3, 1.0],
+                        format!("{} (F{})", tr("Recording macro"), slot + 4),
+                    );
+                }
+
+                if let Some(menu) = ui.begin_menu(tr("Debug")) {
+                    if ui
+                        .menu_item_config(tr("Debugger"))
+                        .selected(show_debugger)
+                        .build()
+                    {
+                        show_debugger = !show_debugger;
+                    }
+                    if ui
+                        .menu_item_config(tr("Outline last sprite draw"))
+                        .selected(show_draw_region)
+                        .build()
+                    {
+                        show_draw_region = !show_draw_region;
+                    }
+                    if ui
+                        .menu_item_config(tr("Ghost previous frame"))
+                        .selected(show_ghost)
+                        .build()
+                    {
+                        show_ghost = !show_ghost;
+                    }
+                    if ui
+                        .menu_item_config(tr("Instruction reference"))
+                        .selected(show_reference)
+                        .build()
+                    {
+                        show_reference = !show_reference;
+                    }
+                    if ui
+                        .menu_item_config(tr("Timeline"))
+                        .selected(show_timeline)
+                        .build()
+                    {
+                        show_timeline = !show_timeline;
+                    }
+                    if ui
+                        .menu_item_config(tr("Memory heatmap"))
+                        .selected(show_memory_heatmap)
+                        .build()
+                    {
+                        show_memory_heatmap = !show_memory_heatmap;
+                    }
+                    if ui
+                        .menu_item_config(tr("Memory diff"))
+                        .selected(show_memory_diff)
+                        .build()
+                    {
+                        show_memory_diff = !show_memory_diff;
+                    }
+                    if ui
+                        .menu_item_config(tr("Teaching mode"))
+                        .selected(teaching.enabled)
+                        .build()
+                    {
+                        teaching.enabled = !teaching.enabled;
+                    }
+                    if ui
+                        .menu_item_config(tr("Console"))
+                        .selected(show_console)
+                        .build()
+                    {
+                        show_console = !show_console;
+                    }
+                    if ui
+                        .menu_item_config(tr("Statistics"))
+                        .selected(show_stats)
+                        .build()
+                    {
+                        show_stats = !show_stats;
+                    }
+                    ui.separator();
+                    if ui.menu_item(tr("Load symbols...")) {
+                        if let Some(symbols) = symbols_open_window() {
+                            debugger_state.symbols = symbols;
+                        }
+                    }
+                    menu.end();
+                }
+
+                if let Some(_menu) = ui.begin_menu(tr("Tools")) {
+                    if ui
+                        .menu_item_config(tr("Sprite editor"))
+                        .selected(show_sprite_editor)
+                        .build()
+                    {
+                        show_sprite_editor = !show_sprite_editor;
+                    }
+                    if ui
+                        .menu_item_config(tr("Code editor"))
+                        .selected(show_code_editor)
+                        .build()
+                    {
+                        show_code_editor = !show_code_editor;
+                    }
+                    if ui
+                        .menu_item_config(tr("Export as array"))
+                        .selected(show_array_export)
+                        .build()
+                    {
+                        show_array_export = !show_array_export;
+                    }
+                    if ui
+                        .menu_item_config(tr("Project"))
+                        .selected(show_project)
+                        .enabled(project.is_some())
+                        .build()
+                    {
+                        show_project = !show_project;
+                    }
+                }
+
+                if let Some(_menu) = ui.begin_menu(tr("Help")) {
+                    if ui
+                        .menu_item_config(tr("Keypad & hotkeys"))
+                        .shortcut("F1")
+                        .selected(show_help)
+                        .build()
+                    {
+                        show_help = !show_help;
+                    }
+                    if ui.menu_item(tr("Welcome")) {
+                        show_welcome = true;
+                    }
+                }
+
+                let halt_width = 55.0 * ui_scale;
+                let fps_width = 90.0 * ui_scale;
+                if let Some(percent) = governor.slow_percent() {
+                    let text = tr("Running at {}% speed").replace("{}", &percent.to_string());
+                    let margin = ui.cursor_pos()[0] + ui.content_region_avail()[0]
+                        - halt_width
+                        - fps_width
+                        - ui.calc_text_size(&text)[0]
+                        - 10.0 * ui_scale;
+                    ui.set_cursor_pos([margin, ui.cursor_pos()[1]]);
+                    ui.text_colored([1.0, 0.8, 0.2, 1.0], text);
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text(tr(
+                            "The computer can't keep up with the emulated clock. Try fewer instructions per frame, or closing the debugger.",
+                        ));
+                    }
+                }
+                let margin =
+                    ui.cursor_pos()[0] + ui.content_region_avail()[0] - halt_width - fps_width;
+                let disabled_scope = ui.begin_disabled(false);
+                {
+                    ui.set_cursor_pos([margin, ui.cursor_pos()[1]]);
+                    let text = if cpu.is_halted() {
+                        tr("Resume")
+                    } else {
+                        tr("Halt")
+                    };
+                    if ui.button_with_size(text, [halt_width, 0.0]) {
+                        cpu.toggle_halt();
+                    }
+                }
+
+                disabled_scope.end();
+                let margin = ui.cursor_pos()[0] + ui.content_region_avail()[0] - fps_width;
+                let disabled_scope = ui.begin_disabled(true);
+                {
+                    ui.set_cursor_pos([margin, ui.cursor_pos()[1]]);
+                    let align_scope =
                         ui.push_style_var(imgui::StyleVar::ButtonTextAlign([1.0, 0.0]));
                     {
                         let no_bg_scope =
@@ -430,75 +1769,898 @@ fn main() {
                     align_scope.end();
                 }
                 disabled_scope.end();
+                main_menu.end();
             }
-            main_menu.end();
 
             if !cpu.is_rom_loaded() {
-                let no_rom_msg = "No ROM loaded!";
+                let no_rom_msg = tr("No ROM loaded!");
                 let text_size = ui.calc_text_size(no_rom_msg);
                 ui.set_cursor_pos([
                     io.display_size[0] / 2.0 - text_size[0] / 2.0,
                     io.display_size[1] / 2.0 - text_size[1] / 2.0,
                 ]);
                 ui.text(no_rom_msg);
-                let size = [80.0, 0.0];
+                let size = [80.0 * ui_scale, 0.0];
                 ui.set_cursor_pos([
                     io.display_size[0] / 2.0 - size[0] / 2.0,
-                    io.display_size[1] / 2.0 + 15.0,
+                    io.display_size[1] / 2.0 + 15.0 * ui_scale,
                 ]);
-                if ui.button_with_size("Load ROM", size) {
-                    loaded_rom_path = rom_select_window(&mut cpu);
+                if ui.button_with_size(tr("Load ROM"), size) {
+                    if let Some(pending) = rom_select_window(false) {
+                        rom_checker.check(pending);
+                    }
                 }
             }
         });
 
+        // Briefly show which ROM the playlist switched to
+        if let Some(rom_path) = playlist.current() {
+            if playlist.len() > 1 && playlist.since_switch() < playlist::TITLE_DURATION {
+                let top = if args.kiosk {
+                    0.0
+                } else {
+                    menu_bar_height(ui.io().display_size[1])
+                };
+                let position = [8.0 * ui_scale, top + 8.0 * ui_scale];
+                ui.get_foreground_draw_list().add_text(
+                    position,
+                    [1.0, 1.0, 1.0, 1.0],
+                    Playlist::title(rom_path),
+                );
+            }
+        }
+
+        // Tabs for switching between machines, right below the menu bar
+        if machine_tabs.len() > 1 && !args.kiosk {
+            let display_width = ui.io().display_size[0];
+            ui.window("##machine_tabs")
+                .position(
+                    [0.0, menu_bar_height(ui.io().display_size[1])],
+                    Condition::Always,
+                )
+                .size([display_width, 0.0], Condition::Always)
+                .flags(imgui::WindowFlags::NO_DOCKING | imgui::WindowFlags::NO_SAVED_SETTINGS)
+                .draw_background(false)
+                .no_decoration()
+                .movable(false)
+                .build(|| {
+                    let Some(_tab_bar) = ui.tab_bar("machines") else {
+                        return;
+                    };
+                    let active_id = machine_tabs[active].0;
+                    for (idx, (id, title)) in machine_tabs.iter().enumerate() {
+                        // Switches made from the menu also move the tab bar
+                        let flags = if *id == active_id && selected_tab_id != active_id {
+                            imgui::TabItemFlags::SET_SELECTED
+                        } else {
+                            imgui::TabItemFlags::empty()
+                        };
+                        let mut opened = true;
+                        let tab = ui.tab_item_with_flags(
+                            format!("{}###machine{}", title, id),
+                            Some(&mut opened),
+                            flags,
+                        );
+                        if tab.is_some() && *id != selected_tab_id {
+                            selected_tab_id = *id;
+                            if idx != active {
+                                machine_action = Some(MachineAction::Select(idx));
+                            }
+                        }
+                        if !opened {
+                            machine_action = Some(MachineAction::Close(idx));
+                        }
+                    }
+                });
+        }
+
+        // Panels can be docked around the edges, leaving the screen visible
+        ui.dockspace_over_main_viewport();
+
+        if show_console {
+            console::draw(ui, &mut console_state, &mut show_console);
+        }
+
+        if show_help {
+            help::draw(ui, &mut show_help);
+        }
+
+        for message in achievements.drain(..) {
+            toasts.push(message);
+        }
+        toasts.draw(ui);
+        flash_guard.draw_notice(ui);
+
+        if show_reference {
+            reference::draw(ui, cpu, &mut reference_state, &mut show_reference);
+        }
+
+        // Only count memory accesses while they're shown
+        cpu.set_memory_access_counting(show_memory_heatmap);
+        if hex_import_state.opened {
+            if let Some(pending) = hex_import::draw(ui, &mut hex_import_state) {
+                rom_checker.check(pending);
+            }
+        }
+
+        // Only record the timeline while it's shown
+        if show_timeline != cpu.timeline().is_some() {
+            cpu.set_timeline_capacity(if show_timeline {
+                timeline::TIMELINE_CAPACITY
+            } else {
+                0
+            });
+        }
+        if show_timeline {
+            timeline::draw(
+                ui,
+                cpu,
+                replay_player.as_mut(),
+                &mut timeline_state,
+                &mut show_timeline,
+            );
+        }
+
+        if show_memory_heatmap {
+            memory_heatmap::draw(ui, cpu, &mut show_memory_heatmap);
+        }
+
+        if show_memory_diff {
+            memory_diff::draw(
+                ui,
+                cpu,
+                &debugger_state.symbols,
+                &mut memory_diff_state,
+                &mut show_memory_diff,
+            );
+        }
+
+        let project_action = match project.as_mut() {
+            Some(project) if show_project => {
+                project::draw(ui, project, &mut project_state, &mut show_project)
+            }
+            _ => None,
+        };
+        if let (Some(ProjectAction::Build), Some(project)) = (&project_action, &project) {
+            if let Some(source) = &project.source {
+                let source = project.path(source);
+                if code_editor_state.path() != Some(source.as_path()) {
+                    code_editor_state.load(source);
+                }
+                show_code_editor = true;
+                code_editor_state.assemble_requested = true;
+            }
+        }
+        let closing_project = matches!(
+            project_action,
+            Some(ProjectAction::Save | ProjectAction::Close)
+        ) || project_to_open.is_some();
+        if let (true, Some(project)) = (closing_project, project.as_mut()) {
+            save_project(
+                project,
+                cpu,
+                &debugger_state,
+                &[
+                    ("debugger", show_debugger),
+                    ("memory_heatmap", show_memory_heatmap),
+                    ("timeline", show_timeline),
+                    ("console", show_console),
+                    ("code_editor", show_code_editor),
+                    ("sprite_editor", show_sprite_editor),
+                ],
+            );
+        }
+        if let Some(ProjectAction::Close) = project_action {
+            project = None;
+            show_project = false;
+        }
+        if let Some(dir) = project_to_open.take() {
+            let opened = Project::open(&dir);
+            open_project(
+                &opened,
+                cpu,
+                &mut debugger_state,
+                &mut code_editor_state,
+                [
+                    ("debugger", &mut show_debugger),
+                    ("memory_heatmap", &mut show_memory_heatmap),
+                    ("timeline", &mut show_timeline),
+                    ("console", &mut show_console),
+                    ("code_editor", &mut show_code_editor),
+                    ("sprite_editor", &mut show_sprite_editor),
+                ],
+            );
+            project = Some(opened);
+            project_state = ProjectState::default();
+            show_project = true;
+        }
+
+        if show_code_editor {
+            if let Some(build) =
+                code_editor::draw(ui, &mut code_editor_state, &mut show_code_editor)
+            {
+                // Projects keep their last build, for tests and playing
+                if let Some(rom_path) = project
+                    .as_ref()
+                    .and_then(|project| project.rom.as_ref().map(|rom| project.path(rom)))
+                {
+                    if let Err(err) = fs::write(&rom_path, &build.rom.rom) {
+                        log::warn!("Could not write {}: {}", rom_path.display(), err);
+                    }
+                }
+                debugger_state.symbols = build.symbols;
+                rom_to_load = Some((build.rom, PROGRAM_BEGIN));
+            }
+        }
+
+        if show_sprite_editor {
+            sprite_editor::draw(ui, cpu, &mut sprite_editor_state, &mut show_sprite_editor);
+        }
+
+        if show_array_export {
+            array_export::draw(ui, cpu, &mut array_export_state, &mut show_array_export);
+        }
+
+        if teaching.enabled {
+            teaching::draw(ui, cpu, &mut teaching);
+        }
+
+        if show_auto_splitter {
+            speedrun::draw_auto_splitter(ui, speedrun, &mut show_auto_splitter);
+        }
+
+        if show_speedrun {
+            speedrun::draw(
+                ui,
+                speedrun,
+                cpu.frame(),
+                menu_bar_height(ui.io().display_size[1]),
+            );
+        }
+
+        if show_welcome {
+            if let Some(rom) = help::draw_welcome(ui, &mut show_welcome) {
+                let rom_path = rom.path();
+                if switch_rom(cpu, &rom_path) {
+                    *loaded_rom_path = Some(rom_path);
+                    *loaded_patch_path = None;
+                    *recording = None;
+                    *replay_player = None;
+                }
+            }
+            if !show_welcome && !config.welcome_shown {
+                config.welcome_shown = true;
+                config_changed = true;
+            }
+        }
+
+        if let Some(checked) = rom_check.as_ref() {
+            if let Some(decision) = rom_check::draw(ui, checked) {
+                let checked = rom_check.take().unwrap();
+                if let Decision::Load(base) = decision {
+                    rom_to_load = Some((checked.pending, base));
+                }
+            }
+        }
+        if let Some((pending, base)) = rom_to_load {
+            cpu.clear();
+            match cpu.load_rom(&pending.rom, base) {
+                Ok(()) => {
+                    *loaded_rom_path = Some(pending.rom_path);
+                    *loaded_patch_path = pending.patch_path;
+                    *recording = None;
+                    *replay_player = None;
+                }
+                Err(err) => show_error(tr("Could not load ROM"), &err.to_string()),
+            }
+        }
+
+        if show_debugger {
+            debugger::draw(ui, cpu, &mut debugger_state, &mut show_debugger);
+        }
+
+        // Stepping through draws always outlines the last one
+        let stepping_draws = show_debugger && cpu.break_on_draw && cpu.is_halted();
+        if show_draw_region || stepping_draws {
+            debugger::draw_region_overlay(ui, cpu);
+        }
+
+        if show_stats {
+            debugger::draw_stats_overlay(ui, &stats, menu_bar_height(ui.io().display_size[1]));
+        }
+
+        // Only keep instruction history while it can be used
+        let history_capacity = if show_debugger {
+            debugger::HISTORY_CAPACITY
+        } else {
+            0
+        };
+        if cpu.history_capacity() != history_capacity {
+            cpu.set_history_capacity(history_capacity);
+        }
+        if show_debugger != cpu.register_log().is_some() {
+            cpu.set_register_log_capacity(if show_debugger {
+                debugger::REGISTER_LOG_CAPACITY
+            } else {
+                0
+            });
+        }
+        cpu.set_ghosting(show_ghost);
+
+        if let Some(player) = replay_player.as_mut() {
+            if let Some(replay) = replay_viewer::draw(ui, player, cpu) {
+                *recording = Some(replay);
+                *replay_player = None;
+            }
+        }
+
         let draw_data = imgui.render();
 
         unsafe {
-            // Update buffer to the latest emulator screen
-            // TODO: Do this only when necessary
-            graphics::update_render(
-                &mut renderer,
-                &mut buffer,
-                &tex,
-                &cpu.screen.0,
-                &active_palette,
-            );
+            // Update texture to the latest finished frame, if it changed,
+            // at most draws_per_second times a second. While halted or
+            // stepping, the screen shows as drawn so far instead. Frames
+            // held back by the flash guard are retried with later ones.
+            frame_pending |= cpu.take_frame_ready();
+            let upload_due = last_upload.is_none_or(|last_upload| {
+                last_upload.elapsed().as_secs_f64() >= 1.0 / draws_per_second.max(1) as f64
+            });
+            let live = cpu.is_halted() || !cpu.is_rom_loaded() || teaching.enabled;
+            let screen = if live {
+                (cpu.screen.take_dirty() || !showing_live).then_some(&cpu.screen)
+            } else if frame_pending && upload_due {
+                let changed = cpu.take_front_dirty() || showing_live;
+                if changed && !flash_guard.allow(cpu.front_screen(), Instant::now()) {
+                    cpu.mark_screens_dirty();
+                    None
+                } else {
+                    frame_pending = false;
+                    changed.then(|| cpu.front_screen())
+                }
+            } else {
+                None
+            };
+            if let Some(screen) = screen {
+                showing_live = live;
+                last_upload = Some(Instant::now());
+                graphics::update_render(
+                    &mut renderer,
+                    &screen_quad.texture,
+                    screen,
+                    cpu.previous_screen(),
+                    &active_palette,
+                );
+            }
 
             // Clear and draw the screen
+            let border = config
+                .palette_border(active_palette.name)
+                .color(active_palette.disabled_px.into());
+            let [r, g, b] = config.color_adjustment.apply(border);
+            renderer.gl_context().clear_color(r, g, b, 1.0);
             renderer.gl_context().clear(glow::COLOR_BUFFER_BIT);
-            renderer
-                .gl_context()
-                .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_INT, 0);
+            let drawable_size = window.drawable_size();
+            let top_margin = if args.kiosk {
+                0
+            } else {
+                menu_bar_height(drawable_size.1 as f32) as u32
+            };
+            let padding = (config.screen_padding as f32 * ui_scale) as u32;
+            graphics::draw_screen(
+                renderer.gl_context(),
+                graphics::screen_viewport(drawable_size, top_margin, padding),
+            );
             let _ = renderer.render(draw_data);
 
+            if let Some(recorder) = video_recorder.as_mut() {
+                let (width, height) = recorder.size();
+                let frame = match recorder.source() {
+                    video::Source::Screen => video::screen_frame(
+                        &cpu.screen,
+                        &active_palette.screen_palette(),
+                        width,
+                        height,
+                    ),
+                    video::Source::Window => {
+                        graphics::read_window(renderer.gl_context(), drawable_size, (width, height))
+                    }
+                };
+                recorder.push_frame(frame);
+            }
+
             window.gl_swap_window();
+
+            if let Some(screen_window) = projector.as_ref() {
+                let mut screen_size = (cpu.screen.width(), cpu.screen.height());
+                if config.display_rotation.swaps_axes() {
+                    screen_size = (screen_size.1, screen_size.0);
+                }
+                screen_window.draw(
+                    &window,
+                    &gl_context,
+                    renderer.gl_context(),
+                    screen_size,
+                    padding,
+                    vsync,
+                );
+            }
         }
 
-        cpu.tick();
+        // Kept for the crash report, in case this frame panics
+        crash_context.update(*machine_id, cpu, loaded_rom_path.as_ref());
+
+        // Macros are queued on the machine, so each key lands on its
+        // frame however many run at once, and replays being recorded
+        // get them
+        if let Some(key_macro) = macro_to_play.take() {
+            let machine = &mut machines[active];
+            if machine.replay_player.is_none() {
+                let start = machine.cpu.frame();
+                for event in &key_macro.events {
+                    machine.queue_key(start + event.frame, event.key, event.pressed);
+                }
+            }
+        }
 
-        if !vsync_enabled {
-            if max_fps < 1000 {
-                timer_subsystem.delay(1000 / max_fps);
+        if teaching.enabled {
+            teaching.update(&mut machines[active].cpu);
+        } else {
+            for _ in 0..frame_pacer.frames_due(Instant::now()) {
+                machines[active].run_frame();
             }
         }
+        let cpu = &mut machines[active].cpu;
+        governor.update(
+            Instant::now(),
+            cpu.frame(),
+            cpu.is_rom_loaded() && !cpu.is_halted() && !teaching.enabled,
+        );
+
+        if let Some(addr) = cpu.memory.take_violation() {
+            log::warn!("Blocked write into protected memory at {:#05X}.", addr);
+        }
+        if let Some((pc, addr)) = cpu.take_font_write() {
+            toasts.push(format!(
+                "{} ({:#05X} -> {:#05X})",
+                tr("The ROM wrote into the font"),
+                pc,
+                addr
+            ));
+        }
+        if let Some((pc, vx)) = cpu.take_bad_digit() {
+            toasts.push(format!(
+                "{} ({:#05X}: {:#04X})",
+                tr("The ROM asked for a font digit past F"),
+                pc,
+                vx
+            ));
+        }
+        if let Some((pc, i)) = cpu.take_i_overflow() {
+            toasts.push(format!(
+                "{} ({:#05X}: I = {:#06X})",
+                tr("The ROM moved I past the end of memory"),
+                pc,
+                i
+            ));
+        }
+
+        // Nothing changes on screen while no machine runs: the loop slows
+        // down until input arrives, and the texture isn't uploaded again
+        let nothing_running = machines
+            .iter()
+            .all(|machine| !machine.cpu.is_rom_loaded() || machine.cpu.is_halted())
+            && !teaching.enabled
+            && video_recorder.is_none();
+        if nothing_running && idle_wait.is_idle(Instant::now()) {
+            wake_event = idle_wait.wait(&mut event_loop);
+            frame_limiter.reset();
+        } else if vsync == VSync::Off && max_fps < 1000 {
+            frame_limiter.wait(max_fps);
+        }
+    }
+
+    if let Some(project) = project.as_mut() {
+        save_project(
+            project,
+            &machines[active].cpu,
+            &debugger_state,
+            &[
+                ("debugger", show_debugger),
+                ("memory_heatmap", show_memory_heatmap),
+                ("timeline", show_timeline),
+                ("console", show_console),
+                ("code_editor", show_code_editor),
+                ("sprite_editor", show_sprite_editor),
+            ],
+        );
+    }
+
+    if let Some(recorder) = video_recorder {
+        if let Err(err) = recorder.finish() {
+            log::error!("Could not save video: {}", err);
+        }
+    }
+}
+
+// Moves input and sound over to another machine, returning it as the
+// active one. Only the machine in front plays sound and takes input.
+fn switch_machine(machines: &mut [Machine], from: usize, to: usize) -> usize {
+    if from == to || to >= machines.len() {
+        return from;
+    }
+
+    let audio = machines[from].cpu.take_audio();
+    machines[from].release_keys();
+    machines[to].cpu.set_audio(audio);
+    machines[to].cpu.mark_screens_dirty();
+    to
+}
+
+// Scale factor for the window and UI, based on the main display's DPI.
+// Limited so the window still fits on the display.
+fn dpi_scale(video_subsystem: &sdl2::VideoSubsystem) -> f32 {
+    let scale = match video_subsystem.display_dpi(0) {
+        Ok((_, horizontal_dpi, _)) => (horizontal_dpi / BASE_DPI).max(1.0),
+        Err(_) => 1.0,
+    };
+
+    match video_subsystem.display_usable_bounds(0) {
+        Ok(bounds) => {
+            let max_scale = (bounds.width() as f32 / WINDOW_WIDTH as f32)
+                .min(bounds.height() as f32 / WINDOW_HEIGHT as f32);
+            scale.min(max_scale).max(1.0)
+        }
+        Err(_) => scale,
+    }
+}
+
+// Height of the menu bar for the current window size, which is also
+// the area the screen leaves at the top.
+fn menu_bar_height(display_height: f32) -> f32 {
+    display_height * MENU_BAR_HEIGHT as f32 / WINDOW_HEIGHT as f32
+}
+
+// ROM thumbnail in the palette's colors, while it's being generated
+// just a note.
+fn thumbnail_or_placeholder(
+    ui: &imgui::Ui,
+    thumbnail: Option<&screen::Screen>,
+    palette: &ColorPalette,
+) {
+    match thumbnail {
+        Some(screen) => thumbnails::draw(ui, screen, &palette.screen_palette()),
+        None => ui.text_disabled(tr("Generating preview...")),
+    }
+}
+
+// Reads (and patches) a ROM, then restarts the machine with it, at
+// the address it looks meant for.
+fn switch_rom(cpu: &mut Cpu, rom_path: &Path) -> bool {
+    let Some(rom) = read_rom(rom_path, None) else {
+        return false;
+    };
+
+    cpu.clear();
+    match cpu.load_rom(&rom, analyzer::analyze(&rom).base) {
+        Ok(()) => true,
+        Err(err) => {
+            show_error(tr("Could not load ROM"), &err.to_string());
+            false
+        }
+    }
+}
+
+fn roms_select_window() -> Vec<PathBuf> {
+    let path = std::env::current_dir().unwrap();
+    rfd::FileDialog::new()
+        .add_filter("ch8", &["ch8"])
+        .set_directory(&path)
+        .pick_files()
+        .unwrap_or_default()
+}
+
+// Returns the selected ROM path, and the patch path if one was picked.
+fn rom_select_window(with_patch: bool) -> Option<PendingRom> {
+    let path = std::env::current_dir().unwrap();
+    let rom_path = rfd::FileDialog::new()
+        .add_filter("ch8", &["ch8"])
+        .add_filter("hex", &["hex", "txt"])
+        .add_filter("Octo cartridge", &["gif"])
+        .set_directory(&path)
+        .pick_file()?;
+
+    let patch_path = if with_patch {
+        Some(
+            rfd::FileDialog::new()
+                .add_filter("patch", &["ips", "bps"])
+                .set_directory(rom_path.parent().unwrap_or(&path))
+                .pick_file()?,
+        )
+    } else {
+        None
+    };
+
+    let rom = read_rom(&rom_path, patch_path.as_deref())?;
+    Some(PendingRom {
+        rom_path,
+        patch_path,
+        rom,
+    })
+}
+
+// Reads a ROM, applying the given patch. Without one, a "rom.ips" or
+// "rom.bps" next to the ROM gets applied automatically.
+fn read_rom(rom_path: &Path, patch_path: Option<&Path>) -> Option<Vec<u8>> {
+    if let Some(builtin) = builtin_roms::from_path(rom_path) {
+        return Some(builtin.data.to_vec());
+    }
+    if let Some(imported) = hex_import::from_path(rom_path) {
+        return Some(imported);
+    }
+
+    let rom = if cartridge::is_cartridge(rom_path) {
+        fs::read(rom_path)
+            .map_err(|err| err.to_string())
+            .and_then(|gif| cartridge::read_rom(&gif).map_err(|err| err.to_string()))
+    } else if hex_import::is_hex_file(rom_path) {
+        fs::read_to_string(rom_path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| hex_rom::parse(&text).map_err(|err| err.to_string()))
+    } else {
+        fs::read(rom_path).map_err(|err| err.to_string())
+    };
+    let rom = match rom {
+        Ok(rom) => rom,
+        Err(err) => {
+            show_error(tr("Could not read ROM"), &err);
+            return None;
+        }
+    };
+
+    let patch_path = patch_path.map(Path::to_path_buf).or_else(|| {
+        ["ips", "bps"]
+            .iter()
+            .map(|extension| rom_path.with_extension(extension))
+            .find(|path| path.is_file())
+    });
+    log::info!("Read ROM {} ({} bytes)", rom_path.display(), rom.len());
+
+    let Some(patch_path) = patch_path else {
+        return Some(rom);
+    };
+
+    let result = fs::read(&patch_path)
+        .map_err(|err| err.to_string())
+        .and_then(|patch| patch::apply(&rom, &patch).map_err(|err| err.to_string()));
+    match result {
+        Ok(patched) => {
+            log::info!("Applied patch {}", patch_path.display());
+            Some(patched)
+        }
+        Err(err) => {
+            show_error(tr("Could not apply patch"), &err);
+            None
+        }
     }
 }
 
-fn rom_select_window(cpu: &mut Cpu) -> Option<PathBuf> {
+fn rom_export_window(program: &[u8]) {
     let path = std::env::current_dir().unwrap();
     let res = rfd::FileDialog::new()
         .add_filter("ch8", &["ch8"])
         .set_directory(&path)
-        .pick_file();
+        .set_file_name("export.ch8")
+        .save_file();
 
     if let Some(file_path) = res {
-        let rom = fs::read(file_path.clone()).unwrap();
-        cpu.load_rom(rom, PROGRAM_BEGIN);
-        return Some(file_path);
+        if let Err(err) = fs::write(file_path, program) {
+            show_error(tr("Could not export ROM"), &err.to_string());
+        }
     }
+}
 
-    None
+fn replay_open_window() -> Option<Replay> {
+    let path = std::env::current_dir().unwrap();
+    let file_path = rfd::FileDialog::new()
+        .add_filter("c8r", &["c8r"])
+        .set_directory(&path)
+        .pick_file()?;
+
+    let replay = fs::read(file_path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Replay::from_bytes(&bytes).map_err(|err| err.to_string()));
+    match replay {
+        Ok(replay) => Some(replay),
+        Err(err) => {
+            show_error(tr("Could not open replay"), &err);
+            None
+        }
+    }
+}
+
+// Restores a project's debugging session and windows, and opens its
+// source and symbols.
+fn open_project(
+    project: &Project,
+    cpu: &mut Cpu,
+    debugger_state: &mut DebuggerState,
+    code_editor_state: &mut CodeEditorState,
+    panels: [(&str, &mut bool); 6],
+) {
+    cpu.breakpoints = project.breakpoints.clone();
+    debugger_state.set_watches(&project.watches);
+    for (name, opened) in panels {
+        *opened = project.panels.contains(name);
+    }
+    if let Some(ticks) = project.ticks_per_frame {
+        cpu.ticks_per_frame = ticks;
+    }
+    if let Some(source) = &project.source {
+        code_editor_state.load(project.path(source));
+    }
+    if let Some(symbols) = &project.symbols {
+        let path = project.path(symbols);
+        match fs::read_to_string(&path).map(|source| SymbolTable::parse(&source)) {
+            Ok(Ok(symbols)) => debugger_state.symbols = symbols,
+            Ok(Err(err)) => log::warn!("Could not load {}: {}", path.display(), err),
+            Err(err) => log::warn!("Could not read {}: {}", path.display(), err),
+        }
+    }
+}
+
+// Keeps the debugging session and open windows in the project.
+fn save_project(
+    project: &mut Project,
+    cpu: &Cpu,
+    debugger_state: &DebuggerState,
+    panels: &[(&str, bool)],
+) {
+    project.breakpoints = cpu.breakpoints.clone();
+    project.watches = debugger_state.watches();
+    project.ticks_per_frame = Some(cpu.ticks_per_frame);
+    project.panels = panels
+        .iter()
+        .filter(|(_, opened)| *opened)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    if let Err(err) = project.save() {
+        log::warn!("Could not save the project: {}", err);
+    }
+}
+
+fn symbols_open_window() -> Option<SymbolTable> {
+    let path = std::env::current_dir().unwrap();
+    let file_path = rfd::FileDialog::new()
+        .add_filter("sym", &["sym", "txt"])
+        .set_directory(&path)
+        .pick_file()?;
+
+    let symbols = fs::read_to_string(file_path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| SymbolTable::parse(&source).map_err(|err| err.to_string()));
+    match symbols {
+        Ok(symbols) => Some(symbols),
+        Err(err) => {
+            show_error(tr("Could not load symbols"), &err);
+            None
+        }
+    }
+}
+
+fn replay_save_window(replay: &Replay) {
+    let path = std::env::current_dir().unwrap();
+    let res = rfd::FileDialog::new()
+        .add_filter("c8r", &["c8r"])
+        .set_directory(&path)
+        .set_file_name("replay.c8r")
+        .save_file();
+
+    if let Some(file_path) = res {
+        if let Err(err) = fs::write(file_path, replay.to_bytes()) {
+            show_error(tr("Could not save replay"), &err.to_string());
+        }
+    }
+}
+
+// Returns whether the state was saved.
+fn state_save_window(state: &[u8]) -> bool {
+    let path = std::env::current_dir().unwrap();
+    let res = rfd::FileDialog::new()
+        .add_filter("c8s", &["c8s"])
+        .set_directory(&path)
+        .set_file_name("state.c8s")
+        .save_file();
+
+    let Some(file_path) = res else {
+        return false;
+    };
+    match fs::write(file_path, state) {
+        Ok(()) => true,
+        Err(err) => {
+            show_error(tr("Could not save state"), &err.to_string());
+            false
+        }
+    }
+}
+
+fn export_splits_window(splits: &str) {
+    let path = std::env::current_dir().unwrap();
+    let res = rfd::FileDialog::new()
+        .add_filter("csv", &["csv"])
+        .set_directory(&path)
+        .set_file_name("splits.csv")
+        .save_file();
+
+    if let Some(file_path) = res {
+        if let Err(err) = fs::write(file_path, splits) {
+            show_error(tr("Could not export the splits"), &err.to_string());
+        }
+    }
+}
+
+fn video_save_window() -> Option<PathBuf> {
+    let path = std::env::current_dir().unwrap();
+    rfd::FileDialog::new()
+        .add_filter("mp4", &["mp4"])
+        .set_directory(&path)
+        .set_file_name("recording.mp4")
+        .save_file()
+}
+
+fn state_open_window() -> Option<Vec<u8>> {
+    let path = std::env::current_dir().unwrap();
+    let file_path = rfd::FileDialog::new()
+        .add_filter("c8s", &["c8s"])
+        .set_directory(&path)
+        .pick_file()?;
+
+    match fs::read(file_path) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            show_error(tr("Could not load state"), &err.to_string());
+            None
+        }
+    }
+}
+
+fn show_error(title: &str, description: &str) {
+    log::error!("{}: {}", title, description);
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title(title)
+        .set_description(description)
+        .show();
+}
+
+// Keypad:
+// 1 2 3 C    1 2 3 4
+// 4 5 6 D -> Q W E R
+// 7 8 9 E -> A S D F
+// A 0 B F    Z X C V
+//
+fn keypad_key(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::Num1 => Some(1),
+        Keycode::Num2 => Some(2),
+        Keycode::Num3 => Some(3),
+        Keycode::Num4 => Some(0xC),
+
+        Keycode::Q => Some(4),
+        Keycode::W => Some(5),
+        Keycode::E => Some(6),
+        Keycode::R => Some(0xD),
+
+        Keycode::A => Some(7),
+        Keycode::S => Some(8),
+        Keycode::D => Some(9),
+        Keycode::F => Some(0xE),
+
+        Keycode::Z => Some(0xA),
+        Keycode::X => Some(0),
+        Keycode::C => Some(0xB),
+        Keycode::V => Some(0xF),
+        _ => None,
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -506,6 +2668,7 @@ struct ColorPalette {
     pub name: &'static str,
     pub enabled_px: Vector3<f32>,
     pub disabled_px: Vector3<f32>,
+    pub color_blind_safe: bool, // Told apart with any common color deficiency
 }
 
 impl ColorPalette {
@@ -522,25 +2685,76 @@ impl ColorPalette {
                 disabled[1] as f32 / 255.0,
                 disabled[2] as f32 / 255.0,
             ]),
+            color_blind_safe: false,
+        }
+    }
+
+    pub fn color_blind_safe(mut self) -> ColorPalette {
+        self.color_blind_safe = true;
+        self
+    }
+
+    pub fn screen_palette(&self) -> screen::Palette {
+        let to_rgb = |color: Vector3<f32>| {
+            [
+                (color.x * 0xFF as f32) as u8,
+                (color.y * 0xFF as f32) as u8,
+                (color.z * 0xFF as f32) as u8,
+            ]
+        };
+
+        screen::Palette {
+            enabled_px: to_rgb(self.enabled_px),
+            disabled_px: to_rgb(self.disabled_px),
         }
     }
 }
 
 lazy_static! {
 #[rustfmt::skip]
-static ref COLOR_PALETTES: [ColorPalette; 5] = [
+static ref COLOR_PALETTES: [ColorPalette; 9] = [
     ColorPalette::new("Default", [242, 251, 235], [23, 18, 25]),
     ColorPalette::new("Inverted", [23, 18, 25], [242, 251, 235]),
     ColorPalette::new("Brown", [253, 203, 85], [63, 41, 30]),
     ColorPalette::new("Red", [204, 14, 19], [43, 0, 0]),
+    // Blue and yellow, kept apart by red-green deficiencies
+    ColorPalette::new("Deuteranopia", [240, 228, 66], [0, 45, 90]).color_blind_safe(),
+    ColorPalette::new("Protanopia", [86, 180, 233], [25, 20, 10]).color_blind_safe(),
+    // Red and teal, kept apart by blue-yellow deficiencies
+    ColorPalette::new("Tritanopia", [255, 130, 120], [0, 45, 45]).color_blind_safe(),
+    // Lightness alone, for any deficiency
+    ColorPalette::new("High contrast", [255, 255, 255], [0, 0, 0]).color_blind_safe(),
     ColorPalette::new("Custom", [0, 0, 0], [0, 0, 0]), // TODO: Make custom saveable via a config
 ];
 }
 
+// The next palette after idx, skipping the custom one.
+fn next_color_palette(idx: usize) -> usize {
+    (1..=COLOR_PALETTES.len())
+        .map(|offset| (idx + offset) % COLOR_PALETTES.len())
+        .find(|&next| get_color_palette(next).is_some())
+        .unwrap_or(0)
+}
+
+fn color_palette_id(name: &str) -> Option<usize> {
+    (0..COLOR_PALETTES.len()).find(|&idx| get_color_palette(idx).is_some_and(|p| p.name == name))
+}
+
+// Keeps the palette for the loaded ROM. Custom colors aren't saved, so
+// picking them forgets the ROM's palette. Returns whether the config
+// changed.
+fn remember_palette(config: &mut Config, cpu: &Cpu, palette: &ColorPalette) -> bool {
+    let Some(rom_hash) = cpu.rom_hash() else {
+        return false;
+    };
+    let name = Some(palette.name).filter(|_| color_palette_id(palette.name).is_some());
+    config.set_rom_palette(rom_hash, name)
+}
+
 fn get_color_palette(idx: usize) -> Option<ColorPalette> {
     if let Some(palette) = COLOR_PALETTES.get(idx) {
         if palette.name != "Custom" {
-            return Some(palette.clone());
+            return Some(*palette);
         }
     }
     None
@@ -0,0 +1,152 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{fs, path::Path};
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{cpu::Cpu, memory_diff, savestate, symbols::SymbolTable},
+    locale::tr,
+};
+
+// Changes listed at most, e.g. when comparing states of two games.
+const MAX_CHANGES: usize = 512;
+
+// What one side of the diff compares.
+enum Side {
+    Empty,
+    Snapshot { name: String, memory: Vec<u8> },
+    Live, // The running machine's memory
+}
+
+impl Side {
+    fn memory(&self, cpu: &Cpu) -> Option<Vec<u8>> {
+        match self {
+            Side::Empty => None,
+            Side::Snapshot { memory, .. } => Some(memory.clone()),
+            Side::Live => Some(cpu.memory.dump()),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Side::Empty => tr("(none)").to_string(),
+            Side::Snapshot { name, .. } => name.clone(),
+            Side::Live => tr("Live memory").to_string(),
+        }
+    }
+}
+
+pub struct MemoryDiffState {
+    before: Side,
+    after: Side,
+    error: Option<String>,
+}
+
+impl Default for MemoryDiffState {
+    fn default() -> MemoryDiffState {
+        MemoryDiffState {
+            before: Side::Empty,
+            after: Side::Live,
+            error: None,
+        }
+    }
+}
+
+fn load_state(path: &Path) -> Result<Side, String> {
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    let memory = savestate::memory(&bytes).map_err(|err| err.to_string())?;
+    Ok(Side::Snapshot {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        memory: memory.to_vec(),
+    })
+}
+
+// Addresses that differ between two save states, or a state and the
+// running machine, e.g. to find where a game keeps its score.
+pub fn draw(
+    ui: &Ui,
+    cpu: &Cpu,
+    symbols: &SymbolTable,
+    state: &mut MemoryDiffState,
+    opened: &mut bool,
+) {
+    ui.window(format!("{}###memory_diff", tr("Memory diff")))
+        .opened(opened)
+        .size([360.0, 420.0], Condition::FirstUseEver)
+        .build(|| {
+            draw_side(ui, cpu, tr("Before"), &mut state.before, &mut state.error);
+            draw_side(ui, cpu, tr("After"), &mut state.after, &mut state.error);
+            if let Some(error) = &state.error {
+                ui.text_colored([1.0, 0.4, 0.4, 1.0], error);
+            }
+            ui.separator();
+
+            let (Some(before), Some(after)) = (state.before.memory(cpu), state.after.memory(cpu))
+            else {
+                ui.text_disabled(tr("Pick what to compare."));
+                return;
+            };
+            if before.len() != after.len() {
+                ui.text_disabled(tr("Memory sizes differ, comparing the first bytes."));
+            }
+            let changes = memory_diff::diff(&before, &after);
+            ui.text(tr("{} bytes changed").replace("{}", &changes.len().to_string()));
+            ui.child_window("##memory_diff_changes").build(|| {
+                for change in changes.iter().take(MAX_CHANGES) {
+                    ui.text(format!(
+                        "{:#06X}  {:02X} ({:>3}) -> {:02X} ({:>3})",
+                        change.addr, change.old, change.old, change.new, change.new
+                    ));
+                    if let Some(label) = symbols.label(change.addr) {
+                        ui.same_line();
+                        ui.text_disabled(label);
+                    }
+                }
+                if changes.len() > MAX_CHANGES {
+                    ui.text_disabled("...");
+                }
+            });
+        });
+}
+
+fn draw_side(ui: &Ui, cpu: &Cpu, label: &str, side: &mut Side, error: &mut Option<String>) {
+    let _id = ui.push_id(label);
+    ui.text(format!("{}: {}", label, side.name()));
+    if ui.small_button(tr("Load state...")) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("c8s", &["c8s"])
+            .pick_file()
+        {
+            match load_state(&path) {
+                Ok(loaded) => {
+                    *side = loaded;
+                    *error = None;
+                }
+                Err(err) => *error = Some(format!("{}: {}", path.display(), err)),
+            }
+        }
+    }
+    ui.same_line();
+    if ui.small_button(tr("Snapshot")) {
+        *side = Side::Snapshot {
+            name: tr("Snapshot at frame {}").replace("{}", &cpu.frame().to_string()),
+            memory: cpu.memory.dump(),
+        };
+    }
+    ui.same_line();
+    if ui.small_button(tr("Live memory")) {
+        *side = Side::Live;
+    }
+}
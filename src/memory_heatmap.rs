@@ -0,0 +1,133 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{core::cpu::Cpu, locale::tr};
+
+// Width and height of the heatmap, a square of every address
+const HEATMAP_SIZE: f32 = 384.0;
+
+// Addresses per row, 64 for 4K and 256 for 64K.
+fn columns(memory_size: usize) -> usize {
+    ((memory_size as f64).sqrt().ceil() as usize).max(1)
+}
+
+// 0 for no accesses, up to 1 for the most accessed address. Log
+// scaled, so a rarely written variable still stands out next to a
+// sprite drawn every frame.
+fn heat(count: u32, max: u32) -> f32 {
+    if count == 0 || max == 0 {
+        return 0.0;
+    }
+    (1.0 + count as f32).ln() / (1.0 + max as f32).ln()
+}
+
+// Every address as a cell, more green the more it's read and more red
+// the more it's written, e.g. sprites show up green and variables
+// yellow.
+pub fn draw(ui: &Ui, cpu: &mut Cpu, opened: &mut bool) {
+    ui.window(format!("{}###memory_heatmap", tr("Memory heatmap")))
+        .opened(opened)
+        .always_auto_resize(true)
+        .position([420.0, 50.0], Condition::FirstUseEver)
+        .build(|| {
+            if ui.button(tr("Clear")) {
+                cpu.clear_memory_accesses();
+            }
+            ui.same_line();
+            ui.text_colored([0.3, 1.0, 0.3, 1.0], tr("Read"));
+            ui.same_line();
+            ui.text_colored([1.0, 0.3, 0.3, 1.0], tr("Written"));
+
+            let Some(accesses) = cpu.memory_accesses() else {
+                return;
+            };
+            let columns = columns(accesses.size());
+            let rows = accesses.size().div_ceil(columns);
+            let cell_size = HEATMAP_SIZE / columns as f32;
+            let origin = ui.cursor_screen_pos();
+            let size = [HEATMAP_SIZE, rows as f32 * cell_size];
+            ui.dummy(size);
+
+            let draw_list = ui.get_window_draw_list();
+            draw_list
+                .add_rect(
+                    origin,
+                    [origin[0] + size[0], origin[1] + size[1]],
+                    [0.1, 0.1, 0.1, 1.0],
+                )
+                .filled(true)
+                .build();
+            let (max_reads, max_writes) = accesses.max();
+            for addr in 0..accesses.size() {
+                let addr = addr as u16;
+                let read = heat(accesses.reads(addr), max_reads);
+                let written = heat(accesses.writes(addr), max_writes);
+                if read == 0.0 && written == 0.0 {
+                    continue;
+                }
+                let top_left = [
+                    origin[0] + (addr as usize % columns) as f32 * cell_size,
+                    origin[1] + (addr as usize / columns) as f32 * cell_size,
+                ];
+                draw_list
+                    .add_rect(
+                        top_left,
+                        [top_left[0] + cell_size, top_left[1] + cell_size],
+                        [written, read, 0.1, 1.0],
+                    )
+                    .filled(true)
+                    .build();
+            }
+
+            if ui.is_item_hovered() {
+                let [mouse_x, mouse_y] = ui.io().mouse_pos;
+                let column = ((mouse_x - origin[0]) / cell_size) as usize;
+                let row = ((mouse_y - origin[1]) / cell_size) as usize;
+                let addr = row * columns + column.min(columns - 1);
+                if let Some(data) = u16::try_from(addr)
+                    .ok()
+                    .and_then(|addr| cpu.memory.peek(addr))
+                {
+                    let addr = addr as u16;
+                    ui.tooltip_text(format!(
+                        "{:#05X} ({:?}): {:02X}\n{} {}\n{} {}",
+                        addr,
+                        cpu.memory.region(addr),
+                        data,
+                        tr("Reads:"),
+                        accesses.reads(addr),
+                        tr("Writes:"),
+                        accesses.writes(addr)
+                    ));
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod memory_heatmap_tests {
+    use super::{columns, heat};
+
+    #[test]
+    fn test_heat() {
+        assert_eq!(heat(0, 10), 0.0);
+        assert_eq!(heat(5, 0), 0.0);
+        assert_eq!(heat(10, 10), 1.0);
+        assert!(heat(1, 1000) > 0.05);
+        assert!(heat(1, 1000) < heat(2, 1000));
+    }
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(columns(0x1000), 64);
+        assert_eq!(columns(0x10000), 256);
+    }
+}
@@ -0,0 +1,152 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// How long the title stays on screen after switching ROMs
+pub const TITLE_DURATION: Duration = Duration::from_secs(3);
+
+// Queue of ROMs played in rotation (attract mode). Advances after
+// advance_secs (0 = only on request) and wraps around at the end.
+pub struct Playlist {
+    entries: Vec<PathBuf>,
+    current: usize,
+    pub advance_secs: u64,
+    switched_at: Instant,
+}
+
+impl Playlist {
+    pub fn new(advance_secs: u64) -> Playlist {
+        Playlist {
+            entries: Vec::new(),
+            current: 0,
+            advance_secs,
+            switched_at: Instant::now(),
+        }
+    }
+
+    pub fn add(&mut self, path: PathBuf) {
+        self.entries.push(path);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.current = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    pub fn current(&self) -> Option<&Path> {
+        self.entries.get(self.current).map(PathBuf::as_path)
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    // Shown title for a ROM: its file name without extension.
+    pub fn title(path: &Path) -> String {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    // Jumps to an entry and restarts the advance timer.
+    pub fn select(&mut self, idx: usize) -> Option<&Path> {
+        if idx >= self.entries.len() {
+            return None;
+        }
+        self.current = idx;
+        self.switched_at = Instant::now();
+        self.current()
+    }
+
+    pub fn advance(&mut self) -> Option<&Path> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.select((self.current + 1) % self.entries.len())
+    }
+
+    // Whether the current ROM has played for its full time slot.
+    pub fn should_advance(&self, now: Instant) -> bool {
+        self.entries.len() > 1
+            && self.advance_secs > 0
+            && now.duration_since(self.switched_at) >= Duration::from_secs(self.advance_secs)
+    }
+
+    pub fn since_switch(&self) -> Duration {
+        self.switched_at.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod playlist_tests {
+    use std::{
+        path::{Path, PathBuf},
+        time::{Duration, Instant},
+    };
+
+    use super::Playlist;
+
+    #[test]
+    fn test_rotation() {
+        let mut playlist = Playlist::new(0);
+        assert_eq!(playlist.advance(), None);
+
+        playlist.add(PathBuf::from("roms/pong.ch8"));
+        playlist.add(PathBuf::from("roms/tetris.ch8"));
+        assert_eq!(playlist.current(), Some(Path::new("roms/pong.ch8")));
+        assert_eq!(playlist.advance(), Some(Path::new("roms/tetris.ch8")));
+        assert_eq!(playlist.advance(), Some(Path::new("roms/pong.ch8")));
+        assert_eq!(playlist.select(5), None);
+        assert_eq!(playlist.current_index(), 0);
+
+        playlist.clear();
+        assert!(playlist.is_empty());
+        assert_eq!(playlist.current(), None);
+    }
+
+    #[test]
+    fn test_should_advance() {
+        let mut playlist = Playlist::new(30);
+        playlist.add(PathBuf::from("a.ch8"));
+        let later = Instant::now() + Duration::from_secs(31);
+        // A single ROM never rotates
+        assert!(!playlist.should_advance(later));
+
+        playlist.add(PathBuf::from("b.ch8"));
+        assert!(!playlist.should_advance(Instant::now()));
+        assert!(playlist.should_advance(later));
+
+        playlist.advance_secs = 0;
+        assert!(!playlist.should_advance(later));
+    }
+
+    #[test]
+    fn test_title() {
+        assert_eq!(
+            Playlist::title(Path::new("roms/Space Invaders.ch8")),
+            "Space Invaders"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use crate::core::screen::Screen;
+
+// Minimal PNG support for screens, as 1-bit grayscale images (white
+// pixels are on). The image data is stored uncompressed, which is
+// plenty for 64x32 screens, and only such files can be decoded back.
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+pub fn encode_screen(screen: &Screen) -> Vec<u8> {
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(screen.width() as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(screen.height() as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[1, 0, 0, 0, 0]); // 1-bit grayscale, no interlacing
+
+    // Every row starts with its filter type (none)
+    let mut raw = Vec::with_capacity((screen.width() / 8 + 1) * screen.height());
+    for row in screen.rows() {
+        raw.push(0);
+        for word in row {
+            raw.extend_from_slice(&word.to_be_bytes());
+        }
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// Decodes images written by encode_screen.
+pub fn decode_screen(bytes: &[u8]) -> Option<Screen> {
+    let mut rest = bytes.strip_prefix(&SIGNATURE)?;
+    let mut size = None;
+    let mut data = Vec::new();
+
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[0..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let chunk = rest.get(8..8 + length)?;
+        let crc = rest.get(8 + length..12 + length)?;
+        if crc32(&rest[4..8 + length]).to_be_bytes() != crc {
+            return None;
+        }
+
+        match kind {
+            b"IHDR" if chunk.len() == 13 => {
+                if chunk[8..] != [1, 0, 0, 0, 0] {
+                    return None;
+                }
+                let width = u32::from_be_bytes(chunk[0..4].try_into().unwrap()) as usize;
+                let height = u32::from_be_bytes(chunk[4..8].try_into().unwrap()) as usize;
+                size = Some((width, height));
+            }
+            b"IDAT" => data.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = &rest[12 + length..];
+    }
+
+    let (width, height) = size?;
+    if width == 0 || !width.is_multiple_of(64) || height == 0 || height > 1024 {
+        return None;
+    }
+    let raw = zlib_unstored(&data)?;
+    let stride = width / 8 + 1;
+    if raw.len() != stride * height {
+        return None;
+    }
+
+    let mut screen = Screen::with_resolution(width, height);
+    for (y, row) in raw.chunks_exact(stride).enumerate() {
+        if row[0] != 0 {
+            return None;
+        }
+        for x in 0..width {
+            screen.set(x, y, row[1 + x / 8] & (0x80 >> (x % 8)) != 0);
+        }
+    }
+    Some(screen)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        output.push(last as u8);
+        let length = block.len() as u16;
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+fn zlib_unstored(stream: &[u8]) -> Option<Vec<u8>> {
+    if stream.len() < 6 || stream[0] & 0x0F != 8 {
+        return None;
+    }
+
+    let mut output = Vec::new();
+    let mut at = 2;
+    loop {
+        let header = *stream.get(at)?;
+        if header & 0b110 != 0 {
+            return None; // Compressed block
+        }
+        let length = u16::from_le_bytes(stream.get(at + 1..at + 3)?.try_into().unwrap());
+        let inverted = u16::from_le_bytes(stream.get(at + 3..at + 5)?.try_into().unwrap());
+        if length != !inverted {
+            return None;
+        }
+        at += 5;
+        output.extend_from_slice(stream.get(at..at + length as usize)?);
+        at += length as usize;
+        if header & 1 != 0 {
+            break;
+        }
+    }
+
+    let checksum = u32::from_be_bytes(stream.get(at..at + 4)?.try_into().unwrap());
+    (checksum == adler32(&output)).then_some(output)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod png_tests {
+    use super::{adler32, crc32, decode_screen, encode_screen, zlib_stored, zlib_unstored};
+    use crate::core::screen::Screen;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_zlib_round_trip() {
+        let data: Vec<u8> = (0..70_000).map(|idx| idx as u8).collect();
+        assert_eq!(zlib_unstored(&zlib_stored(&data)).unwrap(), data);
+        assert_eq!(zlib_unstored(&zlib_stored(&[])).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_screen_round_trip() {
+        let mut screen = Screen::with_resolution(128, 64);
+        screen.set(0, 0, true);
+        screen.set(127, 63, true);
+        screen.set(9, 30, true);
+
+        let png = encode_screen(&screen);
+        assert!(png.starts_with(b"\x89PNG"));
+        let decoded = decode_screen(&png).unwrap();
+        assert_eq!(decoded.width(), 128);
+        assert_eq!(decoded.words(), screen.words());
+
+        let mut corrupted = png.clone();
+        corrupted[40] ^= 0xFF;
+        assert!(decode_screen(&corrupted).is_none());
+        assert!(decode_screen(b"not a png").is_none());
+    }
+}
@@ -0,0 +1,329 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use imgui::{Condition, Ui};
+
+use crate::{core::test_script, locale::tr, PROGRAM_BEGIN};
+
+pub const PROJECT_FILE: &str = "chip-8-project.cfg";
+const DEFAULT_TESTS: &str = "tests.toml";
+
+// A folder with a homebrew program. Its settings and debugging session
+// are stored in the folder as "key = value" lines, like the config.
+// File paths are relative to the folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    pub dir: PathBuf,
+    pub source: Option<PathBuf>, // Octo source, assembled by Build
+    pub rom: Option<PathBuf>,    // Where builds are written
+    pub symbols: Option<PathBuf>,
+    pub tests: Option<PathBuf>,
+    pub ticks_per_frame: Option<u32>,
+    // Restored on open, saved with the project
+    pub breakpoints: BTreeSet<u16>,
+    pub watches: Vec<String>,
+    pub panels: BTreeSet<String>, // Windows left open
+}
+
+impl Project {
+    // Reads the project file, or guesses the files of a folder without
+    // one: the first .8o source and .sym file, and tests.toml.
+    pub fn open(dir: &Path) -> Project {
+        if let Ok(source) = fs::read_to_string(dir.join(PROJECT_FILE)) {
+            return Project::parse(dir, &source);
+        }
+
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| PathBuf::from(entry.file_name()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+        let find = |extension: &str| {
+            files
+                .iter()
+                .find(|file| file.extension().is_some_and(|ext| ext == extension))
+                .cloned()
+        };
+        let source = find("8o");
+        Project {
+            rom: source.as_ref().map(|source| source.with_extension("ch8")),
+            source,
+            symbols: find("sym"),
+            tests: files
+                .iter()
+                .find(|file| *file == Path::new(DEFAULT_TESTS))
+                .cloned(),
+            ..Project::parse(dir, "")
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(self.dir.join(PROJECT_FILE), self.serialize())
+    }
+
+    pub fn name(&self) -> String {
+        self.dir.file_name().map_or_else(
+            || self.dir.display().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        )
+    }
+
+    // A project file's full path.
+    pub fn path(&self, file: &Path) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    // Unknown keys and invalid values are ignored.
+    pub fn parse(dir: &Path, source: &str) -> Project {
+        let mut project = Project {
+            dir: dir.to_path_buf(),
+            source: None,
+            rom: None,
+            symbols: None,
+            tests: None,
+            ticks_per_frame: None,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            panels: BTreeSet::new(),
+        };
+
+        for line in source.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let path = (!value.is_empty()).then(|| PathBuf::from(value));
+            match key.trim() {
+                "source" => project.source = path,
+                "rom" => project.rom = path,
+                "symbols" => project.symbols = path,
+                "tests" => project.tests = path,
+                "ticks_per_frame" => project.ticks_per_frame = value.parse().ok(),
+                "breakpoints" => {
+                    project.breakpoints = value
+                        .split_whitespace()
+                        .filter_map(|addr| {
+                            u16::from_str_radix(addr.trim_start_matches("0x"), 16).ok()
+                        })
+                        .collect();
+                }
+                // One per line, as expressions may have any character
+                "watch" if !value.is_empty() => project.watches.push(value.to_string()),
+                "panels" => {
+                    project.panels = value.split_whitespace().map(str::to_string).collect();
+                }
+                _ => {}
+            }
+        }
+        project
+    }
+
+    pub fn serialize(&self) -> String {
+        let path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        };
+        let mut output = format!(
+            "source = {}\nrom = {}\nsymbols = {}\ntests = {}\n",
+            path(&self.source),
+            path(&self.rom),
+            path(&self.symbols),
+            path(&self.tests)
+        );
+        if let Some(ticks) = self.ticks_per_frame {
+            output += &format!("ticks_per_frame = {}\n", ticks);
+        }
+        let breakpoints: Vec<String> = self
+            .breakpoints
+            .iter()
+            .map(|addr| format!("{:#05X}", addr))
+            .collect();
+        output += &format!("breakpoints = {}\n", breakpoints.join(" "));
+        for watch in &self.watches {
+            output += &format!("watch = {}\n", watch);
+        }
+        let panels: Vec<&str> = self.panels.iter().map(String::as_str).collect();
+        output += &format!("panels = {}\n", panels.join(" "));
+        output
+    }
+
+    // Runs the tests on the last build.
+    fn run_tests(&self) -> TestResults {
+        let (Some(tests_path), Some(rom_path)) = (&self.tests, &self.rom) else {
+            return Err(tr("The project needs a test file and a ROM.").to_string());
+        };
+        let read_error = |path: &Path, err: io::Error| format!("{}: {}", path.display(), err);
+        let script =
+            fs::read_to_string(self.path(tests_path)).map_err(|err| read_error(tests_path, err))?;
+        let rom = fs::read(self.path(rom_path)).map_err(|err| read_error(rom_path, err))?;
+        let tests = test_script::parse(&script).map_err(|err| err.to_string())?;
+        let results =
+            test_script::run_all(&tests, &rom, PROGRAM_BEGIN).map_err(|err| err.to_string())?;
+        Ok(tests
+            .into_iter()
+            .map(|test| test.name)
+            .zip(results)
+            .collect())
+    }
+}
+
+// Each test's name and failures, or why the tests couldn't run.
+type TestResults = Result<Vec<(String, Vec<String>)>, String>;
+
+pub enum ProjectAction {
+    Build,
+    Save,
+    Close,
+}
+
+#[derive(Default)]
+pub struct ProjectState {
+    test_results: Option<TestResults>,
+}
+
+// The project's files and settings, and its build and test actions.
+pub fn draw(
+    ui: &Ui,
+    project: &mut Project,
+    state: &mut ProjectState,
+    opened: &mut bool,
+) -> Option<ProjectAction> {
+    let mut action = None;
+    ui.window(format!("{} - {}###project", tr("Project"), project.name()))
+        .opened(opened)
+        .always_auto_resize(true)
+        .position([420.0, 300.0], Condition::FirstUseEver)
+        .build(|| {
+            ui.text_disabled(project.dir.display().to_string());
+            let dir = project.dir.clone();
+            for (label, file) in [
+                ("Source", &mut project.source),
+                ("ROM", &mut project.rom),
+                ("Symbols", &mut project.symbols),
+                ("Tests", &mut project.tests),
+            ] {
+                let _id = ui.push_id(label);
+                if ui.small_button(tr("Change...")) {
+                    if let Some(path) = rfd::FileDialog::new().set_directory(&dir).pick_file() {
+                        *file = Some(path.strip_prefix(&dir).unwrap_or(&path).to_path_buf());
+                    }
+                }
+                ui.same_line();
+                let name = file.as_ref().map_or_else(
+                    || tr("(none)").to_string(),
+                    |file| file.display().to_string(),
+                );
+                ui.text(format!("{}: {}", tr(label), name));
+            }
+
+            let build_disabled = ui.begin_disabled(project.source.is_none());
+            if ui.button(tr("Build & Run")) {
+                action = Some(ProjectAction::Build);
+            }
+            build_disabled.end();
+            ui.same_line();
+            if ui.button(tr("Run tests")) {
+                state.test_results = Some(project.run_tests());
+            }
+            ui.same_line();
+            if ui.button(tr("Save project")) {
+                action = Some(ProjectAction::Save);
+            }
+            ui.same_line();
+            if ui.button(tr("Close project")) {
+                action = Some(ProjectAction::Close);
+            }
+
+            match &state.test_results {
+                Some(Ok(results)) => {
+                    ui.separator();
+                    for (name, failures) in results {
+                        if failures.is_empty() {
+                            ui.text_colored([0.4, 1.0, 0.4, 1.0], format!("{} ... ok", name));
+                        } else {
+                            ui.text_colored([1.0, 0.4, 0.4, 1.0], format!("{} ... FAILED", name));
+                            for failure in failures {
+                                ui.text_disabled(format!("    {}", failure));
+                            }
+                        }
+                    }
+                }
+                Some(Err(err)) => ui.text_colored([1.0, 0.4, 0.4, 1.0], err),
+                None => {}
+            }
+        });
+    action
+}
+
+#[cfg(test)]
+mod project_tests {
+    use std::{
+        collections::BTreeSet,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use super::{Project, PROJECT_FILE};
+
+    #[test]
+    fn test_round_trip() {
+        let project = Project {
+            source: Some(PathBuf::from("src/game.8o")),
+            rom: Some(PathBuf::from("game.ch8")),
+            tests: Some(PathBuf::from("tests.toml")),
+            ticks_per_frame: Some(20),
+            breakpoints: BTreeSet::from([0x202, 0x2A0]),
+            watches: vec!["mem[0x3F0] == 5".to_string(), "v3".to_string()],
+            panels: BTreeSet::from(["debugger".to_string(), "timeline".to_string()]),
+            ..Project::parse(Path::new("games/pong"), "")
+        };
+        let parsed = Project::parse(Path::new("games/pong"), &project.serialize());
+        assert_eq!(parsed, project);
+        assert_eq!(parsed.name(), "pong");
+        assert_eq!(
+            parsed.path(Path::new("game.ch8")),
+            Path::new("games/pong/game.ch8")
+        );
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = std::env::temp_dir().join(format!("chip-8-project-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["b.8o", "a.8o", "a.sym", "tests.toml"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let project = Project::open(&dir);
+        assert_eq!(project.source, Some(PathBuf::from("a.8o")));
+        assert_eq!(project.rom, Some(PathBuf::from("a.ch8")));
+        assert_eq!(project.symbols, Some(PathBuf::from("a.sym")));
+        assert_eq!(project.tests, Some(PathBuf::from("tests.toml")));
+
+        // Once saved, the project file wins
+        let project = Project {
+            ticks_per_frame: Some(30),
+            ..project
+        };
+        project.save().unwrap();
+        assert_eq!(Project::open(&dir), project);
+        assert!(dir.join(PROJECT_FILE).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
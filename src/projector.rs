@@ -0,0 +1,179 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use glow::HasContext;
+use imgui_glow_renderer::glow;
+use sdl2::{
+    event::{Event, WindowEvent},
+    mouse::MouseButton,
+    video::{FullscreenType, GLContext, SwapInterval, Window, WindowPos},
+    VideoSubsystem,
+};
+
+use crate::{
+    frame_limiter::{self, VSync},
+    graphics,
+    locale::tr,
+    screen,
+};
+
+const SCALE: u32 = 10;
+
+// A borderless window showing just the emulated display, meant to be
+// dragged onto a projector or second monitor. Drag it around with the
+// mouse, double click to toggle fullscreen.
+pub struct Projector {
+    window: Window,
+    drag_offset: Option<(i32, i32)>, // Mouse position within the window
+}
+
+impl Projector {
+    pub fn open(video_subsystem: &VideoSubsystem, ui_scale: f32) -> Result<Projector, String> {
+        let window = video_subsystem
+            .window(
+                tr("Projector"),
+                (screen::WIDTH as f32 * SCALE as f32 * ui_scale) as u32,
+                (screen::HEIGHT as f32 * SCALE as f32 * ui_scale) as u32,
+            )
+            .opengl()
+            .borderless()
+            .resizable()
+            .build()
+            .map_err(|err| err.to_string())?;
+
+        Ok(Projector {
+            window,
+            drag_offset: None,
+        })
+    }
+
+    // Handles the mouse and window events meant for the projector.
+    // Returns false when the window was asked to close.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::Window {
+                win_event: WindowEvent::Close,
+                ..
+            } => return false,
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                clicks: 2,
+                ..
+            } => {
+                self.drag_offset = None;
+                let fullscreen = match self.window.fullscreen_state() {
+                    FullscreenType::Off => FullscreenType::Desktop,
+                    _ => FullscreenType::Off,
+                };
+                if let Err(err) = self.window.set_fullscreen(fullscreen) {
+                    log::warn!("Could not toggle the projector's fullscreen: {}", err);
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                ..
+            } if self.window.fullscreen_state() == FullscreenType::Off => {
+                let (mouse_x, mouse_y) = global_mouse_position();
+                let (window_x, window_y) = self.window.position();
+                self.drag_offset = Some((mouse_x - window_x, mouse_y - window_y));
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => self.drag_offset = None,
+            Event::MouseMotion { .. } => {
+                if let Some((offset_x, offset_y)) = self.drag_offset {
+                    let (mouse_x, mouse_y) = global_mouse_position();
+                    self.window.set_position(
+                        WindowPos::Positioned(mouse_x - offset_x),
+                        WindowPos::Positioned(mouse_y - offset_y),
+                    );
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn is_event_for(&self, event: &Event) -> bool {
+        event.get_window_id() == Some(self.window.id())
+    }
+
+    // Draws the emulator texture (bound by the main window) into the
+    // projector, keeping its aspect ratio. The GL context is made current
+    // on the main window again afterwards. The border uses the main
+    // window's clear color.
+    pub unsafe fn draw(
+        &self,
+        main_window: &Window,
+        gl_context: &GLContext,
+        gl: &glow::Context,
+        screen_size: (usize, usize),
+        padding: u32,
+        vsync: VSync,
+    ) {
+        if let Err(err) = self.window.gl_make_current(gl_context) {
+            log::warn!("Could not draw the projector: {}", err);
+            return;
+        }
+        // Waiting for vsync twice per frame would halve the framerate
+        let subsystem = self.window.subsystem();
+        let _ = subsystem.gl_set_swap_interval(SwapInterval::Immediate);
+
+        let (width, height) = self.window.drawable_size();
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.clear(glow::COLOR_BUFFER_BIT);
+        let [x, y, width, height] = graphics::screen_viewport((width, height), 0, padding);
+        let [offset_x, offset_y, width, height] =
+            letterbox((width as u32, height as u32), screen_size);
+        graphics::draw_screen(gl, [x + offset_x, y + offset_y, width, height]);
+        self.window.gl_swap_window();
+
+        let _ = main_window.gl_make_current(gl_context);
+        frame_limiter::set_swap_interval(subsystem, vsync);
+    }
+}
+
+fn global_mouse_position() -> (i32, i32) {
+    let (mut x, mut y) = (0, 0);
+    unsafe {
+        sdl2::sys::SDL_GetGlobalMouseState(&mut x, &mut y);
+    }
+    (x, y)
+}
+
+// Largest area of the drawable with the screen's aspect ratio, centered.
+// Returns x, y, width and height, as taken by glViewport.
+fn letterbox(drawable: (u32, u32), screen_size: (usize, usize)) -> [i32; 4] {
+    let (drawable_width, drawable_height) = (drawable.0 as f32, drawable.1 as f32);
+    let (screen_width, screen_height) = (screen_size.0 as f32, screen_size.1 as f32);
+    let scale = (drawable_width / screen_width).min(drawable_height / screen_height);
+
+    let width = (screen_width * scale).round();
+    let height = (screen_height * scale).round();
+    let x = ((drawable_width - width) / 2.0).round();
+    let y = ((drawable_height - height) / 2.0).round();
+    [x as i32, y as i32, width as i32, height as i32]
+}
+
+#[cfg(test)]
+mod projector_tests {
+    use super::letterbox;
+
+    #[test]
+    fn test_letterbox() {
+        // Exact fit
+        assert_eq!(letterbox((640, 320), (64, 32)), [0, 0, 640, 320]);
+        // Bars at the top and bottom
+        assert_eq!(letterbox((1920, 1080), (64, 32)), [0, 60, 1920, 960]);
+        assert_eq!(letterbox((800, 800), (128, 64)), [0, 200, 800, 400]);
+        // Bars on the sides
+        assert_eq!(letterbox((1000, 320), (64, 32)), [180, 0, 640, 320]);
+    }
+}
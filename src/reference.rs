@@ -0,0 +1,109 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        instruction::{OpcodeInfo, OPCODES},
+    },
+    locale::tr,
+};
+
+#[derive(Default)]
+pub struct ReferenceState {
+    search: String,
+}
+
+// Whether the opcode matches the search, by encoding, mnemonic or
+// description (in English or the current language).
+fn matches(info: &OpcodeInfo, search: &str) -> bool {
+    let search = search.trim().to_lowercase();
+    [
+        info.encoding,
+        info.mnemonic,
+        info.description,
+        tr(info.description),
+    ]
+    .iter()
+    .any(|text| text.to_lowercase().contains(&search))
+}
+
+// Every supported opcode, with the current quirks' behavior and a
+// toggle to halt before the opcode runs.
+pub fn draw(ui: &Ui, cpu: &mut Cpu, state: &mut ReferenceState, opened: &mut bool) {
+    ui.window(format!("{}###reference", tr("Instruction reference")))
+        .opened(opened)
+        .size([520.0, 420.0], Condition::FirstUseEver)
+        .build(|| {
+            ui.input_text("##search", &mut state.search)
+                .hint(tr("Search opcodes"))
+                .build();
+            ui.separator();
+
+            for info in OPCODES.iter().filter(|info| matches(info, &state.search)) {
+                let _id = ui.push_id(info.encoding);
+                let mut breaks = cpu.break_opcodes.contains(&info.opcode);
+                if ui.checkbox("##break", &mut breaks) {
+                    if breaks {
+                        cpu.break_opcodes.insert(info.opcode);
+                    } else {
+                        cpu.break_opcodes.remove(&info.opcode);
+                    }
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(tr("Break on this opcode"));
+                }
+                ui.same_line();
+                ui.text(info.encoding);
+                ui.same_line_with_pos(ui.current_font_size() * 6.0);
+                ui.text(info.mnemonic);
+                ui.same_line_with_pos(ui.current_font_size() * 14.0);
+                ui.text_wrapped(tr(info.description));
+
+                if let Some((quirk, enabled, disabled)) = info.quirk {
+                    let (current, other) = if cpu.quirk_enabled(quirk) {
+                        (enabled, disabled)
+                    } else {
+                        (disabled, enabled)
+                    };
+                    ui.set_cursor_pos([ui.current_font_size() * 14.0, ui.cursor_pos()[1]]);
+                    ui.text_disabled(format!(
+                        "{} {}. {} {}.",
+                        tr("Current quirks:"),
+                        tr(current),
+                        tr("Otherwise:"),
+                        tr(other)
+                    ));
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod reference_tests {
+    use super::matches;
+    use crate::core::instruction::OPCODES;
+
+    #[test]
+    fn test_search() {
+        let found = |search| {
+            OPCODES
+                .iter()
+                .filter(|info| matches(info, search))
+                .map(|info| info.encoding)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(found("dxyn"), vec!["Dxyn"]);
+        assert_eq!(found("  SKP "), vec!["Ex9E"]);
+        assert_eq!(found("delay timer"), vec!["Fx07", "Fx15"]);
+        assert_eq!(found("").len(), OPCODES.len());
+    }
+}
@@ -0,0 +1,71 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        replay::{Replay, ReplayPlayer},
+    },
+    locale::tr,
+};
+
+const SPEEDS: [u32; 4] = [1, 2, 4, 8];
+
+// Draws the replay timeline window. Returns a new recording
+// when the user takes control from the current frame.
+pub fn draw(ui: &Ui, player: &mut ReplayPlayer, cpu: &mut Cpu) -> Option<Replay> {
+    let mut branch = None;
+    let display_size = ui.io().display_size;
+
+    ui.window(format!("{}###replay", tr("Replay")))
+        .position(
+            [display_size[0] / 2.0, display_size[1] - 10.0],
+            Condition::Appearing,
+        )
+        .position_pivot([0.5, 1.0])
+        .always_auto_resize(true)
+        .collapsible(false)
+        .build(|| {
+            let text = if player.paused {
+                tr("Play")
+            } else {
+                tr("Pause")
+            };
+            if ui.button_with_size(text, [ui.current_font_size() * 4.25, 0.0]) {
+                player.paused = !player.paused;
+            }
+
+            for speed in SPEEDS {
+                ui.same_line();
+                let label = format!("{}x", speed);
+                if ui.radio_button_bool(label, player.speed == speed) {
+                    player.speed = speed;
+                }
+            }
+
+            ui.same_line();
+            if ui.button(tr("Take control")) {
+                branch = Some(player.branch(cpu));
+            }
+
+            if player.replay().base_state.is_some() {
+                ui.text_disabled(tr("Recorded from a save state"));
+            }
+
+            let mut frame = cpu.frame();
+            ui.set_next_item_width(400.0);
+            if ui.slider(tr("Frame"), 0, player.length(), &mut frame) {
+                player.seek(cpu, frame);
+            }
+        });
+
+    branch
+}
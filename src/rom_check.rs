@@ -0,0 +1,146 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        analyzer::{self, Analysis},
+        memory::PROGRAM_START_MEM,
+    },
+    locale::tr,
+    playlist::Playlist,
+};
+
+// A ROM read from disk (and patched), waiting to be loaded.
+pub struct PendingRom {
+    pub rom_path: PathBuf,
+    pub patch_path: Option<PathBuf>,
+    pub rom: Vec<u8>,
+}
+
+pub struct CheckedRom {
+    pub pending: PendingRom,
+    pub analysis: Analysis,
+}
+
+// Runs the static analyzer on ROMs before they're loaded, in a worker
+// thread so large files don't stall the UI.
+pub struct RomChecker {
+    jobs: Sender<PendingRom>,
+    results: Receiver<CheckedRom>,
+}
+
+impl RomChecker {
+    pub fn new() -> RomChecker {
+        let (jobs, job_receiver) = mpsc::channel::<PendingRom>();
+        let (result_sender, results) = mpsc::channel();
+
+        thread::spawn(move || {
+            for pending in job_receiver {
+                let analysis = analyzer::analyze(&pending.rom);
+                if result_sender
+                    .send(CheckedRom { pending, analysis })
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        RomChecker { jobs, results }
+    }
+
+    pub fn check(&self, pending: PendingRom) {
+        let _ = self.jobs.send(pending);
+    }
+
+    pub fn poll(&self) -> Option<CheckedRom> {
+        self.results.try_recv().ok()
+    }
+}
+
+pub enum Decision {
+    Load(u16), // At this address
+    Cancel,
+}
+
+// Confirmation dialog with the analyzer's findings. Returns what to do
+// with the ROM once a button is clicked.
+pub fn draw(ui: &Ui, checked: &CheckedRom) -> Option<Decision> {
+    let mut decision = None;
+    let analysis = &checked.analysis;
+    let display_size = ui.io().display_size;
+    ui.window(format!("{}###rom_check", tr("ROM check")))
+        .position(
+            [display_size[0] / 2.0, display_size[1] / 2.0],
+            Condition::Appearing,
+        )
+        .position_pivot([0.5, 0.5])
+        .always_auto_resize(true)
+        .collapsible(false)
+        .build(|| {
+            ui.text(Playlist::title(&checked.pending.rom_path));
+            ui.text_disabled(format!(
+                "{} {}, {} {}",
+                analysis.size,
+                tr("bytes"),
+                analysis.reachable,
+                tr("reachable instructions")
+            ));
+
+            if !analysis.extensions.is_empty() {
+                ui.separator();
+                let extensions: Vec<String> = analysis
+                    .extensions
+                    .iter()
+                    .map(|extension| extension.to_string())
+                    .collect();
+                ui.text(format!("{} {}", tr("Requires:"), extensions.join(", ")));
+                ui.text_wrapped(tr(
+                    "Only plain CHIP-8 is emulated, so the ROM may not run correctly.",
+                ));
+            }
+
+            if !analysis.findings.is_empty() {
+                ui.separator();
+                for finding in &analysis.findings {
+                    ui.bullet_text(finding.to_string());
+                }
+            }
+
+            ui.separator();
+            if !analysis.can_load() {
+                ui.text(tr("This ROM can't be loaded."));
+            } else if analysis.base != PROGRAM_START_MEM {
+                // Suggested address first, the usual one as an alternative
+                for base in [analysis.base, PROGRAM_START_MEM] {
+                    if ui.button(format!("{} 0x{:04X}", tr("Load at"), base)) {
+                        decision = Some(Decision::Load(base));
+                    }
+                    ui.same_line();
+                }
+            } else {
+                if ui.button(tr("Load anyway")) {
+                    decision = Some(Decision::Load(analysis.base));
+                }
+                ui.same_line();
+            }
+            if ui.button(tr("Cancel")) {
+                decision = Some(Decision::Cancel);
+            }
+        });
+    decision
+}
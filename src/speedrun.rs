@@ -0,0 +1,315 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        beep::FRAME_RATE,
+        cpu::Cpu,
+        expr::{Expr, ExprError},
+    },
+    locale::tr,
+};
+
+// Speedrun timer counting emulated frames, so runs time the same
+// regardless of the host's speed, stutters or fast forwarding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeedrunTimer {
+    pub auto_start: bool, // Starts on the first keypad input
+    start: Option<u64>,   // Frame the run started at
+    splits: Vec<u64>,     // Frames since the start
+
+    // Auto-splitter: split n happens when condition n becomes true
+    pub auto_split_text: String, // One condition per line, as typed
+    pub auto_split_error: Option<String>,
+    auto_splits: Vec<Expr>,
+    was_true: Option<bool>, // Last value of the next condition
+}
+
+impl SpeedrunTimer {
+    pub fn new() -> SpeedrunTimer {
+        SpeedrunTimer {
+            auto_start: true,
+            start: None,
+            splits: Vec::new(),
+            auto_split_text: String::new(),
+            auto_split_error: None,
+            auto_splits: Vec::new(),
+            was_true: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.start.is_some()
+    }
+
+    pub fn splits(&self) -> &[u64] {
+        &self.splits
+    }
+
+    pub fn start(&mut self, frame: u64) {
+        self.start = Some(frame);
+        self.splits.clear();
+        self.was_true = None;
+    }
+
+    pub fn reset(&mut self) {
+        self.start = None;
+        self.splits.clear();
+        self.was_true = None;
+    }
+
+    // Call on every keypad input.
+    pub fn on_input(&mut self, frame: u64) {
+        if self.auto_start && !self.is_running() {
+            self.start(frame);
+        }
+    }
+
+    // Splits, or starts the run if it isn't running yet.
+    pub fn split(&mut self, frame: u64) {
+        match self.start {
+            Some(start) => self.splits.push(frame.saturating_sub(start)),
+            None => self.start(frame),
+        }
+        self.was_true = None;
+    }
+
+    // Parses auto_split_text into the conditions, skipping empty
+    // lines and # comments. On errors, the conditions are kept.
+    pub fn apply_auto_split_text(&mut self) -> Result<(), (usize, ExprError)> {
+        let mut conditions = Vec::new();
+        for (idx, line) in self.auto_split_text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            conditions.push(Expr::parse(line).map_err(|err| (idx + 1, err))?);
+        }
+        self.auto_splits = conditions;
+        self.was_true = None;
+        Ok(())
+    }
+
+    pub fn auto_split_count(&self) -> usize {
+        self.auto_splits.len()
+    }
+
+    // Call after every frame. Splits when the next condition goes from
+    // false to true, so conditions already true don't split right away.
+    pub fn check_auto_split(&mut self, cpu: &Cpu) {
+        let Some(condition) = self.auto_splits.get(self.splits.len()) else {
+            return;
+        };
+        if !self.is_running() {
+            return;
+        }
+        let is_true = condition.is_true(cpu);
+        if is_true && self.was_true == Some(false) {
+            self.split(cpu.frame());
+        } else {
+            self.was_true = Some(is_true);
+        }
+    }
+
+    // Splits as CSV, with their total and segment times.
+    pub fn export_splits(&self) -> String {
+        let mut output = String::from("split,frames,time,segment_frames,segment_time\n");
+        let mut previous = 0;
+        for (idx, split) in self.splits.iter().enumerate() {
+            output += &format!(
+                "{},{},{},{},{}\n",
+                idx + 1,
+                split,
+                format_frames(*split),
+                split - previous,
+                format_frames(split - previous)
+            );
+            previous = *split;
+        }
+        output
+    }
+
+    // Frames since the start. A machine reset (its frame count going
+    // back) ends the run.
+    pub fn elapsed(&mut self, frame: u64) -> u64 {
+        match self.start {
+            Some(start) if frame < start => {
+                self.reset();
+                0
+            }
+            Some(start) => frame - start,
+            None => 0,
+        }
+    }
+}
+
+// Frames as m:ss.cc of emulated time.
+pub fn format_frames(frames: u64) -> String {
+    let centis = (frames as f64 * 100.0 / FRAME_RATE) as u64;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+// Small window in the top right corner, with the time and splits.
+pub fn draw(ui: &Ui, timer: &mut SpeedrunTimer, frame: u64, top: f32) {
+    let elapsed = timer.elapsed(frame);
+    let display_width = ui.io().display_size[0];
+    ui.window("##speedrun")
+        .position([display_width - 8.0, top + 8.0], Condition::Always)
+        .position_pivot([1.0, 0.0])
+        .flags(imgui::WindowFlags::NO_DOCKING | imgui::WindowFlags::NO_SAVED_SETTINGS)
+        .bg_alpha(0.6)
+        .no_decoration()
+        .always_auto_resize(true)
+        .movable(false)
+        .build(|| {
+            if !timer.is_running() {
+                ui.text_disabled(if timer.auto_start {
+                    tr("Waiting for the first input")
+                } else {
+                    tr("Press F9 to start")
+                });
+            }
+            ui.text(format!(
+                "{}  ({} {})",
+                format_frames(elapsed),
+                elapsed,
+                tr("frames")
+            ));
+
+            let mut previous = 0;
+            for (idx, split) in timer.splits().iter().enumerate() {
+                ui.text_disabled(format!(
+                    "{:>2}. {}  +{}",
+                    idx + 1,
+                    format_frames(*split),
+                    format_frames(split - previous)
+                ));
+                previous = *split;
+            }
+        });
+}
+
+// Editor for the auto-splitter conditions.
+pub fn draw_auto_splitter(ui: &Ui, timer: &mut SpeedrunTimer, opened: &mut bool) {
+    ui.window(format!("{}###auto_splitter", tr("Auto-splitter")))
+        .opened(opened)
+        .always_auto_resize(true)
+        .build(|| {
+            ui.text_wrapped(tr(
+                "One condition per line. Split n happens when condition n becomes true.",
+            ));
+            ui.text_disabled("mem[0x3F0] == 5 && v3 > 0");
+            let size = [ui.current_font_size() * 24.0, ui.current_font_size() * 10.0];
+            if ui
+                .input_text_multiline("##conditions", &mut timer.auto_split_text, size)
+                .build()
+            {
+                timer.auto_split_error = timer
+                    .apply_auto_split_text()
+                    .err()
+                    .map(|(line, err)| format!("{} {}: {}", tr("Line"), line, err));
+            }
+            match timer.auto_split_error.as_ref() {
+                Some(error) => ui.text_colored([1.0, 0.3, 0.3, 1.0], error),
+                None => ui.text_disabled(format!(
+                    "{}: {}",
+                    tr("Conditions"),
+                    timer.auto_split_count()
+                )),
+            }
+        });
+}
+
+#[cfg(test)]
+mod speedrun_tests {
+    use super::{format_frames, SpeedrunTimer};
+    use crate::core::cpu::Cpu;
+
+    #[test]
+    fn test_format_frames() {
+        assert_eq!(format_frames(0), "0:00.00");
+        assert_eq!(format_frames(90), "0:01.50");
+        assert_eq!(format_frames(60 * 75 + 6), "1:15.10");
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut timer = SpeedrunTimer::new();
+        assert_eq!(timer.elapsed(50), 0);
+
+        timer.on_input(100);
+        timer.on_input(110); // Already running
+        assert_eq!(timer.elapsed(160), 60);
+        timer.split(130);
+        timer.split(190);
+        assert_eq!(timer.splits(), &[30, 90]);
+
+        // The machine got reset
+        assert_eq!(timer.elapsed(20), 0);
+        assert!(!timer.is_running());
+        assert!(timer.splits().is_empty());
+
+        timer.auto_start = false;
+        timer.on_input(30);
+        assert!(!timer.is_running());
+        timer.split(40);
+        assert!(timer.is_running());
+        assert!(timer.splits().is_empty());
+    }
+
+    #[test]
+    fn test_auto_split() {
+        let mut cpu = Cpu::new();
+        let mut timer = SpeedrunTimer::new();
+        timer.auto_split_text = "# Level 2\nmem[0x3F0] == 2\n\nv0 > 5".to_string();
+        timer.apply_auto_split_text().unwrap();
+        assert_eq!(timer.auto_split_count(), 2);
+
+        // Already true when the run starts
+        cpu.memory.write(0x3F0, 2);
+        timer.start(0);
+        timer.check_auto_split(&cpu);
+        assert!(timer.splits().is_empty());
+
+        cpu.memory.write(0x3F0, 1);
+        timer.check_auto_split(&cpu);
+        cpu.memory.write(0x3F0, 2);
+        timer.check_auto_split(&cpu);
+        assert_eq!(timer.splits().len(), 1);
+
+        cpu.registers.v[0] = 6;
+        timer.check_auto_split(&cpu);
+        assert_eq!(timer.splits().len(), 1);
+
+        timer.auto_split_text = "v0 >".to_string();
+        assert_eq!(timer.apply_auto_split_text().unwrap_err().0, 1);
+        assert_eq!(timer.auto_split_count(), 2);
+    }
+
+    #[test]
+    fn test_export_splits() {
+        let mut timer = SpeedrunTimer::new();
+        timer.start(10);
+        timer.split(70);
+        timer.split(100);
+        assert_eq!(
+            timer.export_splits(),
+            "split,frames,time,segment_frames,segment_time\n\
+             1,60,0:01.00,60,0:01.00\n\
+             2,90,0:01.50,30,0:00.50\n"
+        );
+    }
+}
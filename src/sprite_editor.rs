@@ -0,0 +1,208 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, MouseButton, Ui};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        hex_rom,
+        sprite::{Sprite, MAX_HEIGHT},
+    },
+    locale::tr,
+};
+
+const LIT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const UNLIT_COLOR: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+
+pub struct SpriteEditorState {
+    sprite: Sprite,
+    address_input: String,
+    painting: bool, // Value set while dragging over the grid
+    error: Option<String>,
+}
+
+impl Default for SpriteEditorState {
+    fn default() -> SpriteEditorState {
+        SpriteEditorState {
+            sprite: Sprite::new(8),
+            address_input: String::new(),
+            painting: true,
+            error: None,
+        }
+    }
+}
+
+// Hex, with or without 0x.
+pub fn parse_address(input: &str) -> Option<u16> {
+    let input = input.trim();
+    let digits = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .unwrap_or(input);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+// A grid to draw sprites on, left button painting and right erasing,
+// read from and written back into memory as the program runs.
+pub fn draw(ui: &Ui, cpu: &mut Cpu, state: &mut SpriteEditorState, opened: &mut bool) {
+    ui.window(format!("{}###sprite_editor", tr("Sprite editor")))
+        .opened(opened)
+        .always_auto_resize(true)
+        .position([420.0, 50.0], Condition::FirstUseEver)
+        .build(|| {
+            let mut wide = state.sprite.is_wide();
+            if ui.checkbox(tr("16x16 (SCHIP)"), &mut wide) {
+                let bytes = state.sprite.to_bytes();
+                state.sprite = Sprite::from_bytes(&bytes, wide);
+            }
+            if !wide {
+                ui.same_line();
+                let mut height = state.sprite.height() as i32;
+                ui.set_next_item_width(ui.current_font_size() * 8.0);
+                if ui.slider(tr("Rows"), 1, MAX_HEIGHT as i32, &mut height) {
+                    state.sprite.set_height(height as usize);
+                }
+            }
+
+            draw_grid(ui, state);
+
+            if ui.button(tr("Clear")) {
+                state.sprite.clear();
+            }
+            ui.same_line();
+            if ui.button(tr("Invert")) {
+                state.sprite.invert();
+            }
+            ui.same_line();
+            if ui.button(tr("Flip horizontally")) {
+                state.sprite.flip_horizontal();
+            }
+            ui.same_line();
+            if ui.button(tr("Flip vertically")) {
+                state.sprite.flip_vertical();
+            }
+
+            ui.separator();
+            let hex = state.sprite.to_hex();
+            ui.text(&hex);
+            if ui.button(tr("Copy hex")) {
+                ui.set_clipboard_text(&hex);
+            }
+            ui.same_line();
+            if ui.button(tr("Paste hex")) {
+                match hex_rom::parse(&ui.clipboard_text().unwrap_or_default()) {
+                    Ok(bytes) => {
+                        state.sprite = Sprite::from_bytes(&bytes, state.sprite.is_wide());
+                        state.error = None;
+                    }
+                    Err(err) => state.error = Some(err.to_string()),
+                }
+            }
+
+            ui.separator();
+            draw_memory(ui, cpu, state);
+            if let Some(error) = &state.error {
+                ui.text_colored([1.0, 0.4, 0.4, 1.0], error);
+            }
+        });
+}
+
+fn draw_grid(ui: &Ui, state: &mut SpriteEditorState) {
+    let sprite = &mut state.sprite;
+    let cell = ui.current_font_size() * if sprite.is_wide() { 1.0 } else { 1.5 };
+    let origin = ui.cursor_screen_pos();
+    let size = [cell * sprite.width() as f32, cell * sprite.height() as f32];
+    let draw_list = ui.get_window_draw_list();
+    for y in 0..sprite.height() {
+        for x in 0..sprite.width() {
+            let top_left = [origin[0] + x as f32 * cell, origin[1] + y as f32 * cell];
+            let color = if sprite.get(x, y) {
+                LIT_COLOR
+            } else {
+                UNLIT_COLOR
+            };
+            draw_list
+                .add_rect(
+                    top_left,
+                    [top_left[0] + cell - 1.0, top_left[1] + cell - 1.0],
+                    color,
+                )
+                .filled(true)
+                .build();
+        }
+    }
+
+    ui.invisible_button("##sprite_grid", size);
+    if !ui.is_item_hovered() && !ui.is_item_active() {
+        return;
+    }
+    let [mouse_x, mouse_y] = ui.io().mouse_pos;
+    let x = ((mouse_x - origin[0]) / cell).floor();
+    let y = ((mouse_y - origin[1]) / cell).floor();
+    if x < 0.0 || y < 0.0 {
+        return;
+    }
+    let (x, y) = (x as usize, y as usize);
+    // A drag paints the opposite of the pixel it started on
+    if ui.is_item_activated() {
+        state.painting = !sprite.get(x, y);
+    }
+    if ui.is_item_active() {
+        sprite.set(x, y, state.painting);
+    } else if ui.is_mouse_down(MouseButton::Right) {
+        sprite.set(x, y, false);
+    }
+}
+
+fn draw_memory(ui: &Ui, cpu: &mut Cpu, state: &mut SpriteEditorState) {
+    ui.set_next_item_width(ui.current_font_size() * 6.0);
+    ui.input_text("##sprite_address", &mut state.address_input)
+        .hint(tr("Address"))
+        .build();
+    ui.same_line();
+    if ui.button(tr("Use I")) {
+        state.address_input = format!("{:#05X}", cpu.registers.i);
+    }
+
+    let len = state.sprite.to_bytes().len();
+    let addr = parse_address(&state.address_input)
+        .filter(|&addr| addr as usize + len <= cpu.memory.size());
+    let disabled = ui.begin_disabled(addr.is_none());
+    ui.same_line();
+    if ui.button(tr("Read from memory")) {
+        if let Some(addr) = addr {
+            let bytes: Vec<u8> = (0..len as u16)
+                .filter_map(|offset| cpu.memory.peek(addr + offset))
+                .collect();
+            state.sprite = Sprite::from_bytes(&bytes, state.sprite.is_wide());
+        }
+    }
+    ui.same_line();
+    if ui.button(tr("Write to memory")) {
+        if let Some(addr) = addr {
+            if let Err(err) = cpu.memory.load_slice(addr, &state.sprite.to_bytes()) {
+                log::warn!("Could not write the sprite: {}", err);
+            }
+        }
+    }
+    disabled.end();
+}
+
+#[cfg(test)]
+mod sprite_editor_tests {
+    use super::parse_address;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0x300"), Some(0x300));
+        assert_eq!(parse_address(" 2A0 "), Some(0x2A0));
+        assert_eq!(parse_address("label"), None);
+    }
+}
@@ -0,0 +1,259 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::time::{Duration, Instant};
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        instruction::Instruction,
+        registers::{DELAY_TIMER, SOUND_TIMER},
+    },
+    locale::tr,
+};
+
+// V0-VF, then these
+const REGISTER_NAMES: [&str; 4] = ["I", "SP", "DT", "ST"];
+const REGISTER_COUNT: usize = 16 + REGISTER_NAMES.len();
+
+// How long changed registers stay highlighted.
+const FLASH_DURATION: Duration = Duration::from_millis(800);
+const FETCH_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const I_COLOR: [f32; 4] = [0.4, 0.8, 1.0, 1.0];
+
+// Presentation mode for teaching how a CPU works: runs a few
+// instructions per second, showing each fetch, decode and the
+// registers it changed.
+pub struct TeachingState {
+    pub enabled: bool,
+    pub speed: f32, // Instructions per second
+    last_step: Option<Instant>,
+    last_pc: u16,
+    last_instruction: Option<Instruction>,
+    changed_at: [Option<Instant>; REGISTER_COUNT],
+}
+
+impl Default for TeachingState {
+    fn default() -> TeachingState {
+        TeachingState {
+            enabled: false,
+            speed: 2.0,
+            last_step: None,
+            last_pc: 0,
+            last_instruction: None,
+            changed_at: [None; REGISTER_COUNT],
+        }
+    }
+}
+
+impl TeachingState {
+    // Runs in place of the machine's frames, stepping once the
+    // current step has been on screen long enough.
+    pub fn update(&mut self, cpu: &mut Cpu) {
+        if cpu.is_halted() || !cpu.is_rom_loaded() {
+            return;
+        }
+        let interval = Duration::from_secs_f32(1.0 / self.speed.max(0.1));
+        if self
+            .last_step
+            .is_some_and(|last_step| last_step.elapsed() < interval)
+        {
+            return;
+        }
+        self.step(cpu);
+    }
+
+    pub fn step(&mut self, cpu: &mut Cpu) {
+        let before = register_values(cpu);
+        self.last_pc = cpu.registers.pc;
+        self.last_instruction = Some(cpu.memory.read_instruction(cpu.registers.pc));
+        let _ = cpu.step();
+
+        let now = Instant::now();
+        self.last_step = Some(now);
+        for idx in changed_registers(&before, &register_values(cpu)) {
+            self.changed_at[idx] = Some(now);
+        }
+    }
+
+    // 1 right after changing, fading to 0.
+    fn flash(&self, idx: usize) -> f32 {
+        self.changed_at[idx].map_or(0.0, |changed_at| {
+            1.0 - (changed_at.elapsed().as_secs_f32() / FLASH_DURATION.as_secs_f32()).min(1.0)
+        })
+    }
+}
+
+fn register_values(cpu: &Cpu) -> [u16; REGISTER_COUNT] {
+    let registers = &cpu.registers;
+    let mut values = [0; REGISTER_COUNT];
+    for (value, v) in values.iter_mut().zip(registers.v) {
+        *value = v as u16;
+    }
+    values[16] = registers.i;
+    values[17] = registers.sp as u16;
+    values[18] = registers.timers[DELAY_TIMER].read() as u16;
+    values[19] = registers.timers[SOUND_TIMER].read() as u16;
+    values
+}
+
+fn changed_registers(before: &[u16], after: &[u16]) -> Vec<usize> {
+    (0..before.len())
+        .filter(|&idx| before[idx] != after[idx])
+        .collect()
+}
+
+// The operand fields an instruction uses, e.g. [("x", "0"), ("kk", "01")]
+// for 6001.
+pub fn fields(instruction: &Instruction) -> Vec<(&'static str, String)> {
+    let x = ("x", format!("{:X}", instruction.x()));
+    let y = ("y", format!("{:X}", instruction.y()));
+    match instruction.parts().0 {
+        0x0 if instruction.nnn() == 0x0E0 || instruction.nnn() == 0x0EE => vec![],
+        0x0 | 0x1 | 0x2 | 0xA | 0xB => vec![("nnn", format!("{:03X}", instruction.nnn()))],
+        0x3 | 0x4 | 0x6 | 0x7 | 0xC => vec![x, ("kk", format!("{:02X}", instruction.kk()))],
+        0x5 | 0x8 | 0x9 => vec![x, y],
+        0xD => vec![x, y, ("n", format!("{:X}", instruction.n()))],
+        _ => vec![x],
+    }
+}
+
+pub fn draw(ui: &Ui, cpu: &mut Cpu, state: &mut TeachingState) {
+    let mut opened = state.enabled;
+    ui.window(format!("{}###teaching", tr("Teaching mode")))
+        .opened(&mut opened)
+        .position([10.0, 50.0], Condition::FirstUseEver)
+        .always_auto_resize(true)
+        .build(|| {
+            ui.set_next_item_width(ui.current_font_size() * 10.0);
+            ui.slider(tr("Instructions per second"), 0.5, 10.0, &mut state.speed);
+            let text = if cpu.is_halted() {
+                tr("Resume")
+            } else {
+                tr("Halt")
+            };
+            if ui.button(text) {
+                cpu.toggle_halt();
+            }
+            ui.same_line();
+            let halted_scope = ui.begin_disabled(!cpu.is_halted() || !cpu.is_rom_loaded());
+            let step = ui.button(tr("Step"));
+            halted_scope.end();
+
+            ui.separator();
+            ui.text(tr("1. Fetch"));
+            draw_memory(ui, cpu, state.last_pc);
+
+            ui.separator();
+            ui.text(tr("2. Decode"));
+            match state.last_instruction.as_ref() {
+                Some(instruction) => {
+                    let (a, b, c, d) = instruction.parts();
+                    ui.text_colored(FETCH_COLOR, format!("{:X}{:X}{:X}{:X}", a, b, c, d));
+                    ui.same_line();
+                    ui.text(format!("{:?}", instruction.opcode()));
+                    for (name, value) in fields(instruction) {
+                        ui.same_line();
+                        ui.text_disabled(format!("{}={}", name, value));
+                    }
+                }
+                None => ui.text_disabled("-"),
+            }
+
+            ui.separator();
+            ui.text(tr("3. Execute"));
+            let values = register_values(cpu);
+            for (idx, value) in values.iter().enumerate() {
+                if idx % 4 != 0 {
+                    ui.same_line_with_pos(ui.current_font_size() * 5.0 * (idx % 4) as f32);
+                }
+                let name = match idx {
+                    0..=15 => format!("V{:X}", idx),
+                    _ => REGISTER_NAMES[idx - 16].to_string(),
+                };
+                let flash = state.flash(idx);
+                let color = [1.0, 1.0 - 0.2 * flash, 1.0 - flash, 1.0];
+                ui.text_colored(color, format!("{}: {:02X}", name, value));
+            }
+            ui.text(format!("PC: {:#05X}", cpu.registers.pc));
+
+            if step {
+                state.step(cpu);
+            }
+        });
+    state.enabled = opened;
+}
+
+// Memory around the last fetch, with the fetched bytes and the byte
+// at I highlighted.
+fn draw_memory(ui: &Ui, cpu: &Cpu, pc: u16) {
+    const ROW: u16 = 8;
+    let first_row = (pc / ROW).saturating_sub(1) * ROW;
+    for row in (first_row..first_row + ROW * 3).step_by(ROW as usize) {
+        ui.text_disabled(format!("{:03X}:", row));
+        for addr in row..row + ROW {
+            ui.same_line();
+            let Some(data) = cpu.memory.peek(addr) else {
+                continue;
+            };
+            let text = format!("{:02X}", data);
+            if addr == pc || addr == pc.wrapping_add(1) {
+                ui.text_colored(FETCH_COLOR, text);
+            } else if addr == cpu.registers.i {
+                ui.text_colored(I_COLOR, text);
+            } else {
+                ui.text(text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod teaching_tests {
+    use super::{changed_registers, fields, TeachingState};
+    use crate::core::{cpu::Cpu, instruction::Instruction};
+
+    #[test]
+    fn test_fields() {
+        let field = |code| fields(&Instruction::new(code));
+        assert_eq!(field((0, 0, 0xE, 0)), vec![]);
+        assert_eq!(field((0x1, 2, 3, 4)), vec![("nnn", "234".to_string())]);
+        assert_eq!(
+            field((0x6, 0, 0, 1)),
+            vec![("x", "0".to_string()), ("kk", "01".to_string())]
+        );
+        assert_eq!(
+            field((0xD, 1, 2, 5)),
+            vec![
+                ("x", "1".to_string()),
+                ("y", "2".to_string()),
+                ("n", "5".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        assert_eq!(changed_registers(&[1, 2, 3], &[1, 5, 4]), vec![1, 2]);
+
+        // LD V3, 7 - LD I, 0x300
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&[0x63, 0x07, 0xA3, 0x00], 0x0200).unwrap();
+        let mut state = TeachingState::default();
+        state.step(&mut cpu);
+        assert_eq!(state.last_pc, 0x200);
+        assert!(state.changed_at[3].is_some());
+        assert!(state.changed_at[16].is_none());
+
+        state.update(&mut cpu); // Too soon for another step
+        assert_eq!(cpu.registers.pc, 0x202);
+    }
+}
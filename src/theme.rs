@@ -0,0 +1,64 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Style, StyleColor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Dark,
+    Light,
+    Classic,
+}
+
+pub const THEMES: [Theme; 3] = [Theme::Dark, Theme::Light, Theme::Classic];
+
+// imgui's default accent (blue)
+pub const DEFAULT_ACCENT: [f32; 3] = [0.26, 0.59, 0.98];
+
+impl Theme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::Classic => "Classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Theme> {
+        THEMES.into_iter().find(|theme| theme.name() == name)
+    }
+}
+
+// Resets the style colors to the theme's, then tints the interactive
+// widgets (buttons, headers, sliders, tabs...) with the accent color.
+pub fn apply(style: &mut Style, theme: Theme, accent: [f32; 3]) {
+    match theme {
+        Theme::Dark => style.use_dark_colors(),
+        Theme::Light => style.use_light_colors(),
+        Theme::Classic => style.use_classic_colors(),
+    };
+
+    let [r, g, b] = accent;
+    let with_alpha = |alpha: f32| [r, g, b, alpha];
+
+    style[StyleColor::CheckMark] = with_alpha(1.0);
+    style[StyleColor::SliderGrab] = with_alpha(0.78);
+    style[StyleColor::SliderGrabActive] = with_alpha(1.0);
+    style[StyleColor::Button] = with_alpha(0.40);
+    style[StyleColor::ButtonHovered] = with_alpha(1.0);
+    style[StyleColor::ButtonActive] = with_alpha(0.85);
+    style[StyleColor::Header] = with_alpha(0.31);
+    style[StyleColor::HeaderHovered] = with_alpha(0.80);
+    style[StyleColor::HeaderActive] = with_alpha(1.0);
+    style[StyleColor::FrameBgHovered] = with_alpha(0.40);
+    style[StyleColor::FrameBgActive] = with_alpha(0.67);
+    style[StyleColor::TabHovered] = with_alpha(0.80);
+    style[StyleColor::DockingPreview] = with_alpha(0.70);
+    style[StyleColor::TextSelectedBg] = with_alpha(0.35);
+}
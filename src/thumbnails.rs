@@ -0,0 +1,187 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use imgui::Ui;
+
+use crate::{
+    core::{
+        cpu::Cpu,
+        hash,
+        screen::{Palette, Screen},
+    },
+    png, PROGRAM_BEGIN,
+};
+
+// How long each ROM runs before its screen is taken
+const THUMBNAIL_FRAMES: u32 = 120;
+
+const CACHE_DIR: &str = "thumbnails";
+
+// Size of an emulated pixel in thumbnails, at 64x32
+const PIXEL_SIZE: f32 = 2.0;
+
+// Preview screens of ROMs, generated by running each one for a couple
+// of seconds in a background thread. They're cached as PNGs keyed by
+// the ROM's hash, so every ROM only runs once.
+pub struct Thumbnails {
+    screens: HashMap<u64, Option<Screen>>, // None while being generated, or if it failed
+    hashes: HashMap<PathBuf, Option<u64>>, // None if the ROM couldn't be read
+    jobs: Sender<(u64, Vec<u8>)>,
+    results: Receiver<(u64, Option<Screen>)>,
+}
+
+impl Thumbnails {
+    pub fn new() -> Thumbnails {
+        let (jobs, job_receiver) = mpsc::channel::<(u64, Vec<u8>)>();
+        let (result_sender, results) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (rom_hash, rom) in job_receiver {
+                let screen = render(&rom, THUMBNAIL_FRAMES);
+                if let Some(screen) = screen.as_ref() {
+                    save_cached(rom_hash, screen);
+                }
+                if result_sender.send((rom_hash, screen)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Thumbnails {
+            screens: HashMap::new(),
+            hashes: HashMap::new(),
+            jobs,
+            results,
+        }
+    }
+
+    // Picks up the thumbnails finished since the last call.
+    pub fn poll(&mut self) {
+        while let Ok((rom_hash, screen)) = self.results.try_recv() {
+            self.screens.insert(rom_hash, screen);
+        }
+    }
+
+    // The ROM's thumbnail, if ready. Otherwise it starts being made.
+    pub fn get(&mut self, rom: &[u8]) -> Option<&Screen> {
+        let rom_hash = hash::hash_bytes(rom);
+        if !self.screens.contains_key(&rom_hash) {
+            let cached = load_cached(rom_hash);
+            if cached.is_none() {
+                let _ = self.jobs.send((rom_hash, rom.to_vec()));
+            }
+            self.screens.insert(rom_hash, cached);
+        }
+        self.screens.get(&rom_hash).and_then(Option::as_ref)
+    }
+
+    // Same as get, reading the ROM file the first time only.
+    pub fn get_for_path(&mut self, path: &Path) -> Option<&Screen> {
+        if !self.hashes.contains_key(path) {
+            let rom = fs::read(path).ok();
+            let rom_hash = rom.as_ref().map(|rom| {
+                self.get(rom);
+                hash::hash_bytes(rom)
+            });
+            self.hashes.insert(path.to_path_buf(), rom_hash);
+        }
+        let rom_hash = (*self.hashes.get(path)?)?;
+        self.screens.get(&rom_hash).and_then(Option::as_ref)
+    }
+}
+
+// Runs the ROM for the given amount of frames, as fast as it can since
+// timers count emulated frames. Returns its screen, or None if it
+// didn't load.
+pub fn render(rom: &[u8], frames: u32) -> Option<Screen> {
+    let mut cpu = Cpu::new();
+    cpu.load_rom(rom, PROGRAM_BEGIN).ok()?;
+    cpu.disable_beep();
+
+    for _ in 0..frames {
+        cpu.tick();
+    }
+    Some(cpu.screen)
+}
+
+fn cache_path(rom_hash: u64) -> PathBuf {
+    Path::new(CACHE_DIR).join(format!("{:016X}.png", rom_hash))
+}
+
+fn load_cached(rom_hash: u64) -> Option<Screen> {
+    png::decode_screen(&fs::read(cache_path(rom_hash)).ok()?)
+}
+
+fn save_cached(rom_hash: u64, screen: &Screen) {
+    let result = fs::create_dir_all(CACHE_DIR)
+        .and_then(|_| fs::write(cache_path(rom_hash), png::encode_screen(screen)));
+    if let Err(err) = result {
+        log::warn!("Could not cache a thumbnail: {}", err);
+    }
+}
+
+// Draws the thumbnail at the cursor, in the given palette.
+pub fn draw(ui: &Ui, screen: &Screen, palette: &Palette) {
+    let pixel_size = PIXEL_SIZE * 64.0 / screen.width() as f32;
+    let size = [
+        screen.width() as f32 * pixel_size,
+        screen.height() as f32 * pixel_size,
+    ];
+    let origin = ui.cursor_screen_pos();
+    ui.dummy(size);
+
+    let color = |[r, g, b]: [u8; 3]| [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0];
+    let draw_list = ui.get_window_draw_list();
+    draw_list
+        .add_rect(
+            origin,
+            [origin[0] + size[0], origin[1] + size[1]],
+            color(palette.disabled_px),
+        )
+        .filled(true)
+        .build();
+    for (x, y, _) in screen.iter().filter(|(_, _, on)| *on) {
+        let top_left = [
+            origin[0] + x as f32 * pixel_size,
+            origin[1] + y as f32 * pixel_size,
+        ];
+        draw_list
+            .add_rect(
+                top_left,
+                [top_left[0] + pixel_size, top_left[1] + pixel_size],
+                color(palette.enabled_px),
+            )
+            .filled(true)
+            .build();
+    }
+}
+
+#[cfg(test)]
+mod thumbnails_tests {
+    use super::render;
+
+    #[test]
+    fn test_render() {
+        // LD I, font 0 - DRW V0, V0, 5 - JP 0x204
+        let screen = render(&[0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04], 2).unwrap();
+        assert!(screen.get(0, 0));
+        assert!(!screen.get(0, 5));
+
+        // Too large to fit in memory
+        assert!(render(&[0; 0x1000], 2).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use imgui::{Condition, Ui};
+
+use crate::{
+    core::{cpu::Cpu, replay::ReplayPlayer, timeline::EventKind},
+    locale::tr,
+};
+
+// Events kept while the timeline is shown
+pub const TIMELINE_CAPACITY: usize = 8192;
+
+const TIMELINE_WIDTH: f32 = 480.0;
+
+// A row per kind of event
+const LANES: [(&str, [f32; 4]); 4] = [
+    ("Keys", [0.4, 0.8, 1.0, 1.0]),
+    ("Sound", [1.0, 0.8, 0.2, 1.0]),
+    ("Clears", [1.0, 0.4, 0.4, 1.0]),
+    ("Save states", [0.4, 1.0, 0.4, 1.0]),
+];
+
+pub struct TimelineState {
+    visible_frames: u64,
+}
+
+impl Default for TimelineState {
+    fn default() -> TimelineState {
+        TimelineState {
+            visible_frames: 600,
+        }
+    }
+}
+
+fn lane(kind: EventKind) -> usize {
+    match kind {
+        EventKind::KeyPress(_) => 0,
+        EventKind::SoundStart => 1,
+        EventKind::Clear => 2,
+        EventKind::SaveState => 3,
+    }
+}
+
+fn describe(kind: EventKind) -> String {
+    match kind {
+        EventKind::KeyPress(key) => format!("{} {:X}", tr("Key"), key),
+        EventKind::SoundStart => tr("Sound").to_string(),
+        EventKind::Clear => tr("Screen cleared").to_string(),
+        EventKind::SaveState => tr("State saved").to_string(),
+    }
+}
+
+// The frames shown, up to the current one, or the whole replay.
+fn visible_range(current: u64, length: u64, visible_frames: u64) -> (u64, u64) {
+    let end = (current + 1).max(length);
+    (end.saturating_sub(visible_frames), end)
+}
+
+// Events over the last frames, a lane per kind. Clicking seeks, through
+// the replay being played or else the debugger's instruction history.
+pub fn draw(
+    ui: &Ui,
+    cpu: &mut Cpu,
+    mut player: Option<&mut ReplayPlayer>,
+    state: &mut TimelineState,
+    opened: &mut bool,
+) {
+    ui.window(format!("{}###timeline", tr("Timeline")))
+        .opened(opened)
+        .always_auto_resize(true)
+        .position([10.0, 420.0], Condition::FirstUseEver)
+        .build(|| {
+            let mut visible_frames = state.visible_frames as i32;
+            ui.set_next_item_width(ui.current_font_size() * 12.0);
+            if ui.slider(tr("Frames shown"), 60, 3600, &mut visible_frames) {
+                state.visible_frames = visible_frames.max(1) as u64;
+            }
+
+            let length = player.as_ref().map_or(0, |player| player.length());
+            let (start, end) = visible_range(cpu.frame(), length, state.visible_frames);
+            let frame_width = TIMELINE_WIDTH / (end - start).max(1) as f32;
+            let lane_height = ui.current_font_size() * 1.2;
+            let label_width = ui.current_font_size() * 7.0;
+
+            let origin = ui.cursor_screen_pos();
+            let strip = [origin[0] + label_width, origin[1]];
+            let size = [TIMELINE_WIDTH, lane_height * LANES.len() as f32];
+            let draw_list = ui.get_window_draw_list();
+            for (idx, (name, _)) in LANES.iter().enumerate() {
+                let top = strip[1] + idx as f32 * lane_height;
+                draw_list.add_text([origin[0], top], [0.7, 0.7, 0.7, 1.0], tr(name));
+                draw_list
+                    .add_rect(
+                        [strip[0], top + 1.0],
+                        [strip[0] + size[0], top + lane_height - 1.0],
+                        [0.15, 0.15, 0.15, 1.0],
+                    )
+                    .filled(true)
+                    .build();
+            }
+            if let Some(timeline) = cpu.timeline() {
+                for event in timeline.range(start, end) {
+                    let x = strip[0] + (event.frame - start) as f32 * frame_width;
+                    let top = strip[1] + lane(event.kind) as f32 * lane_height;
+                    draw_list
+                        .add_line(
+                            [x, top + 2.0],
+                            [x, top + lane_height - 2.0],
+                            LANES[lane(event.kind)].1,
+                        )
+                        .thickness(frame_width.max(2.0))
+                        .build();
+                }
+            }
+            let cursor = strip[0] + cpu.frame().saturating_sub(start) as f32 * frame_width;
+            draw_list
+                .add_line([cursor, strip[1]], [cursor, strip[1] + size[1]], [1.0; 4])
+                .build();
+
+            ui.set_cursor_screen_pos(strip);
+            let clicked = ui.invisible_button("##timeline", size);
+            if !ui.is_item_hovered() {
+                return;
+            }
+            let mouse_x = ui.io().mouse_pos[0] - strip[0];
+            let frame = start + (mouse_x / frame_width).max(0.0) as u64;
+            let can_seek = match player.as_ref() {
+                Some(_) => true,
+                None => cpu.can_rewind_to(frame),
+            };
+            ui.tooltip(|| {
+                ui.text(format!("{} {}", tr("Frame"), frame));
+                if let Some(timeline) = cpu.timeline() {
+                    for event in timeline.range(frame, frame + 1) {
+                        ui.text(describe(event.kind));
+                    }
+                }
+                if !can_seek {
+                    ui.text_disabled(tr(
+                        "Seeking needs a replay, or the debugger open while the frame ran.",
+                    ));
+                }
+            });
+            if clicked && can_seek {
+                match player.take() {
+                    Some(player) => player.seek(cpu, frame),
+                    None => {
+                        cpu.rewind_to_frame(frame);
+                    }
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod timeline_tests {
+    use super::visible_range;
+
+    #[test]
+    fn test_visible_range() {
+        assert_eq!(visible_range(10, 0, 600), (0, 11));
+        assert_eq!(visible_range(1000, 0, 600), (401, 1001));
+        // Replays show up to their end
+        assert_eq!(visible_range(10, 300, 600), (0, 300));
+    }
+}
@@ -0,0 +1,65 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use imgui::{Condition, Ui};
+
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+const MAX_TOASTS: usize = 5;
+
+// Short messages stacked in the bottom right corner, fading away.
+pub struct Toasts {
+    toasts: VecDeque<(String, Instant)>,
+}
+
+impl Toasts {
+    pub fn new() -> Toasts {
+        Toasts {
+            toasts: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, message: String) {
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back((message, Instant::now()));
+    }
+
+    pub fn draw(&mut self, ui: &Ui) {
+        self.toasts
+            .retain(|(_, shown)| shown.elapsed() < TOAST_DURATION);
+
+        let [width, height] = ui.io().display_size;
+        let margin = 8.0;
+        for (idx, (message, shown)) in self.toasts.iter().rev().enumerate() {
+            // Fades out during the last second
+            let left = TOAST_DURATION.saturating_sub(shown.elapsed()).as_secs_f32();
+            let offset = idx as f32 * (ui.frame_height_with_spacing() + margin * 2.0);
+            ui.window(format!("##toast{}", idx))
+                .position(
+                    [width - margin, height - margin - offset],
+                    Condition::Always,
+                )
+                .position_pivot([1.0, 1.0])
+                .flags(imgui::WindowFlags::NO_DOCKING | imgui::WindowFlags::NO_SAVED_SETTINGS)
+                .bg_alpha(0.8 * left.min(1.0))
+                .no_decoration()
+                .no_inputs()
+                .always_auto_resize(true)
+                .build(|| {
+                    ui.text_colored([1.0, 1.0, 1.0, left.min(1.0)], message);
+                });
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//  _             _
+// | |           | |
+// | | _____  ___| |_   _ ___
+// | |/ / _ \/ _ \ | | | / __|
+// |   <  __/  __/ | |_| \__ \
+// |_|\_\___|\___|_|\__,_|___/
+//
+// https://github.com/keelus/chip-8-emu
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+};
+
+use crate::core::{
+    beep::FRAME_RATE,
+    screen::{Palette, Screen},
+};
+
+// Frames waiting for the encoder. Past this, new frames get dropped
+// instead of slowing the emulator down.
+const MAX_PENDING_FRAMES: usize = 16;
+
+// Scale of the emulated screen in screen recordings
+pub const SCREEN_SCALE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Screen, // Just the emulated display
+    Window, // The whole window, debugger panels included
+}
+
+// Records frames into a video file by piping them to ffmpeg, which has
+// to be installed. Frames are raw BGR, bottom row first (as read back
+// from OpenGL), and are written from a separate thread.
+pub struct VideoRecorder {
+    source: Source,
+    width: usize,
+    height: usize,
+    sender: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<io::Result<bool>>>,
+    dropped_frames: u64,
+}
+
+impl VideoRecorder {
+    // Sizes are rounded down to even numbers, as most codecs need.
+    pub fn start(
+        path: &Path,
+        source: Source,
+        width: usize,
+        height: usize,
+    ) -> io::Result<VideoRecorder> {
+        let (width, height) = (width & !1, height & !1);
+        let mut child = Command::new("ffmpeg")
+            .args(ffmpeg_args(path, width, height))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(MAX_PENDING_FRAMES);
+        let writer = thread::spawn(move || {
+            for frame in receiver {
+                stdin.write_all(&frame)?;
+            }
+            drop(stdin); // Lets ffmpeg finish the file
+            Ok(child.wait()?.success())
+        });
+
+        log::info!("Recording {}x{} video to {}", width, height, path.display());
+        Ok(VideoRecorder {
+            source,
+            width,
+            height,
+            sender: Some(sender),
+            writer: Some(writer),
+            dropped_frames: 0,
+        })
+    }
+
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn push_frame(&mut self, frame: Vec<u8>) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        match sender.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.dropped_frames += 1,
+            // The writer stopped, finish reports why
+            Err(TrySendError::Disconnected(_)) => self.sender = None,
+        }
+    }
+
+    // Waits for ffmpeg to write the whole file.
+    pub fn finish(mut self) -> Result<(), String> {
+        self.sender = None;
+        if self.dropped_frames > 0 {
+            log::warn!(
+                "{} frames were dropped while recording video.",
+                self.dropped_frames
+            );
+        }
+
+        match self.writer.take().unwrap().join() {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => Err("ffmpeg could not encode the video.".to_string()),
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(_) => Err("The video writer crashed.".to_string()),
+        }
+    }
+}
+
+fn ffmpeg_args(path: &Path, width: usize, height: usize) -> Vec<String> {
+    [
+        "-y",
+        "-loglevel",
+        "error",
+        "-f",
+        "rawvideo",
+        "-pixel_format",
+        "bgr24",
+        "-video_size",
+        &format!("{}x{}", width, height),
+        "-framerate",
+        &FRAME_RATE.to_string(),
+        "-i",
+        "-",
+        "-vf",
+        "vflip",
+        "-c:v",
+        "libx264",
+        "-pix_fmt",
+        "yuv420p",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .chain([path.display().to_string()])
+    .collect()
+}
+
+// Scales the screen to the given size (nearest neighbour), as a bottom
+// up BGR frame.
+pub fn screen_frame(screen: &Screen, palette: &Palette, width: usize, height: usize) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(width * height * 3);
+    for y in (0..height).rev() {
+        let screen_y = y * screen.height() / height;
+        for x in 0..width {
+            let screen_x = x * screen.width() / width;
+            let [r, g, b] = if screen.get(screen_x, screen_y) {
+                palette.enabled_px
+            } else {
+                palette.disabled_px
+            };
+            frame.extend_from_slice(&[b, g, r]);
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod video_tests {
+    use std::path::Path;
+
+    use super::{ffmpeg_args, screen_frame};
+    use crate::core::screen::{Palette, Screen};
+
+    #[test]
+    fn test_screen_frame() {
+        let palette = Palette {
+            enabled_px: [1, 2, 3],
+            disabled_px: [0, 0, 0],
+        };
+        let mut screen = Screen::new();
+        screen.set(0, 31, true);
+
+        // Bottom-left pixel first, 2x2 pixels per screen pixel
+        let frame = screen_frame(&screen, &palette, 128, 64);
+        assert_eq!(frame.len(), 128 * 64 * 3);
+        assert_eq!(&frame[0..9], &[3, 2, 1, 3, 2, 1, 0, 0, 0]);
+        assert_eq!(&frame[128 * 3..128 * 3 + 6], &[3, 2, 1, 3, 2, 1]);
+        assert_eq!(&frame[128 * 3 * 2..128 * 3 * 2 + 3], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let args = ffmpeg_args(Path::new("out.mp4"), 640, 320);
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-video_size", "640x320"]));
+        assert!(args.windows(2).any(|pair| pair == ["-framerate", "60"]));
+        assert_eq!(args.last().unwrap(), "out.mp4");
+    }
+}